@@ -0,0 +1,302 @@
+//! The benchmarks of a single state machine fed with synthetic signed msgs, without network.
+//!
+//! ```text
+//! cargo bench --features bench
+//! ```
+
+use bft_rs::bench::BenchEngine;
+use bft_rs::objects::{LogType, SignedVote, Vote, VoteType};
+use bft_rs::params::BftConfig;
+use bft_rs::*;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const AUTHORITY_COUNTS: [usize; 3] = [4, 16, 64];
+const WAL_RECORD_SIZES: [usize; 3] = [256, 4096, 65536];
+const BLOCK_SIZE: usize = 1024;
+const HEIGHT: Height = 1;
+const ROUND: Round = 0;
+
+/// The support signing by the address as the integration tests do, and hashing by sha256.
+/// The blocks are fed by the benchmarks.
+struct BenchSupport {
+    address: Address,
+    commits: Arc<AtomicUsize>,
+}
+
+impl BftSupport for BenchSupport {
+    type Error = String;
+
+    fn check_block(
+        &self,
+        _block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, String> {
+        Ok(VerifyResp {
+            is_pass: true,
+            round: height_round.1,
+            #[cfg(feature = "compact_block")]
+            complete_block: _block.clone(),
+            failure: None,
+        })
+    }
+
+    fn transmit(&self, _outbound: Outbound) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn commit(&self, _commit: Commit) -> Result<Option<Status>, String> {
+        self.commits.fetch_add(1, Ordering::Relaxed);
+        Ok(None)
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(Block, Hash), String> {
+        Err("the blocks are fed by the benchmarks".to_string())
+    }
+
+    fn sign(&self, _hash: &Hash) -> Result<Signature, String> {
+        Ok(self.address.to_vec().into())
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, String> {
+        recover(signature, hash).ok_or_else(|| "invalid signature".to_string())
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        hash(msg)
+    }
+}
+
+fn hash(msg: &[u8]) -> Hash {
+    Hash::try_from(Sha256::digest(msg).as_slice()).unwrap()
+}
+
+// simplified as the integration tests, the signature is the address
+fn recover(signature: &Signature, _hash: &Hash) -> Option<Address> {
+    let sig: &[u8] = signature;
+    Address::try_from(sig).ok()
+}
+
+fn generate_block() -> Block {
+    vec![0u8; BLOCK_SIZE].into()
+}
+
+fn generate_authorities(count: usize) -> Vec<Node> {
+    (0..count)
+        .map(|i| {
+            let mut address = [0u8; ADDRESS_LEN];
+            address[..8].copy_from_slice(&(i as u64 + 1).to_be_bytes());
+            Node {
+                address: Address::from(address),
+                proposal_weight: 1,
+                vote_weight: 1,
+            }
+        })
+        .collect()
+}
+
+fn config() -> BftConfig {
+    // the signatures and the blocks are checked in place, and nothing is re-attempted
+    BftConfig {
+        sig_verify_threads: 0,
+        speculative_verify_threads: 0,
+        commit_retry_times: 0,
+        get_block_retry_times: 0,
+        ..BftConfig::default()
+    }
+}
+
+fn signed_vote(vote_type: VoteType, block_hash: Hash, voter: Address) -> SignedVote {
+    let vote = Vote {
+        vote_type,
+        height: HEIGHT,
+        round: ROUND,
+        block_hash,
+        voter,
+    };
+    SignedVote::new(vote, voter.to_vec().into())
+}
+
+/// The votes of the authorities except the [`proposer`], which votes by itself.
+fn vote_msgs(
+    vote_type: VoteType,
+    block_hash: Hash,
+    authorities: &[Node],
+    proposer: &Address,
+) -> Vec<BftMsg> {
+    authorities
+        .iter()
+        .filter(|node| node.address != *proposer)
+        .map(|node| {
+            let signed_vote = signed_vote(vote_type.clone(), block_hash, node.address);
+            BftMsg::Vote(rlp::encode(&signed_vote))
+        })
+        .collect()
+}
+
+/// A function to start a state machine of the proposer at the first height, which has proposed
+/// and prevoted the block of the returned hash.
+fn start_proposer(
+    commits: &Arc<AtomicUsize>,
+    authorities: &[Node],
+) -> (BenchEngine<BenchSupport>, Address, Hash) {
+    let status = Status {
+        height: HEIGHT - 1,
+        interval: None,
+        authority_list: authorities.to_vec(),
+        authority_diff: None,
+        scheduled_authorities: Vec::new(),
+        consensus_params: None,
+    };
+    let start = |address: Address| {
+        let support = BenchSupport {
+            address,
+            commits: commits.clone(),
+        };
+        let mut engine = BenchEngine::new(Arc::new(support), address, config()).unwrap();
+        engine.process(BftMsg::Status(status.clone()));
+        // the timer of CommitWait begins the height
+        engine.fire_timers();
+        engine
+    };
+    // the proposer depends on the authorities only, so it is found by any one of them
+    let mut engine = start(authorities[0].address);
+    let proposer = engine.proposer().unwrap();
+    if proposer != authorities[0].address {
+        engine = start(proposer);
+    }
+    let block = generate_block();
+    let block_hash = hash(&block);
+    let errors = engine.process(BftMsg::Feed(Feed {
+        height: HEIGHT,
+        block,
+        block_hash,
+    }));
+    assert!(errors.is_empty(), "proposing encounters {:?}", errors);
+    engine.outbound();
+    (engine, proposer, block_hash)
+}
+
+fn bench_vote_ingestion(c: &mut Criterion) {
+    let mut group = c.benchmark_group("vote_ingestion");
+    let commits = Arc::new(AtomicUsize::new(0));
+    for &count in AUTHORITY_COUNTS.iter() {
+        let authorities = generate_authorities(count);
+        group.throughput(Throughput::Elements(count as u64 - 1));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter_batched(
+                || {
+                    let (engine, proposer, block_hash) = start_proposer(&commits, &authorities);
+                    let msgs = vote_msgs(VoteType::Prevote, block_hash, &authorities, &proposer);
+                    (engine, msgs)
+                },
+                |(mut engine, msgs)| {
+                    for msg in msgs {
+                        engine.process(msg);
+                    }
+                    engine
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+fn bench_quorum(c: &mut Criterion) {
+    let mut group = c.benchmark_group("quorum");
+    let commits = Arc::new(AtomicUsize::new(0));
+    for &count in AUTHORITY_COUNTS.iter() {
+        let authorities = generate_authorities(count);
+        group.bench_with_input(BenchmarkId::from_parameter(count), &count, |b, _| {
+            b.iter_batched(
+                || {
+                    let (engine, proposer, block_hash) = start_proposer(&commits, &authorities);
+                    let prevotes =
+                        vote_msgs(VoteType::Prevote, block_hash, &authorities, &proposer);
+                    let precommits =
+                        vote_msgs(VoteType::Precommit, block_hash, &authorities, &proposer);
+                    (engine, prevotes, precommits)
+                },
+                |(mut engine, prevotes, precommits)| {
+                    for msg in prevotes {
+                        engine.process(msg);
+                    }
+                    // the timer of PrevoteWait of no duration moves on to precommit
+                    engine.fire_timers();
+                    for msg in precommits {
+                        engine.process(msg);
+                    }
+                    engine
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+    group.finish();
+    assert!(
+        commits.load(Ordering::Relaxed) > 0,
+        "no height is committed"
+    );
+}
+
+fn bench_proof(c: &mut Criterion) {
+    let mut group = c.benchmark_group("proof");
+    let commits = Arc::new(AtomicUsize::new(0));
+    for &count in AUTHORITY_COUNTS.iter() {
+        let authorities = generate_authorities(count);
+        let (mut engine, _, block_hash) = start_proposer(&commits, &authorities);
+        let votes: Vec<SignedVote> = authorities
+            .iter()
+            .map(|node| signed_vote(VoteType::Precommit, block_hash, node.address))
+            .collect();
+        group.bench_with_input(BenchmarkId::new("generate", count), &count, |b, _| {
+            b.iter(|| engine.generate_proof(ROUND, block_hash, votes.clone()))
+        });
+        let proof = engine.generate_proof(ROUND, block_hash, votes.clone());
+        group.bench_with_input(BenchmarkId::new("check", count), &count, |b, _| {
+            b.iter(|| {
+                check_proof(
+                    &proof,
+                    HEIGHT + 1,
+                    &authorities,
+                    SignScheme::default(),
+                    hash,
+                    recover,
+                )
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_wal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("wal");
+    let commits = Arc::new(AtomicUsize::new(0));
+    let authorities = generate_authorities(AUTHORITY_COUNTS[0]);
+    let (mut engine, _, _) = start_proposer(&commits, &authorities);
+    for &size in WAL_RECORD_SIZES.iter() {
+        let record = vec![0u8; size];
+        group.throughput(Throughput::Bytes(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter(|| engine.save_wal(LogType::Proposal, &record).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_vote_ingestion,
+    bench_quorum,
+    bench_proof,
+    bench_wal
+);
+criterion_main!(benches);
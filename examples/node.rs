@@ -0,0 +1,371 @@
+//! A full node of a trivial key-value chain, connected to the other nodes by a TCP mesh on
+//! localhost. A cluster of 4 nodes is launched by:
+//!
+//! ```text
+//! cargo run --example node --features simple_support -- cluster 4
+//! ```
+//!
+//! Each line `<key>=<value>` typed into a node started by `node` is packed into a block.
+
+use bft_rs::params::BftConfig;
+use bft_rs::support::{keccak256, SimpleSupport, SimpleSupportError};
+use bft_rs::wire::{WireKind, WireMsg};
+use bft_rs::*;
+use crossbeam::crossbeam_channel::{unbounded, Receiver, Sender};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const USAGE: &str = "usage:
+    node cluster <count> [dir]      generate the missing keys in the dir and start the nodes
+    node node <index> <count> [dir] start the node of the index by the keys in the dir";
+
+const BASE_PORT: u16 = 40_000;
+const INTERVAL_MILLIS: u64 = 3_000;
+const RECONNECT_MILLIS: u64 = 500;
+const MAX_FRAME_SIZE: usize = 64 * 1024 * 1024;
+
+/// The errors of the key-value chain, which are only read by the `Debug` of the engine logs.
+#[allow(dead_code)]
+#[derive(Debug)]
+enum NodeError {
+    Crypto(SimpleSupportError),
+    InvalidTx(String),
+}
+
+/// The support of the key-value chain, whose blocks are lines of `<key>=<value>`.
+struct KvSupport {
+    index: usize,
+    crypto: SimpleSupport,
+    authority_list: Vec<Node>,
+    pool: Mutex<Vec<String>>,
+    state: Mutex<BTreeMap<String, String>>,
+}
+
+impl BftSupport for KvSupport {
+    type Error = NodeError;
+
+    fn check_block(
+        &self,
+        block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, NodeError> {
+        parse_txs(block)?;
+        Ok(VerifyResp {
+            is_pass: true,
+            round: height_round.1,
+            #[cfg(feature = "compact_block")]
+            complete_block: block.clone(),
+            failure: None,
+        })
+    }
+
+    fn transmit(&self, _outbound: Outbound) -> Result<(), NodeError> {
+        // the outbound msgs are forwarded from the outbox
+        Ok(())
+    }
+
+    fn commit(&self, commit: Commit) -> Result<Option<Status>, NodeError> {
+        let txs = parse_txs(&commit.block)?;
+        let mut state = self.state.lock().unwrap();
+        for (key, value) in txs {
+            state.insert(key, value);
+        }
+        let root = keccak256(format!("{:?}", *state).as_bytes());
+        println!(
+            "node {} commits height {} of {} keys, state root {:?}",
+            self.index,
+            commit.height,
+            state.len(),
+            root
+        );
+        Ok(Some(Status {
+            height: commit.height,
+            interval: Some(INTERVAL_MILLIS),
+            authority_list: self.authority_list.clone(),
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
+        }))
+    }
+
+    fn get_block(&self, height: Height) -> Result<(Block, Hash), NodeError> {
+        let mut txs: Vec<String> = self.pool.lock().unwrap().drain(..).collect();
+        // an idle chain still moves on
+        txs.push(format!("height={}", height));
+        let block: Block = txs.join("\n").into_bytes().into();
+        let block_hash = keccak256(&block);
+        Ok((block, block_hash))
+    }
+
+    fn sign(&self, hash: &Hash) -> Result<Signature, NodeError> {
+        self.crypto.sign(hash).map_err(NodeError::Crypto)
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, NodeError> {
+        self.crypto
+            .check_sig(signature, hash)
+            .map_err(NodeError::Crypto)
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        keccak256(msg)
+    }
+}
+
+fn parse_txs(block: &[u8]) -> Result<Vec<(String, String)>, NodeError> {
+    let txs = std::str::from_utf8(block).map_err(|e| NodeError::InvalidTx(e.to_string()))?;
+    txs.lines()
+        .map(|tx| {
+            let mut parts = tx.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if !key.is_empty() => {
+                    Ok((key.to_string(), value.to_string()))
+                }
+                _ => Err(NodeError::InvalidTx(tx.to_string())),
+            }
+        })
+        .collect()
+}
+
+fn main() {
+    env_logger::init();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let parse = |arg: Option<&String>| {
+        arg.and_then(|arg| arg.parse::<usize>().ok())
+            .unwrap_or_else(|| exit(USAGE))
+    };
+    match args.first().map(String::as_str) {
+        Some("cluster") => {
+            let count = parse(args.get(1));
+            let dir = PathBuf::from(args.get(2).map_or("cluster", String::as_str));
+            run_cluster(count, &dir);
+        }
+        Some("node") => {
+            let index = parse(args.get(1));
+            let count = parse(args.get(2));
+            let dir = PathBuf::from(args.get(3).map_or("cluster", String::as_str));
+            if index >= count {
+                exit(USAGE);
+            }
+            run_node(index, count, &dir);
+        }
+        _ => exit(USAGE),
+    }
+}
+
+fn run_cluster(count: usize, dir: &Path) {
+    fs::create_dir_all(dir).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+    for index in 0..count {
+        let path = key_path(dir, index);
+        if !path.exists() {
+            let key: [u8; 32] = rand::random();
+            fs::write(&path, to_hex(&key)).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+        }
+    }
+    let exe = env::current_exe().unwrap_or_else(|e| exit(&format!("{:?}", e)));
+    let children: Vec<_> = (0..count)
+        .map(|index| {
+            Command::new(&exe)
+                .arg("node")
+                .arg(index.to_string())
+                .arg(count.to_string())
+                .arg(dir)
+                .spawn()
+                .unwrap_or_else(|e| exit(&format!("{:?}", e)))
+        })
+        .collect();
+    for mut child in children {
+        let _ = child.wait();
+    }
+}
+
+fn run_node(index: usize, count: usize, dir: &Path) {
+    // the demo derives the addresses from the keys of all the nodes in the dir,
+    // a real deployment shares the addresses instead
+    let cryptos: Vec<SimpleSupport> = (0..count)
+        .map(|i| {
+            let key = fs::read_to_string(key_path(dir, i))
+                .ok()
+                .and_then(|hex| from_hex(hex.trim()))
+                .unwrap_or_else(|| exit(&format!("invalid key of node {}", i)));
+            SimpleSupport::new(&key, vec![], None).unwrap_or_else(|e| exit(&format!("{:?}", e)))
+        })
+        .collect();
+    let authority_list: Vec<Node> = cryptos
+        .iter()
+        .map(|crypto| Node::new(crypto.address(), 1, 1))
+        .collect();
+    let crypto = cryptos
+        .into_iter()
+        .nth(index)
+        .unwrap_or_else(|| exit(USAGE));
+    let address = crypto.address();
+    let support = Arc::new(KvSupport {
+        index,
+        crypto,
+        authority_list: authority_list.clone(),
+        pool: Mutex::new(Vec::new()),
+        state: Mutex::new(BTreeMap::new()),
+    });
+
+    let wal_path = dir.join(format!("wal{}", index));
+    let (actuator, outbox) = BftActuator::new_with_outbox(
+        support.clone(),
+        address,
+        &wal_path.to_string_lossy(),
+        BftConfig::default(),
+    );
+    let actuator = Arc::new(actuator);
+
+    listen(index, actuator.clone());
+    let peers: Vec<(Address, Sender<Vec<u8>>)> = authority_list
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != index)
+        .map(|(i, node)| (node.address, connect(i)))
+        .collect();
+    thread::spawn(move || forward(outbox, peers));
+
+    actuator
+        .send_status(Status {
+            height: 0,
+            interval: Some(INTERVAL_MILLIS),
+            authority_list,
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
+        })
+        .unwrap_or_else(|e| exit(&format!("{:?}", e)));
+    println!("node {} of {:?} is started", index, address);
+
+    // the lines of stdin are the txs, the node runs on after stdin is closed
+    for line in io::stdin().lock().lines().map_while(Result::ok) {
+        if parse_txs(line.as_bytes()).is_ok() {
+            support.pool.lock().unwrap().push(line);
+        } else {
+            eprintln!("a tx is a line of <key>=<value>");
+        }
+    }
+    loop {
+        thread::park();
+    }
+}
+
+fn socket_address(index: usize) -> SocketAddr {
+    SocketAddr::from(([127, 0, 0, 1], BASE_PORT + index as u16))
+}
+
+fn key_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("node{}.key", index))
+}
+
+/// Accept the connections of the peers and send the msgs they read to the actuator.
+fn listen(index: usize, actuator: Arc<BftActuator>) {
+    let listener =
+        TcpListener::bind(socket_address(index)).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let actuator = actuator.clone();
+            thread::spawn(move || read_msgs(stream, &actuator));
+        }
+    });
+}
+
+fn read_msgs(mut stream: TcpStream, actuator: &BftActuator) {
+    loop {
+        let mut len = [0u8; 4];
+        if stream.read_exact(&mut len).is_err() {
+            return;
+        }
+        let len = u32::from_be_bytes(len) as usize;
+        if len > MAX_FRAME_SIZE {
+            return;
+        }
+        let mut encode = vec![0u8; len];
+        if stream.read_exact(&mut encode).is_err() {
+            return;
+        }
+        let msg = match rlp::decode::<WireMsg>(&encode).map(|wire_msg| wire_msg.kind) {
+            Ok(WireKind::Proposal) => BftMsg::Proposal(encode),
+            Ok(WireKind::Vote) => BftMsg::Vote(encode),
+            _ => continue,
+        };
+        if let Err(e) = actuator.send(msg) {
+            eprintln!("a msg from the peer encounters {:?}", e);
+        }
+    }
+}
+
+/// Keep a connection to the peer of the [`index`], and write the msgs of the returned sender
+/// to it. The msgs failing to be written are dropped, which the consensus sends again.
+fn connect(index: usize) -> Sender<Vec<u8>> {
+    let (sender, receiver): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = unbounded();
+    thread::spawn(move || {
+        let mut stream: Option<TcpStream> = None;
+        for encode in receiver.iter() {
+            if stream.is_none() {
+                stream = TcpStream::connect(socket_address(index)).ok();
+            }
+            let written = stream.as_mut().is_some_and(|s| {
+                s.write_all(&(encode.len() as u32).to_be_bytes())
+                    .and_then(|_| s.write_all(&encode))
+                    .is_ok()
+            });
+            if !written {
+                stream = None;
+                thread::sleep(Duration::from_millis(RECONNECT_MILLIS));
+            }
+        }
+    });
+    sender
+}
+
+fn forward(outbox: Receiver<Outbound>, peers: Vec<(Address, Sender<Vec<u8>>)>) {
+    for outbound in outbox.iter() {
+        let encode = match outbound.msg {
+            OutboundMsg::Proposal(encode) | OutboundMsg::Vote(encode) => encode,
+            #[cfg(feature = "compact_block")]
+            _ => continue,
+        };
+        for (address, peer) in peers.iter() {
+            let is_target = match &outbound.target {
+                Target::Broadcast => true,
+                Target::Unicast(target) => target == address,
+            };
+            if is_target {
+                let _ = peer.send(encode.clone());
+            }
+        }
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn exit(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
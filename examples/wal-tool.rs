@@ -0,0 +1,72 @@
+//! A tool to inspect and repair the wal of a stopped node.
+//!
+//! ```text
+//! wal-tool list <wal_dir>
+//! wal-tool decode <wal_dir> [height]
+//! wal-tool verify <wal_dir>
+//! wal-tool drop <wal_dir> <index>...
+//! ```
+
+use bft_rs::wal::{drop_records, inspect, verify};
+use std::collections::HashSet;
+use std::env;
+use std::process;
+
+const USAGE: &str = "usage:
+    wal-tool list <wal_dir>             list the records with their indexes
+    wal-tool decode <wal_dir> [height]  decode the records, of the height if it is given
+    wal-tool verify <wal_dir>           check the records and the indexes
+    wal-tool drop <wal_dir> <index>...  drop the records of the indexes";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() < 2 {
+        exit(USAGE);
+    }
+    let dir = &args[1];
+
+    match args[0].as_str() {
+        "list" => {
+            let records = inspect(dir).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+            for (index, (height, log_type, _)) in records.iter().enumerate() {
+                println!("{}\theight {}\t{:?}", index, height, log_type);
+            }
+        }
+        "decode" => {
+            let height = args
+                .get(2)
+                .map(|h| h.parse::<u64>().unwrap_or_else(|_| exit(USAGE)));
+            let records = inspect(dir).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+            for (index, (h, log_type, record)) in records.iter().enumerate() {
+                if height.is_none() || height == Some(*h) {
+                    println!("{}\theight {}\t{:?}\t{:?}", index, h, log_type, record);
+                }
+            }
+        }
+        "verify" => {
+            let problems = verify(dir).unwrap_or_else(|e| exit(&format!("{:?}", e)));
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                exit(&format!("{} problems found", problems.len()));
+            }
+            println!("ok");
+        }
+        "drop" => {
+            let indexes: HashSet<usize> = args[2..]
+                .iter()
+                .map(|index| index.parse::<usize>().unwrap_or_else(|_| exit(USAGE)))
+                .collect();
+            let dropped = drop_records(dir, |index, _, _| !indexes.contains(&index))
+                .unwrap_or_else(|e| exit(&format!("{:?}", e)));
+            println!("{} records dropped", dropped);
+        }
+        _ => exit(USAGE),
+    }
+}
+
+fn exit(msg: &str) -> ! {
+    eprintln!("{}", msg);
+    process::exit(1);
+}
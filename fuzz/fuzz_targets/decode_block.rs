@@ -0,0 +1,10 @@
+#![no_main]
+use bft_rs::utils::{decode_block, encode_block};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((height, block, block_hash)) = decode_block(data) {
+        let encode = encode_block(height, &block, &block_hash);
+        assert_eq!(decode_block(&encode).unwrap(), (height, block, block_hash));
+    }
+});
@@ -0,0 +1,10 @@
+#![no_main]
+use bft_rs::utils::{combine_two, extract_two};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((first, second)) = extract_two(data) {
+        let encode = combine_two(first, second);
+        assert_eq!(extract_two(&encode).unwrap(), (first, second));
+    }
+});
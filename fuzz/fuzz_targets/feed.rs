@@ -0,0 +1,10 @@
+#![no_main]
+use bft_rs::Feed;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(feed) = rlp::decode::<Feed>(data) {
+        let decoded: Feed = rlp::decode(&rlp::encode(&feed)).unwrap();
+        assert_eq!(decoded, feed);
+    }
+});
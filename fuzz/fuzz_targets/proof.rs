@@ -0,0 +1,13 @@
+#![no_main]
+use bft_rs::fuzz::decode_proof;
+use bft_rs::params::BftConfig;
+use bft_rs::Proof;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let config = BftConfig::default();
+    if let Ok(proof) = decode_proof(data, &config) {
+        let decoded: Proof = rlp::decode(&rlp::encode(&proof)).unwrap();
+        assert_eq!(decoded, proof);
+    }
+});
@@ -0,0 +1,15 @@
+#![no_main]
+use bft_rs::fuzz::decode_proposal;
+use bft_rs::objects::SignedProposal;
+use bft_rs::params::BftConfig;
+use bft_rs::Block;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let config = BftConfig::default();
+    if let Ok((signed_proposal, _)) = decode_proposal::<Block>(data, &config) {
+        let decoded: SignedProposal = rlp::decode(&rlp::encode(&signed_proposal)).unwrap();
+        assert_eq!(decoded, signed_proposal);
+    }
+    let _ = rlp::decode::<SignedProposal>(data);
+});
@@ -0,0 +1,11 @@
+#![no_main]
+use bft_rs::fuzz::decode_vote;
+use bft_rs::objects::SignedVote;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(signed_vote) = decode_vote(data) {
+        let decoded: SignedVote = rlp::decode(&rlp::encode(&signed_vote)).unwrap();
+        assert_eq!(decoded, signed_vote);
+    }
+});
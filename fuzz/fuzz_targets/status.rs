@@ -0,0 +1,13 @@
+#![no_main]
+use bft_rs::fuzz::decode_status;
+use bft_rs::params::BftConfig;
+use bft_rs::Status;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let config = BftConfig::default();
+    if let Ok(status) = decode_status(data, &config) {
+        let decoded: Status = rlp::decode(&rlp::encode(&status)).unwrap();
+        assert_eq!(decoded, status);
+    }
+});
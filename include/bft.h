@@ -0,0 +1,62 @@
+/* The C bindings of bft-rs, built by
+ * `cargo rustc --release --features ffi --crate-type staticlib`.
+ * See src/ffi.rs for the details. */
+
+#ifndef BFT_H
+#define BFT_H
+
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/types.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#define BFT_OK 0
+#define BFT_ERR_ARG (-1)
+#define BFT_ERR_DECODE (-2)
+#define BFT_ERR_SEND (-3)
+
+#define BFT_MSG_PROPOSAL 0u
+#define BFT_MSG_VOTE 1u
+#define BFT_MSG_STATUS 2u
+#define BFT_MSG_FEED 3u
+#define BFT_MSG_TICK 4u
+
+typedef struct BftActuator BftActuator;
+
+/* Called in the consensus threads, so they must be thread-safe. The buffers passed to them
+ * are only valid during the call. The hashes are 32 bytes and the addresses 20 bytes. */
+typedef struct BftCallbacks {
+    void *ctx;
+    /* target is NULL for a broadcast */
+    int32_t (*transmit)(void *ctx, uint32_t kind, const uint8_t *target, const uint8_t *msg,
+                        size_t len);
+    /* the status of the height is sent by bft_actuator_send after it */
+    int32_t (*commit)(void *ctx, uint64_t height, const uint8_t *block, size_t block_len,
+                      const uint8_t *proof, size_t proof_len);
+    int32_t (*check_block)(void *ctx, const uint8_t *block, size_t len,
+                           const uint8_t *block_hash, uint64_t height, uint64_t round);
+    /* return the length of the signature, or a negative value on failure */
+    ssize_t (*sign)(void *ctx, const uint8_t *hash, uint8_t *signature, size_t capacity);
+    int32_t (*check_sig)(void *ctx, const uint8_t *signature, size_t len, const uint8_t *hash,
+                         uint8_t *address);
+    void (*crypt_hash)(void *ctx, const uint8_t *msg, size_t len, uint8_t *hash);
+} BftCallbacks;
+
+/* Return NULL if an argument is invalid. */
+BftActuator *bft_actuator_new(const uint8_t *address, const char *wal_path,
+                              BftCallbacks callbacks);
+
+int32_t bft_actuator_send(const BftActuator *actuator, uint32_t kind, const uint8_t *msg,
+                          size_t len);
+
+/* Stop the state machine and release the actuator. */
+void bft_actuator_stop(BftActuator *actuator);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BFT_H */
@@ -1,21 +1,52 @@
 use crate::*;
 use crate::{
-    collectors::{BlockCollector, ProposalCollector, VoteCollector},
-    error::{handle_err, BftError, BftResult},
+    byzantine::ByzantineStrategy,
+    collectors::{BlockCollector, FastMap, ProposalCollector, VoteCollector, CACHE_N},
+    compress::decompress_block,
+    error::{handle_err, report_err, BftError, BftResult, ErrorInfo, MsgKind},
+    fairness::ProposerWindow,
+    inbox::Inbox,
+    limiter::{PeerScore, PeerStatsTable, RateLimiter},
+    msg_trace::Tracer,
     objects::*,
-    params::BftParams,
-    timer::{TimeoutInfo, WaitTimer},
-    utils::extract_two,
+    params::{BftConfig, BftParams, NilVotePolicy, MAX_TIMEOUT},
+    proof_store::ProofStore,
+    retry::RetryQueue,
+    runtime::Task,
+    timer::{TimeoutInfo, TimerSetter, WaitTimer},
+    utils::{
+        check_block_size, check_list_len, check_signed_proposal_size, commit_with_retry,
+        encode_block, encode_height_start, extract_proposal, next_height, open_wal,
+        peek_height_round, unix_millis,
+    },
+    verifier::{BlockChecked, BlockVerifier, CheckedSigs, SigChecked, SigJob, SigVerifier},
     wal::Wal,
 };
 
-use crossbeam::crossbeam_channel::{select, unbounded, Receiver, RecvError, Sender};
+#[cfg(feature = "tracing")]
+use crate::spans::ConsensusSpans;
+#[cfg(feature = "commit_stats")]
+use crate::stats::StatsRecorder;
+use crossbeam::crossbeam_channel::{
+    after, bounded, never, select, Receiver, RecvError, Select, Sender, TryRecvError,
+};
 #[allow(unused_imports)]
-use log::{debug, error, info, log};
-use std::collections::HashMap;
+use log::{debug, error, info, log, warn};
+use lru_cache::LruCache;
+#[cfg(feature = "tokio")]
+use min_max_heap::MinMaxHeap;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+#[cfg(feature = "tokio")]
+use std::sync::Mutex;
 use std::thread;
 use std::time::{Duration, Instant};
+#[cfg(feature = "tokio")]
+use tokio::sync::Notify;
 
 pub(crate) const INIT_HEIGHT: Height = 0;
 pub(crate) const INIT_ROUND: Round = 0;
@@ -24,33 +55,126 @@ pub(crate) const TIMEOUT_RETRANSE_COEF: u32 = 15;
 
 #[cfg(feature = "verify_req")]
 const VERIFY_AWAIT_COEF: u32 = 50;
+/// The waker of a state machine run as a task, called after a msg is sent to it.
+pub(crate) type Waker = Arc<dyn Fn() + Send + Sync>;
+
+/// The directory in the wal directory the block bodies are spilled to.
+const BLOCK_SPILL_DIR: &str = "spill";
+
+/// The trusted state to start a BFT state machine at, instead of replaying wal.
+#[derive(Clone, Debug)]
+pub struct StartPoint {
+    /// the height to start consensus at
+    pub height: Height,
+    /// the trusted proof of the previous height
+    pub proof: Proof,
+    /// the authority list of the height
+    pub authority_list: Vec<Node>,
+}
 
 /// BFT state message.
-pub struct Bft<T: BftSupport> {
+pub struct Bft<T: BftSupport<B>, B: BlockPayload = Block> {
     // channel
-    pub(crate) msg_sender: Sender<BftMsg>,
-    pub(crate) msg_receiver: Receiver<BftMsg>,
-    pub(crate) timer_seter: Sender<TimeoutInfo>,
+    pub(crate) msg_sender: Sender<BftMsg<B>>,
+    pub(crate) msg_receiver: Receiver<BftMsg<B>>,
+    /// The proposals and votes of the future heights the actuator sends apart under
+    /// `OverflowPolicy::DropOldestFutureHeight`, never delivers otherwise.
+    pub(crate) future_receiver: Receiver<BftMsg<B>>,
+    /// The msgs the node feeds to itself while the channel is full, sent in order before
+    /// the next msg is taken.
+    pub(crate) refeed: VecDeque<BftMsg<B>>,
+    pub(crate) timer_seter: TimerSetter,
     pub(crate) timer_notity: Receiver<TimeoutInfo>,
+    pub(crate) sig_notify: Receiver<SigChecked<B>>,
+    pub(crate) block_notify: Receiver<BlockChecked>,
     // bft-core params
     pub(crate) height: Height,
+    pub(crate) shared_height: Arc<AtomicU64>,
     pub(crate) round: Round,
     pub(crate) step: Step,
     pub(crate) block_hash: Option<Hash>,
     pub(crate) lock_status: Option<LockStatus>,
-    pub(crate) height_filter: HashMap<Address, Instant>,
-    pub(crate) round_filter: HashMap<Address, Instant>,
+    pub(crate) height_filter: FastMap<Address, Instant>,
+    pub(crate) round_filter: FastMap<Address, Instant>,
+    pub(crate) rate_limiter: RateLimiter,
+    pub(crate) peer_score: PeerScore,
+    pub(crate) peer_stats: PeerStatsTable,
+    pub(crate) ingress_stats: IngressStats,
+    pub(crate) proposer_window: ProposerWindow,
+    pub(crate) retry_queue: RetryQueue,
+    /// The msgs drained from the channel to be processed by priority, if it is on.
+    pub(crate) inbox: Option<Inbox<B>>,
+    pub(crate) outbox: Option<Sender<Outbound>>,
+    /// The waker of the task the state machine runs as, called after the threads besides the
+    /// actuator feed it, `None` if it runs on its own thread or a worker of the shared runtime.
+    pub(crate) waker: Option<Waker>,
+    /// The recorder of the msg trace, if tracing is on.
+    pub(crate) tracer: Option<Tracer>,
+    pub(crate) verifier: Option<SigVerifier<B>>,
+    /// The signatures checked by the verifier pool of the msg being processed.
+    pub(crate) checked_sigs: Option<CheckedSigs>,
+    /// The verified signatures and the addresses recovered.
+    pub(crate) sig_cache: LruCache<(Signature, Hash), Address>,
+    /// The buffer reused to rlp encode the msgs to hash and to save to wal.
+    pub(crate) encode_buf: RefCell<RlpStream>,
+    /// The codec the msg being processed is encoded by, the one of the wal record in the
+    /// replay, otherwise the configured one.
+    pub(crate) msg_codec: RlpCodec,
+    pub(crate) block_verifier: Option<BlockVerifier<B>>,
+    /// The blocks of the future-round proposals being checked by the block verifier pool.
+    pub(crate) speculating: HashSet<Hash>,
     pub(crate) last_commit_round: Option<Round>,
     pub(crate) last_commit_block_hash: Option<Hash>,
+    /// The height whose commit is in flight, 0 if none. The same commit is not delivered
+    /// again until it returns.
+    pub(crate) committing: Arc<AtomicU64>,
     pub(crate) authority_manage: AuthorityManage,
     pub(crate) params: BftParams,
     pub(crate) htime: Instant,
     // caches
     pub(crate) feed: Option<Hash>,
     pub(crate) status: Option<Status>,
-    pub(crate) verify_results: HashMap<Round, VerifyResp>,
+    /// The statuses of the future heights carrying authority diffs, which wait for the
+    /// statuses of the heights they are based on.
+    pub(crate) pending_statuses: BTreeMap<Height, Status>,
+    /// The key rotation of the node activated by the status being set.
+    pub(crate) pending_key: Option<KeyRotation>,
+    /// The own votes waiting for their signatures from `sign_async`, keyed by the ticket.
+    pub(crate) pending_signs: HashMap<SignTicket, PendingSign>,
+    /// The ticket of the next `sign_async` request.
+    pub(crate) next_sign_ticket: SignTicket,
+    /// The block hashes signed for the heights, the rounds and the steps, so the node never
+    /// signs two different payloads for one of them.
+    pub(crate) sign_guard: HashMap<(Height, Round, Option<VoteType>), Hash>,
+    /// The round of the lock and the block hash of the last own precommit on a block of the
+    /// height, which the prevotes are audited against if the `lock_audit` of the config is on.
+    pub(crate) audit_lock: Option<(Round, Hash)>,
+    /// A tick received before the status of the height in the externally ticked mode.
+    pub(crate) tick_pending: bool,
+    /// Whether `RoundExhausted` is reported at the height.
+    pub(crate) round_exhausted: bool,
+    /// The next height whose block is requested in advance in the pipelining mode.
+    pub(crate) pipelined_height: Option<Height>,
+    /// The block of the next height got in advance, fed when the node reaches the height.
+    pub(crate) next_feed: Option<Hash>,
+    pub(crate) verify_results: HashMap<Hash, VerifyResp>,
+    /// The user-defined deadlines of verifying the blocks.
+    #[cfg(feature = "verify_req")]
+    pub(crate) verify_deadlines: HashMap<Hash, Duration>,
+    /// The height and round when the verification times out.
+    #[cfg(feature = "verify_req")]
+    pub(crate) verify_timed_out: Option<(Height, Round)>,
+    /// The proposals parked until the missing txs of their compact blocks arrive,
+    /// `None` once the txs are fetched.
+    #[cfg(feature = "compact_block")]
+    pub(crate) txs_requests: HashMap<Hash, Option<Vec<u8>>>,
+    #[cfg(feature = "commit_stats")]
+    pub(crate) stats: StatsRecorder,
+    #[cfg(feature = "tracing")]
+    pub(crate) spans: ConsensusSpans,
     pub(crate) proof: Proof,
-    pub(crate) blocks: BlockCollector,
+    pub(crate) proof_store: Option<Arc<dyn ProofStore>>,
+    pub(crate) blocks: BlockCollector<B>,
     pub(crate) proposals: ProposalCollector,
     pub(crate) votes: VoteCollector,
     pub(crate) wal_log: Wal,
@@ -60,84 +184,403 @@ pub struct Bft<T: BftSupport> {
     pub(crate) consensus_power: bool,
 
     // byzantine mark
-    pub(crate) is_byzantine: bool,
+    pub(crate) byzantine: Option<ByzantineStrategy>,
+}
+
+/// A state machine run by a worker of the shared runtime, which is started, polled and
+/// restarted from wal as the supervisor of a main loop does.
+pub(crate) struct Supervised<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    engine: Bft<T, B>,
+    start_point: Option<StartPoint>,
+    started: bool,
+    /// The time to restart the main loop from wal at, after an internal error or a panic.
+    restart_at: Option<Instant>,
+    backoff: RestartBackoff,
+}
+
+impl<T, B> Supervised<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    fn new(engine: Bft<T, B>, start_point: Option<StartPoint>) -> Self {
+        Supervised {
+            engine,
+            start_point,
+            started: false,
+            restart_at: None,
+            backoff: RestartBackoff::default(),
+        }
+    }
+
+    /// A function to check whether something waits to be processed without blocking.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn is_ready(&self) -> bool {
+        !self.started
+            || self.restart_at.is_some_and(|at| at <= Instant::now())
+            || !self.engine.timer_notity.is_empty()
+            || !self.engine.msg_receiver.is_empty()
+            || !self.engine.future_receiver.is_empty()
+            || !self.engine.refeed.is_empty()
+            || !self.engine.sig_notify.is_empty()
+            || !self.engine.block_notify.is_empty()
+    }
 }
 
-impl<T> Bft<T>
+impl<T, B> Task for Supervised<T, B>
 where
-    T: BftSupport + 'static,
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
 {
-    fn new(
-        s: Sender<BftMsg>,
-        r: Receiver<BftMsg>,
-        ts: Sender<TimeoutInfo>,
+    fn register<'a>(&'a self, sel: &mut Select<'a>) {
+        sel.recv(&self.engine.timer_notity);
+        sel.recv(&self.engine.msg_receiver);
+        sel.recv(&self.engine.future_receiver);
+        sel.recv(&self.engine.sig_notify);
+        sel.recv(&self.engine.block_notify);
+    }
+
+    fn next_due(&self) -> Option<Duration> {
+        if let Some(at) = self.restart_at {
+            return Some(at.saturating_duration_since(Instant::now()));
+        }
+        if !self.started {
+            return Some(Duration::from_millis(0));
+        }
+        self.engine.next_due()
+    }
+
+    fn poll(&mut self) -> bool {
+        let Supervised {
+            engine,
+            start_point,
+            started,
+            restart_at,
+            backoff,
+        } = self;
+        // the msgs wait in the channels until the backoff ends
+        if restart_at.is_some_and(|at| at > Instant::now()) {
+            return true;
+        }
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            if restart_at.take().is_some() {
+                engine.restart_from_wal();
+            } else if !*started {
+                *started = true;
+                if let Some(start_point) = start_point.take() {
+                    engine.start_at(start_point)?;
+                } else {
+                    engine.load_wal_log();
+                }
+            }
+            engine.poll()
+        }));
+        match result {
+            Ok(Ok(killed)) => return !killed,
+            Ok(Err(e)) => report_err(&e, &engine.params, &*engine.function),
+            Err(_) => error!(
+                target: &engine.params.log_target,
+                "Node {:?} main loop panics", engine.params.address
+            ),
+        }
+        *restart_at = Some(Instant::now() + backoff.next(engine));
+        true
+    }
+}
+
+/// The backoff of the restarts of a main loop from wal, doubled after each restart at the
+/// same height, so a persistent fault does not turn the supervisor into a busy loop.
+#[derive(Default)]
+pub(crate) struct RestartBackoff {
+    height: Height,
+    restarts: u32,
+}
+
+impl RestartBackoff {
+    /// A function to count a restart of the [`engine`], report `RestartStorm` if it restarts
+    /// `restart_escalation_times` times at the same height. Return the backoff before it.
+    fn next<T: BftSupport<B> + 'static, B: BlockPayload>(
+        &mut self,
+        engine: &Bft<T, B>,
+    ) -> Duration {
+        let config = &engine.params.config;
+        if engine.height != self.height {
+            self.height = engine.height;
+            self.restarts = 0;
+        }
+        self.restarts = self.restarts.saturating_add(1);
+        if self.restarts == config.restart_escalation_times {
+            report_err(
+                &BftError::RestartStorm(
+                    ErrorInfo::new(format!("{} restarts", self.restarts)).height(self.height),
+                ),
+                &engine.params,
+                &*engine.function,
+            );
+        }
+        let max_backoff = Duration::from_millis(config.restart_max_backoff_millis);
+        let backoff = Duration::from_millis(config.restart_backoff_millis)
+            .checked_mul(2u32.saturating_pow(self.restarts - 1))
+            .map_or(max_backoff, |backoff| backoff.min(max_backoff));
+        warn!(
+            target: &engine.params.log_target,
+            "Node {:?} restarts main loop from wal in {:?}", engine.params.address, backoff
+        );
+        backoff
+    }
+}
+
+impl<T, B> Bft<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        s: Sender<BftMsg<B>>,
+        r: Receiver<BftMsg<B>>,
+        ts: TimerSetter,
         tn: Receiver<TimeoutInfo>,
         f: Arc<T>,
         local_address: Address,
-        wal_path: &str,
+        wal_log: Wal,
+        config: BftConfig,
+        shared_height: Arc<AtomicU64>,
+        outbox: Option<Sender<Outbound>>,
+        waker: Option<Waker>,
     ) -> Self {
         info!(
-            "Node {:?} initializing with wal_path: {}",
-            local_address, wal_path
+            target: &config.log_target(),
+            "Node {:?} initializing with wal_path: {}", local_address, wal_log.dir
         );
+        let (verifier2bft, bft4verifier) = bounded(config.channel_capacity);
+        let verifier = SigVerifier::start(
+            config.sig_verify_threads,
+            config.channel_capacity,
+            f.clone(),
+            verifier2bft,
+            waker.clone(),
+            local_address,
+        );
+        // nothing will be fed back without the verifier pool
+        let sig_notify = if verifier.is_some() {
+            bft4verifier
+        } else {
+            never()
+        };
+        let (block_verifier2bft, bft4block_verifier) = bounded(config.channel_capacity);
+        let block_verifier = BlockVerifier::start(
+            config.speculative_verify_threads,
+            f.clone(),
+            block_verifier2bft,
+            waker.clone(),
+            local_address,
+        );
+        let block_notify = if block_verifier.is_some() {
+            bft4block_verifier
+        } else {
+            never()
+        };
+        let proof_store = config.proof_store.clone();
+        let block_spill_threshold = config.block_spill_threshold;
         Bft {
             msg_sender: s,
             msg_receiver: r,
+            future_receiver: never(),
+            refeed: VecDeque::new(),
             timer_seter: ts,
             timer_notity: tn,
+            sig_notify,
+            block_notify,
             height: INIT_HEIGHT,
+            shared_height,
             round: INIT_ROUND,
             step: Step::default(),
             block_hash: None,
             lock_status: None,
-            height_filter: HashMap::new(),
-            round_filter: HashMap::new(),
+            height_filter: FastMap::default(),
+            round_filter: FastMap::default(),
+            rate_limiter: RateLimiter::new(config.msg_rate, config.msg_burst),
+            peer_score: PeerScore::new(
+                config.sig_fail_threshold,
+                Duration::from_millis(config.sig_fail_ban_millis),
+            ),
+            peer_stats: PeerStatsTable::new(),
+            ingress_stats: IngressStats::default(),
+            proposer_window: ProposerWindow::new(config.proposer_window),
+            retry_queue: RetryQueue::new(
+                config.transmit_retry_times,
+                Duration::from_millis(config.transmit_retry_millis),
+                config.transmit_retry_capacity,
+            ),
+            inbox: if config.msg_priority {
+                Some(Inbox::new(config.channel_capacity))
+            } else {
+                None
+            },
+            outbox,
+            waker,
+            tracer: None,
+            verifier,
+            checked_sigs: None,
+            sig_cache: LruCache::new(config.sig_cache_capacity),
+            encode_buf: RefCell::new(RlpStream::new()),
+            msg_codec: config.rlp_codec,
+            block_verifier,
+            speculating: HashSet::new(),
             last_commit_round: None,
             last_commit_block_hash: None,
+            committing: Arc::new(AtomicU64::new(0)),
             htime: Instant::now(),
-            params: BftParams::new(local_address),
+            params: BftParams::new(local_address, config),
             feed: None,
             verify_results: HashMap::new(),
+            #[cfg(feature = "verify_req")]
+            verify_deadlines: HashMap::new(),
+            #[cfg(feature = "verify_req")]
+            verify_timed_out: None,
+            #[cfg(feature = "compact_block")]
+            txs_requests: HashMap::new(),
+            #[cfg(feature = "commit_stats")]
+            stats: StatsRecorder::new(),
+            #[cfg(feature = "tracing")]
+            spans: ConsensusSpans::new(),
             proof: Proof::default(),
+            proof_store,
             status: None,
+            pending_statuses: BTreeMap::new(),
+            pending_key: None,
+            pending_signs: HashMap::new(),
+            next_sign_ticket: 0,
+            sign_guard: HashMap::new(),
+            audit_lock: None,
+            tick_pending: false,
+            round_exhausted: false,
+            pipelined_height: None,
+            next_feed: None,
             authority_manage: AuthorityManage::new(),
-            blocks: BlockCollector::new(),
+            blocks: BlockCollector::with_spill(
+                Path::new(&wal_log.dir).join(BLOCK_SPILL_DIR),
+                block_spill_threshold,
+            ),
             proposals: ProposalCollector::new(),
             votes: VoteCollector::new(),
-            wal_log: Wal::new(wal_path).unwrap(),
+            wal_log,
             function: f,
             consensus_power: false,
-            is_byzantine: false,
+            byzantine: None,
         }
     }
 
     /// A function to start a BFT state machine.
+    #[allow(clippy::too_many_arguments)]
     pub fn start(
-        s: Sender<BftMsg>,
-        r: Receiver<BftMsg>,
+        s: Sender<BftMsg<B>>,
+        r: Receiver<BftMsg<B>>,
         f: Arc<T>,
         local_address: Address,
         wal_path: &str,
+        config: BftConfig,
+        shared_height: Arc<AtomicU64>,
+        outbox: Option<Sender<Outbound>>,
+        start_point: Option<StartPoint>,
     ) {
-        // define message channel and timeout channel
-        let (bft2timer, timer4bft) = unbounded();
-        let (timer2bft, bft4timer) = unbounded();
-
-        let mut engine = Bft::new(
+        Self::launch(
             s,
             r,
-            bft2timer,
-            bft4timer,
+            never(),
             f,
-            local_address.clone(),
+            local_address,
             wal_path,
+            config,
+            shared_height,
+            outbox,
+            start_point,
         );
+    }
+
+    /// A function to start a BFT state machine, returns the waker to call after a msg is sent
+    /// if it runs as a task, which is not woken by the channel.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn launch(
+        s: Sender<BftMsg<B>>,
+        r: Receiver<BftMsg<B>>,
+        fr: Receiver<BftMsg<B>>,
+        f: Arc<T>,
+        local_address: Address,
+        wal_path: &str,
+        config: BftConfig,
+        shared_height: Arc<AtomicU64>,
+        outbox: Option<Sender<Outbound>>,
+        start_point: Option<StartPoint>,
+    ) -> Option<Waker> {
+        // define message channel and timeout channel
+        let channel_capacity = config.channel_capacity;
+        let (timer2bft, bft4timer) = bounded(channel_capacity);
+        let log_target = config.log_target();
+        let runtime = config.runtime.clone();
+        #[cfg(feature = "tokio")]
+        let tokio_handle = config.tokio_handle.clone();
+        let address = local_address;
+        let wal_path = wal_path.to_string();
+        let create = move |timer_seter: TimerSetter, waker: Option<Waker>| {
+            let params = BftParams::new(address, config.clone());
+            let wal_log = open_wal(&wal_path, &params, &*f);
+            let mut engine = Bft::new(
+                s,
+                r,
+                timer_seter,
+                bft4timer,
+                f,
+                address,
+                wal_log,
+                config,
+                shared_height,
+                outbox,
+                waker,
+            );
+            engine.future_receiver = fr;
+            engine
+        };
+
+        // run by the shared timer and a worker of the runtime
+        if let Some(runtime) = runtime {
+            let timer_seter = runtime.timer_setter(timer2bft);
+            runtime.spawn(Box::new(move || {
+                Box::new(Supervised::new(create(timer_seter, None), start_point)) as Box<dyn Task>
+            }));
+            return None;
+        }
+
+        // run as a task of the tokio runtime
+        #[cfg(feature = "tokio")]
+        {
+            if let Some(handle) = tokio_handle {
+                let timers = Arc::new(Mutex::new(MinMaxHeap::new()));
+                let notify = Arc::new(Notify::new());
+                // a wake before the task waits is kept as a permit, so no msg is left behind
+                let waker: Waker = {
+                    let notify = notify.clone();
+                    Arc::new(move || notify.notify_one())
+                };
+                let engine = create(TimerSetter::Queue(timers.clone()), Some(waker.clone()));
+                let task = Supervised::new(engine, start_point);
+                crate::task::spawn(&handle, task, timers, timer2bft, notify);
+                return Some(waker);
+            }
+        }
 
         // start timer module.
+        let (bft2timer, timer4bft) = bounded(channel_capacity);
         let _timer_thread = thread::Builder::new()
             .name("bft_timer".to_string())
             .spawn(move || {
-                let timer = WaitTimer::new(timer2bft, timer4bft);
+                let mut timer = WaitTimer::new(timer2bft, timer4bft);
+                timer.set_log_target(&log_target);
                 timer.start();
             })
             .unwrap_or_else(|_| panic!("Node {:?} starts time-thread failed!", local_address));
@@ -146,152 +589,653 @@ where
         let _main_thread = thread::Builder::new()
             .name("main_loop".to_string())
             .spawn(move || {
-                engine.load_wal_log();
+                let mut engine = create(bft2timer.into(), None);
+                engine.supervise(start_point);
+            })
+            .unwrap_or_else(|_| panic!("Node {:?} starts main-thread failed!", local_address));
+        None
+    }
 
-                loop {
-                    let mut get_timer_msg = Err(RecvError);
-                    let mut get_msg = Err(RecvError);
+    /// The supervisor of the main loop, which restarts the main loop from wal
+    /// after an internal error or a panic.
+    fn supervise(&mut self, mut start_point: Option<StartPoint>) {
+        let mut restart = false;
+        let mut backoff = RestartBackoff::default();
+        loop {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                if restart {
+                    self.restart_from_wal();
+                } else if let Some(start_point) = start_point.take() {
+                    self.start_at(start_point)?;
+                } else {
+                    self.load_wal_log();
+                }
+                self.run()
+            }));
+            match result {
+                Ok(Ok(())) => break,
+                Ok(Err(e)) => report_err(&e, &self.params, &*self.function),
+                Err(_) => error!(
+                    target: &self.params.log_target,
+                    "Node {:?} main loop panics", self.params.address
+                ),
+            }
+            thread::sleep(backoff.next(self));
+            restart = true;
+        }
+    }
 
-                    select! {
-                        recv(engine.timer_notity) -> msg => get_timer_msg = msg,
-                        recv(engine.msg_receiver) -> msg => get_msg = msg,
-                    }
+    /// The main loop, returns on receiving `Kill` or an internal error.
+    fn run(&mut self) -> BftResult<()> {
+        loop {
+            let mut get_timer_msg = Err(RecvError);
+            let mut get_msg = Err(RecvError);
+            let mut get_checked = Err(RecvError);
+            let mut get_block_checked = Err(RecvError);
+            // wake up when a failed transmission is due to retry or msgs wait in the inbox
+            let due = match self.next_due() {
+                Some(duration) => after(duration),
+                None => never(),
+            };
 
-                    if let Ok(msg) = get_timer_msg {
-                        handle_err(engine.timeout_process(msg, true), &engine.params.address);
-                    }
-                    if let Ok(msg) = get_msg {
-                        match msg {
-                            BftMsg::Kill => {
-                                break;
-                            }
-                            _ => {
-                                handle_err(engine.process(msg, true), &engine.params.address);
-                            }
-                        }
-                    }
+            select! {
+                recv(self.timer_notity) -> msg => get_timer_msg = msg,
+                recv(self.msg_receiver) -> msg => get_msg = msg,
+                recv(self.future_receiver) -> msg => get_msg = msg,
+                recv(self.sig_notify) -> msg => get_checked = msg,
+                recv(self.block_notify) -> msg => get_block_checked = msg,
+                recv(due) -> _ => {},
+            }
+            if self.turn(get_timer_msg, get_msg, get_checked, get_block_checked)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// A function to turn the main loop once without blocking on a worker of the shared
+    /// runtime, returns true on receiving `Kill`.
+    fn poll(&mut self) -> BftResult<bool> {
+        let get_timer_msg = self.timer_notity.try_recv().map_err(|_| RecvError);
+        let get_msg = self.try_recv_msg().map_err(|_| RecvError);
+        let get_checked = self.sig_notify.try_recv().map_err(|_| RecvError);
+        let get_block_checked = self.block_notify.try_recv().map_err(|_| RecvError);
+        self.turn(get_timer_msg, get_msg, get_checked, get_block_checked)
+    }
+
+    /// A function to take a msg from the channel, or else from the lane of the future heights.
+    fn try_recv_msg(&self) -> Result<BftMsg<B>, TryRecvError> {
+        self.msg_receiver
+            .try_recv()
+            .or_else(|_| self.future_receiver.try_recv())
+    }
+
+    /// A function to get the duration until the main loop is due without any msg, zero when
+    /// msgs wait in the inbox or to be fed again, or the time a failed transmission is due to
+    /// retry.
+    fn next_due(&self) -> Option<Duration> {
+        match self.inbox.as_ref() {
+            Some(inbox) if !inbox.is_empty() => Some(Duration::from_millis(0)),
+            _ if !self.refeed.is_empty() => Some(Duration::from_millis(0)),
+            _ => self.retry_queue.next_due(),
+        }
+    }
+
+    /// A function to process what the channels deliver in a turn of the main loop, returns
+    /// true on receiving `Kill`.
+    fn turn(
+        &mut self,
+        get_timer_msg: Result<TimeoutInfo, RecvError>,
+        get_msg: Result<BftMsg<B>, RecvError>,
+        get_checked: Result<SigChecked<B>, RecvError>,
+        get_block_checked: Result<BlockChecked, RecvError>,
+    ) -> BftResult<bool> {
+        // the msgs are processed in the span of the current step
+        #[cfg(feature = "tracing")]
+        let span = self.spans.current();
+        #[cfg(feature = "tracing")]
+        let _entered = span.enter();
+
+        if let Ok(msg) = get_timer_msg {
+            if let Some(tracer) = self.tracer.as_mut() {
+                let result = tracer.record_timeout(&msg);
+                self.check_trace_err(result);
+            }
+            let result = self.timeout_process(msg, true);
+            self.check_internal_err(result)?;
+        }
+        if let Ok(checked) = get_checked {
+            let result = self.process_checked(checked);
+            self.check_internal_err(result)?;
+        }
+        if let Ok(checked) = get_block_checked {
+            let result = self.process_block_checked(checked);
+            self.check_internal_err(result)?;
+        }
+        self.flush_refeed();
+        if let Ok(msg) = self.next_msg(get_msg) {
+            if let Some(tracer) = self.tracer.as_mut() {
+                let result = tracer.record_inbound(&msg);
+                self.check_trace_err(result);
+            }
+            match msg {
+                BftMsg::Kill => {
+                    return Ok(true);
                 }
-            })
-            .unwrap_or_else(|_| panic!("Node {:?} starts main-thread failed!", local_address));
+                _ => {
+                    let result = self.dispatch_or_process(msg);
+                    self.check_internal_err(result)?;
+                }
+            }
+        }
+        self.retry_transmit();
+        Ok(false)
     }
 
-    pub(crate) fn process(&mut self, msg: BftMsg, need_wal: bool) -> BftResult<()> {
+    /// A function to take the next msg to process. If the msgs are prioritized, the msg is
+    /// queued with the ones waiting in the channel and the most urgent one is taken.
+    fn next_msg(&mut self, msg: Result<BftMsg<B>, RecvError>) -> Result<BftMsg<B>, RecvError> {
+        if self.inbox.is_none() {
+            return msg;
+        }
+        if let Ok(msg) = msg {
+            self.queue_msg(msg);
+        }
+        while !self.inbox.as_ref().unwrap().is_full() {
+            match self.try_recv_msg() {
+                Ok(msg) => self.queue_msg(msg),
+                Err(_) => break,
+            }
+        }
+        self.inbox.as_mut().unwrap().pop().ok_or(RecvError)
+    }
+
+    fn queue_msg(&mut self, msg: BftMsg<B>) {
+        let urgent = self.is_urgent(&msg);
+        self.inbox.as_mut().unwrap().push(msg, urgent);
+    }
+
+    /// A function to check whether a msg is needed to reach the quorum of the current height
+    /// and round, the undecodable msgs are rejected later.
+    fn is_urgent(&self, msg: &BftMsg<B>) -> bool {
         match msg {
-            BftMsg::Proposal(encode) => {
-                if self.consensus_power {
-                    let (signed_proposal_encode, block) = extract_two(&encode)?;
-                    let signed_proposal: SignedProposal = rlp::decode(&signed_proposal_encode)
-                        .map_err(|e| {
-                            BftError::DecodeErr(format!("signed_proposal encounters {:?}", e))
-                        })?;
-                    debug!(
-                        "Node {:?} receives {:?}",
-                        self.params.address, &signed_proposal
-                    );
-                    self.check_and_save_proposal(
-                        &signed_proposal,
-                        &block.into(),
-                        &signed_proposal_encode,
-                        need_wal,
-                    )?;
-
-                    let proposal = signed_proposal.proposal;
-                    if self.step <= Step::ProposeWait {
-                        self.handle_proposal(&proposal)?;
-                        self.set_proposal(proposal);
-                        if self.step == Step::ProposeWait {
-                            self.transmit_prevote(false)?;
-                        }
-                    }
-                    // handle commit after proposal is ready while bft process blocked in Commit Step
-                    if self.step == Step::Commit {
-                        info!("Node {:?} receives lacking proposal in commit step", self.params.address);
-                        self.handle_commit()?;
+            BftMsg::Proposal(encode) => extract_proposal(encode)
+                .ok()
+                .and_then(|(signed_proposal, _, _)| peek_height_round(signed_proposal, 0))
+                .is_some_and(|(height, round)| height == self.height && round == self.round),
+            BftMsg::Vote(encode) => peek_height_round(encode, 1)
+                .is_some_and(|(height, round)| height == self.height && round >= self.round),
+            BftMsg::VerifyResp(_) => true,
+            _ => false,
+        }
+    }
+
+    /// A function to pass internal errors up to the supervisor and handle the others.
+    fn check_internal_err(&self, result: BftResult<()>) -> BftResult<()> {
+        match result {
+            Err(BftError::InternalErr(e)) => Err(BftError::InternalErr(e)),
+            _ => {
+                handle_err(result, &self.params, &*self.function);
+                Ok(())
+            }
+        }
+    }
+
+    /// A function to stop tracing after the msg trace fails to be written.
+    pub(crate) fn check_trace_err(&mut self, result: BftResult<()>) {
+        if let Err(e) = result {
+            report_err(&e, &self.params, &*self.function);
+            self.tracer = None;
+        }
+    }
+
+    /// A function to process a msg from the channel, the signatures of proposals and votes
+    /// are checked by the verifier pool first if it is enabled.
+    fn dispatch_or_process(&mut self, msg: BftMsg<B>) -> BftResult<()> {
+        if self.consensus_power {
+            self.filter_ingress(&msg)?;
+        }
+        if self.verifier.is_none() || !self.consensus_power {
+            return self.process(msg, true);
+        }
+        let sigs = match &msg {
+            BftMsg::Proposal(encode) => self.collect_proposal_sigs(encode)?,
+            BftMsg::Vote(encode) => self.collect_vote_sigs(encode)?,
+            _ => return self.process(msg, true),
+        };
+        if sigs.is_empty() {
+            return self.process_checked(SigChecked {
+                msg,
+                sigs: CheckedSigs::new(),
+            });
+        }
+        let job = SigJob { msg, sigs };
+        match self.verifier.as_ref().unwrap().dispatch(job) {
+            Ok(()) => Ok(()),
+            Err(job) => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} checks signatures in the main loop as the verifier pool is busy",
+                    self.params.address
+                );
+                self.process_checked(SigChecked {
+                    msg: job.msg,
+                    sigs: CheckedSigs::new(),
+                })
+            }
+        }
+    }
+
+    /// A function to process a msg whose signatures are checked by the verifier pool.
+    fn process_checked(&mut self, checked: SigChecked<B>) -> BftResult<()> {
+        self.save_checked_sigs(checked.sigs);
+        let result = self.process(checked.msg, true);
+        self.checked_sigs = None;
+        result
+    }
+
+    /// A function to save the result of a block checked ahead of its round, which is reused
+    /// when the proposal is handled in its round.
+    fn process_block_checked(&mut self, checked: BlockChecked) -> BftResult<()> {
+        let BlockChecked {
+            height,
+            block_hash,
+            result,
+        } = checked;
+        self.speculating.remove(&block_hash);
+        if height != self.height {
+            return Err(BftError::ObsoleteMsg(
+                format!("speculative check of block_hash {:?}", block_hash).into(),
+            ));
+        }
+        match result {
+            Ok(verify_resp) => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} checks {:?} of block_hash {:?} ahead",
+                    self.params.address,
+                    verify_resp,
+                    block_hash
+                );
+                // the verify results are only replayed from wal with verify_req
+                self.check_and_save_verify_resp(
+                    &verify_resp,
+                    &block_hash,
+                    cfg!(feature = "verify_req"),
+                )?;
+                #[cfg(feature = "verify_req")]
+                self.check_verify_wait()?;
+            }
+            // the block is checked again in its round
+            Err(e) => warn!(
+                target: &self.params.log_target,
+                "Node {:?} encounters BftError::CheckTxsFailed({:?})", self.params.address, e
+            ),
+        }
+        Ok(())
+    }
+
+    /// A function to go on with the precommit if the node waits for the verify result.
+    #[cfg(feature = "verify_req")]
+    fn check_verify_wait(&mut self) -> BftResult<()> {
+        if self.step == Step::VerifyWait {
+            if self.check_verify()? == VerifyResult::Undetermined {
+                self.change_to_step(Step::VerifyWait);
+            } else {
+                self.transmit_precommit(false)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn process(&mut self, msg: BftMsg<B>, need_wal: bool) -> BftResult<()> {
+        match msg {
+            BftMsg::Proposal(encode) if self.consensus_power => {
+                let (signed_proposal_encode, block, codec) = extract_proposal(&encode)?;
+                let block = decompress_block(block, codec, self.params.config.max_block_size)?;
+                check_block_size(&block, &self.params.config)?;
+                check_signed_proposal_size(signed_proposal_encode, &self.params.config)?;
+                let signed_proposal: SignedProposal =
+                    rlp::decode(signed_proposal_encode).map_err(|e| {
+                        BftError::DecodeErr(format!("signed_proposal encounters {:?}", e).into())
+                    })?;
+                let block = B::from_bytes(&block)
+                    .map(Arc::new)
+                    .map_err(|e| BftError::DecodeErr(format!("block encounters {:?}", e).into()))?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &signed_proposal
+                );
+                self.check_and_save_proposal(
+                    &signed_proposal,
+                    &block,
+                    signed_proposal_encode,
+                    need_wal,
+                )?;
+
+                let proposal = signed_proposal.proposal;
+                if self.step <= Step::ProposeWait {
+                    self.handle_proposal(&proposal)?;
+                    self.set_proposal(proposal);
+                    if self.step == Step::ProposeWait {
+                        self.transmit_prevote(false)?;
                     }
                 }
+                // handle commit after proposal is ready while bft process blocked in Commit Step
+                if self.step == Step::Commit {
+                    info!(
+                        target: &self.params.log_target,
+                        "Node {:?} receives lacking proposal in commit step", self.params.address
+                    );
+                    self.handle_commit()?;
+                }
             }
 
-            BftMsg::Vote(encode) => {
-                if self.consensus_power {
-                    let signed_vote: SignedVote = rlp::decode(&encode).map_err(|e| {
-                        BftError::DecodeErr(format!("signed_vote encounters {:?}", e))
-                    })?;
-                    debug!("Node {:?} receives {:?}", self.params.address, signed_vote);
-                    self.check_and_save_vote(&signed_vote, need_wal)?;
-
-                    let vote = signed_vote.vote;
-                    match vote.vote_type {
-                        VoteType::Prevote => {
-                            if self.step <= Step::PrevoteWait {
-                                self.handle_vote(vote)?;
-                                if self.step >= Step::Prevote && self.check_prevote_count() {
-                                    self.change_to_step(Step::PrevoteWait);
-                                }
+            BftMsg::Vote(encode) if self.consensus_power => {
+                let signed_vote: SignedVote = rlp::decode(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("signed_vote encounters {:?}", e).into())
+                })?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, signed_vote
+                );
+                self.check_and_save_vote(&signed_vote, need_wal)?;
+
+                let vote = signed_vote.vote;
+                #[cfg(feature = "commit_stats")]
+                {
+                    if vote.height == self.height {
+                        self.stats.receive_vote(&vote.vote_type);
+                    }
+                }
+                match vote.vote_type {
+                    VoteType::Prevote => {
+                        if self.step <= Step::PrevoteWait {
+                            self.handle_vote(vote)?;
+                            if self.step >= Step::Prevote && self.check_prevote_count()? {
+                                self.change_to_step(Step::PrevoteWait);
                             }
                         }
-                        VoteType::Precommit => {
-                            if self.step < Step::Precommit {
-                                self.handle_vote(vote.clone())?;
-                            }
-                            if self.step == Step::Precommit || self.step == Step::PrecommitWait {
-                                self.handle_vote(vote)?;
-                                self.handle_precommit()?;
-                            }
+                    }
+                    VoteType::Precommit => {
+                        if self.step < Step::Precommit {
+                            self.handle_vote(vote.clone())?;
+                        }
+                        if self.step == Step::Precommit || self.step == Step::PrecommitWait {
+                            self.handle_vote(vote)?;
+                            self.handle_precommit()?;
                         }
                     }
                 }
             }
 
             BftMsg::Feed(feed) => {
-                debug!("Node {:?} receives {:?}", self.params.address, &feed);
-                self.check_and_save_feed(&feed, need_wal)?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &feed
+                );
+                let height = feed.height;
+                self.check_and_save_feed(feed, need_wal)?;
 
-                if self.step == Step::ProposeWait {
+                if height == self.height && self.step == Step::ProposeWait {
                     self.new_round_start(false)?;
                 }
             }
 
+            BftMsg::Tick => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives tick at height {}", self.params.address, self.height
+                );
+                self.handle_tick(need_wal)?;
+            }
+
+            BftMsg::RevokeFeed { height, block_hash } => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives revoking feed {:?} at height {}",
+                    self.params.address,
+                    block_hash,
+                    height
+                );
+                self.check_and_revoke_feed(height, &block_hash, need_wal)?;
+            }
+
+            BftMsg::KeyRotation(rotation) => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &rotation
+                );
+                self.check_and_save_key_rotation(rotation, need_wal)?;
+            }
+
+            BftMsg::Signature(ticket, signature) => {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives signature of ticket {}", self.params.address, ticket
+                );
+                self.handle_signature(ticket, signature)?;
+            }
+
             BftMsg::Status(status) => {
-                debug!("Node {:?} receives {:?}", self.params.address, &status);
-                self.check_and_save_status(&status, need_wal)?;
-                self.handle_status(status)?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &status
+                );
+                // an authority diff is based on the authorities of the status height, so the
+                // status arriving early waits until the node reaches its height
+                if status.height > self.height && status.authority_diff.is_some() {
+                    if status.height > self.height.saturating_add(CACHE_N) {
+                        return Err(BftError::HigherMsg(
+                            format!("{:?} at height {}", status, self.height).into(),
+                        ));
+                    }
+                    self.pending_statuses.insert(status.height, status);
+                    return Ok(());
+                }
+                let status = self.check_and_save_status(status, need_wal)?;
+                self.handle_status(status, need_wal)?;
             }
 
             #[cfg(feature = "verify_req")]
             BftMsg::VerifyResp(verify_resp) => {
-                debug!("Node {:?} receives {:?}", self.params.address, &verify_resp);
-                self.check_and_save_verify_resp(&verify_resp, need_wal)?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &verify_resp
+                );
+                let block_hash = self
+                    .proposals
+                    .get_proposal(self.height, verify_resp.round)
+                    .map(|signed_proposal| signed_proposal.proposal.block_hash)
+                    .ok_or_else(|| {
+                        BftError::ObsoleteMsg(format!("no proposal of {:?}", verify_resp).into())
+                    })?;
+                self.check_and_save_verify_resp(&verify_resp, &block_hash, need_wal)?;
+                self.check_verify_wait()?;
+            }
 
-                if self.step == Step::VerifyWait {
-                    if self.check_verify() == VerifyResult::Undetermined {
-                        self.change_to_step(Step::VerifyWait);
-                    } else {
-                        self.transmit_precommit(false)?;
-                    }
+            #[cfg(feature = "compact_block")]
+            BftMsg::GetTxs(encode) if self.consensus_power => {
+                let get_txs: GetTxs = rlp::decode(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("get_txs encounters {:?}", e).into())
+                })?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &get_txs
+                );
+                self.check_sender_limit(&get_txs.requester, need_wal)?;
+                if get_txs.height != self.height {
+                    return Err(BftError::ObsoleteMsg(format!("{:?}", get_txs).into()));
                 }
+                let txs = self
+                    .function
+                    .get_txs(&get_txs.block_hash, &get_txs.tx_hashes)
+                    .ok_or_else(|| {
+                        BftError::NotReady(format!("lacks txs of {:?}", get_txs).into())
+                    })?;
+                let txs = Txs {
+                    height: get_txs.height,
+                    block_hash: get_txs.block_hash,
+                    txs,
+                };
+                self.transmit(
+                    OutboundMsg::Txs(self.encode(&txs)),
+                    Target::Unicast(get_txs.requester),
+                );
+            }
+
+            #[cfg(feature = "compact_block")]
+            BftMsg::Txs(encode) if self.consensus_power => {
+                let txs: Txs = rlp::decode(&encode)
+                    .map_err(|e| BftError::DecodeErr(format!("txs encounters {:?}", e).into()))?;
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} receives {:?}", self.params.address, &txs
+                );
+                if txs.height != self.height {
+                    return Err(BftError::ObsoleteMsg(format!("{:?}", txs).into()));
+                }
+                // only the requested txs are accepted, and only once
+                let proposal_encode = self
+                    .txs_requests
+                    .get_mut(&txs.block_hash)
+                    .and_then(Option::take)
+                    .ok_or_else(|| {
+                        BftError::ObsoleteMsg(format!("unrequested {:?}", txs).into())
+                    })?;
+                self.function
+                    .add_txs(&txs.block_hash, &txs.txs)
+                    .map_err(|e| BftError::CheckTxFailed(format!("{:?} of {:?}", e, txs).into()))?;
+                self.process(BftMsg::Proposal(proposal_encode), true)?;
             }
 
             BftMsg::Pause => {
                 self.consensus_power = false;
-                info!("Node {:?} pauses bft process", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} pauses bft process", self.params.address
+                );
             }
 
             BftMsg::Start => {
                 self.consensus_power = true;
-                info!("Node {:?} starts bft process", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} starts bft process", self.params.address
+                );
             }
 
             BftMsg::Clear(proof) => {
                 info!(
-                    "Node {:?} receives clear with {:?}",
-                    self.params.address, &proof
+                    target: &self.params.log_target,
+                    "Node {:?} receives clear with {:?}", self.params.address, &proof
+                );
+                self.clear(proof)?;
+            }
+
+            BftMsg::ExportSnapshot(snapshot_sender) => {
+                let snapshot = self.export_snapshot();
+                snapshot_sender
+                    .send(snapshot)
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of snapshot", e).into()))?;
+            }
+
+            BftMsg::Dump(dump_sender) => {
+                let dump = self.debug_dump();
+                dump_sender
+                    .send(dump)
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of dump", e).into()))?;
+            }
+
+            BftMsg::MissingVoters(height, round, vote_type, voters_sender) => {
+                let voters = self.missing_voters(height, round, &vote_type);
+                voters_sender.send(voters).map_err(|e| {
+                    BftError::SendMsgErr(format!("{:?} of missing voters", e).into())
+                })?;
+            }
+
+            BftMsg::PeerStats(stats_sender) => {
+                stats_sender
+                    .send(self.peer_stats.to_map())
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of peer stats", e).into()))?;
+            }
+
+            BftMsg::IngressStats(stats_sender) => {
+                stats_sender.send(self.ingress_stats).map_err(|e| {
+                    BftError::SendMsgErr(format!("{:?} of ingress stats", e).into())
+                })?;
+            }
+
+            BftMsg::CacheUsage(usage_sender) => {
+                usage_sender
+                    .send(self.cache_usage())
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of cache usage", e).into()))?;
+            }
+
+            BftMsg::ProposerShares(shares_sender) => {
+                shares_sender
+                    .send(
+                        self.proposer_window
+                            .shares(&self.authority_manage.authorities),
+                    )
+                    .map_err(|e| {
+                        BftError::SendMsgErr(format!("{:?} of proposer shares", e).into())
+                    })?;
+            }
+
+            BftMsg::PruneWal(below_height, result_sender) => {
+                let result = self.wal_log.prune(below_height).map_err(|e| {
+                    BftError::SaveWalErr(
+                        ErrorInfo::new(format!("{:?} of pruning below {}", e, below_height))
+                            .height(below_height)
+                            .kind(MsgKind::Wal),
+                    )
+                });
+                result_sender
+                    .send(result)
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of prune wal", e).into()))?;
+            }
+
+            BftMsg::Trace(path, result_sender) => {
+                let result = match path {
+                    Some(path) => Tracer::open(&path).map(|tracer| {
+                        info!(
+                            target: &self.params.log_target,
+                            "Node {:?} starts tracing to {}", self.params.address, path
+                        );
+                        self.tracer = Some(tracer);
+                    }),
+                    None => {
+                        self.tracer = None;
+                        Ok(())
+                    }
+                };
+                result_sender
+                    .send(result)
+                    .map_err(|e| BftError::SendMsgErr(format!("{:?} of trace", e).into()))?;
+            }
+
+            BftMsg::ImportSnapshot(snapshot) => {
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} imports snapshot at height {}", self.params.address, snapshot.height
                 );
-                self.clear(proof);
+                self.import_snapshot(snapshot)?;
             }
 
             BftMsg::Corrupt => {
-                info!("Node {:?} is corrupt to be byzantine", self.params.address);
-                self.is_byzantine = true;
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} is corrupt to be byzantine", self.params.address
+                );
+                self.byzantine = Some(ByzantineStrategy::Random);
+            }
+
+            BftMsg::CorruptWith(strategy) => {
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} is corrupt to be byzantine by {:?}", self.params.address, strategy
+                );
+                self.byzantine = Some(strategy);
             }
 
             _ => {}
@@ -302,30 +1246,47 @@ where
 
     pub(crate) fn timeout_process(&mut self, tminfo: TimeoutInfo, need_wal: bool) -> BftResult<()> {
         if tminfo.height < self.height {
-            return Err(BftError::ObsoleteTimer(format!(
-                "TimeoutInfo height: {} < self.height: {}",
-                tminfo.height, self.height
-            )));
+            return Err(BftError::ObsoleteTimer(
+                format!(
+                    "TimeoutInfo height: {} < self.height: {}",
+                    tminfo.height, self.height
+                )
+                .into(),
+            ));
         }
         if tminfo.height == self.height && tminfo.round < self.round {
-            return Err(BftError::ObsoleteTimer(format!(
-                "TimeoutInfo round: {} < self.round: {}",
-                tminfo.round, self.round
-            )));
+            return Err(BftError::ObsoleteTimer(
+                format!(
+                    "TimeoutInfo round: {} < self.round: {}",
+                    tminfo.round, self.round
+                )
+                .into(),
+            ));
         }
         if tminfo.height == self.height && tminfo.round == self.round && tminfo.step != self.step {
-            return Err(BftError::ObsoleteTimer(format!(
-                "TimeoutInfo step: {:?} != self.step: {:?}",
-                tminfo.step, self.step
-            )));
+            return Err(BftError::ObsoleteTimer(
+                format!(
+                    "TimeoutInfo step: {:?} != self.step: {:?}",
+                    tminfo.step, self.step
+                )
+                .into(),
+            ));
         }
 
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            height = tminfo.height,
+            round = tminfo.round,
+            step = ?tminfo.step,
+            "timeout"
+        );
         if need_wal && tminfo.step != Step::Prevote && tminfo.step != Step::Precommit {
             handle_err(
                 self.wal_log
                     .save(self.height, LogType::TimeOutInfo, &rlp::encode(&tminfo))
-                    .or_else(|e| Err(BftError::SaveWalErr(format!("{:?} of {:?}", e, &tminfo)))),
-                &self.params.address,
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, &tminfo).into())),
+                &self.params,
+                &*self.function,
             );
         }
 
@@ -345,7 +1306,7 @@ where
 
                 #[cfg(feature = "verify_req")]
                 {
-                    let verify_result = self.check_verify();
+                    let verify_result = self.check_verify()?;
                     if verify_result == VerifyResult::Undetermined {
                         self.change_to_step(Step::VerifyWait);
                         return Ok(());
@@ -355,7 +1316,7 @@ where
                 self.transmit_precommit(false)?;
             }
             Step::Precommit => {
-                handle_err(self.transmit_prevote(true), &self.params.address);
+                handle_err(self.transmit_prevote(true), &self.params, &*self.function);
                 self.transmit_precommit(true)?;
             }
             Step::PrecommitWait => {
@@ -365,69 +1326,63 @@ where
 
             #[cfg(feature = "verify_req")]
             Step::VerifyWait => {
-                let signed_proposal = self
-                    .proposals
-                    .get_proposal(self.height, self.round)
-                    .ok_or_else(|| {
-                        BftError::ShouldNotHappen(
-                            "can not fetch proposal from cache when handle commit".to_string(),
-                        )
-                    })?;
-                let proposal = signed_proposal.proposal;
-                if proposal.lock_round.is_none() {
-                    self.clean_polc();
+                self.verify_timed_out = Some((self.height, self.round));
+                if self.check_verify()? == VerifyResult::Undetermined {
+                    return Err(BftError::ShouldNotHappen(
+                        "verification is undetermined after timeout"
+                            .to_string()
+                            .into(),
+                    ));
                 }
                 self.transmit_precommit(false)?;
             }
 
             Step::CommitWait => {
-                self.set_status(&self.status.clone().unwrap());
-                self.goto_new_height(self.height + 1);
-                handle_err(self.flush_cache(), &self.params.address);
+                self.set_status(&self.status.clone().unwrap(), need_wal)?;
+                self.goto_new_height(next_height(self.height)?, need_wal);
+                handle_err(self.flush_cache(), &self.params, &*self.function);
                 self.new_round_start(true)?;
             }
-            _ => error!("Invalid Timeout Info!"),
+            _ => error!(target: &self.params.log_target, "Invalid Timeout Info!"),
         }
 
         Ok(())
     }
 
-    fn handle_proposal(&self, proposal: &Proposal) -> BftResult<()> {
-        if proposal.height == self.height - 1 {
+    fn handle_proposal(&mut self, proposal: &Proposal) -> BftResult<()> {
+        if self.is_last_height(proposal.height) {
             if self.last_commit_round.is_some() && proposal.round >= self.last_commit_round.unwrap()
             {
                 // deal with height fall behind one, round ge last commit round
-                self.retransmit_lower_votes(proposal.round)?;
+                self.retransmit_lower_votes(proposal.round, &proposal.proposer)?;
             }
-            return Err(BftError::ObsoleteMsg(format!(
-                "1 height lower of {:?}",
-                proposal
-            )));
+            return Err(BftError::ObsoleteMsg(
+                format!("1 height lower of {:?}", proposal).into(),
+            ));
         } else if proposal.round < self.round {
-            return Err(BftError::ObsoleteMsg(format!(
-                "lower round of {:?}",
-                proposal
-            )));
+            return Err(BftError::ObsoleteMsg(
+                format!("lower round of {:?}", proposal).into(),
+            ));
         }
 
         Ok(())
     }
 
     fn handle_vote(&mut self, vote: Vote) -> BftResult<()> {
-        if vote.height == self.height - 1 {
+        if self.is_last_height(vote.height) {
             if self.last_commit_round.is_some() && vote.round >= self.last_commit_round.unwrap() {
                 // deal with height fall behind one, round ge last commit round
-                let voter = vote.voter.clone();
+                let voter = vote.voter;
                 let trans_flag = self.filter_height(&voter);
 
                 if trans_flag {
                     self.height_filter.insert(voter, Instant::now());
-                    self.retransmit_lower_votes(vote.round)?;
+                    self.retransmit_lower_votes(vote.round, &voter)?;
                 }
             }
         } else if vote.height == self.height && self.round != 0 && vote.round == self.round - 1 {
             // deal with equal height, round fall behind
-            let voter = vote.voter.clone();
+            let voter = vote.voter;
             let trans_flag = self.filter_round(&voter);
 
             if trans_flag {
@@ -437,11 +1392,11 @@ where
         } else if vote.height == self.height && vote.round >= self.round {
             return Ok(());
         }
-        Err(BftError::ObsoleteMsg(format!("{:?}", &vote)))
+        Err(BftError::ObsoleteMsg(format!("{:?}", &vote).into()))
     }
 
     fn handle_precommit(&mut self) -> BftResult<()> {
-        let result = self.check_precommit_count();
+        let result = self.check_precommit_count()?;
         match result {
             PrecommitRes::Above => self.change_to_step(Step::PrecommitWait),
             PrecommitRes::Nil => {
@@ -461,20 +1416,27 @@ where
     }
 
     fn handle_commit(&mut self) -> BftResult<()> {
-        let lock_status = self
-            .lock_status
-            .clone()
-            .unwrap_or_else(|| panic!("Node {:?} has no lock when commit!", self.params.address));
+        let lock_status = self.lock_status.clone().ok_or_else(|| {
+            BftError::InternalErr("no lock when handle commit".to_string().into())
+        })?;
 
         let proof = self.generate_proof(lock_status.clone());
         self.set_proof(&proof);
 
-        let signed_proposal = self
-            .proposals
-            .get_proposal(self.height, self.round)
+        // the proposal of the round may be of another block, when the node precommits the
+        // block it locked at an earlier round, so the latest proposal of the locked block is
+        // committed
+        let height = self.height;
+        let proposals = &mut self.proposals;
+        let signed_proposal = (0..=self.round)
+            .rev()
+            .filter_map(|round| proposals.get_proposal(height, round))
+            .find(|signed_proposal| signed_proposal.proposal.block_hash == lock_status.block_hash)
             .ok_or_else(|| {
                 BftError::NotReady(
-                    "can not fetch proposal from cache when handle commit".to_string(),
+                    "can not fetch proposal from cache when handle commit"
+                        .to_string()
+                        .into(),
                 )
             })?;
         let proposal = signed_proposal.proposal;
@@ -482,72 +1444,306 @@ where
         let block = self
             .blocks
             .get_block(self.height, &proposal.block_hash)
-            .ok_or_else(|| {
-                BftError::ShouldNotHappen("can not fetch block from cache when commit".to_string())
-            })?
-            .clone();
-        #[cfg(feature = "compact_block")]
-        let block = self
-            .verify_results
-            .get(&self.round)
             .ok_or_else(|| {
                 BftError::ShouldNotHappen(
-                    "can not fetch complete block from cache when commit".to_string(),
+                    "can not fetch block from cache when commit"
+                        .to_string()
+                        .into(),
                 )
             })?
-            .clone()
-            .complete_block;
+            .clone();
+        #[cfg(feature = "compact_block")]
+        let block = {
+            let verify_resp = self
+                .verify_results
+                .get(&lock_status.block_hash)
+                .ok_or_else(|| {
+                    BftError::ShouldNotHappen(
+                        "can not fetch complete block from cache when commit"
+                            .to_string()
+                            .into(),
+                    )
+                })?;
+            B::from_bytes(&verify_resp.complete_block)
+                .map(Arc::new)
+                .map_err(|e| {
+                    BftError::DecodeErr(format!("complete block encounters {:?}", e).into())
+                })?
+        };
 
+        let precommits = if self.params.config.commit_precommits {
+            let mut precommits = lock_status.votes.clone();
+            precommits.sort_by_key(|signed_vote| signed_vote.vote.voter);
+            precommits
+        } else {
+            Vec::new()
+        };
+        #[cfg(feature = "commit_stats")]
+        let stats = {
+            let proposers = (0..=self.round)
+                .map(|round| self.get_proposer(self.height, round).copied())
+                .collect::<BftResult<Vec<Address>>>()?;
+            self.stats.build(self.round, proposers)
+        };
         let commit = Commit {
             height: self.height,
             block,
             proof,
-            address: proposal.proposer.clone(),
+            address: proposal.proposer,
+            precommits,
+            timestamp: proposal.timestamp,
+            #[cfg(feature = "commit_stats")]
+            stats,
         };
 
         info!(
+            target: &self.params.log_target,
             "Node {:?} commits {:?} at height {:?}, consumes consensus time {:?}",
             self.params.address,
             lock_status.block_hash,
             self.height,
             Instant::now() - self.htime
         );
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            height = self.height,
+            round = self.round,
+            block_hash = ?lock_status.block_hash,
+            "commit"
+        );
 
-        let function = self.function.clone();
-        let sender = self.msg_sender.clone();
-        let address = self.params.address.clone();
-        thread::spawn(move || {
-            handle_err(
-                function
-                    .commit(commit)
-                    .map_err(|e| BftError::CommitFailed(format!("{:?}", e)))
-                    .and_then(|status| {
-                        sender
-                            .send(BftMsg::Status(status))
-                            .map_err(|e| BftError::SendMsgErr(format!("{:?}", e)))
-                    }),
-                &address,
+        let height = self.height;
+        if self.committing.swap(height, Ordering::SeqCst) == height {
+            debug!(
+                target: &self.params.log_target,
+                "Node {:?} skips the commit at height {}, which is in flight",
+                self.params.address,
+                height
             );
-        });
+        } else {
+            let function = self.function.clone();
+            let sender = self.msg_sender.clone();
+            let params = self.params.clone();
+            let shared_height = self.shared_height.clone();
+            let committing = self.committing.clone();
+            let waker = self.waker.clone();
+            let round = self.round;
+            thread::spawn(move || {
+                let result = commit_with_retry(&*function, commit, round, &params, &shared_height)
+                    .and_then(|status| match status {
+                        Some(status) => {
+                            sender
+                                .send(BftMsg::Status(status))
+                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e).into()))?;
+                            if let Some(waker) = waker {
+                                waker();
+                            }
+                            Ok(())
+                        }
+                        // the status is delivered through the actuator later
+                        None => Ok(()),
+                    });
+                // a commit of a later height may have taken the flag
+                let _ = committing.compare_exchange(height, 0, Ordering::SeqCst, Ordering::SeqCst);
+                handle_err(result, &params, &*function);
+            });
+        }
 
         self.last_commit_round = Some(self.round);
         self.last_commit_block_hash = Some(proposal.block_hash);
+        self.record_proposer(proposal.proposer);
+        Ok(())
+    }
+
+    /// A function to start at the start point instead of replaying wal.
+    fn start_at(&mut self, start_point: StartPoint) -> BftResult<()> {
+        self.reset_at(start_point)?;
+        self.new_round_start(true)
+    }
+
+    /// A function to reset the states to the start point. The start point is saved to the
+    /// cleared wal as the proof and the status of the previous height, so a restart replays
+    /// from it.
+    fn reset_at(&mut self, start_point: StartPoint) -> BftResult<()> {
+        let StartPoint {
+            height,
+            proof,
+            authority_list,
+        } = start_point;
+        info!(
+            target: &self.params.log_target,
+            "Node {:?} starts at height {} with {:?}", self.params.address, height, proof
+        );
+        let last_height = height.checked_sub(1).ok_or_else(|| {
+            BftError::InitWalErr("start point at the genesis height".to_string().into())
+        })?;
+        if proof.height != 0 && proof.height != last_height {
+            return Err(BftError::CheckProofFailed(
+                format!(
+                    "the start height {} is mismatching with {:?}",
+                    height, proof
+                )
+                .into(),
+            ));
+        }
+
+        self.clear(Proof::default())?;
+        self.wal_log
+            .set_height(last_height)
+            .map_err(|e| BftError::InitWalErr(format!("{:?} of set_height", e).into()))?;
+        self.height = last_height;
+        self.proof = proof;
+        let status = Status {
+            height: last_height,
+            interval: None,
+            authority_list,
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
+        };
+        let status = self.check_and_save_status(status, true)?;
+        self.set_status(&status, true)?;
+        self.goto_new_height(height, true);
         Ok(())
     }
 
-    fn handle_status(&mut self, status: Status) -> BftResult<()> {
+    /// A function to export the consensus state of the current height.
+    fn export_snapshot(&mut self) -> StateSnapshot {
+        let (lock_round, lock_block_hash, lock_block, lock_votes) = match self.lock_status.clone() {
+            Some(lock_status) => {
+                let block = self
+                    .blocks
+                    .get_block(self.height, &lock_status.block_hash)
+                    .map(|block| Block::from(block.to_bytes()))
+                    .unwrap_or_else(|| Block::from(Vec::new()));
+                (
+                    Some(lock_status.round),
+                    lock_status.block_hash,
+                    block,
+                    lock_status.votes,
+                )
+            }
+            None => (None, Hash::default(), Block::from(Vec::new()), Vec::new()),
+        };
+        let authority_manage = &self.authority_manage;
+        StateSnapshot {
+            height: self.height,
+            proof: self.proof.clone(),
+            authority_list: authority_manage.authorities.clone(),
+            authority_list_old: authority_manage.authorities_old.clone(),
+            authority_h_old: authority_manage.authority_h_old,
+            scheduled_authorities: authority_manage.pending_schedule(),
+            lock_round,
+            lock_block_hash,
+            lock_block,
+            lock_votes,
+            votes: self.votes.get_votes(self.height),
+        }
+    }
+
+    /// A function to restart the consensus from the snapshot, the wal is discarded.
+    /// The cached votes are processed again as the received ones.
+    fn import_snapshot(&mut self, snapshot: StateSnapshot) -> BftResult<()> {
+        let config = &self.params.config;
+        check_list_len(
+            "authority_list_old",
+            snapshot.authority_list_old.len(),
+            config.max_authorities,
+        )?;
+        check_list_len(
+            "scheduled_authorities",
+            snapshot.scheduled_authorities.len(),
+            config.max_authorities,
+        )?;
+        check_list_len(
+            "lock_votes",
+            snapshot.lock_votes.len(),
+            config.max_lock_votes,
+        )?;
+        let lock = match snapshot.lock_round {
+            Some(round) => {
+                check_block_size(&snapshot.lock_block, config)?;
+                let block = B::from_bytes(&snapshot.lock_block)
+                    .map(Arc::new)
+                    .map_err(|e| {
+                        BftError::DecodeErr(format!("lock_block encounters {:?}", e).into())
+                    })?;
+                Some((round, block))
+            }
+            None => None,
+        };
+
+        let height = snapshot.height;
+        self.reset_at(StartPoint {
+            height,
+            proof: snapshot.proof,
+            authority_list: snapshot.authority_list,
+        })?;
+        self.authority_manage.authorities_old = snapshot.authority_list_old;
+        self.authority_manage.authority_h_old = snapshot.authority_h_old;
+        self.refresh_priorities();
+        self.authority_manage
+            .schedule_authorities(snapshot.scheduled_authorities)?;
+        self.save_authorities(height);
+
+        if let Some((round, block)) = lock {
+            let block_hash = snapshot.lock_block_hash;
+            if self.blocks.add(height, &block_hash, &block) {
+                handle_err(
+                    self.wal_log
+                        .save(
+                            height,
+                            LogType::Block,
+                            &encode_block(height, &*block, &block_hash),
+                        )
+                        .map_err(|e| {
+                            BftError::SaveWalErr(format!("{:?} of the locked block", e).into())
+                        }),
+                    &self.params,
+                    &*self.function,
+                );
+            }
+            self.block_hash = Some(block_hash);
+            self.lock_status = Some(LockStatus {
+                block_hash,
+                round,
+                votes: snapshot.lock_votes,
+            });
+        }
+        for signed_vote in snapshot.votes.iter() {
+            handle_err(
+                self.send_bft_msg(BftMsg::Vote(self.encode(signed_vote))),
+                &self.params,
+                &*self.function,
+            );
+        }
+        self.new_round_start(true)
+    }
+
+    fn handle_status(&mut self, status: Status, need_wal: bool) -> BftResult<()> {
         // commit timeout since pub block to chain,so resending the block
-        if self.height > 0 && status.height == self.height - 1 && self.step >= Step::Commit {
+        if self.is_last_height(status.height) && self.step >= Step::Commit {
             self.handle_commit()?;
         }
 
         // receive a rich status that height ge self.height is the only way to go to new height
         if status.height >= self.height {
             self.status = Some(status.clone());
+            // a replayed status of a height the wal has passed was waited for before the
+            // crash, such as the genesis one, so the node goes to the next height at once
+            let waited = !need_wal && self.wal_log.height() > status.height;
+
+            if status.height == self.height && !waited && self.params.config.external_tick {
+                self.change_to_step(Step::CommitWait);
+                if self.tick_pending {
+                    return self.handle_tick(need_wal);
+                }
+                return Ok(());
+            }
 
             #[cfg(not(feature = "machine_gun"))]
             {
-                if status.height == self.height {
+                if status.height == self.height && !waited {
                     let cost_time = Instant::now() - self.htime;
                     let interval = self.params.timer.get_total_duration();
                     let tv = if cost_time < interval {
@@ -556,7 +1752,7 @@ where
                         Duration::new(0, 0)
                     };
                     self.change_to_step(Step::CommitWait);
-                    self.set_timer(tv, Step::CommitWait);
+                    self.set_timer(tv, Step::CommitWait)?;
                     return Ok(());
                 }
             }
@@ -567,67 +1763,101 @@ where
                 self.last_commit_round = None;
             }
 
-            self.set_status(&status);
-            self.goto_new_height(status.height + 1);
-            handle_err(self.flush_cache(), &self.params.address);
+            self.set_status(&status, need_wal)?;
+            self.goto_new_height(next_height(status.height)?, need_wal);
+            handle_err(self.flush_cache(), &self.params, &*self.function);
             self.new_round_start(true)?;
 
             debug!(
-                "Node {:?} receives status, goto new height {:?}",
-                self.params.address,
-                status.height + 1
+                target: &self.params.log_target,
+                "Node {:?} receives status, goto new height {:?}", self.params.address, self.height
             );
             return Ok(());
         }
-        Err(BftError::ObsoleteMsg(format!("{:?}", &status)))
+        Err(BftError::ObsoleteMsg(format!("{:?}", &status).into()))
+    }
+
+    /// A function to begin the next height on a tick in the externally ticked mode, the tick
+    /// arriving before the status of the height is kept until the status arrives.
+    fn handle_tick(&mut self, need_wal: bool) -> BftResult<()> {
+        if !self.params.config.external_tick {
+            return Err(BftError::NotReady(
+                "tick without external_tick".to_string().into(),
+            ));
+        }
+        if self.step != Step::CommitWait {
+            self.tick_pending = true;
+            return Ok(());
+        }
+        self.tick_pending = false;
+        // the tick is saved in wal as the timeout of CommitWait to be replayed
+        let timestamp = Instant::now();
+        let tminfo = TimeoutInfo {
+            timestamp,
+            duration: (timestamp - self.htime).as_nanos() as u64,
+            height: self.height,
+            round: self.round,
+            step: Step::CommitWait,
+        };
+        self.timeout_process(tminfo, need_wal)
     }
 
     fn transmit_proposal(&mut self) -> BftResult<()> {
-        if self.is_byzantine {
-            return self.transmit_byzantine_proposal();
+        match self.byzantine {
+            Some(ByzantineStrategy::Random) => return self.transmit_byzantine_proposal(),
+            Some(ByzantineStrategy::ConflictingProposals) => {
+                return self.transmit_conflicting_proposals()
+            }
+            // the vote strategies propose as honest nodes do
+            Some(ByzantineStrategy::LockAmnesia) | Some(ByzantineStrategy::FlipFlop) | None => {}
         }
 
         if self.lock_status.is_none()
-            && (self.feed.is_none() || self.proof.height != self.height - 1)
+            && (self.feed.is_none() || !self.is_last_height(self.proof.height))
         {
             // if a proposer find there is no proposal nor lock, goto step proposewait
-            let coef = if self.round > PROPOSAL_TIMES_COEF {
-                PROPOSAL_TIMES_COEF
-            } else {
-                self.round
-            };
-
-            self.set_timer(
-                self.params.timer.get_propose() * 2u32.pow(coef as u32),
-                Step::ProposeWait,
-            );
-            return Err(BftError::NotReady(format!(
-                "transmit proposal (feed: {:?}, proof: {:?} lock_status: {:?})",
-                self.feed, self.proof, self.lock_status
-            )));
+            self.set_timer(self.get_propose_timeout(), Step::ProposeWait)?;
+            return Err(BftError::NotReady(
+                format!(
+                    "transmit proposal (feed: {:?}, proof: {:?} lock_status: {:?})",
+                    self.feed, self.proof, self.lock_status
+                )
+                .into(),
+            ));
         }
 
-        let msg = if self.lock_status.is_some() {
+        let encode = if self.lock_status.is_some() {
             // if is locked, boradcast the lock proposal
             debug!(
-                "Node {:?} is ready to transmit a locked proposal",
-                self.params.address
+                target: &self.params.log_target,
+                "Node {:?} is ready to transmit a locked proposal", self.params.address
             );
-            let lock_status = self.lock_status.clone().unwrap();
+            let lock_status = self.lock_status.clone().ok_or_else(|| {
+                BftError::InternalErr("no lock when transmit locked proposal".to_string().into())
+            })?;
             let lock_round = lock_status.round;
-            let lock_votes = lock_status.votes;
 
-            let lock_signed_proposal = self
-                .proposals
-                .get_proposal(self.height, lock_round)
-                .unwrap_or_else(|| {
-                    panic!(
-                        "Node {:?} can not get lock_proposal, it should not happen!",
-                        self.params.address
-                    )
-                });
+            // the lock may be set by the votes of a proposal other than the cached one, when
+            // the proposer of the lock round sends conflicting proposals
+            let lock_signed_proposal =
+                match self.proposals.get_proposal(self.height, lock_round).filter(
+                    |signed_proposal| signed_proposal.proposal.block_hash == lock_status.block_hash,
+                ) {
+                    Some(lock_signed_proposal) => lock_signed_proposal,
+                    None => {
+                        self.set_timer(self.get_propose_timeout(), Step::ProposeWait)?;
+                        return Err(BftError::NotReady(
+                            format!(
+                                "transmit proposal without the lock proposal (lock_status: {:?})",
+                                lock_status
+                            )
+                            .into(),
+                        ));
+                    }
+                };
             let lock_proposal = lock_signed_proposal.proposal;
             let block_hash = lock_proposal.block_hash;
+            let lock_votes = lock_status.votes;
 
             let proposal = Proposal {
                 height: self.height,
@@ -636,22 +1866,19 @@ where
                 proof: lock_proposal.proof,
                 lock_round: Some(lock_round),
                 lock_votes,
-                proposer: self.params.address.clone(),
+                proposer: self.params.address,
+                timestamp: self.proposal_timestamp(),
             };
-            let encode = self.build_signed_proposal_encode(&proposal)?;
-            BftMsg::Proposal(encode)
+            self.build_signed_proposal_encode(&proposal)?
         } else {
             // if is not locked, transmit the cached proposal
-            let block_hash = self.feed.clone().unwrap_or_else(|| {
-                panic!(
-                    "Node {:?} has checked before, it should not happen!",
-                    self.params.address
-                )
-            });
-            self.block_hash = Some(block_hash.clone());
+            let block_hash = self.feed.ok_or_else(|| {
+                BftError::InternalErr("no feed when transmit new proposal".to_string().into())
+            })?;
+            self.block_hash = Some(block_hash);
             debug!(
-                "Node {:?} is ready to transmit a new proposal",
-                self.params.address
+                target: &self.params.log_target,
+                "Node {:?} is ready to transmit a new proposal", self.params.address
             );
 
             let proposal = Proposal {
@@ -661,191 +1888,232 @@ where
                 proof: self.proof.clone(),
                 lock_round: None,
                 lock_votes: Vec::new(),
-                proposer: self.params.address.clone(),
+                proposer: self.params.address,
+                timestamp: self.proposal_timestamp(),
             };
-            let encode = self.build_signed_proposal_encode(&proposal)?;
-            BftMsg::Proposal(encode)
+            self.build_signed_proposal_encode(&proposal)?
         };
         debug!(
+            target: &self.params.log_target,
             "Node {:?} transmits proposal at h:{}, r:{}",
-            self.params.address, self.height, self.round
+            self.params.address,
+            self.height,
+            self.round
         );
-        self.function.transmit(msg.clone());
-        self.send_bft_msg(msg)?;
+        self.transmit(OutboundMsg::Proposal(encode.clone()), Target::Broadcast);
+        self.send_bft_msg(BftMsg::Proposal(encode))?;
         Ok(())
     }
 
     pub(crate) fn transmit_prevote(&mut self, resend: bool) -> BftResult<()> {
-        if self.is_byzantine {
+        if self.byzantine == Some(ByzantineStrategy::Random) {
             return self.transmit_byzantine_prevote(resend);
         }
 
         let block_hash = if let Some(lock_status) = self.lock_status.clone() {
             lock_status.block_hash
-        } else if let Some(block_hash) = self.block_hash.clone() {
+        } else {
+            self.block_hash.unwrap_or_default()
+        };
+        let block_hash = if let Some(block_hash) = self.byzantine_vote_hash(&VoteType::Prevote) {
             block_hash
+        } else if self.params.config.lock_audit {
+            self.audit_prevote(block_hash)
         } else {
-            Hash::default()
+            block_hash
         };
+        let block_hash = self
+            .signed_vote_hash(self.height, self.round, &VoteType::Prevote)
+            .unwrap_or(block_hash);
 
         let vote = Vote {
             vote_type: VoteType::Prevote,
             height: self.height,
             round: self.round,
-            block_hash: block_hash.clone(),
-            voter: self.params.address.clone(),
+            block_hash,
+            voter: self.params.address,
         };
-        let signed_vote = self.build_signed_vote(&vote)?;
-        let msg = BftMsg::Vote(rlp::encode(&signed_vote));
+        let signed_vote = self.request_vote_sig(&vote)?;
 
         debug!(
+            target: &self.params.log_target,
             "Node {:?} prevotes to {:?} at h:{} r:{}",
-            self.params.address, block_hash, self.height, self.round
+            self.params.address,
+            block_hash,
+            self.height,
+            self.round
         );
-        self.function.transmit(msg.clone());
         if !resend {
             self.change_to_step(Step::Prevote);
-            handle_err(self.send_bft_msg(msg), &self.params.address);
+        }
+        // a vote signed asynchronously is sent when its signature is delivered
+        if let Some(signed_vote) = signed_vote {
+            self.send_own_vote(&signed_vote, resend);
         }
 
         self.set_timer(
             self.params.timer.get_prevote() * TIMEOUT_RETRANSE_COEF,
             Step::Prevote,
-        );
+        )?;
 
         Ok(())
     }
 
     fn transmit_precommit(&mut self, resend: bool) -> BftResult<()> {
-        if self.is_byzantine {
+        if self.byzantine == Some(ByzantineStrategy::Random) {
             return self.transmit_byzantine_precommit(resend);
         }
 
-        let block_hash = if let Some(lock_status) = self.lock_status.clone() {
-            lock_status.block_hash
+        let block_hash = match self.lock_status.clone() {
+            // precommit nil if the verification of the locked block times out
+            Some(_)
+                if self.is_verify_timed_out()
+                    && self.params.config.nil_vote_policy == NilVotePolicy::PrecommitNil =>
+            {
+                Hash::default()
+            }
+            Some(lock_status) => lock_status.block_hash,
+            None => {
+                self.block_hash = None;
+                Hash::default()
+            }
+        };
+        let block_hash = if let Some(block_hash) = self.byzantine_vote_hash(&VoteType::Precommit) {
+            block_hash
+        } else if self.params.config.lock_audit {
+            self.audit_precommit(block_hash)
         } else {
-            self.block_hash = None;
-            Hash::default()
+            block_hash
         };
+        let block_hash = self
+            .signed_vote_hash(self.height, self.round, &VoteType::Precommit)
+            .unwrap_or(block_hash);
 
         let vote = Vote {
             vote_type: VoteType::Precommit,
             height: self.height,
             round: self.round,
-            block_hash: block_hash.clone(),
-            voter: self.params.address.clone(),
+            block_hash,
+            voter: self.params.address,
         };
-        let signed_vote = self.build_signed_vote(&vote)?;
-        let msg = BftMsg::Vote(rlp::encode(&signed_vote));
+        let signed_vote = self.request_vote_sig(&vote)?;
 
         debug!(
+            target: &self.params.log_target,
             "Node {:?} precommits to {:?} at h:{:?}, r:{:?}",
-            self.params.address, block_hash, self.height, self.round
+            self.params.address,
+            block_hash,
+            self.height,
+            self.round
         );
-        self.function.transmit(msg.clone());
         if !resend {
             self.change_to_step(Step::Precommit);
-            handle_err(self.send_bft_msg(msg), &self.params.address);
+        }
+        if let Some(signed_vote) = signed_vote {
+            self.send_own_vote(&signed_vote, resend);
         }
 
         self.set_timer(
             self.params.timer.get_precommit() * TIMEOUT_RETRANSE_COEF,
             Step::Precommit,
-        );
+        )?;
         Ok(())
     }
 
-    fn retransmit_lower_votes(&self, round: Round) -> BftResult<()> {
-        if self.is_byzantine {
+    /// A function to retransmit the votes of the last height to the lagging [`target`].
+    fn retransmit_lower_votes(&mut self, round: Round, target: &Address) -> BftResult<()> {
+        if self.byzantine == Some(ByzantineStrategy::Random) {
             return self.retransmit_byzantine_lower_votes();
         }
 
         debug!(
-            "Node {:?} receives msg in lower height, retransmit votes",
-            self.params.address
+            target: &self.params.log_target,
+            "Node {:?} receives msg in lower height, retransmit votes", self.params.address
         );
+        let last_height = self.last_height().ok_or_else(|| {
+            BftError::ShouldNotHappen(
+                "retransmit lower votes at the genesis height"
+                    .to_string()
+                    .into(),
+            )
+        })?;
 
         let prevote = Vote {
             vote_type: VoteType::Prevote,
-            height: self.height - 1,
+            height: last_height,
             round,
-            block_hash: self.last_commit_block_hash.clone().unwrap(),
-            voter: self.params.address.clone(),
+            block_hash: self.last_commit_block_hash.unwrap(),
+            voter: self.params.address,
         };
         let signed_prevote = self.build_signed_vote(&prevote)?;
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_prevote)));
+        self.transmit(
+            OutboundMsg::Vote(self.encode(&signed_prevote)),
+            Target::Unicast(*target),
+        );
 
         let precommit = Vote {
             vote_type: VoteType::Precommit,
-            height: self.height - 1,
+            height: last_height,
             round,
-            block_hash: self.last_commit_block_hash.clone().unwrap(),
-            voter: self.params.address.clone(),
+            block_hash: self.last_commit_block_hash.unwrap(),
+            voter: self.params.address,
         };
         let signed_precommit = self.build_signed_vote(&precommit)?;
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
+        self.transmit(
+            OutboundMsg::Vote(self.encode(&signed_precommit)),
+            Target::Unicast(*target),
+        );
         Ok(())
     }
 
-    fn retransmit_nil_precommit(&self, vote: &Vote) -> BftResult<()> {
-        if self.is_byzantine {
+    fn retransmit_nil_precommit(&mut self, vote: &Vote) -> BftResult<()> {
+        if self.byzantine == Some(ByzantineStrategy::Random) {
             return self.retransmit_byzantine_nil_precommit();
         }
 
+        // the precommit signed at the round is retransmitted, nil if none is signed
+        let block_hash = self
+            .signed_vote_hash(vote.height, vote.round, &VoteType::Precommit)
+            .unwrap_or_default();
         let precommit = Vote {
             vote_type: VoteType::Precommit,
             height: vote.height,
             round: vote.round,
-            block_hash: Hash::default(),
-            voter: self.params.address.clone(),
+            block_hash,
+            voter: self.params.address,
         };
         let signed_precommit = self.build_signed_vote(&precommit)?;
 
         debug!(
-            "Node {:?} receives vote in lower round, retransmit nil precommit",
-            self.params.address
+            target: &self.params.log_target,
+            "Node {:?} receives vote in lower round, retransmit precommit to {:?}",
+            self.params.address,
+            block_hash
+        );
+        self.transmit(
+            OutboundMsg::Vote(self.encode(&signed_precommit)),
+            Target::Unicast(vote.voter),
         );
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_precommit)));
         Ok(())
     }
 
     fn new_round_start(&mut self, new_round: bool) -> BftResult<()> {
         if self.step != Step::ProposeWait {
             info!(
-                "Node {:?} starts h:{}, r:{}",
-                self.params.address, self.height, self.round
+                target: &self.params.log_target,
+                "Node {:?} starts h:{}, r:{}", self.params.address, self.height, self.round
             );
         }
         self.change_to_step(Step::ProposeWait);
 
         if self.is_proposer()? {
-            if new_round {
+            // a retained feed of the height, or the one got in advance for the first round,
+            // is proposed instead of getting a new block
+            let retained =
+                self.feed.is_some() && (self.params.config.retain_feed || self.round == INIT_ROUND);
+            if new_round && !retained {
                 self.clean_feed();
-                let function = self.function.clone();
-                let sender = self.msg_sender.clone();
-                let height = self.height;
-                let address = self.params.address.clone();
-
-                thread::spawn(move || {
-                    handle_err(
-                        function
-                            .get_block(height)
-                            .map_err(|e| BftError::GetBlockFailed(format!("{:?}", e)))
-                            .and_then(|(block, block_hash)| {
-                                sender
-                                    .send(BftMsg::Feed(Feed {
-                                        height,
-                                        block,
-                                        block_hash,
-                                    }))
-                                    .map_err(|e| BftError::SendMsgErr(format!("{:?}", e)))
-                            }),
-                        &address,
-                    );
-                });
+                self.request_feed(self.height);
             }
             self.transmit_proposal()?;
             self.transmit_prevote(false)?;
@@ -855,21 +2123,53 @@ where
     }
 
     #[inline]
-    fn goto_new_height(&mut self, new_height: Height) {
+    fn goto_new_height(&mut self, new_height: Height, need_wal: bool) {
         self.clean_save_info();
+        self.clean_feed();
+        // the block of the new height got in advance is fed
+        if self.pipelined_height == Some(new_height) {
+            // the block may be evicted from the cache
+            self.feed = self
+                .next_feed
+                .take()
+                .filter(|block_hash| self.blocks.get_block(new_height, block_hash).is_some());
+        }
+        self.next_feed = None;
+        self.speculating.clear();
+        self.tick_pending = false;
+        self.round_exhausted = false;
         self.clean_filter();
+        #[cfg(feature = "commit_stats")]
+        self.stats.reset();
         handle_err(
             self.wal_log
                 .set_height(new_height)
-                .map_err(|e| BftError::SaveWalErr(format!("{:?} of set_height", e))),
-            &self.params.address,
+                .map_err(|e| BftError::SaveWalErr(format!("{:?} of set_height", e).into())),
+            &self.params,
+            &*self.function,
         );
 
         self.height = new_height;
+        self.shared_height.store(new_height, Ordering::Relaxed);
         self.round = 0;
+        // the start time of the height is saved for the CommitWait after a restart
+        if need_wal {
+            handle_err(
+                self.wal_log
+                    .save(
+                        new_height,
+                        LogType::HeightStart,
+                        &encode_height_start(new_height, unix_millis()),
+                    )
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of height start", e).into())),
+                &self.params,
+                &*self.function,
+            );
+        }
 
         let now = Instant::now();
         info!(
+            target: &self.params.log_target,
             "Node {:?} goto new height {}, last height costs {:?} to reach consensus",
             self.params.address,
             new_height,
@@ -878,56 +2178,79 @@ where
         self.htime = now;
     }
 
-    #[inline]
+    /// A function to get the propose timeout of the round, which doubles each round up to
+    /// `PROPOSAL_TIMES_COEF` times.
+    fn get_propose_timeout(&self) -> Duration {
+        let coef = self.round.min(PROPOSAL_TIMES_COEF) as u32;
+        self.params
+            .timer
+            .get_propose()
+            .checked_mul(2u32.saturating_pow(coef))
+            .map_or(MAX_TIMEOUT, |timeout| timeout.min(MAX_TIMEOUT))
+    }
+
+    /// A function to go to the next round on the own timeouts. Reaching the `max_round` of the
+    /// config is reported once a height, and the rounds go on with the timeouts saturated.
     fn goto_next_round(&mut self) {
+        if let Some(max_round) = self.params.config.max_round {
+            if self.round >= max_round && !self.round_exhausted {
+                self.round_exhausted = true;
+                report_err(
+                    &BftError::RoundExhausted(
+                        ErrorInfo::new(format!("max round {} is reached", max_round))
+                            .at(self.height, self.round),
+                    ),
+                    &self.params,
+                    &*self.function,
+                );
+            }
+        }
         self.round_filter.clear();
         self.round += 1;
         handle_err(
             self.fetch_proposal(self.height, self.round),
-            &self.params.address,
+            &self.params,
+            &*self.function,
         );
     }
 
     fn is_proposer(&self) -> BftResult<bool> {
         let proposer = self.get_proposer(self.height, self.round)?;
         debug!(
+            target: &self.params.log_target,
             "Node {:?} chooses proposer {:?} at h:{}, r:{}",
-            self.params.address, proposer, self.height, self.round
+            self.params.address,
+            proposer,
+            self.height,
+            self.round
         );
 
         if self.params.address == *proposer {
             debug!(
+                target: &self.params.log_target,
                 "Node {:?} becomes proposer at h:{}, r:{}",
-                self.params.address, self.height, self.round
+                self.params.address,
+                self.height,
+                self.round
             );
             return Ok(true);
         }
 
         // if is not proposer, goto step proposewait
-        let coef = if self.round > PROPOSAL_TIMES_COEF {
-            PROPOSAL_TIMES_COEF
-        } else {
-            self.round
-        };
-
-        self.set_timer(
-            self.params.timer.get_propose() * 2u32.pow(coef as u32),
-            Step::ProposeWait,
-        );
+        self.set_timer(self.get_propose_timeout(), Step::ProposeWait)?;
         Ok(false)
     }
 
     fn set_proposal(&mut self, proposal: Proposal) {
         let block_hash = proposal.block_hash;
 
-        if proposal.lock_round.is_some()
-            && (self.lock_status.is_none()
-                || self.lock_status.clone().unwrap().round <= proposal.lock_round.unwrap())
-        {
+        if let Some(lock_round) = proposal.lock_round.filter(|lock_round| {
+            self.lock_status.is_none() || self.lock_status.clone().unwrap().round <= *lock_round
+        }) {
             // receive a proposal with a later PoLC
             debug!(
-                "Node {:?} handles a proposal with a PoLC",
-                self.params.address
+                target: &self.params.log_target,
+                "Node {:?} handles a proposal with a PoLC", self.params.address
             );
 
             if self.round < proposal.round {
@@ -935,10 +2258,10 @@ where
                 self.round = proposal.round;
             }
 
-            self.block_hash = Some(block_hash.clone());
+            self.block_hash = Some(block_hash);
             self.lock_status = Some(LockStatus {
                 block_hash,
-                round: proposal.lock_round.unwrap(),
+                round: lock_round,
                 votes: proposal.lock_votes,
             });
         } else if proposal.lock_round.is_none()
@@ -947,154 +2270,199 @@ where
         {
             // receive a proposal without PoLC
             debug!(
-                "Node {:?} handles a proposal without a PoLC",
-                self.params.address
+                target: &self.params.log_target,
+                "Node {:?} handles a proposal without a PoLC", self.params.address
             );
             self.block_hash = Some(block_hash);
         } else {
             debug!(
-                "Node {:?} handles a proposal with an earlier PoLC",
-                self.params.address
+                target: &self.params.log_target,
+                "Node {:?} handles a proposal with an earlier PoLC", self.params.address
             );
-            return;
         }
     }
 
-    fn check_prevote_count(&mut self) -> bool {
-        let mut flag = false;
-        for (round, prevote_count) in self.votes.prevote_count.iter() {
-            debug!(
-                "Node {:?} received {} prevotes in r:{}",
-                self.params.address, prevote_count, round
-            );
-            if self.cal_above_threshold(*prevote_count) && *round >= self.round {
-                flag = true;
-                if self.round < *round {
+    /// A function to jump to the quorum round if it is not earlier than the current round.
+    fn reach_quorum_round(&mut self, quorum: Option<Round>) -> bool {
+        match quorum {
+            Some(round) if round >= self.round => {
+                if self.round < round {
                     self.round_filter.clear();
-                    self.round = *round;
+                    self.round = round;
                 }
+                true
             }
+            _ => false,
         }
-        if !flag {
-            return false;
+    }
+
+    fn check_prevote_count(&mut self) -> BftResult<bool> {
+        debug!(
+            target: &self.params.log_target,
+            "Node {:?} received {:?} prevotes in r:{}",
+            self.params.address,
+            self.votes.prevote_count.get(&self.round),
+            self.round
+        );
+        let round = self.round;
+        if !self.reach_quorum_round(self.votes.prevote_quorum) {
+            return Ok(false);
         }
 
-        if let Some(prevote_set) =
-            self.votes
-                .get_voteset(self.height, self.round, &VoteType::Prevote)
+        if let Some((count, leading)) = self
+            .votes
+            .get_voteset(self.height, self.round, &VoteType::Prevote)
+            .map(|prevote_set| (prevote_set.count, prevote_set.leading))
         {
-            let mut tv = if self.cal_all_vote(prevote_set.count) {
+            let mut tv = if self.cal_all_vote(count) {
                 Duration::new(0, 0)
             } else {
                 self.params.timer.get_prevote()
             };
 
-            for (hash, count) in &prevote_set.votes_by_proposal {
-                if self.cal_above_threshold(*count) {
+            if let Some((hash, count)) = leading {
+                if self.cal_above_threshold(count) {
                     if self.lock_status.is_some()
                         && self.lock_status.clone().unwrap().round < self.round
                     {
-                        if hash.0.is_empty() {
+                        if hash.is_nil() {
                             // receive +2/3 prevote to nil, clean lock info
                             debug!(
+                                target: &self.params.log_target,
                                 "Node {:?} collects over 2/3 prevotes on nil at h:{}, r:{}",
-                                self.params.address, self.height, self.round
+                                self.params.address,
+                                self.height,
+                                self.round
                             );
                             self.clean_polc();
                             self.block_hash = None;
                         } else {
                             // receive a later PoLC, update lock info
-                            self.set_polc(hash, &prevote_set);
+                            self.set_polc(&hash, &VoteType::Prevote);
                         }
                     }
-                    if self.lock_status.is_none() && !hash.0.is_empty() {
+                    if self.lock_status.is_none() && !hash.is_nil() {
                         // receive a PoLC, lock the proposal
-                        self.set_polc(&hash, &prevote_set);
+                        self.set_polc(&hash, &VoteType::Prevote);
                     }
                     tv = Duration::new(0, 0);
-                    break;
                 }
             }
-            if self.step == Step::Prevote {
-                self.set_timer(tv, Step::PrevoteWait);
+            // the timer of the earlier round is obsolete after jumping to the quorum round
+            if self.step == Step::Prevote || (self.step == Step::PrevoteWait && self.round > round)
+            {
+                self.set_timer(tv, Step::PrevoteWait)?;
             }
-            return true;
+            return Ok(true);
         }
-        false
+        Ok(false)
     }
 
-    fn check_precommit_count(&mut self) -> PrecommitRes {
-        let mut flag = false;
-        for (round, precommit_count) in self.votes.precommit_count.iter() {
-            debug!(
-                "Node {:?} received {} precommits in r:{}",
-                self.params.address, precommit_count, round
-            );
-            if self.cal_above_threshold(*precommit_count) && *round >= self.round {
-                flag = true;
-                if self.round < *round {
-                    self.round_filter.clear();
-                    self.round = *round;
-                }
-            }
-        }
-        if !flag {
-            return PrecommitRes::Below;
+    fn check_precommit_count(&mut self) -> BftResult<PrecommitRes> {
+        debug!(
+            target: &self.params.log_target,
+            "Node {:?} received {:?} precommits in r:{}",
+            self.params.address,
+            self.votes.precommit_count.get(&self.round),
+            self.round
+        );
+        let round = self.round;
+        if !self.reach_quorum_round(self.votes.precommit_quorum) {
+            return Ok(PrecommitRes::Below);
         }
 
-        if let Some(precommit_set) =
-            self.votes
-                .get_voteset(self.height, self.round, &VoteType::Precommit)
+        if let Some((count, leading)) = self
+            .votes
+            .get_voteset(self.height, self.round, &VoteType::Precommit)
+            .map(|precommit_set| (precommit_set.count, precommit_set.leading))
         {
-            let tv = if self.cal_all_vote(precommit_set.count) {
+            let tv = if self.cal_all_vote(count) {
                 Duration::new(0, 0)
             } else {
                 self.params.timer.get_precommit()
             };
-            if !self.cal_above_threshold(precommit_set.count) {
-                return PrecommitRes::Below;
+            if !self.cal_above_threshold(count) {
+                return Ok(PrecommitRes::Below);
             }
 
-            for (hash, count) in &precommit_set.votes_by_proposal {
-                if self.cal_above_threshold(*count) {
-                    if hash.0.is_empty() {
+            if let Some((hash, count)) = leading {
+                if self.cal_above_threshold(count) {
+                    if hash.is_nil() {
                         debug!(
+                            target: &self.params.log_target,
                             "Node {:?} reaches nil consensus, goto next round {:?}",
                             self.params.address,
                             self.round + 1
                         );
-                        return PrecommitRes::Nil;
+                        return Ok(PrecommitRes::Nil);
                     } else {
-                        self.set_polc(&hash, &precommit_set);
-                        return PrecommitRes::Proposal;
+                        self.set_polc(&hash, &VoteType::Precommit);
+                        return Ok(PrecommitRes::Proposal);
                     }
                 }
             }
-            if self.step == Step::Precommit {
-                self.set_timer(tv, Step::PrecommitWait);
+            if self.step == Step::Precommit
+                || (self.step == Step::PrecommitWait && self.round > round)
+            {
+                self.set_timer(tv, Step::PrecommitWait)?;
             }
         }
-        PrecommitRes::Above
+        Ok(PrecommitRes::Above)
     }
 
     #[cfg(feature = "verify_req")]
-    fn check_verify(&mut self) -> VerifyResult {
+    fn check_verify(&mut self) -> BftResult<VerifyResult> {
         if let Some(lock_status) = self.lock_status.clone() {
-            let round = lock_status.round;
-            if self.verify_results.contains_key(&round) {
-                if self.verify_results.get(&round).unwrap().is_pass {
-                    return VerifyResult::Approved;
+            let block_hash = lock_status.block_hash;
+            if let Some(verify_resp) = self.verify_results.get(&block_hash) {
+                if verify_resp.is_pass {
+                    return Ok(VerifyResult::Approved);
                 } else {
+                    report_err(
+                        &BftError::CheckBlockFailed(
+                            format!("{:?} of block_hash {:?}", verify_resp.failure, block_hash)
+                                .into(),
+                        ),
+                        &self.params,
+                        &*self.function,
+                    );
                     // clean save info
                     self.clean_polc();
-                    return VerifyResult::Failed;
+                    return Ok(VerifyResult::Failed);
                 }
+            } else if self.is_verify_timed_out() {
+                warn!(
+                    target: &self.params.log_target,
+                    "Node {:?} times out verifying block_hash {:?} at h:{}, r:{}",
+                    self.params.address,
+                    block_hash,
+                    self.height,
+                    self.round
+                );
+                return Ok(VerifyResult::TimedOut);
             } else {
-                let tv = self.params.timer.get_prevote() * VERIFY_AWAIT_COEF;
-                self.set_timer(tv, Step::VerifyWait);
-                return VerifyResult::Undetermined;
+                let tv = self
+                    .verify_deadlines
+                    .get(&block_hash)
+                    .copied()
+                    .unwrap_or_else(|| self.params.timer.get_prevote() * VERIFY_AWAIT_COEF);
+                self.set_timer(tv, Step::VerifyWait)?;
+                return Ok(VerifyResult::Undetermined);
             }
         }
-        VerifyResult::Approved
+        Ok(VerifyResult::Approved)
+    }
+
+    #[inline]
+    fn is_verify_timed_out(&self) -> bool {
+        #[cfg(feature = "verify_req")]
+        {
+            self.verify_timed_out == Some((self.height, self.round))
+        }
+
+        #[cfg(not(feature = "verify_req"))]
+        {
+            false
+        }
     }
 }
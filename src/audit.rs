@@ -0,0 +1,117 @@
+use crate::*;
+use crate::{
+    algorithm::Bft,
+    error::{report_err, ErrorInfo},
+    objects::*,
+};
+use std::collections::HashSet;
+
+impl<T, B> Bft<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    /// A function to audit the own prevote on the [`block_hash`] against the locking rule:
+    /// the node never prevotes a block other than the one it precommitted, unless it has seen
+    /// a PoLC of the block, or of nil which unlocks it, at a round later than the PoLC it
+    /// precommitted with. A violating prevote is reported as `LockViolation` and replaced by a
+    /// nil one.
+    pub(crate) fn audit_prevote(&mut self, block_hash: Hash) -> Hash {
+        let (lock_round, lock_hash) = match self.audit_lock {
+            Some(lock) => lock,
+            None => return block_hash,
+        };
+        if block_hash.is_nil()
+            || block_hash == lock_hash
+            || self.seen_polc(lock_round, &block_hash)
+            || self.seen_polc(lock_round, &Hash::default())
+        {
+            return block_hash;
+        }
+        self.report_lock_violation(format!(
+            "prevote {:?} conflicts with the precommit of {:?} locked at r:{} without a later PoLC",
+            block_hash, lock_hash, lock_round
+        ));
+        Hash::default()
+    }
+
+    /// A function to audit the own precommit on the [`block_hash`] against the locking rule:
+    /// the node never precommits a block without a PoLC of it. A violating precommit is
+    /// reported as `LockViolation` and replaced by a nil one.
+    pub(crate) fn audit_precommit(&mut self, block_hash: Hash) -> Hash {
+        if block_hash.is_nil() {
+            return block_hash;
+        }
+        match self.lock_status.as_ref() {
+            Some(lock_status)
+                if lock_status.block_hash == block_hash && self.is_polc(lock_status) =>
+            {
+                // the lock of an earlier round may be precommitted again, and a PoLC after
+                // its round unlocks it
+                self.audit_lock = Some((lock_status.round, block_hash));
+                block_hash
+            }
+            _ => {
+                self.report_lock_violation(format!(
+                    "precommit {:?} without a PoLC of it",
+                    block_hash
+                ));
+                Hash::default()
+            }
+        }
+    }
+
+    /// A function to check whether a PoLC of the [`block_hash`] is seen at a round after the
+    /// [`lock_round`], by the collected prevotes or the lock got from a proposal.
+    fn seen_polc(&self, lock_round: Round, block_hash: &Hash) -> bool {
+        if let Some(lock_status) = self.lock_status.as_ref() {
+            if lock_status.round > lock_round
+                && lock_status.block_hash == *block_hash
+                && self.is_polc(lock_status)
+            {
+                return true;
+            }
+        }
+        self.votes
+            .votes
+            .iter()
+            .filter(|(height, _)| **height == self.height)
+            .flat_map(|(_, round_votes)| round_votes.round_votes.iter())
+            .filter(|(round, _)| **round > lock_round && **round <= self.round)
+            .filter_map(|(_, step_votes)| step_votes.get_voteset(&VoteType::Prevote))
+            .filter_map(|vote_set| vote_set.votes_by_proposal.get(block_hash))
+            .any(|count| self.cal_above_threshold(*count))
+    }
+
+    /// A function to check the votes of the [`lock_status`] are the prevotes of its round on
+    /// its block from the distinct authorities of over 2/3 weight.
+    fn is_polc(&self, lock_status: &LockStatus) -> bool {
+        let mut voters = HashSet::new();
+        let weight = lock_status
+            .votes
+            .iter()
+            .map(|signed_vote| &signed_vote.vote)
+            .filter(|vote| {
+                vote.vote_type == VoteType::Prevote
+                    && vote.height == self.height
+                    && vote.round == lock_status.round
+                    && vote.block_hash == lock_status.block_hash
+                    && voters.insert(vote.voter)
+            })
+            .filter_map(|vote| {
+                self.authority_manage
+                    .authorities
+                    .iter()
+                    .find(|node| node.address == vote.voter)
+            })
+            .fold(0u64, |weight, node| {
+                weight.saturating_add(u64::from(node.vote_weight))
+            });
+        self.cal_above_threshold(weight)
+    }
+
+    fn report_lock_violation(&self, detail: String) {
+        let e = BftError::LockViolation(ErrorInfo::new(detail).at(self.height, self.round));
+        report_err(&e, &self.params, &*self.function);
+    }
+}
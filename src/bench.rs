@@ -0,0 +1,231 @@
+use crate::algorithm::{Bft, INIT_HEIGHT};
+use crate::error::{BftError, BftResult};
+use crate::objects::{LockStatus, LogType, SignedVote};
+use crate::params::BftConfig;
+use crate::timer::TimeoutInfo;
+use crate::wal::Wal;
+use crate::*;
+
+use crossbeam::crossbeam_channel::{unbounded, Receiver};
+use min_max_heap::MinMaxHeap;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The count of the created engines, which tells apart the wal directories of the engines created
+/// at the same time.
+static ENGINES: AtomicU64 = AtomicU64::new(0);
+
+/// A single state machine driven synchronously by the benchmarks and the simulation tests,
+/// without the main loop and the network. The timers are not waited for but fired by
+/// `fire_timers`, the msgs it sends to itself are processed in place and the outbound msgs are
+/// returned by `outbound`. Its wal is in a temporary directory removed on drop, which survives
+/// a crash by `restart`.
+/// It is not a stable API.
+pub struct BenchEngine<T, B: BlockPayload = Block>
+where
+    T: BftSupport<B> + 'static,
+{
+    engine: Bft<T, B>,
+    timer_receiver: Receiver<TimeoutInfo>,
+    outbox: Receiver<Outbound>,
+    wal_path: Option<PathBuf>,
+    hold_own_msgs: bool,
+}
+
+impl<T, B> BenchEngine<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    /// A function to create a state machine of the [`support`] and the [`address`], whose wal
+    /// is in a new temporary directory.
+    pub fn new(support: Arc<T>, address: Address, config: BftConfig) -> BftResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let id = ENGINES.fetch_add(1, Ordering::Relaxed);
+        let wal_path =
+            env::temp_dir().join(format!("bft-bench-{}-{}-{}", process::id(), nanos, id));
+        Self::open(support, address, config, wal_path)
+    }
+
+    /// A function to crash the state machine, losing the msgs it sends to itself, the timers
+    /// and the outbound msgs not taken yet, and start a new one from its wal.
+    /// Return the new state machine with the errors of the wal records failing to be replayed
+    /// and of the msgs it sends to itself in replaying.
+    pub fn restart(mut self) -> BftResult<(Self, Vec<BftError>)> {
+        let support = self.engine.function.clone();
+        let address = self.engine.params.address;
+        let config = self.engine.params.config.clone();
+        let hold_own_msgs = self.hold_own_msgs;
+        let wal_path = self.wal_path.take().ok_or_else(|| {
+            BftError::InternalErr("restart a state machine without wal".to_string().into())
+        })?;
+        // the wal files are closed before they are opened again
+        drop(self);
+        let mut engine = Self::open(support, address, config, wal_path)?;
+        engine.hold_own_msgs = hold_own_msgs;
+        let mut errors: Vec<BftError> = engine
+            .engine
+            .load_wal_log()
+            .failures
+            .into_iter()
+            .map(|failure| failure.error)
+            .collect();
+        engine.process_own_msgs(&mut errors);
+        Ok((engine, errors))
+    }
+
+    fn open(
+        support: Arc<T>,
+        address: Address,
+        config: BftConfig,
+        wal_path: PathBuf,
+    ) -> BftResult<Self> {
+        let wal_str = wal_path.to_string_lossy().to_string();
+        let wal_log = Wal::new(&wal_str, &config).map_err(|e| {
+            let _ = fs::remove_dir_all(&wal_path);
+            BftError::InitWalErr(format!("{} encounters {:?}", wal_str, e).into())
+        })?;
+        let (msg_sender, msg_receiver) = unbounded();
+        let (timer_sender, timer_receiver) = unbounded();
+        let (outbox_sender, outbox) = unbounded();
+        let engine = Bft::new(
+            msg_sender,
+            msg_receiver,
+            timer_sender.into(),
+            timer_receiver.clone(),
+            support,
+            address,
+            wal_log,
+            config,
+            Arc::new(AtomicU64::new(INIT_HEIGHT)),
+            Some(outbox_sender),
+            None,
+        );
+        Ok(BenchEngine {
+            engine,
+            timer_receiver,
+            outbox,
+            wal_path: Some(wal_path),
+            hold_own_msgs: false,
+        })
+    }
+
+    /// A function to set whether the msgs the state machine sends to itself are held in the
+    /// channel instead of processed in place, to crash it after its own proposal and votes are
+    /// sent and before they are saved in wal.
+    pub fn hold_own_msgs(&mut self, hold: bool) {
+        self.hold_own_msgs = hold;
+    }
+
+    /// A function to process the [`msg`] as the main loop does, saving it in wal, followed by
+    /// the msgs the state machine sends to itself, such as its own proposal and votes, unless
+    /// they are held. Return the errors of them.
+    pub fn process(&mut self, msg: BftMsg<B>) -> Vec<BftError> {
+        let mut errors = Vec::new();
+        if let Err(e) = self.engine.process(msg, true) {
+            errors.push(e);
+        }
+        self.process_own_msgs(&mut errors);
+        errors
+    }
+
+    /// A function to fire the timers set so far in the order of their deadlines without waiting
+    /// for them, the ones set by the fired timers are kept to the next call.
+    /// Return the errors of the fired timers, such as the obsolete ones.
+    pub fn fire_timers(&mut self) -> Vec<BftError> {
+        let mut timers = MinMaxHeap::new();
+        for timeout_info in self.timer_receiver.try_iter() {
+            timers.push(timeout_info);
+        }
+        let mut errors = Vec::new();
+        while let Some(timeout_info) = timers.pop_min() {
+            if let Err(e) = self.engine.timeout_process(timeout_info, true) {
+                errors.push(e);
+            }
+            self.process_own_msgs(&mut errors);
+        }
+        errors
+    }
+
+    /// A function to take the outbound msgs produced so far.
+    pub fn outbound(&mut self) -> Vec<Outbound> {
+        self.outbox.try_iter().collect()
+    }
+
+    /// A function to get the proposer of the current height and round.
+    pub fn proposer(&self) -> BftResult<Address> {
+        self.engine
+            .get_proposer(self.engine.height, self.engine.round)
+            .copied()
+    }
+
+    /// A function to get the shares of the proposers of the latest committed heights.
+    pub fn proposer_shares(&self) -> HashMap<Address, ProposerShare> {
+        self.engine
+            .proposer_window
+            .shares(&self.engine.authority_manage.authorities)
+    }
+
+    /// A function to generate the proof of the current height from the precommit [`votes`] for
+    /// the [`block_hash`] at the [`round`].
+    pub fn generate_proof(
+        &mut self,
+        round: Round,
+        block_hash: Hash,
+        votes: Vec<SignedVote>,
+    ) -> Proof {
+        self.engine.generate_proof(LockStatus {
+            block_hash,
+            round,
+            votes,
+        })
+    }
+
+    /// A function to save the [`msg`] of the [`log_type`] in wal at the current height.
+    pub fn save_wal(&mut self, log_type: LogType, msg: &[u8]) -> io::Result<()> {
+        self.engine.wal_log.save(self.engine.height, log_type, msg)
+    }
+
+    /// A function to get the height of the state machine.
+    pub fn height(&self) -> Height {
+        self.engine.height
+    }
+
+    /// A function to get the round of the state machine.
+    pub fn round(&self) -> Round {
+        self.engine.round
+    }
+
+    fn process_own_msgs(&mut self, errors: &mut Vec<BftError>) {
+        if self.hold_own_msgs {
+            return;
+        }
+        while let Ok(msg) = self.engine.msg_receiver.try_recv() {
+            if let Err(e) = self.engine.process(msg, true) {
+                errors.push(e);
+            }
+        }
+    }
+}
+
+impl<T, B> Drop for BenchEngine<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    fn drop(&mut self) {
+        if let Some(wal_path) = &self.wal_path {
+            let _ = fs::remove_dir_all(wal_path);
+        }
+    }
+}
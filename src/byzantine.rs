@@ -4,10 +4,30 @@ use crate::{
     objects::*,
 };
 use rand::prelude::*;
+use std::sync::Arc;
 
-impl<T> Bft<T>
+/// How a corrupt node misbehaves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByzantineStrategy {
+    /// Sign random proposals and votes, and broadcast several of each step.
+    Random,
+    /// As the proposer, sign two different proposals of the height and round, and transmit
+    /// each to one half of the other authorities, which the evidences of duplicate proposals
+    /// are built from. The node votes as an honest one.
+    ConflictingProposals,
+    /// Forget the own lock: prevote the block of the proposal of the round even if it locks
+    /// another block by a PoLC. The node proposes and precommits as an honest one.
+    LockAmnesia,
+    /// Flip-flop between the first two blocks proposed at the height: prevote and precommit
+    /// the first one in the even rounds and the second one in the odd rounds. The node votes
+    /// as an honest one until two blocks are proposed, and proposes as an honest one.
+    FlipFlop,
+}
+
+impl<T, B> Bft<T, B>
 where
-    T: BftSupport + 'static,
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
 {
     pub(crate) fn transmit_byzantine_proposal(&mut self) -> BftResult<()> {
         self.send_byzantine_proposal()?;
@@ -20,6 +40,73 @@ where
         Ok(())
     }
 
+    pub(crate) fn transmit_conflicting_proposals(&mut self) -> BftResult<()> {
+        let first = self.build_byzantine_proposal()?;
+        let second = self.build_byzantine_proposal()?;
+        let mut others: Vec<Address> = self
+            .authority_manage
+            .authorities
+            .iter()
+            .map(|node| node.address)
+            .filter(|address| *address != self.params.address)
+            .collect();
+        others.sort();
+        let half = others.len() / 2;
+        debug!(
+            target: &self.params.log_target,
+            "Node {:?} transmits conflicting proposals to {:?} and {:?} at h:{}, r:{}",
+            self.params.address,
+            &others[..half],
+            &others[half..],
+            self.height,
+            self.round
+        );
+        for (i, address) in others.into_iter().enumerate() {
+            let encode = if i < half { &first } else { &second };
+            self.transmit(
+                OutboundMsg::Proposal(encode.clone()),
+                Target::Unicast(address),
+            );
+        }
+        // the node goes on by the first one
+        self.send_bft_msg(BftMsg::Proposal(first))?;
+        Ok(())
+    }
+
+    /// A function to get the block hash the node votes for by its strategy in the current
+    /// round, `None` if it votes as an honest node.
+    pub(crate) fn byzantine_vote_hash(&mut self, vote_type: &VoteType) -> Option<Hash> {
+        match self.byzantine? {
+            ByzantineStrategy::LockAmnesia if *vote_type == VoteType::Prevote => self
+                .proposals
+                .get_proposal(self.height, self.round)
+                .map(|signed_proposal| signed_proposal.proposal.block_hash),
+            ByzantineStrategy::FlipFlop => {
+                let mut proposals: Vec<(Round, Hash)> = self
+                    .proposals
+                    .proposals
+                    .iter()
+                    .filter(|(height, _)| **height == self.height)
+                    .flat_map(|(_, round_proposals)| round_proposals.round_proposals.iter())
+                    .map(|(round, signed_proposal)| (*round, signed_proposal.proposal.block_hash))
+                    .filter(|(_, block_hash)| !block_hash.is_nil())
+                    .collect();
+                proposals.sort_by_key(|(round, _)| *round);
+                let mut block_hashes: Vec<Hash> = Vec::with_capacity(2);
+                for (_, block_hash) in proposals {
+                    if !block_hashes.contains(&block_hash) {
+                        block_hashes.push(block_hash);
+                    }
+                }
+                if block_hashes.len() < 2 {
+                    return None;
+                }
+                Some(block_hashes[(self.round % 2) as usize])
+            }
+            _ => None,
+        }
+    }
+
     pub(crate) fn transmit_byzantine_prevote(&mut self, resend: bool) -> BftResult<()> {
         self.send_byzantine_vote(VoteType::Prevote)?;
         self.send_byzantine_vote(VoteType::Prevote)?;
@@ -31,7 +118,7 @@ where
         self.set_timer(
             self.params.timer.get_prevote() * TIMEOUT_RETRANSE_COEF,
             Step::Prevote,
-        );
+        )?;
         Ok(())
     }
 
@@ -46,7 +133,7 @@ where
         self.set_timer(
             self.params.timer.get_precommit() * TIMEOUT_RETRANSE_COEF,
             Step::Prevote,
-        );
+        )?;
         Ok(())
     }
 
@@ -59,10 +146,21 @@ where
     }
 
     fn send_byzantine_proposal(&mut self) -> BftResult<()> {
+        let encode = self.build_byzantine_proposal()?;
+        self.transmit(OutboundMsg::Proposal(encode), Target::Broadcast);
+        Ok(())
+    }
+
+    /// A function to build a signed proposal of a random block, which is signed past the sign
+    /// guard as the guard refuses the conflicting ones.
+    fn build_byzantine_proposal(&mut self) -> BftResult<Vec<u8>> {
         let block = get_rand_vec(20);
         let block_hash = self.function.crypt_hash(&block);
-        self.blocks.add(self.height, &block_hash, &block.into());
-        self.block_hash = Some(block_hash.clone());
+        let block = B::from_bytes(&block).map(Arc::new).map_err(|e| {
+            BftError::DecodeErr(format!("byzantine block encounters {:?}", e).into())
+        })?;
+        self.blocks.add(self.height, &block_hash, &block);
+        self.block_hash = Some(block_hash);
 
         let proposal = Proposal {
             height: self.height,
@@ -71,11 +169,22 @@ where
             proof: self.proof.clone(),
             lock_round: None,
             lock_votes: Vec::new(),
-            proposer: self.params.address.clone(),
+            proposer: self.params.address,
+            timestamp: self.proposal_timestamp(),
         };
-        let encode = self.build_signed_proposal_encode(&proposal)?;
-        self.function.transmit(BftMsg::Proposal(encode).clone());
-        Ok(())
+        let hash = self.hash_to_sign(SignKind::Proposal, &proposal);
+        let signature = self
+            .function
+            .sign(&hash)
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, proposal).into()))?;
+        let signed_proposal = SignedProposal {
+            proposal,
+            signature,
+        };
+        Ok(combine_two(
+            &self.encode(&signed_proposal),
+            &block.to_bytes(),
+        ))
     }
 
     fn send_byzantine_vote(&mut self, vote_type: VoteType) -> BftResult<()> {
@@ -84,21 +193,22 @@ where
             height: self.height,
             round: self.round,
             block_hash: self.get_rand_hash(),
-            voter: self.params.address.clone(),
+            voter: self.params.address,
         };
 
-        let encode = rlp::encode(&vote);
-        let hash = self.function.crypt_hash(&encode);
+        let hash = self.hash_to_sign(SignKind::Vote, &vote);
         let signature = self
             .function
             .sign(&hash)
-            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote)))?;
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote).into()))?;
         let signed_vote = SignedVote {
             vote: vote.clone(),
             signature,
         };
-        self.function
-            .transmit(BftMsg::Vote(rlp::encode(&signed_vote)));
+        self.transmit(
+            OutboundMsg::Vote(self.encode(&signed_vote)),
+            Target::Broadcast,
+        );
         Ok(())
     }
 
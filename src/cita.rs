@@ -0,0 +1,181 @@
+//! The conversions between the proofs of this crate and the ones of cita-bft, for the chains
+//! migrating from cita-bft to validate their historical headers.
+
+use crate::error::{BftError, BftResult};
+use crate::objects::VoteType;
+use crate::verify::check_precommits;
+use crate::{Address, Hash, Height, Node, Proof, Round, Signature};
+
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+/// The `BftProof` of cita-bft, which is serialized into the `content` of a CITA `Proof` of
+/// the type `Bft`. The fields are in the order of cita-bft.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CitaBftProof {
+    /// the committed block hash
+    pub proposal: Hash,
+    /// the commit height
+    pub height: usize,
+    /// the reaching-consensus round
+    pub round: usize,
+    /// the voters and their precommit signatures
+    pub commits: HashMap<Address, Signature>,
+}
+
+/// The `ProofType` of the CITA protobuf `Proof`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CitaProofType {
+    AuthorityRound = 0,
+    Raft = 1,
+    Bft = 2,
+}
+
+/// The CITA protobuf `Proof` in block headers, whose [`content`] is the serialized
+/// `CitaBftProof` if the [`proof_type`] is `Bft`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CitaProof {
+    pub content: Vec<u8>,
+    pub proof_type: CitaProofType,
+}
+
+/// The `Step` of cita-bft, which is signed in the votes of cita-bft.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CitaStep {
+    Propose,
+    ProposeWait,
+    Prevote,
+    PrevoteWait,
+    PrecommitAuth,
+    Precommit,
+    PrecommitWait,
+    Commit,
+    CommitWait,
+}
+
+impl From<CitaStep> for u8 {
+    fn from(step: CitaStep) -> Self {
+        match step {
+            CitaStep::Propose => 0,
+            CitaStep::ProposeWait => 1,
+            CitaStep::Prevote => 2,
+            CitaStep::PrevoteWait => 3,
+            CitaStep::PrecommitAuth => 4,
+            CitaStep::Precommit => 5,
+            CitaStep::PrecommitWait => 6,
+            CitaStep::Commit => 7,
+            CitaStep::CommitWait => 8,
+        }
+    }
+}
+
+impl TryFrom<u8> for CitaStep {
+    type Error = BftError;
+
+    fn try_from(step: u8) -> BftResult<Self> {
+        match step {
+            0 => Ok(CitaStep::Propose),
+            1 => Ok(CitaStep::ProposeWait),
+            2 => Ok(CitaStep::Prevote),
+            3 => Ok(CitaStep::PrevoteWait),
+            4 => Ok(CitaStep::PrecommitAuth),
+            5 => Ok(CitaStep::Precommit),
+            6 => Ok(CitaStep::PrecommitWait),
+            7 => Ok(CitaStep::Commit),
+            8 => Ok(CitaStep::CommitWait),
+            _ => Err(BftError::DecodeErr(
+                format!("invalid cita step {}", step).into(),
+            )),
+        }
+    }
+}
+
+impl From<VoteType> for CitaStep {
+    fn from(vote_type: VoteType) -> Self {
+        match vote_type {
+            VoteType::Prevote => CitaStep::Prevote,
+            VoteType::Precommit => CitaStep::Precommit,
+        }
+    }
+}
+
+impl TryFrom<CitaStep> for VoteType {
+    type Error = BftError;
+
+    /// Only the steps of the votes are mapped, cita-bft signs no other steps.
+    fn try_from(step: CitaStep) -> BftResult<Self> {
+        match step {
+            CitaStep::Prevote => Ok(VoteType::Prevote),
+            CitaStep::Precommit => Ok(VoteType::Precommit),
+            _ => Err(BftError::DecodeErr(
+                format!("cita step {:?} is not a vote", step).into(),
+            )),
+        }
+    }
+}
+
+impl CitaProof {
+    /// A function to get the `content` of the proof if its type is `Bft`.
+    pub fn bft_content(&self) -> BftResult<&[u8]> {
+        match self.proof_type {
+            CitaProofType::Bft => Ok(&self.content),
+            proof_type => Err(BftError::DecodeErr(
+                format!("cita proof type {:?} is not bft", proof_type).into(),
+            )),
+        }
+    }
+}
+
+impl From<&CitaBftProof> for Proof {
+    fn from(proof: &CitaBftProof) -> Self {
+        Proof {
+            height: proof.height as Height,
+            round: proof.round as Round,
+            block_hash: proof.proposal,
+            precommit_votes: proof.commits.clone(),
+            // the proofs of cita-bft do not commit to the authority list
+            authorities_hash: None,
+        }
+    }
+}
+
+impl TryFrom<&Proof> for CitaBftProof {
+    type Error = BftError;
+
+    /// The height and the round overflow `usize` only on the 32-bit targets.
+    fn try_from(proof: &Proof) -> BftResult<Self> {
+        let overflow = |name: &str, value: u64| {
+            BftError::DecodeErr(format!("{} {} overflows the cita proof", name, value).into())
+        };
+        Ok(CitaBftProof {
+            proposal: proof.block_hash,
+            height: usize::try_from(proof.height).map_err(|_| overflow("height", proof.height))?,
+            round: usize::try_from(proof.round).map_err(|_| overflow("round", proof.round))?,
+            commits: proof.precommit_votes.clone(),
+        })
+    }
+}
+
+/// A public function to check a proof of cita-bft in a historical header.
+/// The input [`height`] is the height of block containing the proof.
+/// The input [`authorities`] is the authority_list for the proof check.
+/// cita-bft signs the hash of the serialized `(height, round, CitaStep::Precommit, voter,
+/// Some(proposal))` with its own serializer, so the fn [`precommit_hash`] computes it for the
+/// voter, and [`check_sig`] recovers the signer of the hash.
+pub fn check_cita_proof(
+    proof: &CitaBftProof,
+    height: Height,
+    authorities: &[Node],
+    precommit_hash: impl Fn(&CitaBftProof, &Address) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    check_precommits(
+        &Proof::from(proof),
+        height,
+        authorities,
+        |voter| precommit_hash(proof, voter),
+        check_sig,
+    )
+    .is_ok()
+}
@@ -0,0 +1,29 @@
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The local clock of a node, which the timers and the proposal timestamps are taken by.
+/// The timers still fire by the monotonic clock of the process, so a clock only tells how long
+/// its durations really take.
+pub trait Clock: Debug + Send + Sync {
+    /// A function to get the wall clock, in milliseconds since the unix epoch.
+    fn unix_millis(&self) -> u64;
+    /// A function to get the real duration the clock takes to elapse the [`duration`].
+    fn elapse(&self, duration: Duration) -> Duration;
+}
+
+/// The default clock, which is the system clock.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn unix_millis(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0)
+    }
+
+    fn elapse(&self, duration: Duration) -> Duration {
+        duration
+    }
+}
@@ -1,13 +1,42 @@
 use crate::objects::{SignedProposal, SignedVote, VoteType};
-use crate::{Address, Block, Hash, Height, Round};
+use crate::{Address, BlockPayload, Hash, Height, Node, Round, Signature};
 
+use hex_fmt::HexFmt;
+use rustc_hash::FxHashMap;
 use std::collections::HashMap;
+use std::fs::{self, DirBuilder};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use crate::error::{BftError, BftResult};
+use crate::verify::is_above_threshold;
 use lru_cache::LruCache;
 
 pub(crate) const CACHE_N: u64 = 16;
 
+/// The hash map of the hot paths of vote ingestion. Its keys are the addresses and the hashes
+/// of the msgs whose signatures are checked, so the faster hasher replaces SipHash.
+pub(crate) type FastMap<K, V> = FxHashMap<K, V>;
+
+/// The approximate bytes of a signed_vote in the caches.
+fn vote_bytes(signed_vote: &SignedVote) -> usize {
+    mem::size_of::<SignedVote>() + signed_vote.signature.len()
+}
+
+/// The approximate bytes of a signed_proposal in the caches, the block is not included.
+fn proposal_bytes(signed_proposal: &SignedProposal) -> usize {
+    let proposal = &signed_proposal.proposal;
+    let proof_bytes: usize = proposal
+        .proof
+        .precommit_votes
+        .values()
+        .map(|signature| mem::size_of::<(Address, Signature)>() + signature.len())
+        .sum();
+    let lock_bytes: usize = proposal.lock_votes.iter().map(vote_bytes).sum();
+    mem::size_of::<SignedProposal>() + signed_proposal.signature.len() + proof_bytes + lock_bytes
+}
+
 /// BFT vote collector
 #[derive(Debug, Clone)]
 pub(crate) struct VoteCollector {
@@ -16,6 +45,10 @@ pub(crate) struct VoteCollector {
     /// A HashMap to record prevote count of each round.
     pub(crate) prevote_count: HashMap<Round, u64>,
     pub(crate) precommit_count: HashMap<Round, u64>,
+    /// The highest round whose prevote count is above 2/3 of the current height.
+    pub(crate) prevote_quorum: Option<Round>,
+    /// The highest round whose precommit count is above 2/3 of the current height.
+    pub(crate) precommit_quorum: Option<Round>,
 }
 
 impl VoteCollector {
@@ -25,60 +58,45 @@ impl VoteCollector {
             votes: LruCache::new(CACHE_N as usize),
             prevote_count: HashMap::new(),
             precommit_count: HashMap::new(),
+            prevote_quorum: None,
+            precommit_quorum: None,
         }
     }
 
-    /// A function try to add a vote, return `bool`.
+    /// A function try to add a vote, the vote count and the quorum round of the current height
+    /// are updated against the `weight_sum`. The vote sets are pre-sized for the [`voters`].
     pub(crate) fn add(
         &mut self,
         signed_vote: &SignedVote,
         vote_weight: u64,
         current_height: Height,
+        weight_sum: u64,
+        voters: usize,
     ) -> BftResult<()> {
         let vote = &signed_vote.vote;
         let height = vote.height;
         let round = vote.round;
-        let vote_type = &vote.vote_type;
 
-        if *vote_type == VoteType::Prevote {
-            let counter = self.prevote_count.entry(round).or_insert(0);
-            if self.votes.contains_key(&height) {
-                self.votes
-                    .get_mut(&height)
-                    .unwrap()
-                    .add(signed_vote, vote_weight)?;
-                if height == current_height {
-                    // update prevote count hashmap
-                    *counter += vote_weight;
-                }
-            } else {
-                let mut round_votes = RoundCollector::new();
-                round_votes.add(signed_vote, vote_weight)?;
-                self.votes.insert(height, round_votes);
-                // update prevote count hashmap
-                if height == current_height {
-                    *counter += vote_weight;
-                }
-            }
+        if self.votes.contains_key(&height) {
+            self.votes
+                .get_mut(&height)
+                .unwrap()
+                .add(signed_vote, vote_weight, voters)?;
         } else {
-            let counter = self.precommit_count.entry(round).or_insert(0);
-            if self.votes.contains_key(&height) {
-                self.votes
-                    .get_mut(&height)
-                    .unwrap()
-                    .add(signed_vote, vote_weight)?;
-                if height == current_height {
-                    // update prevote count hashmap
-                    *counter += vote_weight;
-                }
-            } else {
-                let mut round_votes = RoundCollector::new();
-                round_votes.add(signed_vote, vote_weight)?;
-                self.votes.insert(height, round_votes);
-                // update prevote count hashmap
-                if height == current_height {
-                    *counter += vote_weight;
-                }
+            let mut round_votes = RoundCollector::new();
+            round_votes.add(signed_vote, vote_weight, voters)?;
+            self.votes.insert(height, round_votes);
+        }
+
+        if height == current_height {
+            let (vote_count, quorum) = match vote.vote_type {
+                VoteType::Prevote => (&mut self.prevote_count, &mut self.prevote_quorum),
+                VoteType::Precommit => (&mut self.precommit_count, &mut self.precommit_quorum),
+            };
+            let counter = vote_count.entry(round).or_insert(0);
+            *counter = counter.saturating_add(vote_weight);
+            if is_above_threshold(*counter, weight_sum) && *quorum < Some(round) {
+                *quorum = Some(round);
             }
         }
         Ok(())
@@ -89,25 +107,92 @@ impl VoteCollector {
         self.clear_vote_count();
     }
 
+    /// A function to drop the votes of a height other than the current one.
+    pub(crate) fn evict(&mut self, height: Height) {
+        self.votes.remove(&height);
+    }
+
+    /// A function to get the approximate bytes of the cached votes.
+    pub(crate) fn bytes(&self) -> usize {
+        self.votes
+            .iter()
+            .flat_map(|(_, round_votes)| round_votes.round_votes.iter())
+            .flat_map(|(_, step_votes)| step_votes.step_votes.values())
+            .map(|vote_set| vote_set.bytes)
+            .sum()
+    }
+
     /// A function to get the vote set of the height, the round, and the vote type.
     pub(crate) fn get_voteset(
         &mut self,
         height: Height,
         round: Round,
         vote_type: &VoteType,
-    ) -> Option<VoteSet> {
+    ) -> Option<&VoteSet> {
         self.votes
             .get_mut(&height)
             .and_then(|rc| rc.get_voteset(round, vote_type))
     }
 
+    /// A function to get all the votes of the height.
+    pub(crate) fn get_votes(&mut self, height: Height) -> Vec<SignedVote> {
+        let mut votes = Vec::new();
+        if let Some(round_votes) = self.votes.get_mut(&height) {
+            for (_, step_votes) in round_votes.round_votes.iter() {
+                for vote_set in step_votes.step_votes.values() {
+                    votes.extend(vote_set.votes_by_sender.values().cloned());
+                }
+            }
+        }
+        votes
+    }
+
+    /// A function to get the addresses of the [`authorities`] whose votes of the height,
+    /// the round and the vote type are not received, sorted by address.
+    pub(crate) fn missing_voters(
+        &mut self,
+        height: Height,
+        round: Round,
+        vote_type: &VoteType,
+        authorities: &[Node],
+    ) -> Vec<Address> {
+        let vote_set = self.get_voteset(height, round, vote_type);
+        let mut missing: Vec<Address> = authorities
+            .iter()
+            .map(|node| node.address)
+            .filter(|address| {
+                !vote_set
+                    .iter()
+                    .any(|vote_set| vote_set.votes_by_sender.contains_key(address))
+            })
+            .collect();
+        missing.sort();
+        missing
+    }
+
+    /// A function to recalculate the quorum rounds when the `weight_sum` changes.
+    pub(crate) fn refresh_quorum(&mut self, weight_sum: u64) {
+        self.prevote_quorum = quorum_round(&self.prevote_count, weight_sum);
+        self.precommit_quorum = quorum_round(&self.precommit_count, weight_sum);
+    }
+
     /// A function to clean prevote count HashMap at the begining of a height.
     pub(crate) fn clear_vote_count(&mut self) {
         self.prevote_count.clear();
         self.precommit_count.clear();
+        self.prevote_quorum = None;
+        self.precommit_quorum = None;
     }
 }
 
+fn quorum_round(vote_count: &HashMap<Round, u64>, weight_sum: u64) -> Option<Round> {
+    vote_count
+        .iter()
+        .filter(|(_, count)| is_above_threshold(**count, weight_sum))
+        .map(|(round, _)| *round)
+        .max()
+}
+
 /// BFT round vote collector.
 // round -> step collector
 #[derive(Debug, Clone)]
@@ -125,24 +210,29 @@ impl RoundCollector {
     }
 
     /// A function try to add a vote to a round collector.
-    pub(crate) fn add(&mut self, signed_vote: &SignedVote, vote_weight: u64) -> BftResult<()> {
+    pub(crate) fn add(
+        &mut self,
+        signed_vote: &SignedVote,
+        vote_weight: u64,
+        voters: usize,
+    ) -> BftResult<()> {
         let round = signed_vote.vote.round;
 
         if self.round_votes.contains_key(&round) {
             self.round_votes
                 .get_mut(&round)
                 .unwrap()
-                .add(signed_vote, vote_weight)
+                .add(signed_vote, vote_weight, voters)
         } else {
             let mut step_votes = StepCollector::new();
-            step_votes.add(signed_vote, vote_weight)?;
+            step_votes.add(signed_vote, vote_weight, voters)?;
             self.round_votes.insert(round, step_votes);
             Ok(())
         }
     }
 
     /// A functionto get the vote set of the round, and the vote type.
-    pub(crate) fn get_voteset(&mut self, round: Round, vote_type: &VoteType) -> Option<VoteSet> {
+    pub(crate) fn get_voteset(&mut self, round: Round, vote_type: &VoteType) -> Option<&VoteSet> {
         self.round_votes
             .get_mut(&round)
             .and_then(|sc| sc.get_voteset(vote_type))
@@ -154,30 +244,35 @@ impl RoundCollector {
 #[derive(Debug, Default, Clone)]
 pub(crate) struct StepCollector {
     /// A HashMap that K is step, V is the vote set
-    pub(crate) step_votes: HashMap<VoteType, VoteSet>,
+    pub(crate) step_votes: FastMap<VoteType, VoteSet>,
 }
 
 impl StepCollector {
     /// A function to create a new step collector.
     pub(crate) fn new() -> Self {
         StepCollector {
-            step_votes: HashMap::new(),
+            step_votes: FastMap::default(),
         }
     }
 
     /// A function to add a vote to the step collector.
-    pub(crate) fn add(&mut self, signed_vote: &SignedVote, vote_weight: u64) -> BftResult<()> {
+    pub(crate) fn add(
+        &mut self,
+        signed_vote: &SignedVote,
+        vote_weight: u64,
+        voters: usize,
+    ) -> BftResult<()> {
         let vote = &signed_vote.vote;
         let vote_type = &vote.vote_type;
         self.step_votes
             .entry(vote_type.clone())
-            .or_insert_with(VoteSet::new)
+            .or_insert_with(|| VoteSet::new(voters))
             .add(signed_vote, vote_weight)
     }
 
     /// A function to get voteset of the vote type
-    pub(crate) fn get_voteset(&self, vote_type: &VoteType) -> Option<VoteSet> {
-        self.step_votes.get(vote_type).cloned()
+    pub(crate) fn get_voteset(&self, vote_type: &VoteType) -> Option<&VoteSet> {
+        self.step_votes.get(vote_type)
     }
 }
 
@@ -186,20 +281,26 @@ impl StepCollector {
 #[derive(Clone, Debug)]
 pub(crate) struct VoteSet {
     /// A HashMap that K is voter, V is proposal.
-    pub(crate) votes_by_sender: HashMap<Address, SignedVote>,
+    pub(crate) votes_by_sender: FastMap<Address, SignedVote>,
     /// A HashMap that K is proposal V is count of the proposal.
-    pub(crate) votes_by_proposal: HashMap<Hash, u64>,
+    pub(crate) votes_by_proposal: FastMap<Hash, u64>,
     /// Count of vote set.
     pub(crate) count: u64,
+    /// The proposal with the most votes, and its count.
+    pub(crate) leading: Option<(Hash, u64)>,
+    /// The approximate bytes of the votes.
+    pub(crate) bytes: usize,
 }
 
 impl VoteSet {
-    /// A function to create a new vote set.
-    pub(crate) fn new() -> Self {
+    /// A function to create a new vote set pre-sized for the [`voters`].
+    pub(crate) fn new(voters: usize) -> Self {
         VoteSet {
-            votes_by_sender: HashMap::new(),
-            votes_by_proposal: HashMap::new(),
+            votes_by_sender: FastMap::with_capacity_and_hasher(voters, Default::default()),
+            votes_by_proposal: FastMap::default(),
             count: 0,
+            leading: None,
+            bytes: 0,
         }
     }
 
@@ -207,15 +308,17 @@ impl VoteSet {
     pub(crate) fn add(&mut self, signed_vote: &SignedVote, vote_weight: u64) -> BftResult<()> {
         let vote = &signed_vote.vote;
         if self.votes_by_sender.contains_key(&vote.voter) {
-            return Err(BftError::RecvMsgAgain(format!("{:?}", signed_vote)));
+            return Err(BftError::RecvMsgAgain(format!("{:?}", signed_vote).into()));
         }
         self.votes_by_sender
-            .insert(vote.voter.clone(), signed_vote.to_owned());
-        self.count += vote_weight;
-        *self
-            .votes_by_proposal
-            .entry(vote.block_hash.clone())
-            .or_insert(0) += vote_weight;
+            .insert(vote.voter, signed_vote.to_owned());
+        self.count = self.count.saturating_add(vote_weight);
+        self.bytes += vote_bytes(signed_vote);
+        let counter = self.votes_by_proposal.entry(vote.block_hash).or_insert(0);
+        *counter = counter.saturating_add(vote_weight);
+        if self.leading.map_or(0, |(_, count)| count) < *counter {
+            self.leading = Some((vote.block_hash, *counter));
+        }
         Ok(())
     }
 
@@ -273,6 +376,20 @@ impl ProposalCollector {
             .get_mut(&height)
             .and_then(|prc| prc.remove(round))
     }
+
+    /// A function to drop the proposals of a height other than the current one.
+    pub(crate) fn evict(&mut self, height: Height) {
+        self.proposals.remove(&height);
+    }
+
+    /// A function to get the approximate bytes of the cached proposals.
+    pub(crate) fn bytes(&self) -> usize {
+        self.proposals
+            .iter()
+            .flat_map(|(_, round_proposals)| round_proposals.round_proposals.iter())
+            .map(|(_, signed_proposal)| proposal_bytes(signed_proposal))
+            .sum()
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -289,7 +406,9 @@ impl ProposalRoundCollector {
 
     pub(crate) fn add(&mut self, round: Round, signed_proposal: &SignedProposal) -> BftResult<()> {
         if self.round_proposals.contains_key(&round) {
-            return Err(BftError::RecvMsgAgain(format!("{:?}", signed_proposal)));
+            return Err(BftError::RecvMsgAgain(
+                format!("{:?}", signed_proposal).into(),
+            ));
         }
         self.round_proposals.insert(round, signed_proposal.clone());
         Ok(())
@@ -304,56 +423,162 @@ impl ProposalRoundCollector {
     }
 }
 
-pub(crate) struct BlockCollector {
-    pub blocks: LruCache<Height, BlockSet>,
+pub(crate) struct BlockCollector<B: BlockPayload> {
+    pub blocks: LruCache<Height, BlockSet<B>>,
+    spill: Option<BlockSpill>,
 }
 
-impl BlockCollector {
+impl<B: BlockPayload> BlockCollector<B> {
     pub(crate) fn new() -> Self {
         BlockCollector {
             blocks: LruCache::new(CACHE_N as usize),
+            spill: None,
         }
     }
 
-    pub(crate) fn add(&mut self, height: Height, block_hash: &Hash, block: &Block) -> bool {
-        if self.blocks.contains_key(&height) {
-            self.blocks.get_mut(&height).unwrap().add(block_hash, block)
-        } else {
-            let mut block_set = BlockSet::new();
-            block_set.add(block_hash, block);
-            self.blocks.insert(height, block_set);
-            true
+    /// A function to create a block collector spilling the block bodies to the files in the
+    /// [`dir`] once the blocks in memory exceed the [`threshold`] of bytes, 0 disables the
+    /// spill. The files left in the [`dir`] are removed.
+    pub(crate) fn with_spill(dir: PathBuf, threshold: usize) -> Self {
+        let mut collector = BlockCollector::new();
+        if threshold == 0 {
+            return collector;
+        }
+        let _ = fs::remove_dir_all(&dir);
+        if DirBuilder::new().recursive(true).create(&dir).is_ok() {
+            collector.spill = Some(BlockSpill { dir, threshold });
         }
+        collector
     }
 
-    pub(crate) fn get_block(&mut self, height: Height, hash: &Hash) -> Option<&Block> {
+    pub(crate) fn add(&mut self, height: Height, block_hash: &Hash, block: &Arc<B>) -> bool {
+        if self
+            .blocks
+            .get_mut(&height)
+            .is_some_and(|bs| bs.block_set.contains_key(block_hash))
+        {
+            return false;
+        }
+        let size = block.size();
+        let in_memory = self.bytes();
+        // the block is kept in memory if it fails to be spilled
+        let cached = self
+            .spill
+            .as_ref()
+            .filter(|spill| in_memory + size > spill.threshold)
+            .and_then(|spill| spill.write(height, block_hash, &**block))
+            .map_or_else(|| CachedBlock::Memory(block.clone()), CachedBlock::Spilled);
+
+        if !self.blocks.contains_key(&height) {
+            self.blocks.insert(height, BlockSet::new());
+        }
+        self.blocks
+            .get_mut(&height)
+            .unwrap()
+            .add(block_hash, cached, size);
+        true
+    }
+
+    /// A function to get a block, the spilled one is read back from its file.
+    pub(crate) fn get_block(&mut self, height: Height, hash: &Hash) -> Option<Arc<B>> {
         self.blocks
             .get_mut(&height)
             .and_then(|bs| bs.get_block(hash))
     }
+
+    /// A function to drop the blocks of a height other than the current one.
+    pub(crate) fn evict(&mut self, height: Height) {
+        self.blocks.remove(&height);
+    }
+
+    /// A function to drop all the blocks.
+    pub(crate) fn clear(&mut self) {
+        self.blocks.clear();
+    }
+
+    /// A function to get the approximate bytes of the cached blocks in memory.
+    pub(crate) fn bytes(&self) -> usize {
+        self.blocks
+            .iter()
+            .map(|(_, block_set)| block_set.bytes)
+            .sum()
+    }
+}
+
+/// The directory the block bodies are spilled to, and the bytes of the blocks in memory
+/// beyond which the blocks are spilled.
+struct BlockSpill {
+    dir: PathBuf,
+    threshold: usize,
+}
+
+impl BlockSpill {
+    fn write<B: BlockPayload>(
+        &self,
+        height: Height,
+        block_hash: &Hash,
+        block: &B,
+    ) -> Option<Arc<SpilledBlock>> {
+        let path = self
+            .dir
+            .join(format!("{}-{}", height, HexFmt(&block_hash[..])));
+        fs::write(&path, block.to_bytes()).ok()?;
+        Some(Arc::new(SpilledBlock { path }))
+    }
+}
+
+/// The file of a spilled block, which is removed when the block is dropped from the cache.
+#[derive(Debug)]
+pub(crate) struct SpilledBlock {
+    path: PathBuf,
+}
+
+impl Drop for SpilledBlock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
 }
 
 #[derive(Clone, Debug)]
-pub(crate) struct BlockSet {
-    pub block_set: HashMap<Hash, Block>,
+pub(crate) enum CachedBlock<B: BlockPayload> {
+    Memory(Arc<B>),
+    Spilled(Arc<SpilledBlock>),
 }
 
-impl BlockSet {
+#[derive(Clone, Debug)]
+pub(crate) struct BlockSet<B: BlockPayload> {
+    pub block_set: HashMap<Hash, CachedBlock<B>>,
+    /// The approximate bytes of the blocks in memory.
+    pub bytes: usize,
+}
+
+impl<B: BlockPayload> BlockSet<B> {
     pub(crate) fn new() -> Self {
         BlockSet {
             block_set: HashMap::new(),
+            bytes: 0,
         }
     }
 
-    pub(crate) fn add(&mut self, hash: &Hash, block: &Block) -> bool {
+    /// A function to add a block of the [`size`] bytes, which are accounted if it is in memory.
+    pub(crate) fn add(&mut self, hash: &Hash, block: CachedBlock<B>, size: usize) -> bool {
         if self.block_set.contains_key(hash) {
             return false;
         }
-        self.block_set.insert(hash.clone(), block.clone());
+        if let CachedBlock::Memory(_) = block {
+            self.bytes += size;
+        }
+        self.block_set.insert(*hash, block);
         true
     }
 
-    pub(crate) fn get_block(&self, hash: &Hash) -> Option<&Block> {
-        self.block_set.get(hash)
+    pub(crate) fn get_block(&self, hash: &Hash) -> Option<Arc<B>> {
+        match self.block_set.get(hash)? {
+            CachedBlock::Memory(block) => Some(block.clone()),
+            CachedBlock::Spilled(spilled) => fs::read(&spilled.path)
+                .ok()
+                .and_then(|bytes| B::from_bytes(&bytes).ok())
+                .map(Arc::new),
+        }
     }
 }
@@ -0,0 +1,62 @@
+use crate::error::{BftError, BftResult};
+use crate::params::Compression;
+
+use std::borrow::Cow;
+
+/// The codec byte of a snappy compressed block segment.
+pub const CODEC_SNAPPY: u8 = 1;
+/// The codec byte of a zstd compressed block segment.
+pub const CODEC_ZSTD: u8 = 2;
+
+/// A function to compress the block segment, return the codec byte and the compressed bytes.
+/// Return `None` if the compression is disabled, fails or does not shrink the block.
+pub(crate) fn compress_block(block: &[u8], compression: Compression) -> Option<(u8, Vec<u8>)> {
+    let compressed: Option<(u8, Vec<u8>)> = match compression {
+        Compression::None => None,
+        #[cfg(feature = "snap")]
+        Compression::Snappy => snap::raw::Encoder::new()
+            .compress_vec(block)
+            .ok()
+            .map(|encode| (CODEC_SNAPPY, encode)),
+        #[cfg(feature = "zstd")]
+        Compression::Zstd(level) => zstd::bulk::compress(block, level)
+            .ok()
+            .map(|encode| (CODEC_ZSTD, encode)),
+    };
+    compressed.filter(|(_, encode)| encode.len() < block.len())
+}
+
+/// A function to decompress the block segment of the codec, the decompressed block larger than
+/// the `max_size` is rejected before it is allocated.
+#[cfg_attr(not(any(feature = "snap", feature = "zstd")), allow(unused_variables))]
+pub(crate) fn decompress_block(
+    block: &[u8],
+    codec: Option<u8>,
+    max_size: usize,
+) -> BftResult<Cow<'_, [u8]>> {
+    match codec {
+        None => Ok(Cow::Borrowed(block)),
+        #[cfg(feature = "snap")]
+        Some(CODEC_SNAPPY) => {
+            let len = snap::raw::decompress_len(block).map_err(|e| {
+                BftError::DecodeErr(format!("snappy block encounters {:?}", e).into())
+            })?;
+            if len > max_size {
+                return Err(BftError::MsgTooLarge(
+                    format!("block length {} exceeds the limit {}", len, max_size).into(),
+                ));
+            }
+            snap::raw::Decoder::new()
+                .decompress_vec(block)
+                .map(Cow::Owned)
+                .map_err(|e| BftError::DecodeErr(format!("snappy block encounters {:?}", e).into()))
+        }
+        #[cfg(feature = "zstd")]
+        Some(CODEC_ZSTD) => zstd::bulk::decompress(block, max_size)
+            .map(Cow::Owned)
+            .map_err(|e| BftError::DecodeErr(format!("zstd block encounters {:?}", e).into())),
+        Some(codec) => Err(BftError::DecodeErr(
+            format!("block encounters unsupported codec {}", codec).into(),
+        )),
+    }
+}
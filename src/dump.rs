@@ -0,0 +1,140 @@
+use crate::algorithm::Bft;
+use crate::collectors::VoteSet;
+use crate::objects::VoteType;
+use crate::*;
+
+use hex_fmt::HexFmt;
+use serde_derive::Serialize;
+
+/// A point-in-time picture of the state machine, serialized to JSON for debugging.
+#[derive(Serialize)]
+struct DebugDump {
+    address: String,
+    height: Height,
+    round: Round,
+    step: String,
+    consensus_power: bool,
+    proposer: Option<String>,
+    lock: Option<LockDump>,
+    feed: Option<String>,
+    authorities: usize,
+    weight_sum: u64,
+    prevote_quorum: Option<Round>,
+    precommit_quorum: Option<Round>,
+    rounds: Vec<RoundTally>,
+    caches: CacheOccupancy,
+}
+
+#[derive(Serialize)]
+struct LockDump {
+    round: Round,
+    block_hash: String,
+    votes: usize,
+}
+
+/// The votes of a round of the current height.
+#[derive(Serialize)]
+struct RoundTally {
+    round: Round,
+    prevote: Option<VoteTally>,
+    precommit: Option<VoteTally>,
+}
+
+#[derive(Serialize)]
+struct VoteTally {
+    voters: usize,
+    weight: u64,
+    leading: Option<(String, u64)>,
+}
+
+impl From<&VoteSet> for VoteTally {
+    fn from(vote_set: &VoteSet) -> Self {
+        VoteTally {
+            voters: vote_set.votes_by_sender.len(),
+            weight: vote_set.count,
+            leading: vote_set
+                .leading
+                .map(|(block_hash, weight)| (hex(&block_hash), weight)),
+        }
+    }
+}
+
+/// The count of the entries in the caches.
+#[derive(Serialize)]
+struct CacheOccupancy {
+    proposal_heights: usize,
+    block_heights: usize,
+    vote_heights: usize,
+    verify_results: usize,
+    sig_cache: usize,
+    proposal_bytes: usize,
+    block_bytes: usize,
+    vote_bytes: usize,
+}
+
+fn hex(bytes: &[u8]) -> String {
+    format!("{}", HexFmt(bytes))
+}
+
+impl<T, B> Bft<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    /// A function to dump the height, round, step, lock, proposer, vote tallies of each round
+    /// and cache occupancy to JSON.
+    pub(crate) fn debug_dump(&mut self) -> String {
+        let mut rounds: Vec<RoundTally> = match self.votes.votes.get_mut(&self.height) {
+            Some(round_votes) => round_votes
+                .round_votes
+                .iter()
+                .map(|(round, step_votes)| RoundTally {
+                    round: *round,
+                    prevote: step_votes
+                        .get_voteset(&VoteType::Prevote)
+                        .map(VoteTally::from),
+                    precommit: step_votes
+                        .get_voteset(&VoteType::Precommit)
+                        .map(VoteTally::from),
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+        rounds.sort_by_key(|tally| tally.round);
+        let usage = self.cache_usage();
+
+        let dump = DebugDump {
+            address: hex(&self.params.address),
+            height: self.height,
+            round: self.round,
+            step: format!("{:?}", self.step),
+            consensus_power: self.consensus_power,
+            proposer: self
+                .get_proposer(self.height, self.round)
+                .ok()
+                .map(|proposer| hex(&proposer[..])),
+            lock: self.lock_status.as_ref().map(|lock_status| LockDump {
+                round: lock_status.round,
+                block_hash: hex(&lock_status.block_hash),
+                votes: lock_status.votes.len(),
+            }),
+            feed: self.feed.map(|block_hash| hex(&block_hash)),
+            authorities: self.authority_manage.authorities.len(),
+            weight_sum: self.authority_manage.weight_sum,
+            prevote_quorum: self.votes.prevote_quorum,
+            precommit_quorum: self.votes.precommit_quorum,
+            rounds,
+            caches: CacheOccupancy {
+                proposal_heights: self.proposals.proposals.len(),
+                block_heights: self.blocks.blocks.len(),
+                vote_heights: self.votes.votes.len(),
+                verify_results: self.verify_results.len(),
+                sig_cache: self.sig_cache.len(),
+                proposal_bytes: usage.proposals,
+                block_bytes: usage.blocks,
+                vote_bytes: usage.votes,
+            },
+        };
+        serde_json::to_string(&dump).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+    }
+}
@@ -1,78 +1,375 @@
-use crate::Address;
+use crate::params::BftParams;
+use crate::{Address, BftSupport, BlockPayload, Height, Round, VerifyError};
 #[allow(unused_imports)]
 use log::{error, log, trace, warn};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
 
 pub type BftResult<T> = ::std::result::Result<T, BftError>;
+
+/// The kind of the msg an error is encountered with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MsgKind {
+    Proposal,
+    Vote,
+    Status,
+    Feed,
+    VerifyResp,
+    Txs,
+    Block,
+    Proof,
+    Wal,
+    Timer,
+}
+
+/// The context of an error, the fields unknown where it is encountered are `None`.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct ErrorInfo {
+    /// the height of the msg or state
+    pub height: Option<Height>,
+    /// the round of the msg or state
+    pub round: Option<Round>,
+    /// the sender of the msg
+    pub sender: Option<Address>,
+    /// the kind of the msg
+    pub kind: Option<MsgKind>,
+    /// the human-readable detail, which is not stable for parsing
+    pub detail: String,
+}
+
+impl ErrorInfo {
+    /// A function to create the context of the [`detail`].
+    pub fn new<S: Into<String>>(detail: S) -> Self {
+        ErrorInfo {
+            detail: detail.into(),
+            ..Default::default()
+        }
+    }
+
+    /// A function to set the height and round.
+    pub fn at(mut self, height: Height, round: Round) -> Self {
+        self.height = Some(height);
+        self.round = Some(round);
+        self
+    }
+
+    /// A function to set the height.
+    pub fn height(mut self, height: Height) -> Self {
+        self.height = Some(height);
+        self
+    }
+
+    /// A function to set the sender.
+    pub fn sender(mut self, sender: Address) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// A function to set the msg kind.
+    pub fn kind(mut self, kind: MsgKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+}
+
+impl Debug for ErrorInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.detail)?;
+        if let Some(height) = self.height {
+            write!(f, ", h: {}", height)?;
+        }
+        if let Some(round) = self.round {
+            write!(f, ", r: {}", round)?;
+        }
+        if let Some(sender) = &self.sender {
+            write!(f, ", sender: {:?}", sender)?;
+        }
+        if let Some(kind) = self.kind {
+            write!(f, ", kind: {:?}", kind)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<String> for ErrorInfo {
+    fn from(detail: String) -> Self {
+        ErrorInfo::new(detail)
+    }
+}
+
+impl From<&str> for ErrorInfo {
+    fn from(detail: &str) -> Self {
+        ErrorInfo::new(detail)
+    }
+}
+
 /// Error for Bft actuator.
+/// New variants may be added, match the [`code`] for the stable classification.
 #[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
 pub enum BftError {
-    ShouldNotHappen(String),
+    ShouldNotHappen(ErrorInfo),
+    /// An internal state is broken, the main loop restarts from wal.
+    InternalErr(ErrorInfo),
     /// Send message error.
-    SendMsgErr(String),
+    SendMsgErr(ErrorInfo),
     /// Receive message error.
-    RecvMsgErr(String),
+    RecvMsgErr(ErrorInfo),
+    /// The user-defined transmit fails.
+    TransmitFailed(ErrorInfo),
+    /// The bounded message channel is full.
+    ChannelFull(ErrorInfo),
+    /// An own vote violates the locking rules in the `lock_audit` mode, it is replaced by a
+    /// nil one.
+    LockViolation(ErrorInfo),
+    /// The main loop restarts from wal `restart_escalation_times` times at the same height.
+    RestartStorm(ErrorInfo),
 
-    RecvMsgAgain(String),
+    RecvMsgAgain(ErrorInfo),
 
-    ObsoleteMsg(String),
+    ObsoleteMsg(ErrorInfo),
 
-    HigherMsg(String),
+    HigherMsg(ErrorInfo),
 
-    DecodeErr(String),
+    DecodeErr(ErrorInfo),
+    /// A decoded msg exceeds the configured size limits.
+    MsgTooLarge(ErrorInfo),
+    /// The wire envelope has a mismatching version, chain id or kind.
+    MismatchingWireMsg(ErrorInfo),
+    /// The height or the round of a msg is too far from the current ones, the msg is dropped
+    /// before its signature is checked.
+    OutOfWindow(ErrorInfo),
 
-    SaveWalErr(String),
+    SaveWalErr(ErrorInfo),
+    /// Open or create the wal failed.
+    InitWalErr(ErrorInfo),
+    /// Store or load a proof of the proof store failed.
+    ProofStoreErr(ErrorInfo),
+    /// Open or write the msg trace failed.
+    TraceErr(ErrorInfo),
 
-    InvalidSender(String),
+    InvalidSender(ErrorInfo),
+    /// The authority changes of a status cannot be applied to the current authority list.
+    InvalidAuthorities(ErrorInfo),
+    /// The sender exceeds its msg rate limit.
+    RateLimited(ErrorInfo),
+    /// The sender is temporarily banned for repeated signature failures.
+    BannedSender(ErrorInfo),
 
-    MismatchingBlock(String),
+    MismatchingBlock(ErrorInfo),
 
-    CheckBlockFailed(String),
+    CheckBlockFailed(ErrorInfo),
 
-    CheckTxFailed(String),
+    CheckTxFailed(ErrorInfo),
 
-    CheckSigFailed(String),
+    CheckSigFailed(ErrorInfo),
 
-    CheckProofFailed(String),
+    CheckProofFailed(ErrorInfo),
 
-    CheckLockVotesFailed(String),
+    CheckLockVotesFailed(ErrorInfo),
+    /// The timestamp of a proposal drifts too far from the local clock.
+    CheckTimestampFailed(ErrorInfo),
+    /// The consensus params of a status are invalid.
+    InvalidParams(ErrorInfo),
+    /// A proof commits the nil block hash.
+    NilProof(ErrorInfo),
 
-    SignFailed(String),
+    SignFailed(ErrorInfo),
 
-    CommitFailed(String),
+    CommitFailed(ErrorInfo),
+    /// The commit still fails after all the re-attempts, the node waits at the commit step
+    /// until a status of the height arrives.
+    CommitStalled(ErrorInfo),
 
-    GetBlockFailed(String),
+    GetBlockFailed(ErrorInfo),
+    /// The block to propose cannot be got before the propose timeout.
+    FeedUnavailable(ErrorInfo),
 
-    NotReady(String),
+    NotReady(ErrorInfo),
 
-    ObsoleteTimer(String),
+    ObsoleteTimer(ErrorInfo),
+    /// The round reaches the `max_round` of the config, reported once a height. The node goes
+    /// on to the later rounds with the saturated timeouts.
+    RoundExhausted(ErrorInfo),
 }
 
-pub(crate) fn handle_err<T>(result: BftResult<T>, address: &Address) {
-    if let Err(e) = result {
+impl From<VerifyError> for BftError {
+    fn from(e: VerifyError) -> Self {
         match e {
-            BftError::NotReady(_)
-            | BftError::ObsoleteMsg(_)
-            | BftError::HigherMsg(_)
-            | BftError::RecvMsgAgain(_) => trace!("Node {:?} encounters {:?}", address, e),
-
-            BftError::CheckProofFailed(_)
-            | BftError::CheckBlockFailed(_)
-            | BftError::CheckLockVotesFailed(_)
-            | BftError::CheckSigFailed(_)
-            | BftError::CheckTxFailed(_)
-            | BftError::DecodeErr(_)
-            | BftError::InvalidSender(_)
-            | BftError::MismatchingBlock(_) => warn!("Node {:?} encounters {:?}", address, e),
-
-            BftError::ShouldNotHappen(_)
-            | BftError::SendMsgErr(_)
-            | BftError::RecvMsgErr(_)
-            | BftError::CommitFailed(_)
-            | BftError::SaveWalErr(_)
-            | BftError::SignFailed(_)
-            | BftError::GetBlockFailed(_) => error!("Node {:?} encounters {:?}", address, e),
-
-            BftError::ObsoleteTimer(_) => {}
+            VerifyError::Decode(detail) => BftError::DecodeErr(detail.into()),
+            VerifyError::MismatchingWireMsg(detail) => BftError::MismatchingWireMsg(detail.into()),
+        }
+    }
+}
+
+impl BftError {
+    /// A function to get the stable numeric code of the error.
+    /// The hundreds digit is the category: 1 internal, 2 msg receiving, 3 storage, 4 validation,
+    /// 5 user-defined functions, 6 scheduling.
+    pub fn code(&self) -> u16 {
+        match self {
+            BftError::ShouldNotHappen(_) => 100,
+            BftError::InternalErr(_) => 101,
+            BftError::SendMsgErr(_) => 102,
+            BftError::RecvMsgErr(_) => 103,
+            BftError::TransmitFailed(_) => 104,
+            BftError::ChannelFull(_) => 105,
+            BftError::LockViolation(_) => 106,
+            BftError::RestartStorm(_) => 107,
+            BftError::RecvMsgAgain(_) => 200,
+            BftError::ObsoleteMsg(_) => 201,
+            BftError::HigherMsg(_) => 202,
+            BftError::DecodeErr(_) => 203,
+            BftError::MsgTooLarge(_) => 204,
+            BftError::MismatchingWireMsg(_) => 205,
+            BftError::OutOfWindow(_) => 206,
+            BftError::SaveWalErr(_) => 300,
+            BftError::InitWalErr(_) => 301,
+            BftError::ProofStoreErr(_) => 302,
+            BftError::TraceErr(_) => 303,
+            BftError::InvalidSender(_) => 400,
+            BftError::InvalidAuthorities(_) => 401,
+            BftError::RateLimited(_) => 402,
+            BftError::BannedSender(_) => 403,
+            BftError::MismatchingBlock(_) => 404,
+            BftError::CheckBlockFailed(_) => 405,
+            BftError::CheckTxFailed(_) => 406,
+            BftError::CheckSigFailed(_) => 407,
+            BftError::CheckProofFailed(_) => 408,
+            BftError::CheckLockVotesFailed(_) => 409,
+            BftError::CheckTimestampFailed(_) => 410,
+            BftError::InvalidParams(_) => 411,
+            BftError::NilProof(_) => 412,
+            BftError::SignFailed(_) => 500,
+            BftError::CommitFailed(_) => 501,
+            BftError::GetBlockFailed(_) => 502,
+            BftError::CommitStalled(_) => 503,
+            BftError::FeedUnavailable(_) => 504,
+            BftError::NotReady(_) => 600,
+            BftError::ObsoleteTimer(_) => 601,
+            BftError::RoundExhausted(_) => 602,
+        }
+    }
+
+    /// A function to get the context of the error.
+    pub fn info(&self) -> &ErrorInfo {
+        match self {
+            BftError::ShouldNotHappen(info)
+            | BftError::InternalErr(info)
+            | BftError::SendMsgErr(info)
+            | BftError::RecvMsgErr(info)
+            | BftError::TransmitFailed(info)
+            | BftError::ChannelFull(info)
+            | BftError::LockViolation(info)
+            | BftError::RestartStorm(info)
+            | BftError::RecvMsgAgain(info)
+            | BftError::ObsoleteMsg(info)
+            | BftError::HigherMsg(info)
+            | BftError::OutOfWindow(info)
+            | BftError::DecodeErr(info)
+            | BftError::MsgTooLarge(info)
+            | BftError::MismatchingWireMsg(info)
+            | BftError::SaveWalErr(info)
+            | BftError::InitWalErr(info)
+            | BftError::ProofStoreErr(info)
+            | BftError::TraceErr(info)
+            | BftError::InvalidSender(info)
+            | BftError::InvalidAuthorities(info)
+            | BftError::RateLimited(info)
+            | BftError::BannedSender(info)
+            | BftError::MismatchingBlock(info)
+            | BftError::CheckBlockFailed(info)
+            | BftError::CheckTxFailed(info)
+            | BftError::CheckSigFailed(info)
+            | BftError::CheckProofFailed(info)
+            | BftError::CheckLockVotesFailed(info)
+            | BftError::CheckTimestampFailed(info)
+            | BftError::InvalidParams(info)
+            | BftError::NilProof(info)
+            | BftError::SignFailed(info)
+            | BftError::CommitFailed(info)
+            | BftError::CommitStalled(info)
+            | BftError::GetBlockFailed(info)
+            | BftError::FeedUnavailable(info)
+            | BftError::NotReady(info)
+            | BftError::ObsoleteTimer(info)
+            | BftError::RoundExhausted(info) => info,
+        }
+    }
+}
+
+/// A function to log the error of the [`result`] and report it to the `on_error` of the support.
+pub(crate) fn handle_err<T, S: BftSupport<B>, B: BlockPayload>(
+    result: BftResult<T>,
+    params: &BftParams,
+    function: &S,
+) {
+    if let Err(e) = result {
+        report_err(&e, params, function);
+    }
+}
+
+/// A function to log the [`e`] and report it to the `on_error` of the support.
+pub(crate) fn report_err<S: BftSupport<B>, B: BlockPayload>(
+    e: &BftError,
+    params: &BftParams,
+    function: &S,
+) {
+    log_err(e, params);
+    function.on_error(e);
+}
+
+fn log_err(e: &BftError, params: &BftParams) {
+    let (target, address) = (&params.log_target, &params.address);
+    match e {
+        BftError::NotReady(_)
+        | BftError::ObsoleteMsg(_)
+        | BftError::HigherMsg(_)
+        | BftError::OutOfWindow(_)
+        | BftError::RecvMsgAgain(_)
+        | BftError::RateLimited(_)
+        | BftError::BannedSender(_) => {
+            trace!(target: target, "Node {:?} encounters {:?}", address, e)
+        }
+
+        BftError::CheckProofFailed(_)
+        | BftError::CheckBlockFailed(_)
+        | BftError::CheckLockVotesFailed(_)
+        | BftError::CheckTimestampFailed(_)
+        | BftError::InvalidParams(_)
+        | BftError::NilProof(_)
+        | BftError::CheckSigFailed(_)
+        | BftError::CheckTxFailed(_)
+        | BftError::DecodeErr(_)
+        | BftError::MsgTooLarge(_)
+        | BftError::MismatchingWireMsg(_)
+        | BftError::ChannelFull(_)
+        | BftError::TransmitFailed(_)
+        | BftError::InvalidSender(_)
+        | BftError::InvalidAuthorities(_)
+        | BftError::MismatchingBlock(_) => {
+            warn!(target: target, "Node {:?} encounters {:?}", address, e)
         }
+
+        BftError::ShouldNotHappen(_)
+        | BftError::InternalErr(_)
+        | BftError::SendMsgErr(_)
+        | BftError::RecvMsgErr(_)
+        | BftError::LockViolation(_)
+        | BftError::RestartStorm(_)
+        | BftError::CommitFailed(_)
+        | BftError::CommitStalled(_)
+        | BftError::SaveWalErr(_)
+        | BftError::InitWalErr(_)
+        | BftError::ProofStoreErr(_)
+        | BftError::TraceErr(_)
+        | BftError::SignFailed(_)
+        | BftError::GetBlockFailed(_)
+        | BftError::FeedUnavailable(_)
+        | BftError::RoundExhausted(_) => {
+            error!(target: target, "Node {:?} encounters {:?}", address, e)
+        }
+
+        BftError::ObsoleteTimer(_) => {}
     }
 }
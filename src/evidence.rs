@@ -0,0 +1,228 @@
+use crate::codec::encode_by;
+use crate::objects::{SignedProposal, SignedVote};
+use crate::verify::{get_signed_hash, SignKind, SignScheme};
+use crate::{Address, Hash, Node, Signature};
+
+use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+use serde_derive::{Deserialize, Serialize};
+
+const KIND_DUPLICATE_VOTE: u8 = 0;
+const KIND_DUPLICATE_PROPOSAL: u8 = 1;
+
+/// Two signed votes of a voter for different blocks at the same height, round and vote type.
+/// The votes are ordered by block hash, so the same equivocation has a single encoding.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateVoteEvidence {
+    pub first: SignedVote,
+    pub second: SignedVote,
+}
+
+impl DuplicateVoteEvidence {
+    /// A function to build the evidence of the two conflicting votes in the canonical order.
+    /// Return `None` if the votes do not conflict.
+    pub fn new(a: SignedVote, b: SignedVote) -> Option<Self> {
+        let (va, vb) = (&a.vote, &b.vote);
+        if va.height != vb.height
+            || va.round != vb.round
+            || va.vote_type != vb.vote_type
+            || va.voter != vb.voter
+            || va.block_hash == vb.block_hash
+        {
+            return None;
+        }
+        if *va.block_hash < *vb.block_hash {
+            Some(DuplicateVoteEvidence {
+                first: a,
+                second: b,
+            })
+        } else {
+            Some(DuplicateVoteEvidence {
+                first: b,
+                second: a,
+            })
+        }
+    }
+}
+
+impl Encodable for DuplicateVoteEvidence {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2).append(&self.first).append(&self.second);
+    }
+}
+
+impl Decodable for DuplicateVoteEvidence {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let first: SignedVote = r.val_at(0)?;
+                let second: SignedVote = r.val_at(1)?;
+                Ok(DuplicateVoteEvidence { first, second })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// Two signed proposals of a proposer for different blocks at the same height and round.
+/// The proposals are ordered by block hash, so the same equivocation has a single encoding.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DuplicateProposalEvidence {
+    pub first: SignedProposal,
+    pub second: SignedProposal,
+}
+
+impl DuplicateProposalEvidence {
+    /// A function to build the evidence of the two conflicting proposals in the canonical order.
+    /// Return `None` if the proposals do not conflict.
+    pub fn new(a: SignedProposal, b: SignedProposal) -> Option<Self> {
+        let (pa, pb) = (&a.proposal, &b.proposal);
+        if pa.height != pb.height
+            || pa.round != pb.round
+            || pa.proposer != pb.proposer
+            || pa.block_hash == pb.block_hash
+        {
+            return None;
+        }
+        if *pa.block_hash < *pb.block_hash {
+            Some(DuplicateProposalEvidence {
+                first: a,
+                second: b,
+            })
+        } else {
+            Some(DuplicateProposalEvidence {
+                first: b,
+                second: a,
+            })
+        }
+    }
+}
+
+impl Encodable for DuplicateProposalEvidence {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2).append(&self.first).append(&self.second);
+    }
+}
+
+impl Decodable for DuplicateProposalEvidence {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let first: SignedProposal = r.val_at(0)?;
+                let second: SignedProposal = r.val_at(1)?;
+                Ok(DuplicateProposalEvidence { first, second })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// An evidence of a misbehaving authority.
+#[allow(clippy::large_enum_variant)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Evidence {
+    DuplicateVote(DuplicateVoteEvidence),
+    DuplicateProposal(DuplicateProposalEvidence),
+}
+
+impl Evidence {
+    /// A function to get the address of the misbehaving authority.
+    pub fn offender(&self) -> Address {
+        match self {
+            Evidence::DuplicateVote(evi) => evi.first.vote.voter,
+            Evidence::DuplicateProposal(evi) => evi.first.proposal.proposer,
+        }
+    }
+}
+
+impl Encodable for Evidence {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        match self {
+            Evidence::DuplicateVote(evi) => {
+                s.begin_list(2).append(&KIND_DUPLICATE_VOTE).append(evi);
+            }
+            Evidence::DuplicateProposal(evi) => {
+                s.begin_list(2).append(&KIND_DUPLICATE_PROPOSAL).append(evi);
+            }
+        }
+    }
+}
+
+impl Decodable for Evidence {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let kind: u8 = r.val_at(0)?;
+                match kind {
+                    KIND_DUPLICATE_VOTE => Ok(Evidence::DuplicateVote(r.val_at(1)?)),
+                    KIND_DUPLICATE_PROPOSAL => Ok(Evidence::DuplicateProposal(r.val_at(1)?)),
+                    _ => Err(DecoderError::Custom("invalid evidence kind")),
+                }
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A public function for evidence validation.
+/// The input [`authorities`] is the authority_list of the evidence height.
+/// The input [`scheme`] is the one the msgs are signed by.
+/// The fn [`check_sig`], [`crypt_hash`] are user-defined.
+pub fn verify_evidence(
+    evi: &Evidence,
+    authorities: &[Node],
+    scheme: SignScheme,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+) -> bool {
+    let offender = evi.offender();
+    if !authorities.iter().any(|node| node.address == offender) {
+        return false;
+    }
+    // the same msgs in the canonical order rebuild the evidence
+    let signed: Vec<(SignKind, Vec<u8>, &Signature)> = match evi {
+        Evidence::DuplicateVote(evi) => {
+            if DuplicateVoteEvidence::new(evi.first.clone(), evi.second.clone()).as_ref()
+                != Some(evi)
+            {
+                return false;
+            }
+            vec![
+                (
+                    SignKind::Vote,
+                    encode_by(&evi.first.vote, scheme.codec),
+                    &evi.first.signature,
+                ),
+                (
+                    SignKind::Vote,
+                    encode_by(&evi.second.vote, scheme.codec),
+                    &evi.second.signature,
+                ),
+            ]
+        }
+        Evidence::DuplicateProposal(evi) => {
+            if DuplicateProposalEvidence::new(evi.first.clone(), evi.second.clone()).as_ref()
+                != Some(evi)
+            {
+                return false;
+            }
+            vec![
+                (
+                    SignKind::Proposal,
+                    encode_by(&evi.first.proposal, scheme.codec),
+                    &evi.first.signature,
+                ),
+                (
+                    SignKind::Proposal,
+                    encode_by(&evi.second.proposal, scheme.codec),
+                    &evi.second.signature,
+                ),
+            ]
+        }
+    };
+    signed.iter().all(|(kind, msg, signature)| {
+        check_sig(
+            signature,
+            &get_signed_hash(*kind, scheme.domain, msg, &crypt_hash),
+        ) == Some(offender)
+    })
+}
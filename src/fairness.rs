@@ -0,0 +1,91 @@
+use crate::{Address, Height, Node, ProposerShare};
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// The proposers of the latest committed heights, which measure the realized shares of the
+/// authorities in proposing.
+#[derive(Debug)]
+pub(crate) struct ProposerWindow {
+    capacity: usize,
+    proposers: VecDeque<Address>,
+    last_height: Option<Height>,
+    /// The authorities deviating beyond the tolerance, reported once until they are back.
+    deviating: HashSet<Address>,
+}
+
+impl ProposerWindow {
+    /// A function to create a window of the proposers of the latest [`capacity`] heights,
+    /// nothing is recorded if it is 0.
+    pub(crate) fn new(capacity: usize) -> Self {
+        ProposerWindow {
+            capacity,
+            proposers: VecDeque::with_capacity(capacity),
+            last_height: None,
+            deviating: HashSet::new(),
+        }
+    }
+
+    /// A function to record the proposer of the block committed at the [`height`], false if
+    /// the height is recorded already.
+    pub(crate) fn record(&mut self, height: Height, proposer: Address) -> bool {
+        if self.capacity == 0 || self.last_height >= Some(height) {
+            return false;
+        }
+        self.last_height = Some(height);
+        if self.proposers.len() == self.capacity {
+            self.proposers.pop_front();
+        }
+        self.proposers.push_back(proposer);
+        true
+    }
+
+    /// A function to get the shares of the [`authorities`] and the other proposers in the
+    /// window. The expected shares are taken by the proposal weights of the authorities.
+    pub(crate) fn shares(&self, authorities: &[Node]) -> HashMap<Address, ProposerShare> {
+        let weight_sum: u64 = authorities
+            .iter()
+            .map(|node| u64::from(node.proposal_weight))
+            .sum();
+        let mut shares: HashMap<Address, ProposerShare> = HashMap::new();
+        for node in authorities.iter() {
+            let share = shares.entry(node.address).or_default();
+            if weight_sum > 0 {
+                share.expected_share += f64::from(node.proposal_weight) / weight_sum as f64;
+            }
+        }
+        for proposer in self.proposers.iter() {
+            shares.entry(*proposer).or_default().proposals += 1;
+        }
+        let total = self.proposers.len();
+        if total > 0 {
+            for share in shares.values_mut() {
+                share.actual_share = share.proposals as f64 / total as f64;
+            }
+        }
+        shares
+    }
+
+    /// A function to get the addresses whose actual shares newly deviate from the expected
+    /// ones by more than the [`tolerance`]. They are checked when the window is full.
+    pub(crate) fn check_deviations(
+        &mut self,
+        authorities: &[Node],
+        tolerance: f64,
+    ) -> Vec<(Address, ProposerShare)> {
+        if self.proposers.len() < self.capacity {
+            return Vec::new();
+        }
+        let mut deviations = Vec::new();
+        for (address, share) in self.shares(authorities) {
+            if (share.actual_share - share.expected_share).abs() > tolerance {
+                if self.deviating.insert(address) {
+                    deviations.push((address, share));
+                }
+            } else {
+                self.deviating.remove(&address);
+            }
+        }
+        deviations.sort_by_key(|(address, _)| *address);
+        deviations
+    }
+}
@@ -0,0 +1,306 @@
+//! The C bindings of `BftActuator`, declared in `include/bft.h`. The msgs are passed as byte
+//! buffers, the proposals and the votes as the wire envelopes, the statuses and the feeds rlp
+//! encoded. Build a static library of them by
+//! `cargo rustc --release --features ffi --crate-type staticlib`.
+
+use crate::error::BftError;
+use crate::params::BftConfig;
+use crate::*;
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
+use std::sync::Arc;
+
+/// The call succeeds.
+pub const BFT_OK: i32 = 0;
+/// A pointer argument is null or an argument is malformed.
+pub const BFT_ERR_ARG: i32 = -1;
+/// The msg fails to be decoded.
+pub const BFT_ERR_DECODE: i32 = -2;
+/// The msg fails to be sent to the state machine.
+pub const BFT_ERR_SEND: i32 = -3;
+
+/// The kind of a proposal msg, a wire envelope.
+pub const BFT_MSG_PROPOSAL: u32 = 0;
+/// The kind of a vote msg, a wire envelope.
+pub const BFT_MSG_VOTE: u32 = 1;
+/// The kind of a rlp encoded status msg.
+pub const BFT_MSG_STATUS: u32 = 2;
+/// The kind of a rlp encoded feed msg.
+pub const BFT_MSG_FEED: u32 = 3;
+/// The kind of a tick msg, which has no bytes.
+pub const BFT_MSG_TICK: u32 = 4;
+
+/// The callbacks of the host, called in the consensus threads with the [`ctx`], so they must
+/// be thread-safe. The buffers passed to them are only valid during the call, and a callback
+/// returns `BFT_OK` on success.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct BftCallbacks {
+    /// The context of the host passed to every callback.
+    pub ctx: *mut c_void,
+    /// Transmit the wire envelope of the kind to the node of the 20 bytes address, or to all the
+    /// nodes if the address is null.
+    pub transmit: extern "C" fn(
+        ctx: *mut c_void,
+        kind: u32,
+        target: *const u8,
+        msg: *const u8,
+        len: usize,
+    ) -> i32,
+    /// Commit the block of the height with the rlp encoded proof. The status of the height is
+    /// sent by `bft_actuator_send` after it.
+    pub commit: extern "C" fn(
+        ctx: *mut c_void,
+        height: u64,
+        block: *const u8,
+        block_len: usize,
+        proof: *const u8,
+        proof_len: usize,
+    ) -> i32,
+    /// Check the block of the 32 bytes hash at the height and the round.
+    pub check_block: extern "C" fn(
+        ctx: *mut c_void,
+        block: *const u8,
+        len: usize,
+        block_hash: *const u8,
+        height: u64,
+        round: u64,
+    ) -> i32,
+    /// Sign the 32 bytes hash into the signature buffer of the capacity, return the length of
+    /// the signature, or a negative value on failure.
+    pub sign: extern "C" fn(
+        ctx: *mut c_void,
+        hash: *const u8,
+        signature: *mut u8,
+        capacity: usize,
+    ) -> isize,
+    /// Recover the 20 bytes address into the address buffer from the signature of the
+    /// 32 bytes hash.
+    pub check_sig: extern "C" fn(
+        ctx: *mut c_void,
+        signature: *const u8,
+        len: usize,
+        hash: *const u8,
+        address: *mut u8,
+    ) -> i32,
+    /// Hash the msg into the 32 bytes hash buffer.
+    pub crypt_hash: extern "C" fn(ctx: *mut c_void, msg: *const u8, len: usize, hash: *mut u8),
+}
+
+/// The capacity of the signature buffer passed to the sign callback.
+const MAX_SIGNATURE_LEN: usize = 256;
+
+/// The support calling the callbacks of the host.
+struct FfiSupport {
+    callbacks: BftCallbacks,
+}
+
+// the host guarantees the callbacks and their context are thread-safe
+unsafe impl Send for FfiSupport {}
+unsafe impl Sync for FfiSupport {}
+
+impl BftSupport for FfiSupport {
+    type Error = i32;
+
+    fn check_block(
+        &self,
+        block: &Block,
+        block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, i32> {
+        let code = (self.callbacks.check_block)(
+            self.callbacks.ctx,
+            block.as_ptr(),
+            block.len(),
+            block_hash.as_ptr(),
+            height_round.0,
+            height_round.1,
+        );
+        Ok(VerifyResp {
+            is_pass: code == BFT_OK,
+            round: height_round.1,
+            #[cfg(feature = "compact_block")]
+            complete_block: block.clone(),
+            failure: None,
+        })
+    }
+
+    fn transmit(&self, outbound: Outbound) -> Result<(), i32> {
+        let target = match &outbound.target {
+            Target::Broadcast => ptr::null(),
+            Target::Unicast(address) => address.as_ptr(),
+        };
+        let (kind, msg) = match &outbound.msg {
+            OutboundMsg::Proposal(encode) => (BFT_MSG_PROPOSAL, encode),
+            OutboundMsg::Vote(encode) => (BFT_MSG_VOTE, encode),
+            // the host does not fetch the txs of the compact blocks
+            #[cfg(feature = "compact_block")]
+            _ => return Ok(()),
+        };
+        check_code((self.callbacks.transmit)(
+            self.callbacks.ctx,
+            kind,
+            target,
+            msg.as_ptr(),
+            msg.len(),
+        ))
+    }
+
+    fn commit(&self, commit: Commit) -> Result<Option<Status>, i32> {
+        let proof = rlp::encode(&commit.proof);
+        check_code((self.callbacks.commit)(
+            self.callbacks.ctx,
+            commit.height,
+            commit.block.as_ptr(),
+            commit.block.len(),
+            proof.as_ptr(),
+            proof.len(),
+        ))?;
+        Ok(None)
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(Block, Hash), i32> {
+        // the host feeds the blocks by BFT_MSG_FEED
+        Err(BFT_ERR_ARG)
+    }
+
+    fn sign(&self, hash: &Hash) -> Result<Signature, i32> {
+        let mut signature = vec![0u8; MAX_SIGNATURE_LEN];
+        let len = (self.callbacks.sign)(
+            self.callbacks.ctx,
+            hash.as_ptr(),
+            signature.as_mut_ptr(),
+            signature.len(),
+        );
+        if len < 0 || len as usize > MAX_SIGNATURE_LEN {
+            return Err(BFT_ERR_ARG);
+        }
+        signature.truncate(len as usize);
+        Ok(signature.into())
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, i32> {
+        let mut address = [0u8; ADDRESS_LEN];
+        check_code((self.callbacks.check_sig)(
+            self.callbacks.ctx,
+            signature.as_ptr(),
+            signature.len(),
+            hash.as_ptr(),
+            address.as_mut_ptr(),
+        ))?;
+        Ok(Address::from(address))
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        let mut hash = [0u8; HASH_LEN];
+        (self.callbacks.crypt_hash)(
+            self.callbacks.ctx,
+            msg.as_ptr(),
+            msg.len(),
+            hash.as_mut_ptr(),
+        );
+        Hash::from(hash)
+    }
+}
+
+fn check_code(code: i32) -> Result<(), i32> {
+    if code == BFT_OK {
+        Ok(())
+    } else {
+        Err(code)
+    }
+}
+
+/// Create an actuator of the 20 bytes [`address`] with its wal in the [`wal_path`], and start
+/// the state machine calling the [`callbacks`]. Return null if an argument is invalid.
+/// The actuator is released by `bft_actuator_stop`.
+///
+/// # Safety
+///
+/// The [`address`] points to 20 bytes and the [`wal_path`] is a nul-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn bft_actuator_new(
+    address: *const u8,
+    wal_path: *const c_char,
+    callbacks: BftCallbacks,
+) -> *mut BftActuator {
+    if address.is_null() || wal_path.is_null() {
+        return ptr::null_mut();
+    }
+    let mut bytes = [0u8; ADDRESS_LEN];
+    bytes.copy_from_slice(slice::from_raw_parts(address, ADDRESS_LEN));
+    let wal_path = match CStr::from_ptr(wal_path).to_str() {
+        Ok(wal_path) => wal_path,
+        Err(_) => return ptr::null_mut(),
+    };
+    let actuator = BftActuator::new_with_config(
+        Arc::new(FfiSupport { callbacks }),
+        Address::from(bytes),
+        wal_path,
+        BftConfig::default(),
+    );
+    Box::into_raw(Box::new(actuator))
+}
+
+/// Send the msg of the [`kind`] in the [`len`] bytes of the [`msg`] to the state machine.
+///
+/// # Safety
+///
+/// The [`actuator`] is created by `bft_actuator_new` and not stopped, and the [`msg`] points
+/// to the [`len`] bytes.
+#[no_mangle]
+pub unsafe extern "C" fn bft_actuator_send(
+    actuator: *const BftActuator,
+    kind: u32,
+    msg: *const u8,
+    len: usize,
+) -> i32 {
+    let actuator = match actuator.as_ref() {
+        Some(actuator) => actuator,
+        None => return BFT_ERR_ARG,
+    };
+    let encode = if msg.is_null() {
+        &[][..]
+    } else {
+        slice::from_raw_parts(msg, len)
+    };
+    let msg = match kind {
+        BFT_MSG_PROPOSAL => BftMsg::Proposal(encode.to_vec()),
+        BFT_MSG_VOTE => BftMsg::Vote(encode.to_vec()),
+        BFT_MSG_STATUS => match rlp::decode(encode) {
+            Ok(status) => BftMsg::Status(status),
+            Err(_) => return BFT_ERR_DECODE,
+        },
+        BFT_MSG_FEED => match rlp::decode(encode) {
+            Ok(feed) => BftMsg::Feed(feed),
+            Err(_) => return BFT_ERR_DECODE,
+        },
+        BFT_MSG_TICK => BftMsg::Tick,
+        _ => return BFT_ERR_ARG,
+    };
+    match actuator.send(msg) {
+        Ok(()) => BFT_OK,
+        Err(BftError::DecodeErr(_)) | Err(BftError::MismatchingWireMsg(_)) => BFT_ERR_DECODE,
+        Err(_) => BFT_ERR_SEND,
+    }
+}
+
+/// Stop the state machine and release the [`actuator`].
+///
+/// # Safety
+///
+/// The [`actuator`] is created by `bft_actuator_new` and not stopped.
+#[no_mangle]
+pub unsafe extern "C" fn bft_actuator_stop(actuator: *mut BftActuator) {
+    if actuator.is_null() {
+        return;
+    }
+    let actuator = Box::from_raw(actuator);
+    let _ = actuator.send(BftMsg::Kill);
+}
@@ -0,0 +1,54 @@
+use crate::compress::decompress_block;
+use crate::error::{BftError, BftResult};
+use crate::objects::{SignedProposal, SignedVote};
+use crate::params::BftConfig;
+use crate::utils::{
+    check_block_size, check_proof_size, check_signed_proposal_size, check_status_size,
+    extract_proposal, peek_height_round,
+};
+use crate::{BlockPayload, Proof, Status};
+
+use rlp::Rlp;
+
+/// A function to decode a proposal msg with the wire envelope opened, the same way the state
+/// machine does with the size limits of the [`config`], return the signed_proposal and the block.
+pub fn decode_proposal<B: BlockPayload>(
+    encode: &[u8],
+    config: &BftConfig,
+) -> BftResult<(SignedProposal, B)> {
+    let (signed_proposal_encode, block, codec) = extract_proposal(encode)?;
+    // the height and the round are peeked by the ingress filter before decoding
+    let _ = peek_height_round(signed_proposal_encode, 0);
+    let block = decompress_block(block, codec, config.max_block_size)?;
+    check_block_size(&block, config)?;
+    check_signed_proposal_size(signed_proposal_encode, config)?;
+    let signed_proposal: SignedProposal = rlp::decode(signed_proposal_encode)
+        .map_err(|e| BftError::DecodeErr(format!("signed_proposal encounters {:?}", e).into()))?;
+    let block = B::from_bytes(&block)
+        .map_err(|e| BftError::DecodeErr(format!("block encounters {:?}", e).into()))?;
+    Ok((signed_proposal, block))
+}
+
+/// A function to decode a vote msg with the wire envelope opened, the same way the state
+/// machine does.
+pub fn decode_vote(encode: &[u8]) -> BftResult<SignedVote> {
+    // the height and the round are peeked by the ingress filter before decoding
+    let _ = peek_height_round(encode, 1);
+    rlp::decode(encode)
+        .map_err(|e| BftError::DecodeErr(format!("signed_vote encounters {:?}", e).into()))
+}
+
+/// A function to decode a status of wal, the same way the state machine does with the size
+/// limits of the [`config`].
+pub fn decode_status(encode: &[u8], config: &BftConfig) -> BftResult<Status> {
+    check_status_size(encode, config)?;
+    rlp::decode(encode)
+        .map_err(|e| BftError::DecodeErr(format!("status encounters {:?}", e).into()))
+}
+
+/// A function to decode a proof of wal, the same way the state machine does with the size
+/// limits of the [`config`].
+pub fn decode_proof(encode: &[u8], config: &BftConfig) -> BftResult<Proof> {
+    check_proof_size(&Rlp::new(encode), config)?;
+    rlp::decode(encode).map_err(|e| BftError::DecodeErr(format!("proof encounters {:?}", e).into()))
+}
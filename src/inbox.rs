@@ -0,0 +1,48 @@
+use crate::{BftMsg, BlockPayload};
+
+use std::collections::VecDeque;
+
+/// A queue of the msgs drained from the channel, the urgent ones are processed before the
+/// others and the msgs of the same priority are processed in the order they arrive.
+pub(crate) struct Inbox<B: BlockPayload> {
+    capacity: usize,
+    urgent: VecDeque<BftMsg<B>>,
+    normal: VecDeque<BftMsg<B>>,
+}
+
+impl<B: BlockPayload> Inbox<B> {
+    /// A function to create a new inbox draining at most the [`capacity`] of msgs.
+    pub(crate) fn new(capacity: usize) -> Self {
+        Inbox {
+            capacity,
+            urgent: VecDeque::new(),
+            normal: VecDeque::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, msg: BftMsg<B>, urgent: bool) {
+        if urgent {
+            self.urgent.push_back(msg);
+        } else {
+            self.normal.push_back(msg);
+        }
+    }
+
+    /// A function to take the earliest urgent msg, or the earliest other one if there is
+    /// no urgent msg.
+    pub(crate) fn pop(&mut self) -> Option<BftMsg<B>> {
+        self.urgent.pop_front().or_else(|| self.normal.pop_front())
+    }
+
+    pub(crate) fn is_full(&self) -> bool {
+        self.len() >= self.capacity
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.urgent.len() + self.normal.len()
+    }
+}
@@ -1,161 +1,658 @@
 //! An efficent and stable Rust library of BFT protocol for distributed system.
 use crate::{
-    algorithm::Bft,
+    algorithm::{Bft, StartPoint, Waker},
+    byzantine::ByzantineStrategy,
     error::{BftError, BftResult},
-    objects::{Vote, VoteType},
-    utils::{get_total_weight, get_votes_weight},
+    objects::{SignedProposal, SignedVote, StateSnapshot, Step, VoteType},
+    replay::{ReplayProgress, ReplayReport},
 };
 
-use crate::utils::extract_two;
-use crossbeam::crossbeam_channel::{unbounded, Sender};
-use hex_fmt::HexFmt;
+use crate::codec::{AppendBy, CodecEncodable, RlpCodec};
+use crate::params::{BftConfig, NilVotePolicy, OverflowPolicy};
+use crate::proof_store::{MemoryProofStore, ProofStore};
+use crate::utils::{combine_two, get_msg_height, msg_info};
+use crate::wire::{WireKind, WireMsg};
+use crossbeam::crossbeam_channel::{bounded, Receiver, Sender, TrySendError};
 #[allow(unused_imports)]
 use log::{debug, error, info, log, trace};
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
 use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::fmt::{Debug, Formatter, Result as FmtResult};
-use std::hash::{Hash as Hashable, Hasher};
-use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+#[cfg(feature = "commit_stats")]
+use std::time::Duration;
+
+pub use crate::verify::{
+    check_proof, check_proof_with_authorities_hash, get_authorities_hash, get_proposal_hash,
+    get_signed_hash, try_check_proof, try_check_proof_with_authorities_hash, ProofError, SignKind,
+    SignScheme,
+};
+pub use bft_verify::{
+    Address, Block, Error as VerifyError, Hash, Height, Node, Proof, Round, Signature, ADDRESS_LEN,
+    HASH_LEN,
+};
 
 /// Define the core functions of the BFT state machine.
 pub mod algorithm;
+/// Define the audit of the own votes against the locking rules.
+pub(crate) mod audit;
+/// Define the harness driving a single state machine in the benchmarks and the simulations.
+#[cfg(feature = "bench")]
+pub mod bench;
 /// Define simple byzantine behaviors.
 pub mod byzantine;
+/// Define the conversions of the proofs of cita-bft.
+#[cfg(feature = "cita")]
+pub mod cita;
+/// Define the local clock the timers and the proposal timestamps are taken by.
+pub mod clock;
+/// Define the rlp codecs of the byte wrappers.
+pub use bft_verify::codec;
 /// Define collectors of blocks, signed_proposals and signed_votes.
 pub mod collectors;
+/// Define the compression of the block segment in proposals.
+pub mod compress;
+/// Define the JSON debug dump of the internal state.
+pub(crate) mod dump;
 /// Define errors.
 pub mod error;
-/// Define structures only for this crate, including Proposal, Vote, Step.
+/// Define the evidences of misbehaving authorities for slashing.
+pub mod evidence;
+/// Define the window of the proposers of the latest committed heights.
+pub(crate) mod fairness;
+/// Define the C bindings of the actuator.
+#[cfg(feature = "ffi")]
+pub mod ffi;
+/// Define the versioned multi-segment framing.
+pub use bft_verify::frame;
+/// Define the decode entry points of the fuzz targets.
+#[cfg(feature = "fuzz")]
+pub mod fuzz;
+/// Define the prioritized queue of the msgs to process.
+pub(crate) mod inbox;
+/// Define the commit certificates for light clients.
+pub mod light;
+/// Define rate limiter and peer scoring of msg senders.
+pub(crate) mod limiter;
+/// Define the recording of inbound and outbound msgs for post-mortems.
+pub mod msg_trace;
+/// Define structures including Proposal, Vote, Step.
 pub mod objects;
 /// Define params including time interval and local address.
 pub mod params;
+/// Define the height-indexed archive of proofs.
+pub mod proof_store;
+/// Define the progress reports, the dry run and the step debugger of replaying wal.
+pub mod replay;
+/// Define the retry queue of failed transmissions.
+pub(crate) mod retry;
+/// Define the timer and the workers shared by the actuators of a process.
+pub mod runtime;
+/// Define the tracing spans of heights, rounds and steps.
+#[cfg(feature = "tracing")]
+pub(crate) mod spans;
+/// Define the recorder of the consensus metrics in commits.
+#[cfg(feature = "commit_stats")]
+pub(crate) mod stats;
+/// Define the reference implementation of BftSupport with secp256k1 and keccak256.
+#[cfg(feature = "simple_support")]
+pub mod support;
+/// Define the main loop run as a tokio task.
+#[cfg(feature = "tokio")]
+pub(crate) mod task;
 /// Define a timeout structure and the timer process.
 pub mod timer;
 /// Define utils of the BFT state machine.
 pub mod utils;
+/// Define the pool checking signatures off the main loop.
+pub(crate) mod verifier;
+/// Define the verification core shared with light clients and bridge contracts, which is the
+/// `bft-verify` crate building with `no_std + alloc`.
+pub use bft_verify::verify;
 /// Define wal support.
 pub mod wal;
+/// Define the versioned wire envelope of signed_proposals and signed_votes.
+pub use bft_verify::wire;
 
-/// Define the structure of the node address.
-#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
-pub struct Address(Vec<u8>);
-/// Define the structure of the hash.
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub struct Hash(Vec<u8>);
-/// Define the structure of the signature.
-#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash)]
-pub struct Signature(Vec<u8>);
-/// Define the structure of the block.
-/// It is the consensus content, which should be serialized and wrapped.
-#[derive(Clone, Eq, PartialEq)]
-pub struct Block(Vec<u8>);
+/// The ticket of an asynchronous signing request, which the signature is delivered with.
+pub type SignTicket = u64;
 
-macro_rules! impl_traits_for_vecu8_wraper {
-    ($name: ident) => {
-        impl $name {
-            pub fn to_vec(&self) -> Vec<u8> {
-                self.0.clone()
-            }
-        }
-
-        impl Default for $name {
-            fn default() -> Self {
-                $name(vec![])
-            }
-        }
+/// The block payload of the consensus, which is the bytes `Block` by default.
+/// Structured blocks are only serialized for transmitting proposals and saving wal.
+pub trait BlockPayload: Clone + Debug + Eq + Send + Sync + 'static {
+    /// A function to serialize the block payload.
+    fn to_bytes(&self) -> Vec<u8>;
+    /// A function to deserialize the block payload.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecoderError>;
+    /// A function to get the approximate bytes of the block payload in memory, which are
+    /// accounted in the caches. Override it if serializing the block is costly.
+    fn size(&self) -> usize {
+        self.to_bytes().len()
+    }
+}
 
-        impl Deref for $name {
-            type Target = Vec<u8>;
-            fn deref(&self) -> &Self::Target {
-                &self.0
-            }
-        }
+impl BlockPayload for Block {
+    fn to_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
 
-        impl Encodable for $name {
-            fn rlp_append(&self, s: &mut RlpStream) {
-                s.begin_list(1).append(&self.0);
-            }
-        }
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecoderError> {
+        Ok(bytes.into())
+    }
 
-        impl Decodable for $name {
-            fn decode(r: &Rlp) -> Result<Self, DecoderError> {
-                match r.prototype()? {
-                    Prototype::List(1) => {
-                        let v: Vec<u8> = r.val_at(0)?;
-                        Ok($name(v))
-                    }
-                    _ => Err(DecoderError::RlpInconsistentLengthAndData),
-                }
-            }
-        }
+    fn size(&self) -> usize {
+        self.len()
+    }
+}
 
-        impl From<Vec<u8>> for $name {
-            fn from(v: Vec<u8>) -> Self {
-                $name(v)
-            }
-        }
+pub struct BftActuator<B: BlockPayload = Block> {
+    sender: Sender<BftMsg<B>>,
+    /// The lane of the proposals and votes of the future heights under
+    /// `OverflowPolicy::DropOldestFutureHeight`, whose oldest msg is dropped when it is full.
+    future_sender: Sender<BftMsg<B>>,
+    future_receiver: Receiver<BftMsg<B>>,
+    overflow_policy: OverflowPolicy,
+    chain_id: u64,
+    codec: RlpCodec,
+    height: Arc<AtomicU64>,
+    proof_store: Arc<dyn ProofStore>,
+    log_target: String,
+    waker: Option<Waker>,
+}
 
-        impl From<&[u8]> for $name {
-            fn from(v: &[u8]) -> Self {
-                $name(v.to_vec())
-            }
-        }
+impl<B: BlockPayload> BftActuator<B> {
+    /// A function to create a new Bft actuator and start the BFT state machine.
+    pub fn new<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+    ) -> Self {
+        Self::new_with_config(support, address, wal_path, BftConfig::default())
+    }
 
-        impl Debug for $name {
-            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-                write!(f, "{:?}", &format!("{:<10}", HexFmt(&self.0)),)
-            }
-        }
-    };
-}
+    /// A function to create a new Bft actuator with user-defined configs and start the BFT state machine.
+    pub fn new_with_config<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+    ) -> Self {
+        Self::start(support, address, wal_path, config, None, None)
+    }
 
-impl_traits_for_vecu8_wraper!(Address);
-impl_traits_for_vecu8_wraper!(Hash);
-impl_traits_for_vecu8_wraper!(Signature);
-impl_traits_for_vecu8_wraper!(Block);
+    /// A function to create a new Bft actuator whose state machine runs as a task on the tokio
+    /// runtime of the [`handle`], instead of its own timer thread and main thread. The timers
+    /// are taken by `tokio::time`, so the time driver of the runtime is enabled. The queries
+    /// of the actuator still block the caller until the task answers.
+    #[cfg(feature = "tokio")]
+    pub fn spawn_on<T: BftSupport<B> + 'static>(
+        handle: &tokio::runtime::Handle,
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+    ) -> Self {
+        let config = BftConfig {
+            tokio_handle: Some(handle.clone()),
+            ..config
+        };
+        Self::start(support, address, wal_path, config, None, None)
+    }
 
-pub type Height = u64;
+    /// A function to create a new Bft actuator which outputs signed_proposals and signed_votes
+    /// to the returned receiver instead of calling `BftSupport::transmit`.
+    pub fn new_with_outbox<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+    ) -> (Self, Receiver<Outbound>) {
+        let (outbox_sender, outbox_receiver) = bounded(config.channel_capacity);
+        let actuator = Self::start(
+            support,
+            address,
+            wal_path,
+            config,
+            Some(outbox_sender),
+            None,
+        );
+        (actuator, outbox_receiver)
+    }
 
-pub type Round = u64;
+    /// A function to create a new Bft actuator with user-defined configs and start the BFT state
+    /// machine at the [`height`] with the trusted [`proof`] of the previous height and the
+    /// [`authority_list`] of the height. The wal in the [`wal_path`] is discarded instead of
+    /// replayed.
+    pub fn new_at<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+        height: Height,
+        proof: Proof,
+        authority_list: Vec<Node>,
+    ) -> Self {
+        let start_point = StartPoint {
+            height,
+            proof,
+            authority_list,
+        };
+        Self::start(support, address, wal_path, config, None, Some(start_point))
+    }
 
-pub struct BftActuator(Sender<BftMsg>);
+    /// A function to replay the wal in the [`wal_path`] without starting consensus, to validate
+    /// it before starting the actuator. The [`support`] checks the blocks and the signatures,
+    /// while its `commit`, `get_block` and `transmit` are stubbed out. The wal is replayed on a
+    /// copy and left unchanged. Return the records failing to be replayed.
+    pub fn dry_run_replay<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+    ) -> BftResult<ReplayReport> {
+        replay::dry_run_replay::<T, B>(support, address, wal_path, config)
+    }
 
-impl BftActuator {
-    /// A function to create a new Bft actuator and start the BFT state machine.
-    pub fn new<T: BftSupport + 'static>(support: Arc<T>, address: Address, wal_path: &str) -> Self {
-        let (sender, internal_receiver) = unbounded();
-        Bft::start(
+    fn start<T: BftSupport<B> + 'static>(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+        outbox: Option<Sender<Outbound>>,
+        start_point: Option<StartPoint>,
+    ) -> Self {
+        let (sender, internal_receiver) = bounded(config.channel_capacity);
+        let (future_sender, future_receiver) = bounded(config.channel_capacity);
+        let overflow_policy = config.overflow_policy;
+        let chain_id = config.chain_id;
+        let codec = config.rlp_codec;
+        let log_target = config.log_target();
+        let height = Arc::new(AtomicU64::new(algorithm::INIT_HEIGHT));
+        // the state machine and the actuator share the same proof store
+        let proof_store = config.proof_store.clone().unwrap_or_else(|| {
+            Arc::new(MemoryProofStore::new(config.proof_store_capacity)) as Arc<dyn ProofStore>
+        });
+        let config = BftConfig {
+            proof_store: Some(proof_store.clone()),
+            ..config
+        };
+        let waker = Bft::launch(
             sender.clone(),
             internal_receiver,
+            future_receiver.clone(),
             support,
             address,
             wal_path,
+            config,
+            height.clone(),
+            outbox,
+            start_point,
         );
-        BftActuator(sender)
+        BftActuator {
+            sender,
+            future_sender,
+            future_receiver,
+            overflow_policy,
+            chain_id,
+            codec,
+            height,
+            proof_store,
+            log_target,
+            waker,
+        }
     }
 
     /// A function for sending msg to the BFT state machine.
-    pub fn send(&self, msg: BftMsg) -> BftResult<()> {
-        let info = format!("{:?} by BftActuator", &msg);
-        self.0.send(msg).map_err(|_| BftError::SendMsgErr(info))
+    /// The proposals and votes should be the wire envelopes received from other nodes,
+    /// the ones of a mismatching chain id are rejected.
+    pub fn send(&self, msg: BftMsg<B>) -> BftResult<()> {
+        let msg = self.open_wire_msg(msg)?;
+        self.send_msg(msg)
+    }
+
+    /// A function to get the proof of the [`height`] from the proof store,
+    /// `None` if it is not stored.
+    pub fn get_proof(&self, height: Height) -> BftResult<Option<Proof>> {
+        self.proof_store.get_proof(height)
+    }
+
+    /// A function to export the consensus state of the BFT state machine.
+    pub fn export_snapshot(&self) -> BftResult<StateSnapshot> {
+        let (snapshot_sender, snapshot_receiver) = bounded(1);
+        self.send_msg(BftMsg::ExportSnapshot(snapshot_sender))?;
+        snapshot_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of snapshot", e).into()))
+    }
+
+    /// A function to dump the internal state of the BFT state machine in JSON for debugging.
+    /// The fields are not stable.
+    pub fn debug_dump(&self) -> BftResult<String> {
+        let (dump_sender, dump_receiver) = bounded(1);
+        self.send_msg(BftMsg::Dump(dump_sender))?;
+        dump_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of dump", e).into()))
+    }
+
+    /// A function to get the authorities whose votes of the [`height`], the [`round`] and the
+    /// [`vote_type`] are not received, sorted by address.
+    /// Only the current height and the last height are tracked.
+    pub fn missing_voters(
+        &self,
+        height: Height,
+        round: Round,
+        vote_type: VoteType,
+    ) -> BftResult<Vec<Address>> {
+        let (voters_sender, voters_receiver) = bounded(1);
+        self.send_msg(BftMsg::MissingVoters(
+            height,
+            round,
+            vote_type,
+            voters_sender,
+        ))?;
+        voters_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of missing voters", e).into()))?
+    }
+
+    /// A function to get the msg counters of the senders, which detect the validators flooding
+    /// or consistently late. At most 1024 senders are tracked, the least recent are dropped.
+    pub fn peer_stats(&self) -> BftResult<HashMap<Address, PeerStats>> {
+        let (stats_sender, stats_receiver) = bounded(1);
+        self.send_msg(BftMsg::PeerStats(stats_sender))?;
+        stats_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of peer stats", e).into()))
+    }
+
+    /// A function to get the counters of the msgs dropped by their heights and rounds before
+    /// their signatures are checked, which detect the spam of hopeless msgs.
+    pub fn ingress_stats(&self) -> BftResult<IngressStats> {
+        let (stats_sender, stats_receiver) = bounded(1);
+        self.send_msg(BftMsg::IngressStats(stats_sender))?;
+        stats_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of ingress stats", e).into()))
+    }
+
+    /// A function to get the approximate bytes of the cached blocks, proposals and votes, which
+    /// are capped by the `cache_bytes_cap` of the config.
+    pub fn cache_usage(&self) -> BftResult<CacheUsage> {
+        let (usage_sender, usage_receiver) = bounded(1);
+        self.send_msg(BftMsg::CacheUsage(usage_sender))?;
+        usage_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of cache usage", e).into()))
+    }
+
+    /// A function to get the shares of the authorities and the other proposers in proposing
+    /// the blocks of the latest committed heights, in the window of the `proposer_window` of
+    /// the config. The expected shares are taken by the proposal weights of the current
+    /// authorities.
+    pub fn proposer_shares(&self) -> BftResult<HashMap<Address, ProposerShare>> {
+        let (shares_sender, shares_receiver) = bounded(1);
+        self.send_msg(BftMsg::ProposerShares(shares_sender))?;
+        shares_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of proposer shares", e).into()))
+    }
+
+    /// A function to prune the wal files of the heights below the [`below_height`], they are
+    /// moved to the `archive_dir` of the `wal_retention` if it is set. The current height is
+    /// never pruned.
+    pub fn prune_wal(&self, below_height: Height) -> BftResult<()> {
+        let (result_sender, result_receiver) = bounded(1);
+        self.send_msg(BftMsg::PruneWal(below_height, result_sender))?;
+        result_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of prune wal", e).into()))?
+    }
+
+    /// A function to start appending the inbound msgs, the fired timers and the outbound msgs
+    /// to the msg trace in the [`path`], or stop it if it is `None`. See `msg_trace::read_trace`.
+    pub fn set_trace(&self, path: Option<&str>) -> BftResult<()> {
+        let (result_sender, result_receiver) = bounded(1);
+        self.send_msg(BftMsg::Trace(path.map(str::to_string), result_sender))?;
+        result_receiver
+            .recv()
+            .map_err(|e| BftError::RecvMsgErr(format!("{:?} of trace", e).into()))?
+    }
+
+    /// A function for sending a signed_proposal with its block to the BFT state machine.
+    pub fn send_proposal(&self, signed_proposal: SignedProposal, block: B) -> BftResult<()> {
+        let encode = combine_two(&rlp::encode(&signed_proposal), &block.to_bytes());
+        self.send_msg(BftMsg::Proposal(encode))
+    }
+
+    /// A function for sending a signed_vote to the BFT state machine.
+    pub fn send_vote(&self, signed_vote: SignedVote) -> BftResult<()> {
+        self.send_msg(BftMsg::Vote(rlp::encode(&signed_vote)))
+    }
+
+    /// A function to begin the next height after the status of the current height, with
+    /// `external_tick` of `BftConfig` set. A tick before the status is kept until it arrives.
+    pub fn tick(&self) -> BftResult<()> {
+        self.send_msg(BftMsg::Tick)
+    }
+
+    /// A function to withdraw the feed of the [`block_hash`] at the [`height`] before it is
+    /// proposed, such as a block found to contain an invalid tx. The proposals sent are not
+    /// affected. Feed a replacement after it.
+    pub fn revoke_feed(&self, height: Height, block_hash: Hash) -> BftResult<()> {
+        self.send_msg(BftMsg::RevokeFeed { height, block_hash })
+    }
+
+    /// A function for sending the status of a height to the BFT state machine, such as the
+    /// one of a commit acknowledged asynchronously. The statuses of the future heights carrying
+    /// authority diffs are buffered until the node reaches their heights.
+    pub fn send_status(&self, status: Status) -> BftResult<()> {
+        self.send_msg(BftMsg::Status(status))
+    }
+
+    /// A function for sending the handover certificate of a validator rotating its signing
+    /// address. Send it to all the nodes well before the activation height, the rotating node
+    /// switches its address and calls the `rotate_key` of the support at the height.
+    pub fn send_key_rotation(&self, rotation: KeyRotation) -> BftResult<()> {
+        self.send_msg(BftMsg::KeyRotation(rotation))
+    }
+
+    /// A function for delivering the signature of a `sign_async` request pending with the
+    /// [`ticket`]. The signatures delivered after the `sign_timeout_millis` of `BftConfig`
+    /// are dropped.
+    pub fn send_signature(&self, ticket: SignTicket, signature: Signature) -> BftResult<()> {
+        self.send_msg(BftMsg::Signature(ticket, signature))
+    }
+
+    fn send_msg(&self, msg: BftMsg<B>) -> BftResult<()> {
+        self.push_msg(msg)?;
+        // the state machine run as a task is not woken by the channel
+        if let Some(waker) = self.waker.as_ref() {
+            waker();
+        }
+        Ok(())
+    }
+
+    fn push_msg(&self, msg: BftMsg<B>) -> BftResult<()> {
+        // the context is built on the error path only, formatting a msg may walk a whole block
+        let info = |msg: &BftMsg<B>| {
+            let mut info = msg_info(msg);
+            info.detail.push_str(" by BftActuator");
+            info
+        };
+        match self.overflow_policy {
+            OverflowPolicy::Block => self
+                .sender
+                .send(msg)
+                .map_err(|e| BftError::SendMsgErr(info(&e.0))),
+            OverflowPolicy::Error => self.sender.try_send(msg).map_err(|e| match e {
+                TrySendError::Full(msg) => BftError::ChannelFull(info(&msg)),
+                TrySendError::Disconnected(msg) => BftError::SendMsgErr(info(&msg)),
+            }),
+            OverflowPolicy::DropOldestFutureHeight => {
+                // the msgs of the future heights go by their own lane, so evicting the oldest
+                // of them never reorders the others, which wait for free space
+                let height = self.height.load(Ordering::Relaxed);
+                if get_msg_height(&msg).is_none_or(|h| h <= height) {
+                    return self
+                        .sender
+                        .send(msg)
+                        .map_err(|e| BftError::SendMsgErr(info(&e.0)));
+                }
+                let mut msg = msg;
+                loop {
+                    match self.future_sender.try_send(msg) {
+                        Ok(()) => return Ok(()),
+                        Err(TrySendError::Disconnected(m)) => {
+                            return Err(BftError::SendMsgErr(info(&m)))
+                        }
+                        Err(TrySendError::Full(m)) => msg = m,
+                    }
+                    if let Ok(oldest) = self.future_receiver.try_recv() {
+                        debug!(
+                            target: &self.log_target,
+                            "BftActuator drops {:?} of a future height", oldest
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// A function to open the wire envelope of a proposal or vote.
+    fn open_wire_msg(&self, msg: BftMsg<B>) -> BftResult<BftMsg<B>> {
+        match msg {
+            BftMsg::Proposal(encode) => Ok(BftMsg::Proposal(WireMsg::open(
+                &encode,
+                self.chain_id,
+                self.codec,
+                WireKind::Proposal,
+            )?)),
+            BftMsg::Vote(encode) => Ok(BftMsg::Vote(WireMsg::open(
+                &encode,
+                self.chain_id,
+                self.codec,
+                WireKind::Vote,
+            )?)),
+            #[cfg(feature = "compact_block")]
+            BftMsg::GetTxs(encode) => Ok(BftMsg::GetTxs(WireMsg::open(
+                &encode,
+                self.chain_id,
+                self.codec,
+                WireKind::GetTxs,
+            )?)),
+            #[cfg(feature = "compact_block")]
+            BftMsg::Txs(encode) => Ok(BftMsg::Txs(WireMsg::open(
+                &encode,
+                self.chain_id,
+                self.codec,
+                WireKind::Txs,
+            )?)),
+            _ => Ok(msg),
+        }
     }
 }
 
 #[derive(Debug, Clone)]
-pub enum BftMsg {
+pub enum BftMsg<B: BlockPayload = Block> {
     Proposal(Vec<u8>),
     Vote(Vec<u8>),
     Status(Status),
     VerifyResp(VerifyResp),
-    Feed(Feed),
+    Feed(Feed<B>),
+    /// Begin the next height after the status of the height in the externally ticked mode.
+    Tick,
+    /// Withdraw the feed of the block hash at the height, so it is not proposed.
+    /// A replacement can be fed after it.
+    RevokeFeed {
+        height: Height,
+        block_hash: Hash,
+    },
+    /// Rotate the signing address of an authority at the activation height.
+    KeyRotation(KeyRotation),
+    /// The signature of a pending `sign_async` request of the ticket.
+    Signature(SignTicket, Signature),
+    #[cfg(feature = "compact_block")]
+    GetTxs(Vec<u8>),
+    #[cfg(feature = "compact_block")]
+    Txs(Vec<u8>),
 
     Pause,
     Start,
     Clear(Proof),
+    /// Export the consensus state to the sender.
+    ExportSnapshot(Sender<StateSnapshot>),
+    /// Restart the consensus from the state, the wal is discarded.
+    ImportSnapshot(StateSnapshot),
+    /// Dump the internal state in JSON to the sender.
+    Dump(Sender<String>),
+    /// Query the authorities whose votes of the height, the round and the vote type
+    /// are not received.
+    MissingVoters(Height, Round, VoteType, Sender<BftResult<Vec<Address>>>),
+    /// Query the msg counters of the senders.
+    PeerStats(Sender<HashMap<Address, PeerStats>>),
+    /// Query the counters of the msgs dropped before their signatures are checked.
+    IngressStats(Sender<IngressStats>),
+    /// Query the approximate bytes of the cached blocks, proposals and votes.
+    CacheUsage(Sender<CacheUsage>),
+    /// Query the shares of the proposers of the latest committed heights.
+    ProposerShares(Sender<HashMap<Address, ProposerShare>>),
+    /// Prune the wal files of the heights below the height.
+    PruneWal(Height, Sender<BftResult<()>>),
+    /// Start recording the msg trace to the file, or stop it if it is `None`.
+    Trace(Option<String>, Sender<BftResult<()>>),
 
     Kill,
+    /// Turn the node byzantine by the `Random` strategy.
     Corrupt,
+    /// Turn the node byzantine by the strategy.
+    CorruptWith(ByzantineStrategy),
+}
+
+/// The serialized signed_proposal or signed_vote to transmit to other nodes,
+/// wrapped in a wire envelope.
+#[derive(Debug, Clone)]
+pub enum OutboundMsg {
+    Proposal(Vec<u8>),
+    Vote(Vec<u8>),
+    /// The request of the txs missing in a compact block, unicast to its proposer.
+    #[cfg(feature = "compact_block")]
+    GetTxs(Vec<u8>),
+    /// The requested txs, unicast to the requester.
+    #[cfg(feature = "compact_block")]
+    Txs(Vec<u8>),
+}
+
+/// The target of an outbound msg.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    /// Transmit to all the nodes.
+    Broadcast,
+    /// Transmit to the given node only, such as the retransmission to a lagging node.
+    Unicast(Address),
+}
+
+/// An outbound msg with its target, output by `BftSupport::transmit` or the receiver
+/// returned by `BftActuator::new_with_outbox`.
+#[derive(Debug, Clone)]
+pub struct Outbound {
+    pub msg: OutboundMsg,
+    pub target: Target,
+}
+
+impl<B: BlockPayload> From<OutboundMsg> for BftMsg<B> {
+    fn from(msg: OutboundMsg) -> Self {
+        match msg {
+            OutboundMsg::Proposal(encode) => BftMsg::Proposal(encode),
+            OutboundMsg::Vote(encode) => BftMsg::Vote(encode),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::GetTxs(encode) => BftMsg::GetTxs(encode),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::Txs(encode) => BftMsg::Txs(encode),
+        }
+    }
 }
 
 #[cfg(feature = "verify_req")]
@@ -164,23 +661,109 @@ pub enum VerifyResult {
     Approved,
     Failed,
     Undetermined,
+    /// The verification is not done before the deadline.
+    TimedOut,
 }
 
 /// A reaching consensus result of a giving height.
 /// It will send outside for block execution, and the current proof should be persisted for sync process.
 #[derive(Clone, PartialEq, Eq)]
-pub struct Commit {
+pub struct Commit<B: BlockPayload = Block> {
     /// the commit height
     pub height: Height,
     /// the reaching-consensus block content, which contains a proof of previous height.
-    pub block: Block,
+    /// It is shared with the block cache of the state machine.
+    pub block: Arc<B>,
     /// the proof of current height
     pub proof: Proof,
     /// the proposer address
     pub address: Address,
+    /// the signed precommits the proof is built from, sorted by voter,
+    /// empty unless `commit_precommits` of `BftConfig` is set
+    pub precommits: Vec<SignedVote>,
+    /// the timestamp of the committed proposal, in milliseconds, which the precommitters
+    /// have checked against their local clocks
+    pub timestamp: Option<u64>,
+    /// the consensus metrics of the height, which are not encoded
+    #[cfg(feature = "commit_stats")]
+    pub stats: CommitStats,
+}
+
+/// The msg counters of a sender, counted since the node starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PeerStats {
+    /// the count of proposals with valid signatures
+    pub proposals: u64,
+    /// the count of prevotes with valid signatures
+    pub prevotes: u64,
+    /// the count of precommits with valid signatures
+    pub precommits: u64,
+    /// the count of msgs failing the signature check
+    pub invalid_sigs: u64,
+    /// the count of msgs below the last height, which arrive too late
+    pub obsolete_msgs: u64,
+}
+
+/// The approximate bytes of the cached blocks, proposals and votes of all the heights.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheUsage {
+    /// the bytes of the blocks in memory, the spilled ones are not counted
+    pub blocks: usize,
+    /// the bytes of the signed_proposals without their blocks
+    pub proposals: usize,
+    /// the bytes of the signed_votes
+    pub votes: usize,
+}
+
+/// The share of an address in proposing the blocks of the latest committed heights.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ProposerShare {
+    /// the count of the committed blocks it proposed in the window
+    pub proposals: u64,
+    /// its share of the proposal weights of the current authorities
+    pub expected_share: f64,
+    /// its share of the committed blocks in the window
+    pub actual_share: f64,
+}
+
+/// The counters of the proposals and the votes dropped before their signatures are checked,
+/// as their heights or rounds are 16 or more away from the current ones, counted since the
+/// node starts.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IngressStats {
+    /// the count of msgs of the heights too far above the current height
+    pub higher_heights: u64,
+    /// the count of msgs of the heights too far below the current height
+    pub lower_heights: u64,
+    /// the count of msgs of the current height and the rounds too far above the current round
+    pub higher_rounds: u64,
+    /// the count of msgs of the current height and the rounds too far below the current round
+    pub lower_rounds: u64,
 }
 
-impl Debug for Commit {
+/// The consensus metrics of a committed height.
+#[cfg(feature = "commit_stats")]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommitStats {
+    /// the count of rounds used
+    pub rounds: u64,
+    /// the time spent in the propose steps of all the rounds
+    pub propose_duration: Duration,
+    /// the time spent in the prevote steps of all the rounds
+    pub prevote_duration: Duration,
+    /// the time spent waiting for the verify results, zero without the `verify_req` feature
+    pub verify_duration: Duration,
+    /// the time spent in the precommit steps of all the rounds
+    pub precommit_duration: Duration,
+    /// the count of prevotes received
+    pub prevotes: u64,
+    /// the count of precommits received
+    pub precommits: u64,
+    /// the proposer of each round
+    pub proposers: Vec<Address>,
+}
+
+impl<B: BlockPayload> Debug for Commit<B> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(
             f,
@@ -190,29 +773,44 @@ impl Debug for Commit {
     }
 }
 
-impl Encodable for Commit {
+impl<B: BlockPayload> Encodable for Commit<B> {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(4)
+        // the commit without precommits and timestamp keeps the legacy encoding
+        let legacy = self.precommits.is_empty() && self.timestamp.is_none();
+        s.begin_list(if legacy { 4 } else { 6 })
             .append(&self.height)
-            .append(&self.block)
+            .append(&Block::from(self.block.to_bytes()))
             .append(&self.proof)
             .append(&self.address);
+        if !legacy {
+            s.append_list(&self.precommits).append(&self.timestamp);
+        }
     }
 }
 
-impl Decodable for Commit {
+impl<B: BlockPayload> Decodable for Commit<B> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(4) => {
+            Prototype::List(len @ 4) | Prototype::List(len @ 6) => {
                 let height: Height = r.val_at(0)?;
                 let block: Block = r.val_at(1)?;
+                let block = Arc::new(B::from_bytes(&block)?);
                 let proof: Proof = r.val_at(2)?;
                 let address: Address = r.val_at(3)?;
+                let (precommits, timestamp): (Vec<SignedVote>, Option<u64>) = if len == 6 {
+                    (r.list_at(4)?, r.val_at(5)?)
+                } else {
+                    (Vec::new(), None)
+                };
                 Ok(Commit {
                     height,
                     block,
                     proof,
                     address,
+                    precommits,
+                    timestamp,
+                    #[cfg(feature = "commit_stats")]
+                    stats: CommitStats::default(),
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -231,28 +829,72 @@ pub struct Status {
     pub interval: Option<u64>,
     /// a new authority list for next height
     pub authority_list: Vec<Node>,
+    /// the changes of the authority list for next height, applied to the current authority list
+    /// instead of the [`authority_list`] if it is some
+    pub authority_diff: Option<AuthorityDiff>,
+    /// the authority lists announced in advance, each takes the place of the authority list
+    /// at its activation height
+    pub scheduled_authorities: Vec<ScheduledAuthorities>,
+    /// the consensus params for next height, applied together with the authority list
+    pub consensus_params: Option<ConsensusParams>,
+}
+
+impl Status {
+    fn has_authority_changes(&self) -> bool {
+        self.authority_diff.is_some() || !self.scheduled_authorities.is_empty()
+    }
 }
 
 impl Encodable for Status {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(3)
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for Status {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        // the status without authority changes or consensus params keeps the legacy encoding
+        let len = if self.consensus_params.is_some() {
+            6
+        } else if self.has_authority_changes() {
+            5
+        } else {
+            3
+        };
+        s.begin_list(len)
             .append(&self.height)
             .append(&self.interval)
-            .append_list(&self.authority_list);
+            .append_list_by(&self.authority_list, codec);
+        if len > 3 {
+            s.append_by(&self.authority_diff, codec)
+                .append_list_by(&self.scheduled_authorities, codec);
+        }
+        if let Some(consensus_params) = &self.consensus_params {
+            s.append(consensus_params);
+        }
     }
 }
 
 impl Decodable for Status {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(3) => {
+            Prototype::List(len @ 3) | Prototype::List(len @ 5) | Prototype::List(len @ 6) => {
                 let height: Height = r.val_at(0)?;
                 let interval: Option<u64> = r.val_at(1)?;
                 let authority_list: Vec<Node> = r.list_at(2)?;
+                let (authority_diff, scheduled_authorities) = if len > 3 {
+                    (r.val_at(3)?, r.list_at(4)?)
+                } else {
+                    (None, Vec::new())
+                };
+                let consensus_params = if len == 6 { Some(r.val_at(5)?) } else { None };
                 Ok(Status {
                     height,
                     interval,
                     authority_list,
+                    authority_diff,
+                    scheduled_authorities,
+                    consensus_params,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -260,39 +902,273 @@ impl Decodable for Status {
     }
 }
 
+/// The consensus params carried by a status, which are applied together at the next height,
+/// so the authorities change them by on-chain governance. The ones left `None` are kept.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ConsensusParams {
+    /// the fractions of the interval the steps wait
+    pub timeouts: Option<TimeoutParams>,
+    /// the cap of the approximate bytes of the caches, see `BftConfig::cache_bytes_cap`
+    pub cache_bytes_cap: Option<u64>,
+    /// what to precommit when the verification of the locked block times out
+    pub nil_vote_policy: Option<NilVotePolicy>,
+}
+
+impl Encodable for ConsensusParams {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let nil_vote_policy: Option<u8> = self.nil_vote_policy.map(u8::from);
+        s.begin_list(3)
+            .append(&self.timeouts)
+            .append(&self.cache_bytes_cap)
+            .append(&nil_vote_policy);
+    }
+}
+
+impl Decodable for ConsensusParams {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let timeouts: Option<TimeoutParams> = r.val_at(0)?;
+                let cache_bytes_cap: Option<u64> = r.val_at(1)?;
+                let nil_vote_policy: Option<u8> = r.val_at(2)?;
+                let nil_vote_policy = match nil_vote_policy {
+                    Some(policy) => Some(
+                        NilVotePolicy::try_from(policy)
+                            .map_err(|_| DecoderError::Custom("invalid nil vote policy"))?,
+                    ),
+                    None => None,
+                };
+                Ok(ConsensusParams {
+                    timeouts,
+                    cache_bytes_cap,
+                    nil_vote_policy,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// The fractions of the interval the propose, prevote and precommit steps wait, each as
+/// (numerator, denominator).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeoutParams {
+    pub propose: (u64, u64),
+    pub prevote: (u64, u64),
+    pub precommit: (u64, u64),
+}
+
+impl TimeoutParams {
+    /// Whether every fraction has a non-zero denominator and is not above 1.
+    pub fn is_valid(&self) -> bool {
+        [self.propose, self.prevote, self.precommit]
+            .iter()
+            .all(|(numerator, denominator)| *denominator > 0 && numerator <= denominator)
+    }
+}
+
+impl Encodable for TimeoutParams {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(6)
+            .append(&self.propose.0)
+            .append(&self.propose.1)
+            .append(&self.prevote.0)
+            .append(&self.prevote.1)
+            .append(&self.precommit.0)
+            .append(&self.precommit.1);
+    }
+}
+
+impl Decodable for TimeoutParams {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(6) => Ok(TimeoutParams {
+                propose: (r.val_at(0)?, r.val_at(1)?),
+                prevote: (r.val_at(2)?, r.val_at(3)?),
+                precommit: (r.val_at(4)?, r.val_at(5)?),
+            }),
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// An authority list announced in advance.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScheduledAuthorities {
+    /// the first height the authority list takes effect
+    pub activation_height: Height,
+    /// the authority list
+    pub authority_list: Vec<Node>,
+}
+
+impl Encodable for ScheduledAuthorities {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for ScheduledAuthorities {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(2)
+            .append(&self.activation_height)
+            .append_list_by(&self.authority_list, codec);
+    }
+}
+
+impl Decodable for ScheduledAuthorities {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let activation_height: Height = r.val_at(0)?;
+                let authority_list: Vec<Node> = r.list_at(1)?;
+                Ok(ScheduledAuthorities {
+                    activation_height,
+                    authority_list,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// The changes of the authority list.
+/// The removed nodes are removed before the added nodes are added, an added node replaces
+/// the node of the same address.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuthorityDiff {
+    /// the nodes to add or update
+    pub add: Vec<Node>,
+    /// the addresses of the nodes to remove
+    pub remove: Vec<Address>,
+}
+
+impl Encodable for AuthorityDiff {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for AuthorityDiff {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(2)
+            .append_list_by(&self.add, codec)
+            .append_list_by(&self.remove, codec);
+    }
+}
+
+impl Decodable for AuthorityDiff {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let add: Vec<Node> = r.list_at(0)?;
+                let remove: Vec<Address> = r.list_at(1)?;
+                Ok(AuthorityDiff { add, remove })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A handover certificate of a validator switching its signing address from [`old`] to [`new`]
+/// at the [`activation_height`], signed by the key of [`old`] over the hash of
+/// [`signed_msg`]. The weights of the authority are kept.
+/// The host gossips it to all the nodes well before the activation height, as a node not
+/// receiving it rejects the msgs of the new address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyRotation {
+    /// the address to rotate from
+    pub old: Address,
+    /// the address to rotate to
+    pub new: Address,
+    /// the first height the new address proposes and votes at
+    pub activation_height: Height,
+    /// the signature of the old address
+    pub signature: Signature,
+}
+
+impl KeyRotation {
+    /// A function to get the msg whose hash is signed by the old address, which is encoded by
+    /// the [`codec`] of the chain.
+    pub fn signed_msg(&self, codec: RlpCodec) -> Vec<u8> {
+        let mut s = RlpStream::new();
+        s.begin_list(3)
+            .append_by(&self.old, codec)
+            .append_by(&self.new, codec)
+            .append(&self.activation_height);
+        s.out()
+    }
+}
+
+impl Encodable for KeyRotation {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for KeyRotation {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(4)
+            .append_by(&self.old, codec)
+            .append_by(&self.new, codec)
+            .append(&self.activation_height)
+            .append_by(&self.signature, codec);
+    }
+}
+
+impl Decodable for KeyRotation {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(4) => Ok(KeyRotation {
+                old: r.val_at(0)?,
+                new: r.val_at(1)?,
+                activation_height: r.val_at(2)?,
+                signature: r.val_at(3)?,
+            }),
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
 /// A feed block for a giving height.
 /// It should be served from outside and supply as consensus content.
 #[derive(Clone, PartialEq, Eq)]
-pub struct Feed {
+pub struct Feed<B: BlockPayload = Block> {
     /// the height of the block
     pub height: Height,
     /// the content of the block
-    pub block: Block,
+    pub block: B,
     /// the hash of the block
     pub block_hash: Hash,
 }
 
-impl Debug for Feed {
+impl<B: BlockPayload> Debug for Feed<B> {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "Feed {{ h: {}}}", self.height)
     }
 }
 
-impl Encodable for Feed {
+impl<B: BlockPayload> Encodable for Feed<B> {
     fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl<B: BlockPayload> CodecEncodable for Feed<B> {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
         s.begin_list(3)
             .append(&self.height)
-            .append(&self.block)
-            .append(&self.block_hash);
+            .append_by(&Block::from(self.block.to_bytes()), codec)
+            .append_by(&self.block_hash, codec);
     }
 }
 
-impl Decodable for Feed {
+impl<B: BlockPayload> Decodable for Feed<B> {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
             Prototype::List(3) => {
                 let height: Height = r.val_at(0)?;
                 let block: Block = r.val_at(1)?;
+                let block = B::from_bytes(&block)?;
                 let block_hash: Hash = r.val_at(2)?;
                 Ok(Feed {
                     height,
@@ -313,99 +1189,149 @@ pub struct VerifyResp {
     /// the round of proposal which contains the block
     pub round: Round,
     #[cfg(feature = "compact_block")]
-    /// the block with complete transactions.
+    /// the serialized block with complete transactions.
     pub complete_block: Block,
+    /// the reason of a failed block verification.
+    pub failure: Option<VerifyFailure>,
 }
 
 impl Debug for VerifyResp {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(
             f,
-            "VerifyResp {{ pass: {}, round: {}}}",
-            self.is_pass, self.round
+            "VerifyResp {{ pass: {}, round: {}, failure: {:?}}}",
+            self.is_pass, self.round, self.failure
         )
     }
 }
 
+#[cfg(not(feature = "compact_block"))]
+const VERIFY_RESP_LEGACY_LEN: usize = 2;
+#[cfg(feature = "compact_block")]
+const VERIFY_RESP_LEGACY_LEN: usize = 3;
+
 impl Encodable for VerifyResp {
     fn rlp_append(&self, s: &mut RlpStream) {
-        #[cfg(not(feature = "compact_block"))]
-        s.begin_list(2).append(&self.is_pass).append(&self.round);
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
 
-        #[cfg(feature = "compact_block")]
-        s.begin_list(3)
+impl CodecEncodable for VerifyResp {
+    #[cfg_attr(not(feature = "compact_block"), allow(unused_variables))]
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(VERIFY_RESP_LEGACY_LEN + 1)
             .append(&self.is_pass)
-            .append(&self.round)
-            .append(&self.complete_block);
+            .append(&self.round);
+
+        #[cfg(feature = "compact_block")]
+        s.append_by(&self.complete_block, codec);
+
+        match &self.failure {
+            Some(failure) => s.append(failure),
+            None => s.begin_list(0),
+        };
     }
 }
 
 impl Decodable for VerifyResp {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
-        match r.prototype()? {
-            #[cfg(not(feature = "compact_block"))]
-            Prototype::List(2) => {
-                let is_pass: bool = r.val_at(0)?;
-                let round: Round = r.val_at(1)?;
-                Ok(VerifyResp { is_pass, round })
+        let failure = match r.prototype()? {
+            // the ones without failure are saved in wal by earlier versions
+            Prototype::List(VERIFY_RESP_LEGACY_LEN) => None,
+            Prototype::List(len) if len == VERIFY_RESP_LEGACY_LEN + 1 => {
+                let failure = r.at(VERIFY_RESP_LEGACY_LEN)?;
+                if failure.item_count()? == 0 {
+                    None
+                } else {
+                    Some(failure.as_val()?)
+                }
             }
+            _ => return Err(DecoderError::RlpInconsistentLengthAndData),
+        };
+        Ok(VerifyResp {
+            is_pass: r.val_at(0)?,
+            round: r.val_at(1)?,
             #[cfg(feature = "compact_block")]
-            Prototype::List(3) => {
-                let is_pass: bool = r.val_at(0)?;
-                let round: Round = r.val_at(1)?;
-                let complete_block: Block = r.val_at(2)?;
-                Ok(VerifyResp {
-                    is_pass,
-                    round,
-                    complete_block,
-                })
+            complete_block: r.val_at(2)?,
+            failure,
+        })
+    }
+}
+
+/// The reason of a failed block verification, such as an invalid tx or a state root mismatch.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VerifyFailure {
+    /// the user-defined error code.
+    pub code: u32,
+    /// the readable reason.
+    pub reason: String,
+}
+
+impl Encodable for VerifyFailure {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(2).append(&self.code).append(&self.reason);
+    }
+}
+
+impl Decodable for VerifyFailure {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let code: u32 = r.val_at(0)?;
+                let reason: String = r.val_at(1)?;
+                Ok(VerifyFailure { code, reason })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
         }
     }
 }
 
-/// The bft node
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
-pub struct Node {
-    /// the address of the node
-    pub address: Address,
-    /// the weight of being a proposer
-    pub proposal_weight: u32,
-    /// the weight of calculating vote
-    pub vote_weight: u32,
+/// The request of the txs missing in a compact block.
+#[cfg(feature = "compact_block")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GetTxs {
+    /// the height of the proposal
+    pub height: Height,
+    /// the hash of the compact block
+    pub block_hash: Hash,
+    /// the hashes of the missing txs
+    pub tx_hashes: Vec<Hash>,
+    /// the address of the requester
+    pub requester: Address,
 }
 
-impl Debug for Node {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "Node {{ addr: {:?}, w: {}/{}}}",
-            self.address, self.proposal_weight, self.vote_weight,
-        )
+#[cfg(feature = "compact_block")]
+impl Encodable for GetTxs {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
     }
 }
 
-impl Encodable for Node {
-    fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(3)
-            .append(&self.address)
-            .append(&self.proposal_weight)
-            .append(&self.vote_weight);
+#[cfg(feature = "compact_block")]
+impl CodecEncodable for GetTxs {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(4)
+            .append(&self.height)
+            .append_by(&self.block_hash, codec)
+            .append_list_by(&self.tx_hashes, codec)
+            .append_by(&self.requester, codec);
     }
 }
 
-impl Decodable for Node {
+#[cfg(feature = "compact_block")]
+impl Decodable for GetTxs {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(3) => {
-                let address: Address = r.val_at(0)?;
-                let proposal_weight: u32 = r.val_at(1)?;
-                let vote_weight: u32 = r.val_at(2)?;
-                Ok(Node {
-                    address,
-                    proposal_weight,
-                    vote_weight,
+            Prototype::List(4) => {
+                let height: Height = r.val_at(0)?;
+                let block_hash: Hash = r.val_at(1)?;
+                let tx_hashes: Vec<Hash> = r.list_at(2)?;
+                let requester: Address = r.val_at(3)?;
+                Ok(GetTxs {
+                    height,
+                    block_hash,
+                    tx_hashes,
+                    requester,
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -413,125 +1339,69 @@ impl Decodable for Node {
     }
 }
 
-impl Node {
-    pub fn new(address: Address, proposal_weight: u32, vote_weight: u32) -> Self {
-        Node {
-            address,
-            proposal_weight,
-            vote_weight,
-        }
-    }
-
-    pub fn set_address(address: Address) -> Self {
-        Self::new(address, 1, 1)
-    }
-}
-
-/// Proof
-#[derive(Clone, Eq, PartialEq)]
-pub struct Proof {
-    /// proof height
+/// The requested txs of a compact block, serialized by the user.
+#[cfg(feature = "compact_block")]
+#[derive(Clone, PartialEq, Eq)]
+pub struct Txs {
+    /// the height of the proposal
     pub height: Height,
-    /// the reaching-consensus round
-    pub round: Round,
-    /// the reaching-consensus block hash
+    /// the hash of the compact block
     pub block_hash: Hash,
-    /// the voters and corresponding signatures
-    pub precommit_votes: HashMap<Address, Signature>,
+    /// the serialized txs
+    pub txs: Vec<u8>,
 }
 
-impl Debug for Proof {
+#[cfg(feature = "compact_block")]
+impl Debug for Txs {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(
             f,
-            "Proof {{ h: {}, r: {}, hash: {:?}}}",
-            self.height, self.round, self.block_hash,
+            "Txs {{ h: {}, block_hash: {:?}, len: {}}}",
+            self.height,
+            self.block_hash,
+            self.txs.len()
         )
     }
 }
 
-impl Default for Proof {
-    fn default() -> Self {
-        Proof {
-            height: 0,
-            round: 0,
-            block_hash: Hash::default(),
-            precommit_votes: HashMap::new(),
-        }
-    }
-}
-
-impl Hashable for Proof {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        self.height.hash(state);
-        self.round.hash(state);
-        self.block_hash.hash(state);
-        //TODO: Ignore precommit_votes maybe leaves flaws
+#[cfg(feature = "compact_block")]
+impl Encodable for Txs {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
     }
 }
 
-impl Encodable for Proof {
-    fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(5)
+#[cfg(feature = "compact_block")]
+impl CodecEncodable for Txs {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(3)
             .append(&self.height)
-            .append(&self.round)
-            .append(&self.block_hash);
-        let mut key_values: Vec<(Address, Signature)> =
-            self.precommit_votes.clone().into_iter().collect();
-        key_values.sort();
-        let mut key_list: Vec<Address> = vec![];
-        let mut value_list: Vec<Signature> = vec![];
-        key_values.iter().for_each(|(address, sig)| {
-            key_list.push(address.to_owned());
-            value_list.push(sig.to_owned());
-        });
-        s.begin_list(key_list.len());
-        for key in key_list {
-            s.append(&key);
-        }
-        s.begin_list(value_list.len());
-        for value in value_list {
-            s.append(&value);
-        }
+            .append_by(&self.block_hash, codec)
+            .append(&self.txs);
     }
 }
 
-impl Decodable for Proof {
+#[cfg(feature = "compact_block")]
+impl Decodable for Txs {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(5) => {
+            Prototype::List(3) => {
                 let height: Height = r.val_at(0)?;
-                let round: Round = r.val_at(1)?;
-                let block_hash: Hash = r.val_at(2)?;
-                let key_list: Vec<Address> = r.list_at(3)?;
-                let value_list: Vec<Signature> = r.list_at(4)?;
-                if key_list.len() != value_list.len() {
-                    error!(
-                        "Decode proof error, key_list_len {}, value_list_len{}",
-                        key_list.len(),
-                        value_list.len()
-                    );
-                    return Err(DecoderError::RlpIncorrectListLen);
-                }
-                let precommit_votes: HashMap<_, _> =
-                    key_list.into_iter().zip(value_list.into_iter()).collect();
-                Ok(Proof {
+                let block_hash: Hash = r.val_at(1)?;
+                let txs: Vec<u8> = r.val_at(2)?;
+                Ok(Txs {
                     height,
-                    round,
                     block_hash,
-                    precommit_votes,
+                    txs,
                 })
             }
-            _ => {
-                error!("Decode proof error, the prototype is {:?}", r.prototype());
-                Err(DecoderError::RlpInconsistentLengthAndData)
-            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
         }
     }
 }
 
-/// User-defined functions.
-pub trait BftSupport: Sync + Send {
+/// User-defined functions, the block payload is the bytes `Block` by default.
+pub trait BftSupport<B: BlockPayload = Block>: Sync + Send {
     type Error: ::std::fmt::Debug;
     /// A user-defined function for block validation.
     /// Every proposal bft received will call this function, even if the feed block.
@@ -539,82 +1409,102 @@ pub trait BftSupport: Sync + Send {
     /// The [`signed_proposal_hash`] is corresponding to the proposal of the [`block`].
     fn check_block(
         &self,
-        block: &Block,
+        block: &B,
         block_hash: &Hash,
         signed_proposal_hash: &Hash,
         height_round: (Height, Round),
         is_lock: bool,
         proposer: &Address,
     ) -> Result<VerifyResp, Self::Error>;
+    /// A user-defined function for the deadline of verifying the [`block`], in milliseconds.
+    /// The node waits for the `VerifyResp` until the deadline with the `verify_req` feature,
+    /// then precommits nil. The default wait is used if it is `None`.
+    fn verify_deadline(&self, _block: &B) -> Option<u64> {
+        None
+    }
+    /// A user-defined function for the hashes of the txs missing in the local pool of the
+    /// compact [`block`], they are fetched from the proposer before `check_block`.
+    #[cfg(feature = "compact_block")]
+    fn missing_txs(&self, _block: &B) -> Vec<Hash> {
+        Vec::new()
+    }
+    /// A user-defined function for serializing the txs of the [`tx_hashes`] in the compact block
+    /// of the [`block_hash`], requested by other nodes. Return `None` if any of them is lacking.
+    #[cfg(feature = "compact_block")]
+    fn get_txs(&self, _block_hash: &Hash, _tx_hashes: &[Hash]) -> Option<Vec<u8>> {
+        None
+    }
+    /// A user-defined function for adding the fetched [`txs`] of the compact block of the
+    /// [`block_hash`] into the local pool, the proposal is checked again after that.
+    #[cfg(feature = "compact_block")]
+    fn add_txs(&self, _block_hash: &Hash, _txs: &[u8]) -> Result<(), Self::Error> {
+        Ok(())
+    }
     /// A user-defined function for transmitting signed_proposals and signed_votes.
     /// The signed_proposals and signed_votes have been serialized,
     /// users do not have to care about the structure of SignedProposal and SignedVote.
-    fn transmit(&self, msg: BftMsg);
+    /// A `Unicast` target can also be broadcast if the network does not support it.
+    /// A failed transmission will be re-attempted with backoff.
+    /// It is not called for the actuator created by `BftActuator::new_with_outbox`, whose
+    /// outbound msgs are taken from the returned receiver, so return `Ok(())` there.
+    fn transmit(&self, outbound: Outbound) -> Result<(), Self::Error>;
     /// A user-defined function for processing the reaching-consensus block.
-    /// Users can execute the block and add it into chain.
-    fn commit(&self, commit: Commit) -> Result<Status, Self::Error>;
+    /// Users can execute the block and add it into chain, and return the status of the height.
+    /// An asynchronous execution layer can return `Ok(None)` and deliver the status later by
+    /// `BftActuator::send_status`.
+    fn commit(&self, commit: Commit<B>) -> Result<Option<Status>, Self::Error>;
     /// A user-defined function for feeding the bft consensus.
     /// The new block provided will feed for bft consensus of giving [`height`]
-    fn get_block(&self, height: Height) -> Result<(Block, Hash), Self::Error>;
+    fn get_block(&self, height: Height) -> Result<(B, Hash), Self::Error>;
+    /// A user-defined function for the empty block proposed at the [`height`] when `get_block`
+    /// keeps failing until the propose timeout. Return `None` to not propose.
+    fn empty_block(&self, _height: Height) -> Option<(B, Hash)> {
+        None
+    }
     /// A user-defined function for signing a [`hash`].
     fn sign(&self, hash: &Hash) -> Result<Signature, Self::Error>;
+    /// A user-defined function for signing the [`hash`] of an own vote asynchronously, such as
+    /// by a remote signer or an HSM. Return `Ok(None)` if the signing is pending, and deliver
+    /// the signature by the `send_signature` of the actuator with the [`ticket`] later, so the
+    /// main loop is not blocked. The default signs it by `sign` synchronously.
+    fn sign_async(
+        &self,
+        hash: &Hash,
+        _ticket: SignTicket,
+    ) -> Result<Option<Signature>, Self::Error> {
+        self.sign(hash).map(Some)
+    }
     /// A user-defined function for checking a [`signature`].
     fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, Self::Error>;
+    /// A user-defined function for checking a [`batch`] of signatures, return the recovered
+    /// addresses in order, `None` for the invalid ones.
+    /// The default checks them one by one, override it for batch verification.
+    fn check_sigs(&self, batch: &[(Signature, Hash)]) -> Vec<Option<Address>> {
+        batch
+            .iter()
+            .map(|(signature, hash)| self.check_sig(signature, hash).ok())
+            .collect()
+    }
     /// A user-defined function for hashing a [`msg`].
     fn crypt_hash(&self, msg: &[u8]) -> Hash;
-}
-
-/// A public function for proof validation.
-/// The input [`height`] is the height of block containing the proof.
-/// The input [`authorities`] is the authority_list for the proof check.
-/// The fn [`crypt_hash`], [`check_sig`] are user-defined.
-pub fn check_proof(
-    proof: &Proof,
-    height: Height,
-    authorities: &[Node],
-    crypt_hash: impl Fn(&[u8]) -> Hash,
-    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
-) -> bool {
-    if proof.height == 0 {
-        return true;
-    }
-    if height != proof.height + 1 {
-        return false;
-    }
-
-    let vote_addresses: Vec<Address> = proof
-        .precommit_votes
-        .iter()
-        .map(|(sender, _)| sender.clone())
-        .collect();
-
-    if get_votes_weight(authorities, &vote_addresses) * 3 <= get_total_weight(authorities) * 2 {
-        return false;
-    }
-
-    proof.precommit_votes.iter().all(|(voter, sig)| {
-        if authorities.iter().any(|node| node.address == *voter) {
-            let vote = Vote {
-                vote_type: VoteType::Precommit,
-                height: proof.height,
-                round: proof.round,
-                block_hash: proof.block_hash.clone(),
-                voter: voter.clone(),
-            };
-            let msg = rlp::encode(&vote);
-            if let Some(address) = check_sig(sig, &crypt_hash(&msg)) {
-                return address == *voter;
-            }
-        }
-        false
-    })
-}
-
-/// A public function for get_proposal_hash from BftMsg::Proposal
-pub fn get_proposal_hash(encode: &[u8], crypt_hash: impl Fn(&[u8]) -> Hash) -> Option<Hash> {
-    if let Ok((signed_proposal_encode, _)) = extract_two(&encode) {
-        Some(crypt_hash(signed_proposal_encode))
-    } else {
-        None
-    }
+    /// A user-defined function for handling the [`error`] the state machine encounters,
+    /// called after it is logged. Users can count, alert on or react to the errors here.
+    /// It is called in the consensus threads, so it should return quickly.
+    fn on_error(&self, _error: &BftError) {}
+    /// A user-defined function for the [`progress`] of replaying the wal on start,
+    /// called after each record is replayed.
+    fn on_replay_progress(&self, _progress: &ReplayProgress) {}
+    /// A user-defined function for switching the signing key to the new address of the
+    /// [`rotation`], called when the node reaches its activation height. The `sign` after it
+    /// signs with the new key.
+    fn rotate_key(&self, _rotation: &KeyRotation) {}
+    /// A user-defined function for the node moving to the [`step`] of the [`height`] and the
+    /// [`round`], including the steps replayed from wal. Users can persist or monitor the
+    /// position of the state machine here, it should return quickly.
+    fn on_step_change(&self, _height: Height, _round: Round, _step: Step) {}
+    /// A user-defined function for the [`share`] of the [`address`] in proposing the latest
+    /// committed heights deviating from its expected share beyond the
+    /// `proposer_share_tolerance` of the config. It is called once until the share is back
+    /// within the tolerance.
+    fn on_proposer_deviation(&self, _address: &Address, _share: &ProposerShare) {}
 }
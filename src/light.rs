@@ -0,0 +1,134 @@
+use crate::{
+    check_proof, Address, BlockPayload, Commit, Hash, Height, Node, Proof, Round, SignScheme,
+    Signature,
+};
+
+use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+use serde_derive::{Deserialize, Serialize};
+
+/// A commit certificate for light clients and bridges, which proves the block of the
+/// [`block_hash`] is committed at the [`height`] without the block content.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct LightCommit {
+    /// the commit height
+    pub height: Height,
+    /// the reaching-consensus round
+    pub round: Round,
+    /// the committed block hash
+    pub block_hash: Hash,
+    /// the voters and their precommit signatures, sorted by voter
+    pub precommits: Vec<(Address, Signature)>,
+    /// the `authorities_hash` of the proof, see `get_authorities_hash`
+    pub authorities_hash: Option<Hash>,
+}
+
+impl From<&Proof> for LightCommit {
+    fn from(proof: &Proof) -> Self {
+        let mut precommits: Vec<(Address, Signature)> = proof
+            .precommit_votes
+            .iter()
+            .map(|(voter, sig)| (*voter, sig.clone()))
+            .collect();
+        precommits.sort_by_key(|(voter, _)| *voter);
+        LightCommit {
+            height: proof.height,
+            round: proof.round,
+            block_hash: proof.block_hash,
+            precommits,
+            authorities_hash: proof.authorities_hash,
+        }
+    }
+}
+
+impl<B: BlockPayload> From<&Commit<B>> for LightCommit {
+    fn from(commit: &Commit<B>) -> Self {
+        LightCommit::from(&commit.proof)
+    }
+}
+
+impl LightCommit {
+    /// A function to rebuild the proof the certificate is made of.
+    pub fn to_proof(&self) -> Proof {
+        Proof {
+            height: self.height,
+            round: self.round,
+            block_hash: self.block_hash,
+            precommit_votes: self.precommits.iter().cloned().collect(),
+            authorities_hash: self.authorities_hash,
+        }
+    }
+}
+
+impl Encodable for LightCommit {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        s.begin_list(5)
+            .append(&self.height)
+            .append(&self.round)
+            .append(&self.block_hash)
+            .begin_list(self.precommits.len());
+        for (voter, sig) in &self.precommits {
+            s.begin_list(2).append(voter).append(sig);
+        }
+        s.append(&self.authorities_hash);
+    }
+}
+
+impl Decodable for LightCommit {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(5) => {
+                let height: Height = r.val_at(0)?;
+                let round: Round = r.val_at(1)?;
+                let block_hash: Hash = r.val_at(2)?;
+                let precommits = r
+                    .at(3)?
+                    .iter()
+                    .map(|item| -> Result<(Address, Signature), DecoderError> {
+                        Ok((item.val_at(0)?, item.val_at(1)?))
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                // the canonical encoding has no duplicate voters
+                if precommits.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    return Err(DecoderError::Custom("unsorted precommits"));
+                }
+                let authorities_hash: Option<Hash> = r.val_at(4)?;
+                Ok(LightCommit {
+                    height,
+                    round,
+                    block_hash,
+                    precommits,
+                    authorities_hash,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A public function for light commit validation.
+/// The input [`trusted_validators`] is the authority_list of the commit height.
+/// The input [`scheme`] is the one the precommits are signed by.
+/// The fn [`crypt_hash`], [`check_sig`] are user-defined.
+pub fn verify_light_commit(
+    cert: &LightCommit,
+    trusted_validators: &[Node],
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    // no block is committed at the genesis height
+    if cert.height == 0 {
+        return false;
+    }
+    match cert.height.checked_add(1) {
+        Some(height) => check_proof(
+            &cert.to_proof(),
+            height,
+            trusted_validators,
+            scheme,
+            crypt_hash,
+            check_sig,
+        ),
+        None => false,
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{Address, PeerStats};
+
+use lru_cache::LruCache;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+pub(crate) const LIMITER_CACHE_N: usize = 1024;
+
+/// A token bucket of a sender.
+#[derive(Debug, Clone)]
+pub(crate) struct TokenBucket {
+    tokens: u64,
+    last_refill: Instant,
+}
+
+/// Per-sender rate limiter, keyed by the claimed address of a msg.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// Tokens refilled per second, 0 disables the limiter.
+    rate: u64,
+    /// The capacity of a bucket.
+    burst: u64,
+    buckets: LruCache<Address, TokenBucket>,
+}
+
+impl RateLimiter {
+    /// A function to create a new rate limiter.
+    pub(crate) fn new(rate: u64, burst: u64) -> Self {
+        RateLimiter {
+            rate,
+            burst,
+            buckets: LruCache::new(LIMITER_CACHE_N),
+        }
+    }
+
+    /// A function to take a token of the address, return `false` if it runs out of tokens.
+    pub(crate) fn check(&mut self, address: &Address) -> bool {
+        if self.rate == 0 {
+            return true;
+        }
+        let now = Instant::now();
+        if !self.buckets.contains_key(address) {
+            self.buckets.insert(
+                *address,
+                TokenBucket {
+                    tokens: self.burst,
+                    last_refill: now,
+                },
+            );
+        }
+        let bucket = self.buckets.get_mut(address).unwrap();
+        let elapsed = now - bucket.last_refill;
+        let refill = elapsed.as_millis() as u64 * self.rate / 1000;
+        if refill > 0 {
+            bucket.tokens = (bucket.tokens + refill).min(self.burst);
+            bucket.last_refill = now;
+        }
+        if bucket.tokens == 0 {
+            return false;
+        }
+        bucket.tokens -= 1;
+        true
+    }
+}
+
+/// A scoring table which temporarily ignores senders producing repeated signature failures.
+#[derive(Debug)]
+pub(crate) struct PeerScore {
+    /// The count of continuous failures to ban a sender, 0 disables the scoring.
+    threshold: u32,
+    ban_duration: Duration,
+    failures: LruCache<Address, u32>,
+    banned: LruCache<Address, Instant>,
+}
+
+impl PeerScore {
+    /// A function to create a new peer scoring table.
+    pub(crate) fn new(threshold: u32, ban_duration: Duration) -> Self {
+        PeerScore {
+            threshold,
+            ban_duration,
+            failures: LruCache::new(LIMITER_CACHE_N),
+            banned: LruCache::new(LIMITER_CACHE_N),
+        }
+    }
+
+    /// A function to check whether the address is banned, expired bans are lifted.
+    pub(crate) fn is_banned(&mut self, address: &Address) -> bool {
+        let expired = match self.banned.get_mut(address) {
+            Some(until) => Instant::now() >= *until,
+            None => return false,
+        };
+        if expired {
+            self.banned.remove(address);
+        }
+        !expired
+    }
+
+    /// A function to record a signature failure, return `true` if the address gets banned.
+    pub(crate) fn record_failure(&mut self, address: &Address) -> bool {
+        if self.threshold == 0 {
+            return false;
+        }
+        let count = match self.failures.get_mut(address) {
+            Some(count) => {
+                *count += 1;
+                *count
+            }
+            None => {
+                self.failures.insert(*address, 1);
+                1
+            }
+        };
+        if count >= self.threshold {
+            self.failures.remove(address);
+            self.banned
+                .insert(*address, Instant::now() + self.ban_duration);
+            return true;
+        }
+        false
+    }
+
+    /// A function to record a signature success, which resets the failure count.
+    pub(crate) fn record_success(&mut self, address: &Address) {
+        self.failures.remove(address);
+    }
+}
+
+/// The msg counters of the senders, keyed by the claimed address of a msg.
+#[derive(Debug)]
+pub(crate) struct PeerStatsTable {
+    stats: LruCache<Address, PeerStats>,
+}
+
+impl PeerStatsTable {
+    /// A function to create a new table of msg counters.
+    pub(crate) fn new() -> Self {
+        PeerStatsTable {
+            stats: LruCache::new(LIMITER_CACHE_N),
+        }
+    }
+
+    /// A function to get the counters of the address to update.
+    pub(crate) fn record(&mut self, address: &Address) -> &mut PeerStats {
+        if !self.stats.contains_key(address) {
+            self.stats.insert(*address, PeerStats::default());
+        }
+        self.stats.get_mut(address).unwrap()
+    }
+
+    /// A function to copy the counters of all the recorded senders.
+    pub(crate) fn to_map(&self) -> HashMap<Address, PeerStats> {
+        self.stats
+            .iter()
+            .map(|(address, stats)| (*address, *stats))
+            .collect()
+    }
+}
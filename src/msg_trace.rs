@@ -0,0 +1,276 @@
+use crate::codec::RlpCodec;
+use crate::error::{BftError, BftResult, ErrorInfo};
+use crate::objects::StateSnapshot;
+use crate::timer::TimeoutInfo;
+use crate::utils::{decode_revoke_feed, encode_revoke_feed, unix_millis};
+use crate::{
+    Address, BftMsg, Block, BlockPayload, Feed, Height, KeyRotation, Outbound, OutboundMsg, Proof,
+    Round, Status, Target, VerifyResp,
+};
+
+use rlp::{DecoderError, Rlp, RlpStream};
+use std::convert::TryFrom;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+
+const KIND_PROPOSAL: u8 = 0;
+const KIND_VOTE: u8 = 1;
+const KIND_STATUS: u8 = 2;
+const KIND_VERIFY_RESP: u8 = 3;
+const KIND_FEED: u8 = 4;
+#[cfg(feature = "compact_block")]
+const KIND_GET_TXS: u8 = 5;
+#[cfg(feature = "compact_block")]
+const KIND_TXS: u8 = 6;
+const KIND_PAUSE: u8 = 7;
+const KIND_START: u8 = 8;
+const KIND_CLEAR: u8 = 9;
+const KIND_IMPORT_SNAPSHOT: u8 = 10;
+const KIND_KILL: u8 = 11;
+const KIND_TIMEOUT: u8 = 12;
+const KIND_REVOKE_FEED: u8 = 13;
+const KIND_TICK: u8 = 14;
+const KIND_KEY_ROTATION: u8 = 15;
+const KIND_SIGNATURE: u8 = 16;
+const KIND_OUTBOUND_PROPOSAL: u8 = 32;
+const KIND_OUTBOUND_VOTE: u8 = 33;
+#[cfg(feature = "compact_block")]
+const KIND_OUTBOUND_GET_TXS: u8 = 34;
+#[cfg(feature = "compact_block")]
+const KIND_OUTBOUND_TXS: u8 = 35;
+
+/// A trace record is `[u64 le unix millis][u8 kind][u32 le len][payload]`.
+const RECORD_HEADER_LEN: usize = 8 + 1 + 4;
+
+/// An event of the msg trace. The proposals and votes are not wrapped in wire envelopes.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum TraceEvent<B: BlockPayload = Block> {
+    /// A msg the state machine receives, the queries are not recorded.
+    Inbound(BftMsg<B>),
+    /// A timer of the height, the round and the step fires.
+    Timeout {
+        height: Height,
+        round: Round,
+        step: String,
+    },
+    /// A msg the state machine transmits, the retransmissions are not recorded.
+    Outbound(Outbound),
+}
+
+/// A record of the msg trace.
+#[derive(Debug)]
+pub struct TraceRecord<B: BlockPayload = Block> {
+    /// the unix time the event is recorded, in milliseconds
+    pub timestamp: u64,
+    pub event: TraceEvent<B>,
+}
+
+/// The recorder of the msg trace, which appends to the trace file.
+pub(crate) struct Tracer {
+    file: File,
+    path: String,
+}
+
+impl Tracer {
+    pub(crate) fn open(path: &str) -> BftResult<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| BftError::TraceErr(format!("{:?} of {}", e, path).into()))?;
+        Ok(Tracer {
+            file,
+            path: path.to_string(),
+        })
+    }
+
+    pub(crate) fn record_inbound<B: BlockPayload>(&mut self, msg: &BftMsg<B>) -> BftResult<()> {
+        let (kind, payload) = match msg {
+            BftMsg::Proposal(encode) => (KIND_PROPOSAL, encode.clone()),
+            BftMsg::Vote(encode) => (KIND_VOTE, encode.clone()),
+            BftMsg::Status(status) => (KIND_STATUS, rlp::encode(status)),
+            BftMsg::VerifyResp(verify_resp) => (KIND_VERIFY_RESP, rlp::encode(verify_resp)),
+            BftMsg::Feed(feed) => (KIND_FEED, rlp::encode(feed)),
+            BftMsg::RevokeFeed { height, block_hash } => (
+                KIND_REVOKE_FEED,
+                encode_revoke_feed(*height, block_hash, RlpCodec::V1),
+            ),
+            #[cfg(feature = "compact_block")]
+            BftMsg::GetTxs(encode) => (KIND_GET_TXS, encode.clone()),
+            #[cfg(feature = "compact_block")]
+            BftMsg::Txs(encode) => (KIND_TXS, encode.clone()),
+            BftMsg::Pause => (KIND_PAUSE, Vec::new()),
+            BftMsg::Start => (KIND_START, Vec::new()),
+            BftMsg::Clear(proof) => (KIND_CLEAR, rlp::encode(proof)),
+            BftMsg::ImportSnapshot(snapshot) => (KIND_IMPORT_SNAPSHOT, rlp::encode(snapshot)),
+            BftMsg::Kill => (KIND_KILL, Vec::new()),
+            BftMsg::Tick => (KIND_TICK, Vec::new()),
+            BftMsg::KeyRotation(rotation) => (KIND_KEY_ROTATION, rlp::encode(rotation)),
+            BftMsg::Signature(ticket, signature) => {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(ticket).append(signature);
+                (KIND_SIGNATURE, stream.out())
+            }
+            // the queries do not change the state
+            _ => return Ok(()),
+        };
+        self.write(kind, &payload)
+    }
+
+    pub(crate) fn record_timeout(&mut self, timeout_info: &TimeoutInfo) -> BftResult<()> {
+        self.write(KIND_TIMEOUT, &rlp::encode(timeout_info))
+    }
+
+    pub(crate) fn record_outbound(&mut self, outbound: &Outbound) -> BftResult<()> {
+        let (kind, encode) = match &outbound.msg {
+            OutboundMsg::Proposal(encode) => (KIND_OUTBOUND_PROPOSAL, encode),
+            OutboundMsg::Vote(encode) => (KIND_OUTBOUND_VOTE, encode),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::GetTxs(encode) => (KIND_OUTBOUND_GET_TXS, encode),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::Txs(encode) => (KIND_OUTBOUND_TXS, encode),
+        };
+        // a broadcast has an empty target
+        let target: Vec<u8> = match &outbound.target {
+            Target::Broadcast => Vec::new(),
+            Target::Unicast(address) => address.to_vec(),
+        };
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&target).append(encode);
+        self.write(kind, &stream.out())
+    }
+
+    fn write(&mut self, kind: u8, payload: &[u8]) -> BftResult<()> {
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + payload.len());
+        record.extend_from_slice(&unix_millis().to_le_bytes());
+        record.push(kind);
+        record.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        record.extend_from_slice(payload);
+        self.file
+            .write_all(&record)
+            .map_err(|e| BftError::TraceErr(format!("{:?} of {}", e, self.path).into()))
+    }
+}
+
+/// A public function to read the records of the msg trace in the [`path`].
+/// A torn record at the end is ignored.
+pub fn read_trace<B: BlockPayload>(path: &str) -> Result<Vec<TraceRecord<B>>, io::Error> {
+    let buf = fs::read(path)?;
+    let mut records = Vec::new();
+    let mut index = 0;
+    while index + RECORD_HEADER_LEN <= buf.len() {
+        let mut timestamp = [0u8; 8];
+        let mut len = [0u8; 4];
+        timestamp.copy_from_slice(&buf[index..index + 8]);
+        len.copy_from_slice(&buf[index + 9..index + 13]);
+        let kind = buf[index + 8];
+        let body = index + RECORD_HEADER_LEN;
+        let body_len = u32::from_le_bytes(len) as usize;
+        if body + body_len > buf.len() {
+            break;
+        }
+        let event = decode_event(kind, &buf[body..body + body_len]).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("record at offset {} of {} fails, {:?}", index, path, e),
+            )
+        })?;
+        records.push(TraceRecord {
+            timestamp: u64::from_le_bytes(timestamp),
+            event,
+        });
+        index = body + body_len;
+    }
+    Ok(records)
+}
+
+/// A public function to get the inbound msgs of the trace [`records`], which are fed to
+/// the `ReplayDebugger`.
+pub fn inbound_msgs<B: BlockPayload>(records: Vec<TraceRecord<B>>) -> Vec<BftMsg<B>> {
+    records
+        .into_iter()
+        .filter_map(|record| match record.event {
+            TraceEvent::Inbound(msg) => Some(msg),
+            _ => None,
+        })
+        .collect()
+}
+
+fn decode_event<B: BlockPayload>(kind: u8, payload: &[u8]) -> BftResult<TraceEvent<B>> {
+    let decode_err =
+        |e: DecoderError| BftError::DecodeErr(format!("{:?} of trace kind {}", e, kind).into());
+    let msg = match kind {
+        KIND_PROPOSAL => BftMsg::Proposal(payload.to_vec()),
+        KIND_VOTE => BftMsg::Vote(payload.to_vec()),
+        KIND_STATUS => BftMsg::Status(rlp::decode::<Status>(payload).map_err(decode_err)?),
+        KIND_VERIFY_RESP => {
+            BftMsg::VerifyResp(rlp::decode::<VerifyResp>(payload).map_err(decode_err)?)
+        }
+        KIND_FEED => BftMsg::Feed(rlp::decode::<Feed<B>>(payload).map_err(decode_err)?),
+        KIND_REVOKE_FEED => {
+            let (height, block_hash) = decode_revoke_feed(payload).map_err(decode_err)?;
+            BftMsg::RevokeFeed { height, block_hash }
+        }
+        #[cfg(feature = "compact_block")]
+        KIND_GET_TXS => BftMsg::GetTxs(payload.to_vec()),
+        #[cfg(feature = "compact_block")]
+        KIND_TXS => BftMsg::Txs(payload.to_vec()),
+        KIND_PAUSE => BftMsg::Pause,
+        KIND_START => BftMsg::Start,
+        KIND_CLEAR => BftMsg::Clear(rlp::decode::<Proof>(payload).map_err(decode_err)?),
+        KIND_IMPORT_SNAPSHOT => {
+            BftMsg::ImportSnapshot(rlp::decode::<StateSnapshot>(payload).map_err(decode_err)?)
+        }
+        KIND_KILL => BftMsg::Kill,
+        KIND_TICK => BftMsg::Tick,
+        KIND_KEY_ROTATION => {
+            BftMsg::KeyRotation(rlp::decode::<KeyRotation>(payload).map_err(decode_err)?)
+        }
+        KIND_SIGNATURE => {
+            let rlp = Rlp::new(payload);
+            BftMsg::Signature(
+                rlp.val_at(0).map_err(decode_err)?,
+                rlp.val_at(1).map_err(decode_err)?,
+            )
+        }
+        KIND_TIMEOUT => {
+            let timeout_info = rlp::decode::<TimeoutInfo>(payload).map_err(decode_err)?;
+            return Ok(TraceEvent::Timeout {
+                height: timeout_info.height,
+                round: timeout_info.round,
+                step: format!("{:?}", timeout_info.step),
+            });
+        }
+        _ => return decode_outbound(kind, payload).map(TraceEvent::Outbound),
+    };
+    Ok(TraceEvent::Inbound(msg))
+}
+
+fn decode_outbound(kind: u8, payload: &[u8]) -> BftResult<Outbound> {
+    let rlp = Rlp::new(payload);
+    let decode_err =
+        |e: DecoderError| BftError::DecodeErr(format!("{:?} of trace kind {}", e, kind).into());
+    let target: Vec<u8> = rlp.val_at(0).map_err(decode_err)?;
+    let encode: Vec<u8> = rlp.val_at(1).map_err(decode_err)?;
+    let target = if target.is_empty() {
+        Target::Broadcast
+    } else {
+        Target::Unicast(Address::try_from(&target[..])?)
+    };
+    let msg = match kind {
+        KIND_OUTBOUND_PROPOSAL => OutboundMsg::Proposal(encode),
+        KIND_OUTBOUND_VOTE => OutboundMsg::Vote(encode),
+        #[cfg(feature = "compact_block")]
+        KIND_OUTBOUND_GET_TXS => OutboundMsg::GetTxs(encode),
+        #[cfg(feature = "compact_block")]
+        KIND_OUTBOUND_TXS => OutboundMsg::Txs(encode),
+        _ => {
+            return Err(BftError::DecodeErr(ErrorInfo::new(format!(
+                "invalid trace kind {}",
+                kind
+            ))))
+        }
+    };
+    Ok(Outbound { msg, target })
+}
@@ -1,167 +1,56 @@
+use crate::codec::{AppendBy, CodecEncodable, RlpCodec};
+use crate::utils::PriorityCycle;
+use crate::verify::get_total_weight;
 use crate::*;
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
-use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::mem;
+use std::time::Instant;
 
-#[derive(Clone, PartialEq, Eq, Hash)]
-pub(crate) struct Proposal {
-    /// the height of proposal
-    pub(crate) height: Height,
-    /// the round of proposal
-    pub(crate) round: Round,
-    /// block hash
-    pub(crate) block_hash: Hash,
-    /// the proof of previous height
-    pub(crate) proof: Proof,
-    /// the lock round of the proposal
-    pub(crate) lock_round: Option<Round>,
-    /// the lock votes of the proposal
-    pub(crate) lock_votes: Vec<SignedVote>,
-    /// proposer address
-    pub(crate) proposer: Address,
-}
-
-impl Debug for Proposal {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "Proposal {{ h: {}, r: {}, hash:{:?}, addr: {:?}}}",
-            self.height, self.round, self.block_hash, self.proposer,
-        )
-    }
-}
-
-impl Encodable for Proposal {
-    fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(7)
-            .append(&self.height)
-            .append(&self.round)
-            .append(&self.block_hash)
-            .append(&self.proof)
-            .append(&self.lock_round)
-            .append_list(&self.lock_votes)
-            .append(&self.proposer);
-    }
-}
-
-impl Decodable for Proposal {
-    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
-        match r.prototype()? {
-            Prototype::List(7) => {
-                let height: Height = r.val_at(0)?;
-                let round: Round = r.val_at(1)?;
-                let block_hash: Hash = r.val_at(2)?;
-                let proof: Proof = r.val_at(3)?;
-                let lock_round: Option<Round> = r.val_at(4)?;
-                let lock_votes: Vec<SignedVote> = r.list_at(5)?;
-                let proposer: Address = r.val_at(6)?;
-                Ok(Proposal {
-                    height,
-                    round,
-                    block_hash,
-                    proof,
-                    lock_round,
-                    lock_votes,
-                    proposer,
-                })
-            }
-            _ => Err(DecoderError::RlpInconsistentLengthAndData),
-        }
-    }
-}
-
-#[derive(Clone, PartialEq, Eq, Hash)]
-pub(crate) struct SignedProposal {
-    pub(crate) proposal: Proposal,
-    pub(crate) signature: Signature,
-}
-
-impl Debug for SignedProposal {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "SignedProposal {{ proposal: {:?}, sig: {:?}}}",
-            self.proposal, self.signature,
-        )
-    }
-}
+pub use bft_verify::objects::{Proposal, SignedProposal, SignedVote, Vote, VoteType};
 
-impl Encodable for SignedProposal {
+impl Encodable for AuthorityManage {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(2)
-            .append(&self.proposal)
-            .append(&self.signature);
+        self.rlp_append_by(s, RlpCodec::V1);
     }
 }
 
-impl Decodable for SignedProposal {
-    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
-        match r.prototype()? {
-            Prototype::List(2) => {
-                let proposal: Proposal = r.val_at(0)?;
-                let signature: Signature = r.val_at(1)?;
-                Ok(SignedProposal {
-                    proposal,
-                    signature,
-                })
-            }
-            _ => Err(DecoderError::RlpInconsistentLengthAndData),
-        }
-    }
-}
-
-/// A vote to a proposal.
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub(crate) struct Vote {
-    /// Prevote or precommit
-    pub(crate) vote_type: VoteType,
-    /// the height of vote
-    pub(crate) height: Height,
-    /// the round of vote
-    pub(crate) round: Round,
-    /// the content vote for
-    pub(crate) block_hash: Hash,
-    /// voter address
-    pub(crate) voter: Address,
-}
-
-impl Debug for Vote {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "{:?} {{ h: {}, r: {}, hash: {:?}, addr: {:?}}}",
-            self.vote_type, self.height, self.round, self.block_hash, self.voter,
-        )
-    }
-}
-
-impl Encodable for Vote {
-    fn rlp_append(&self, s: &mut RlpStream) {
-        let vote_type: u8 = self.vote_type.clone().into();
+impl CodecEncodable for AuthorityManage {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
         s.begin_list(5)
-            .append(&vote_type)
-            .append(&self.height)
-            .append(&self.round)
-            .append(&self.block_hash)
-            .append(&self.voter);
+            .append_list_by(&self.authorities, codec)
+            .append_list_by(&self.authorities_old, codec)
+            .append(&self.authority_h_old)
+            .append_list_by(&self.pending_schedule(), codec)
+            .append_list_by(&self.rotations, codec);
     }
 }
 
-impl Decodable for Vote {
+impl Decodable for AuthorityManage {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(5) => {
-                let vote_type: u8 = r.val_at(0)?;
-                let vote_type: VoteType = VoteType::from(vote_type);
-                let height: Height = r.val_at(1)?;
-                let round: Round = r.val_at(2)?;
-                let block_hash: Hash = r.val_at(3)?;
-                let voter: Address = r.val_at(4)?;
-                Ok(Vote {
-                    vote_type,
-                    height,
-                    round,
-                    block_hash,
-                    voter,
+            // the authorities saved before the key rotations are of 4 items
+            Prototype::List(len @ 4) | Prototype::List(len @ 5) => {
+                let authorities: Vec<Node> = r.list_at(0)?;
+                let authorities_old: Vec<Node> = r.list_at(1)?;
+                let authority_h_old: Height = r.val_at(2)?;
+                let scheduled: Vec<ScheduledAuthorities> = r.list_at(3)?;
+                let rotations: Vec<KeyRotation> = if len == 5 { r.list_at(4)? } else { Vec::new() };
+                let weight_sum = get_total_weight(&authorities);
+                let schedule = scheduled
+                    .into_iter()
+                    .map(|s| (s.activation_height, s.authority_list))
+                    .collect();
+                Ok(AuthorityManage {
+                    authorities,
+                    authorities_old,
+                    authority_h_old,
+                    weight_sum,
+                    schedule,
+                    rotations,
+                    priorities: PriorityCycle::default(),
+                    priorities_old: PriorityCycle::default(),
                 })
             }
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
@@ -169,36 +58,66 @@ impl Decodable for Vote {
     }
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
-pub(crate) struct SignedVote {
-    pub(crate) vote: Vote,
-    pub(crate) signature: Signature,
-}
-
-impl Debug for SignedVote {
-    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        write!(
-            f,
-            "SignedVote {{ vote: {:?}, sig: {:?}}}",
-            self.vote, self.signature,
-        )
-    }
+/// The consensus state of a node, to migrate the node to another machine.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StateSnapshot {
+    /// the height of the node
+    pub height: Height,
+    /// the proof of the previous height
+    pub proof: Proof,
+    /// the authority list of the height
+    pub authority_list: Vec<Node>,
+    /// the authority list before the last change
+    pub authority_list_old: Vec<Node>,
+    /// the height of the last authority change
+    pub authority_h_old: Height,
+    /// the authority lists waiting for their activation heights
+    pub scheduled_authorities: Vec<ScheduledAuthorities>,
+    /// the round of the PoLC the node locks on
+    pub lock_round: Option<Round>,
+    /// the hash of the locked block, nil if not locked
+    pub lock_block_hash: Hash,
+    /// the locked block, empty if not locked
+    pub lock_block: Block,
+    /// the votes of the PoLC
+    pub lock_votes: Vec<SignedVote>,
+    /// the cached votes of the height
+    pub votes: Vec<SignedVote>,
 }
 
-impl Encodable for SignedVote {
+impl Encodable for StateSnapshot {
     fn rlp_append(&self, s: &mut RlpStream) {
-        s.begin_list(2).append(&self.vote).append(&self.signature);
+        s.begin_list(11)
+            .append(&self.height)
+            .append(&self.proof)
+            .append_list(&self.authority_list)
+            .append_list(&self.authority_list_old)
+            .append(&self.authority_h_old)
+            .append_list(&self.scheduled_authorities)
+            .append(&self.lock_round)
+            .append(&self.lock_block_hash)
+            .append(&self.lock_block)
+            .append_list(&self.lock_votes)
+            .append_list(&self.votes);
     }
 }
 
-impl Decodable for SignedVote {
+impl Decodable for StateSnapshot {
     fn decode(r: &Rlp) -> Result<Self, DecoderError> {
         match r.prototype()? {
-            Prototype::List(2) => {
-                let vote: Vote = r.val_at(0)?;
-                let signature: Signature = r.val_at(1)?;
-                Ok(SignedVote { vote, signature })
-            }
+            Prototype::List(11) => Ok(StateSnapshot {
+                height: r.val_at(0)?,
+                proof: r.val_at(1)?,
+                authority_list: r.list_at(2)?,
+                authority_list_old: r.list_at(3)?,
+                authority_h_old: r.val_at(4)?,
+                scheduled_authorities: r.list_at(5)?,
+                lock_round: r.val_at(6)?,
+                lock_block_hash: r.val_at(7)?,
+                lock_block: r.val_at(8)?,
+                lock_votes: r.list_at(9)?,
+                votes: r.list_at(10)?,
+            }),
             _ => Err(DecoderError::RlpInconsistentLengthAndData),
         }
     }
@@ -212,11 +131,29 @@ pub(crate) struct LockStatus {
     pub(crate) votes: Vec<SignedVote>,
 }
 
+/// An own vote waiting for its signature from `sign_async`.
+#[derive(Clone, Debug)]
+pub(crate) struct PendingSign {
+    pub(crate) vote: Vote,
+    /// the time the signature is dropped after
+    pub(crate) deadline: Instant,
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct AuthorityManage {
     pub(crate) authorities: Vec<Node>,
     pub(crate) authorities_old: Vec<Node>,
     pub(crate) authority_h_old: Height,
+    /// The total vote weight of the authorities.
+    pub(crate) weight_sum: u64,
+    /// The authority lists announced in advance, keyed by the activation height.
+    pub(crate) schedule: BTreeMap<Height, Vec<Node>>,
+    /// The accepted key rotations waiting for their activation heights.
+    pub(crate) rotations: Vec<KeyRotation>,
+    /// The proposers selected by the accumulated priorities of the authorities.
+    pub(crate) priorities: PriorityCycle,
+    /// The proposers selected by the accumulated priorities of the old authorities.
+    pub(crate) priorities_old: PriorityCycle,
 }
 
 impl AuthorityManage {
@@ -225,10 +162,22 @@ impl AuthorityManage {
             authorities: Vec::new(),
             authorities_old: Vec::new(),
             authority_h_old: 0,
+            weight_sum: 0,
+            schedule: BTreeMap::new(),
+            rotations: Vec::new(),
+            priorities: PriorityCycle::default(),
+            priorities_old: PriorityCycle::default(),
         }
     }
 
-    pub(crate) fn receive_authorities_list(&mut self, height: Height, mut authorities: Vec<Node>) {
+    /// A function to receive the authority list of next height, return `true` if it changes.
+    /// An invalid list is rejected and the current one is kept, see `check_authorities`.
+    pub(crate) fn receive_authorities_list(
+        &mut self,
+        height: Height,
+        mut authorities: Vec<Node>,
+    ) -> BftResult<bool> {
+        check_authorities(&authorities)?;
         authorities.sort();
         if self.authorities != authorities {
             self.authorities_old.clear();
@@ -237,12 +186,201 @@ impl AuthorityManage {
 
             self.authorities.clear();
             self.authorities.extend_from_slice(&authorities);
+            self.weight_sum = get_total_weight(&self.authorities);
+            self.priorities_old = mem::take(&mut self.priorities);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// A function to accumulate the priorities of the authorities and the old ones again if
+    /// they are not cached, which is done once for an authority list.
+    pub(crate) fn refresh_priorities(&mut self) {
+        if !self.priorities.is_of(&self.authorities) {
+            self.priorities = PriorityCycle::new(&self.authorities);
+        }
+        if !self.priorities_old.is_of(&self.authorities_old) {
+            self.priorities_old = PriorityCycle::new(&self.authorities_old);
+        }
+    }
+
+    /// A function to get the index of the proposer of the [`round`] of the [`height`] in the
+    /// [`authorities`] from the cached priorities, `None` if they are not cached.
+    pub(crate) fn priority_index(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+    ) -> Option<usize> {
+        [&self.priorities, &self.priorities_old]
+            .iter()
+            .find_map(|cycle| cycle.proposer_index(height, round, authorities))
+    }
+
+    /// A function to apply the diff to the current authorities, return the authority list
+    /// of next height.
+    pub(crate) fn apply_diff(&self, diff: &AuthorityDiff) -> BftResult<Vec<Node>> {
+        if let Some(node) = diff.add.iter().enumerate().find_map(|(i, node)| {
+            diff.add[..i]
+                .iter()
+                .find(|added| added.address == node.address)
+        }) {
+            return Err(BftError::InvalidAuthorities(
+                format!("{:?} adds {:?} more than once", diff, node.address).into(),
+            ));
+        }
+        let mut authorities = self.authorities.clone();
+        for address in diff.remove.iter() {
+            let index = authorities
+                .iter()
+                .position(|node| &node.address == address)
+                .ok_or_else(|| {
+                    BftError::InvalidAuthorities(format!("removes unknown {:?}", address).into())
+                })?;
+            authorities.remove(index);
+        }
+        for node in diff.add.iter() {
+            authorities.retain(|authority| authority.address != node.address);
+            authorities.push(node.clone());
         }
+        if authorities.is_empty() {
+            return Err(BftError::InvalidAuthorities(
+                format!("{:?} removes all the authorities", diff).into(),
+            ));
+        }
+        authorities.sort();
+        Ok(authorities)
+    }
+
+    /// A function to add the announced authority lists to the schedule, a later announcement
+    /// of the same activation height replaces the earlier one.
+    pub(crate) fn schedule_authorities(
+        &mut self,
+        scheduled: Vec<ScheduledAuthorities>,
+    ) -> BftResult<()> {
+        for s in scheduled.iter() {
+            check_authorities(&s.authority_list).map_err(|e| {
+                BftError::InvalidAuthorities(
+                    format!(
+                        "{:?} at activation height {}",
+                        e.info(),
+                        s.activation_height
+                    )
+                    .into(),
+                )
+            })?;
+        }
+        for mut s in scheduled {
+            s.authority_list.sort();
+            self.schedule.insert(s.activation_height, s.authority_list);
+        }
+        Ok(())
+    }
+
+    /// A function to take the authority lists activated at or below the height out of the
+    /// schedule, return the latest one.
+    pub(crate) fn take_activated(&mut self, height: Height) -> Option<Vec<Node>> {
+        let activated: Vec<Height> = self.schedule.range(..=height).map(|(h, _)| *h).collect();
+        activated
+            .into_iter()
+            .filter_map(|h| self.schedule.remove(&h))
+            .last()
+    }
+
+    /// A function to add a key rotation of a current authority, a later rotation of the same
+    /// old address replaces the earlier one. The signature is checked by the caller.
+    pub(crate) fn add_rotation(&mut self, rotation: KeyRotation) -> BftResult<()> {
+        if !self
+            .authorities
+            .iter()
+            .any(|node| node.address == rotation.old)
+        {
+            return Err(BftError::InvalidAuthorities(
+                format!("{:?} rotates from a non-authority", rotation).into(),
+            ));
+        }
+        if rotation.old == rotation.new
+            || self
+                .authorities
+                .iter()
+                .any(|node| node.address == rotation.new)
+            || self
+                .rotations
+                .iter()
+                .any(|r| r.new == rotation.new && r.old != rotation.old)
+        {
+            return Err(BftError::InvalidAuthorities(
+                format!("{:?} rotates to an address in use", rotation).into(),
+            ));
+        }
+        self.rotations.retain(|r| r.old != rotation.old);
+        self.rotations.push(rotation);
+        Ok(())
+    }
+
+    /// A function to take the key rotations activated at or below the height out.
+    pub(crate) fn take_rotations(&mut self, height: Height) -> Vec<KeyRotation> {
+        let (activated, pending) = self
+            .rotations
+            .drain(..)
+            .partition(|r| r.activation_height <= height);
+        self.rotations = pending;
+        activated
     }
+
+    /// A function to get the authority lists waiting for their activation heights.
+    pub(crate) fn pending_schedule(&self) -> Vec<ScheduledAuthorities> {
+        self.schedule
+            .iter()
+            .map(|(activation_height, authority_list)| ScheduledAuthorities {
+                activation_height: *activation_height,
+                authority_list: authority_list.clone(),
+            })
+            .collect()
+    }
+}
+
+/// A function to check the [`authorities`] are not empty, have no repeated address, and
+/// neither their vote weights nor their proposal weights sum to zero. The weights are summed
+/// without overflow, so they are taken as they are.
+pub(crate) fn check_authorities(authorities: &[Node]) -> BftResult<()> {
+    if authorities.is_empty() {
+        return Err(BftError::InvalidAuthorities("empty authority_list".into()));
+    }
+    // a repeated address would count its weight twice in the quorums
+    let mut nodes: Vec<&Node> = authorities.iter().collect();
+    nodes.sort();
+    if let Some(pair) = nodes
+        .windows(2)
+        .find(|pair| pair[0].address == pair[1].address)
+    {
+        let detail = if pair[0] == pair[1] {
+            format!("{:?} repeats in the authority_list", pair[0])
+        } else {
+            format!(
+                "{:?} conflicts with {:?} in the authority_list",
+                pair[0], pair[1]
+            )
+        };
+        return Err(BftError::InvalidAuthorities(detail.into()));
+    }
+    if authorities.iter().all(|node| node.vote_weight == 0) {
+        return Err(BftError::InvalidAuthorities(
+            "the vote weights of the authority_list sum to zero".into(),
+        ));
+    }
+    if authorities.iter().all(|node| node.proposal_weight == 0) {
+        return Err(BftError::InvalidAuthorities(
+            "the proposal weights of the authority_list sum to zero".into(),
+        ));
+    }
+    Ok(())
 }
 
-#[derive(Debug, PartialEq, PartialOrd, Eq, Clone, Copy, Hash)]
-pub(crate) enum Step {
+/// The step of the state machine in a round.
+#[derive(Debug, Default, PartialEq, PartialOrd, Eq, Clone, Copy, Hash)]
+pub enum Step {
+    #[default]
     Propose,
     ProposeWait,
     Prevote,
@@ -255,33 +393,28 @@ pub(crate) enum Step {
     CommitWait,
 }
 
-impl Default for Step {
-    fn default() -> Step {
-        Step::Propose
-    }
-}
-
-impl From<u8> for Step {
-    fn from(s: u8) -> Self {
+impl TryFrom<u8> for Step {
+    type Error = BftError;
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
         match s {
-            0 => Step::Propose,
-            1 => Step::ProposeWait,
-            2 => Step::Prevote,
-            3 => Step::PrevoteWait,
+            0 => Ok(Step::Propose),
+            1 => Ok(Step::ProposeWait),
+            2 => Ok(Step::Prevote),
+            3 => Ok(Step::PrevoteWait),
             #[cfg(feature = "verify_req")]
-            4 => Step::VerifyWait,
-            5 => Step::Precommit,
-            6 => Step::PrecommitWait,
-            7 => Step::Commit,
-            8 => Step::CommitWait,
-            _ => panic!("Invalid vote type!"),
+            4 => Ok(Step::VerifyWait),
+            5 => Ok(Step::Precommit),
+            6 => Ok(Step::PrecommitWait),
+            7 => Ok(Step::Commit),
+            8 => Ok(Step::CommitWait),
+            _ => Err(BftError::DecodeErr(format!("invalid step {}", s).into())),
         }
     }
 }
 
-impl Into<u8> for Step {
-    fn into(self) -> u8 {
-        match self {
+impl From<Step> for u8 {
+    fn from(val: Step) -> Self {
+        match val {
             Step::Propose => 0,
             Step::ProposeWait => 1,
             Step::Prevote => 2,
@@ -296,33 +429,9 @@ impl Into<u8> for Step {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Hash)]
-pub(crate) enum VoteType {
-    Prevote,
-    Precommit,
-}
-
-impl From<u8> for VoteType {
-    fn from(s: u8) -> Self {
-        match s {
-            0 => VoteType::Prevote,
-            1 => VoteType::Precommit,
-            _ => panic!("Invalid vote type!"),
-        }
-    }
-}
-
-impl Into<u8> for VoteType {
-    fn into(self) -> u8 {
-        match self {
-            VoteType::Prevote => 0,
-            VoteType::Precommit => 1,
-        }
-    }
-}
-
-#[derive(Debug)]
-pub(crate) enum LogType {
+/// The type of a wal record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogType {
     Proposal,
     Vote,
     Status,
@@ -331,27 +440,42 @@ pub(crate) enum LogType {
     VerifyResp,
     TimeOutInfo,
     Block,
+    Authorities,
+    RevokeFeed,
+    HeightStart,
 }
 
-impl From<u8> for LogType {
-    fn from(s: u8) -> Self {
+impl LogType {
+    /// A function to get the log type of the byte, `None` if it is invalid.
+    pub(crate) fn from_u8(s: u8) -> Option<Self> {
         match s {
-            0 => LogType::Proposal,
-            1 => LogType::Vote,
-            2 => LogType::Status,
-            3 => LogType::Proof,
-            4 => LogType::Feed,
-            5 => LogType::VerifyResp,
-            6 => LogType::TimeOutInfo,
-            7 => LogType::Block,
-            _ => panic!("Invalid vote type!"),
+            0 => Some(LogType::Proposal),
+            1 => Some(LogType::Vote),
+            2 => Some(LogType::Status),
+            3 => Some(LogType::Proof),
+            4 => Some(LogType::Feed),
+            5 => Some(LogType::VerifyResp),
+            6 => Some(LogType::TimeOutInfo),
+            7 => Some(LogType::Block),
+            8 => Some(LogType::Authorities),
+            9 => Some(LogType::RevokeFeed),
+            10 => Some(LogType::HeightStart),
+            _ => None,
         }
     }
 }
 
-impl Into<u8> for LogType {
-    fn into(self) -> u8 {
-        match self {
+impl TryFrom<u8> for LogType {
+    type Error = BftError;
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
+        LogType::from_u8(s)
+            .ok_or_else(|| BftError::DecodeErr(format!("invalid log type {}", s).into()))
+    }
+}
+
+impl From<LogType> for u8 {
+    fn from(val: LogType) -> Self {
+        match val {
             LogType::Proposal => 0,
             LogType::Vote => 1,
             LogType::Status => 2,
@@ -360,6 +484,9 @@ impl Into<u8> for LogType {
             LogType::VerifyResp => 5,
             LogType::TimeOutInfo => 6,
             LogType::Block => 7,
+            LogType::Authorities => 8,
+            LogType::RevokeFeed => 9,
+            LogType::HeightStart => 10,
         }
     }
 }
@@ -1,8 +1,380 @@
-use crate::Address;
+use crate::clock::Clock;
+use crate::codec::RlpCodec;
+use crate::proof_store::ProofStore;
+use crate::runtime::BftRuntime;
+use crate::verify::SignScheme;
+use crate::{Address, ConsensusParams, Round, TimeoutParams};
 
 use std::cell::Cell;
+use std::convert::TryFrom;
+use std::sync::Arc;
 use std::time::Duration;
 
+pub(crate) const DEFAULT_CHANNEL_CAPACITY: usize = 1024;
+pub(crate) const DEFAULT_MSG_RATE: u64 = 200;
+pub(crate) const DEFAULT_MSG_BURST: u64 = 1000;
+pub(crate) const DEFAULT_SIG_FAIL_THRESHOLD: u32 = 10;
+pub(crate) const DEFAULT_SIG_FAIL_BAN_MILLIS: u64 = 60_000;
+pub(crate) const DEFAULT_MAX_LOCK_VOTES: usize = 1024;
+pub(crate) const DEFAULT_MAX_PRECOMMIT_VOTES: usize = 1024;
+pub(crate) const DEFAULT_MAX_AUTHORITIES: usize = 1024;
+pub(crate) const DEFAULT_MAX_BLOCK_SIZE: usize = 32 * 1024 * 1024;
+pub(crate) const DEFAULT_TRANSMIT_RETRY_TIMES: u32 = 5;
+pub(crate) const DEFAULT_TRANSMIT_RETRY_MILLIS: u64 = 100;
+pub(crate) const DEFAULT_TRANSMIT_RETRY_CAPACITY: usize = 256;
+pub(crate) const DEFAULT_COMMIT_RETRY_TIMES: u32 = 5;
+pub(crate) const DEFAULT_COMMIT_RETRY_MILLIS: u64 = 200;
+pub(crate) const DEFAULT_COMMIT_RETRY_MAX_MILLIS: u64 = 30_000;
+pub(crate) const DEFAULT_GET_BLOCK_RETRY_TIMES: u32 = 5;
+pub(crate) const DEFAULT_RESTART_BACKOFF_MILLIS: u64 = 100;
+pub(crate) const DEFAULT_RESTART_MAX_BACKOFF_MILLIS: u64 = 10_000;
+pub(crate) const DEFAULT_RESTART_ESCALATION_TIMES: u32 = 5;
+pub(crate) const DEFAULT_GET_BLOCK_RETRY_MILLIS: u64 = 100;
+pub(crate) const DEFAULT_CHAIN_ID: u64 = 0;
+pub(crate) const DEFAULT_SIG_VERIFY_THREADS: usize = 4;
+pub(crate) const DEFAULT_SIG_CACHE_CAPACITY: usize = 4096;
+pub(crate) const DEFAULT_MAX_CLOCK_DRIFT_MILLIS: u64 = 10_000;
+pub(crate) const DEFAULT_SIGN_TIMEOUT_MILLIS: u64 = 1_000;
+/// The max duration a step waits, which the scaled timeouts are saturated at.
+pub(crate) const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+pub(crate) const DEFAULT_PROOF_STORE_CAPACITY: usize = 1024;
+pub(crate) const DEFAULT_PROPOSER_WINDOW: usize = 1000;
+pub(crate) const DEFAULT_WAL_RETENTION_HEIGHTS: u64 = 3;
+pub(crate) const DEFAULT_WAL_SEGMENT_SIZE: u64 = 64 * 1024 * 1024;
+pub(crate) const DEFAULT_LOG_TARGET: &str = "bft_rs";
+
+/// The policy when the internal message channel is full.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Block the sender until the channel has free space.
+    Block,
+    /// Queue the proposals and votes of the future heights apart and drop the oldest of them
+    /// to make room, block the sender of the others.
+    DropOldestFutureHeight,
+    /// Return `BftError::ChannelFull` to the sender.
+    Error,
+}
+
+/// The compression of the block segment in the outbound proposals.
+/// The compressed proposals are sent in the wire envelopes of `WIRE_VERSION_COMPRESSED`,
+/// which the nodes of older versions reject, so enable it after all the nodes upgrade.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// Send the block segment as it is.
+    None,
+    /// Compress the block segment with snappy.
+    #[cfg(feature = "snap")]
+    Snappy,
+    /// Compress the block segment with zstd of the level.
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+/// What a node precommits when the verification of its locked block times out.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NilVotePolicy {
+    /// Precommit nil, so the round moves on without the unverified block.
+    PrecommitNil,
+    /// Precommit the locked block, trusting the 2/3+ prevotes of the authorities on it.
+    PrecommitLocked,
+}
+
+impl From<NilVotePolicy> for u8 {
+    fn from(policy: NilVotePolicy) -> Self {
+        match policy {
+            NilVotePolicy::PrecommitNil => 0,
+            NilVotePolicy::PrecommitLocked => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for NilVotePolicy {
+    type Error = u8;
+
+    fn try_from(policy: u8) -> Result<Self, u8> {
+        match policy {
+            0 => Ok(NilVotePolicy::PrecommitNil),
+            1 => Ok(NilVotePolicy::PrecommitLocked),
+            _ => Err(policy),
+        }
+    }
+}
+
+/// How the proposer of a round is selected from the authorities by their proposal weights.
+/// All the nodes of a chain must select by the same one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProposerSelection {
+    /// Select the authority the `height + round` nonce falls on in the accumulated weights,
+    /// or a seeded random one with the `random_proposer` feature.
+    Weighted,
+    /// Select by the accumulated priorities as Tendermint does: each step every priority
+    /// increases by its weight, and the highest one is selected and decreases by the weight
+    /// sum. Consecutive heights rotate the proposers in proportion to their weights. The
+    /// weights are divided by their gcd, and scaled down if they still sum above 65536, which
+    /// bounds a selection to `O(n * 65536)`.
+    Priority,
+}
+
+/// The retention of the wal records of the past heights. The records are pruned by whole
+/// segments, so a segment is kept until all of its heights are out of the retention.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WalRetention {
+    /// The count of the past heights whose wal records are kept, 0 keeps all of them
+    /// until `BftActuator::prune_wal` is called.
+    pub heights: u64,
+    /// The directory the pruned wal segments are moved to, they are deleted if it is `None`.
+    pub archive_dir: Option<String>,
+}
+
+impl Default for WalRetention {
+    fn default() -> Self {
+        WalRetention {
+            heights: DEFAULT_WAL_RETENTION_HEIGHTS,
+            archive_dir: None,
+        }
+    }
+}
+
+/// When the wal records are synced to the disk. The records are always flushed to the OS,
+/// which survives a crash of the process but not a power failure until they are synced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalSyncPolicy {
+    /// Sync after every record.
+    Always,
+    /// Sync after the count of records.
+    EveryNRecords(u32),
+    /// Sync the first record saved after the interval since the last sync, in milliseconds.
+    IntervalMs(u64),
+    /// Leave the syncing to the OS.
+    Never,
+}
+
+/// User-defined configs of the BFT state machine.
+#[derive(Clone, Debug)]
+pub struct BftConfig {
+    /// The capacity of the internal message channels.
+    pub channel_capacity: usize,
+    /// Whether the votes and the proposals of the current height and round and the verify
+    /// results are processed before the other msgs waiting in the channel.
+    pub msg_priority: bool,
+    /// The cap of the approximate bytes of the cached blocks, proposals and votes, the lowest
+    /// heights except the current one are evicted beyond it. 0 disables the cap.
+    pub cache_bytes_cap: usize,
+    /// The bytes of the cached blocks in memory beyond which the block bodies are spilled to
+    /// the files in the wal directory. 0 keeps all the blocks in memory.
+    pub block_spill_threshold: usize,
+    /// The policy when the message channel is full.
+    pub overflow_policy: OverflowPolicy,
+    /// Proposals and votes allowed per second of each sender, 0 disables rate limiting.
+    pub msg_rate: u64,
+    /// The burst size of the rate limiter.
+    pub msg_burst: u64,
+    /// The count of continuous signature failures to ban a sender, 0 disables banning.
+    pub sig_fail_threshold: u32,
+    /// How long a banned sender is ignored, in milliseconds.
+    pub sig_fail_ban_millis: u64,
+    /// The max length of lock_votes in a decoded proposal.
+    pub max_lock_votes: usize,
+    /// The max count of precommit_votes in a decoded proof.
+    pub max_precommit_votes: usize,
+    /// The max length of authority_list in a status.
+    pub max_authorities: usize,
+    /// The max size of the block payload in a proposal, in bytes.
+    pub max_block_size: usize,
+    /// The max re-attempts of a failed transmission, 0 disables retrying.
+    pub transmit_retry_times: u32,
+    /// The backoff before the first re-attempt, in milliseconds, doubled after each failure.
+    pub transmit_retry_millis: u64,
+    /// The max count of msgs waiting to be transmitted again.
+    pub transmit_retry_capacity: usize,
+    /// The max re-attempts of a failed commit, 0 disables retrying.
+    /// `CommitStalled` is reported after all of them fail.
+    pub commit_retry_times: u32,
+    /// The backoff before the first re-attempt of a commit, in milliseconds,
+    /// doubled after each failure.
+    pub commit_retry_millis: u64,
+    /// The max backoff between the re-attempts of a commit, in milliseconds.
+    pub commit_retry_max_millis: u64,
+    /// The backoff before restarting the main loop from wal after an internal error or a
+    /// panic, in milliseconds, doubled after each restart at the same height.
+    pub restart_backoff_millis: u64,
+    /// The max backoff before restarting the main loop, in milliseconds.
+    pub restart_max_backoff_millis: u64,
+    /// The count of restarts at the same height to report `RestartStorm`, 0 disables it.
+    pub restart_escalation_times: u32,
+    /// The max re-attempts of a failed `get_block`, 0 disables retrying. The re-attempts also
+    /// stop at the propose timeout, then `FeedUnavailable` is reported and the empty block of
+    /// `BftSupport::empty_block` is proposed if there is one.
+    pub get_block_retry_times: u32,
+    /// The backoff before the first re-attempt of `get_block`, in milliseconds,
+    /// doubled after each failure.
+    pub get_block_retry_millis: u64,
+    /// Whether the proposer proposes its feed again in the later rounds of the height instead
+    /// of getting a new block, until the feed is revoked.
+    pub retain_feed: bool,
+    /// Whether the next height begins on `BftMsg::Tick` after the status of the height,
+    /// instead of after the consensus interval.
+    pub external_tick: bool,
+    /// Whether the proposer of the first round of the next height, by the current authorities,
+    /// gets its block once a PoLC is reached, to propose it as soon as it reaches the height.
+    pub pipeline_proposal: bool,
+    /// The chain id carried by the wire envelopes, msgs of other chains are rejected.
+    pub chain_id: u64,
+    /// Whether the signed hashes of the proposals and the votes are separated by the msg kind
+    /// and the `chain_id`, see `sign_scheme`. All the nodes of a chain set the same one.
+    pub sign_domain_separation: bool,
+    /// The codec the msgs are rlp encoded by, carried by the wire envelopes and the wal records.
+    /// All the nodes of a chain set the same one.
+    pub rlp_codec: RlpCodec,
+    /// The count of threads checking signatures off the main loop, 0 checks them in the main loop.
+    pub sig_verify_threads: usize,
+    /// The count of threads checking the blocks of the proposals of future rounds ahead of
+    /// their rounds, 0 checks them as they arrive.
+    pub speculative_verify_threads: usize,
+    /// The max count of verified signatures cached to skip checking them again.
+    pub sig_cache_capacity: usize,
+    /// The compression of the block segment in the outbound proposals.
+    pub block_compression: Compression,
+    /// Whether the commits carry the signed precommits the proofs are built from.
+    pub commit_precommits: bool,
+    /// Whether the proposer stamps its proposals with the local clock.
+    /// The stamped proposals are encoded in a longer list, which the nodes of older versions
+    /// reject, so enable it after all the nodes upgrade.
+    pub proposal_timestamp: bool,
+    /// The max drift between the timestamp of a proposal and the local clock, in milliseconds.
+    pub max_clock_drift_millis: u64,
+    /// What the node precommits when the verification of its locked block times out.
+    pub nil_vote_policy: NilVotePolicy,
+    /// How long a pending `sign_async` request waits for its signature, in milliseconds.
+    pub sign_timeout_millis: u64,
+    /// The max round of a height the node goes to on its own timeouts before it reports
+    /// `RoundExhausted`, once a height. The node still goes on to the later rounds, whose
+    /// timeouts are saturated, so the nodes can agree at one of them. Nothing is reported if it
+    /// is `None`.
+    pub max_round: Option<Round>,
+    /// Whether the own votes are audited against the locking rules: a prevote never conflicts
+    /// with the last own precommit unless a later PoLC is seen, and a precommit always has a
+    /// PoLC. A violating vote is reported as `LockViolation` and replaced by a nil one.
+    pub lock_audit: bool,
+    /// How the proposer of a round is selected.
+    pub proposer_selection: ProposerSelection,
+    /// The count of the latest committed heights whose proposers are tracked for
+    /// `BftActuator::proposer_shares`, 0 disables the tracking.
+    pub proposer_window: usize,
+    /// The max difference between the actual and the expected share of a proposer when the
+    /// window is full, beyond which `on_proposer_deviation` of the support is called. The
+    /// shares are not checked if it is `None`.
+    pub proposer_share_tolerance: Option<f64>,
+    /// The local clock the timers and the proposal timestamps are taken by, `SystemClock` is
+    /// used if it is `None`.
+    pub clock: Option<Arc<dyn Clock>>,
+    /// The store of the proofs of each height, `MemoryProofStore` of the
+    /// [`proof_store_capacity`] heights is used if it is `None`.
+    pub proof_store: Option<Arc<dyn ProofStore>>,
+    /// The count of the latest heights whose proofs are kept by the default proof store.
+    pub proof_store_capacity: usize,
+    /// The retention of the wal records of the past heights.
+    pub wal_retention: WalRetention,
+    /// When the wal records are synced to the disk.
+    pub wal_sync: WalSyncPolicy,
+    /// The size a wal segment is rotated at, in bytes.
+    pub wal_segment_size: u64,
+    /// The target the instance logs under, which tells the instances of one process apart
+    /// in the log filters, `bft_rs` is used if it is `None`.
+    pub log_target: Option<String>,
+    /// The runtime whose timer thread and workers run the instance, shared with the other
+    /// instances of the process. The instance spawns its own timer thread and main thread if
+    /// it is `None`.
+    pub runtime: Option<Arc<BftRuntime>>,
+    /// The tokio runtime the instance runs on as a task, set by `BftActuator::spawn_on`.
+    #[cfg(feature = "tokio")]
+    pub tokio_handle: Option<tokio::runtime::Handle>,
+}
+
+impl Default for BftConfig {
+    fn default() -> Self {
+        BftConfig {
+            channel_capacity: DEFAULT_CHANNEL_CAPACITY,
+            msg_priority: false,
+            cache_bytes_cap: 0,
+            block_spill_threshold: 0,
+            overflow_policy: OverflowPolicy::Block,
+            msg_rate: DEFAULT_MSG_RATE,
+            msg_burst: DEFAULT_MSG_BURST,
+            sig_fail_threshold: DEFAULT_SIG_FAIL_THRESHOLD,
+            sig_fail_ban_millis: DEFAULT_SIG_FAIL_BAN_MILLIS,
+            max_lock_votes: DEFAULT_MAX_LOCK_VOTES,
+            max_precommit_votes: DEFAULT_MAX_PRECOMMIT_VOTES,
+            max_authorities: DEFAULT_MAX_AUTHORITIES,
+            max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+            transmit_retry_times: DEFAULT_TRANSMIT_RETRY_TIMES,
+            transmit_retry_millis: DEFAULT_TRANSMIT_RETRY_MILLIS,
+            transmit_retry_capacity: DEFAULT_TRANSMIT_RETRY_CAPACITY,
+            commit_retry_times: DEFAULT_COMMIT_RETRY_TIMES,
+            commit_retry_millis: DEFAULT_COMMIT_RETRY_MILLIS,
+            commit_retry_max_millis: DEFAULT_COMMIT_RETRY_MAX_MILLIS,
+            restart_backoff_millis: DEFAULT_RESTART_BACKOFF_MILLIS,
+            restart_max_backoff_millis: DEFAULT_RESTART_MAX_BACKOFF_MILLIS,
+            restart_escalation_times: DEFAULT_RESTART_ESCALATION_TIMES,
+            get_block_retry_times: DEFAULT_GET_BLOCK_RETRY_TIMES,
+            get_block_retry_millis: DEFAULT_GET_BLOCK_RETRY_MILLIS,
+            retain_feed: false,
+            external_tick: false,
+            pipeline_proposal: false,
+            chain_id: DEFAULT_CHAIN_ID,
+            sign_domain_separation: false,
+            rlp_codec: RlpCodec::V1,
+            sig_verify_threads: DEFAULT_SIG_VERIFY_THREADS,
+            speculative_verify_threads: 0,
+            sig_cache_capacity: DEFAULT_SIG_CACHE_CAPACITY,
+            block_compression: Compression::None,
+            commit_precommits: false,
+            proposal_timestamp: false,
+            max_clock_drift_millis: DEFAULT_MAX_CLOCK_DRIFT_MILLIS,
+            nil_vote_policy: NilVotePolicy::PrecommitNil,
+            sign_timeout_millis: DEFAULT_SIGN_TIMEOUT_MILLIS,
+            max_round: None,
+            lock_audit: false,
+            proposer_selection: ProposerSelection::Weighted,
+            proposer_window: DEFAULT_PROPOSER_WINDOW,
+            proposer_share_tolerance: None,
+            clock: None,
+            proof_store: None,
+            proof_store_capacity: DEFAULT_PROOF_STORE_CAPACITY,
+            wal_retention: WalRetention::default(),
+            wal_sync: WalSyncPolicy::Never,
+            wal_segment_size: DEFAULT_WAL_SEGMENT_SIZE,
+            log_target: None,
+            runtime: None,
+            #[cfg(feature = "tokio")]
+            tokio_handle: None,
+        }
+    }
+}
+
+impl BftConfig {
+    /// A function to get the scheme the proposals and the votes are signed by, of the
+    /// `rlp_codec` and of the `chain_id` as the domain with `sign_domain_separation` set, which
+    /// light clients pass to `check_proof`.
+    pub fn sign_scheme(&self) -> SignScheme {
+        SignScheme {
+            codec: self.rlp_codec,
+            domain: if self.sign_domain_separation {
+                Some(self.chain_id)
+            } else {
+                None
+            },
+        }
+    }
+
+    /// A function to get the target the instance logs under.
+    pub(crate) fn log_target(&self) -> String {
+        self.log_target
+            .clone()
+            .unwrap_or_else(|| DEFAULT_LOG_TARGET.to_string())
+    }
+}
+
 /// BFT params.
 #[derive(Clone, Debug)]
 pub(crate) struct BftParams {
@@ -10,14 +382,33 @@ pub(crate) struct BftParams {
     pub(crate) address: Address,
     /// A set of BFT timer settings.
     pub(crate) timer: BftTimer,
+    /// The user-defined configs.
+    pub(crate) config: BftConfig,
+    /// The target the instance logs under.
+    pub(crate) log_target: String,
 }
 
 impl BftParams {
     /// A function to create a new BFT params.
-    pub(crate) fn new(local_address: Address) -> Self {
+    pub(crate) fn new(local_address: Address, config: BftConfig) -> Self {
         BftParams {
             address: local_address,
             timer: BftTimer::default(),
+            log_target: config.log_target(),
+            config,
+        }
+    }
+
+    /// A function to apply the consensus params of a status, the ones left `None` are kept.
+    pub(crate) fn apply(&mut self, params: &ConsensusParams) {
+        if let Some(timeouts) = &params.timeouts {
+            self.timer.set_timeouts(timeouts);
+        }
+        if let Some(cache_bytes_cap) = params.cache_bytes_cap {
+            self.config.cache_bytes_cap = cache_bytes_cap as usize;
+        }
+        if let Some(nil_vote_policy) = params.nil_vote_policy {
+            self.config.nil_vote_policy = nil_vote_policy;
         }
     }
 }
@@ -50,19 +441,32 @@ impl BftTimer {
         self.total_duration.set(duration);
     }
 
+    /// A function to set the fractions of the interval the steps wait.
+    pub(crate) fn set_timeouts(&mut self, timeouts: &TimeoutParams) {
+        self.propose = timeouts.propose;
+        self.prevote = timeouts.prevote;
+        self.precommit = timeouts.precommit;
+    }
+
     /// A function to get propose wait duration.
     pub(crate) fn get_propose(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.propose.0 / self.propose.1)
+        self.get_fraction(self.propose)
     }
 
     /// A function to get prevote wait duration.
     pub(crate) fn get_prevote(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.prevote.0 / self.prevote.1)
+        self.get_fraction(self.prevote)
     }
 
     /// A function to get precommit wait duration.
     pub(crate) fn get_precommit(&self) -> Duration {
-        Duration::from_millis(self.total_duration.get() * self.precommit.0 / self.precommit.1)
+        self.get_fraction(self.precommit)
+    }
+
+    /// A function to get the fraction of the interval, saturated at `MAX_TIMEOUT`.
+    fn get_fraction(&self, (numerator, denominator): (u64, u64)) -> Duration {
+        let millis = self.total_duration.get().saturating_mul(numerator) / denominator;
+        Duration::from_millis(millis).min(MAX_TIMEOUT)
     }
 
     #[cfg(not(feature = "machine_gun"))]
@@ -0,0 +1,115 @@
+use crate::error::{BftError, BftResult, ErrorInfo, MsgKind};
+use crate::{Height, Proof};
+
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A height-indexed archive of the proofs the state machine generates or receives.
+/// It is shared by the state machine and `BftActuator::get_proof`, so it takes `&self`.
+pub trait ProofStore: Debug + Send + Sync {
+    /// A function to store the [`proof`] of its height, replacing the stored one.
+    fn put_proof(&self, proof: &Proof) -> BftResult<()>;
+    /// A function to get the proof of the [`height`], `None` if it is not stored.
+    fn get_proof(&self, height: Height) -> BftResult<Option<Proof>>;
+}
+
+/// The default proof store, which keeps the proofs of the last `capacity` heights in memory.
+#[derive(Debug)]
+pub struct MemoryProofStore {
+    capacity: usize,
+    proofs: Mutex<BTreeMap<Height, Proof>>,
+}
+
+impl MemoryProofStore {
+    /// A function to create a memory proof store of the [`capacity`] heights.
+    pub fn new(capacity: usize) -> Self {
+        MemoryProofStore {
+            capacity,
+            proofs: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl ProofStore for MemoryProofStore {
+    fn put_proof(&self, proof: &Proof) -> BftResult<()> {
+        let mut proofs = self.proofs.lock().map_err(|e| {
+            BftError::ProofStoreErr(ErrorInfo::new(format!("{:?}", e)).height(proof.height))
+        })?;
+        proofs.insert(proof.height, proof.clone());
+        while proofs.len() > self.capacity {
+            let lowest = *proofs.keys().next().unwrap();
+            proofs.remove(&lowest);
+        }
+        Ok(())
+    }
+
+    fn get_proof(&self, height: Height) -> BftResult<Option<Proof>> {
+        let proofs = self.proofs.lock().map_err(|e| {
+            BftError::ProofStoreErr(ErrorInfo::new(format!("{:?}", e)).height(height))
+        })?;
+        Ok(proofs.get(&height).cloned())
+    }
+}
+
+/// A proof store keeping the rlp encoded proof of each height in a file of the directory.
+#[derive(Debug)]
+pub struct FileProofStore {
+    dir: PathBuf,
+}
+
+impl FileProofStore {
+    /// A function to open the proof store in the [`dir`], which is created if it does not exist.
+    pub fn new(dir: &str) -> Result<Self, io::Error> {
+        DirBuilder::new().recursive(true).create(dir)?;
+        Ok(FileProofStore {
+            dir: PathBuf::from(dir),
+        })
+    }
+
+    fn proof_path(&self, height: Height) -> PathBuf {
+        self.dir.join(format!("{}.proof", height))
+    }
+}
+
+impl ProofStore for FileProofStore {
+    fn put_proof(&self, proof: &Proof) -> BftResult<()> {
+        let path = self.proof_path(proof.height);
+        // write a temporary file first, so a crash never leaves a partial proof
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, rlp::encode(proof))
+            .and_then(|_| fs::rename(&tmp_path, &path))
+            .map_err(|e| {
+                BftError::ProofStoreErr(
+                    ErrorInfo::new(format!("{:?} of {:?}", e, path))
+                        .height(proof.height)
+                        .kind(MsgKind::Proof),
+                )
+            })
+    }
+
+    fn get_proof(&self, height: Height) -> BftResult<Option<Proof>> {
+        let path = self.proof_path(height);
+        let encode = match fs::read(&path) {
+            Ok(encode) => encode,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => {
+                return Err(BftError::ProofStoreErr(
+                    ErrorInfo::new(format!("{:?} of {:?}", e, path))
+                        .height(height)
+                        .kind(MsgKind::Proof),
+                ))
+            }
+        };
+        rlp::decode(&encode).map(Some).map_err(|e| {
+            BftError::DecodeErr(
+                ErrorInfo::new(format!("{:?} of {:?}", e, path))
+                    .height(height)
+                    .kind(MsgKind::Proof),
+            )
+        })
+    }
+}
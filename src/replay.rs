@@ -0,0 +1,375 @@
+use crate::algorithm::{Bft, INIT_HEIGHT};
+use crate::codec::RlpCodec;
+use crate::error::{BftError, BftResult};
+use crate::objects::LogType;
+use crate::params::BftConfig;
+use crate::wal::Wal;
+use crate::*;
+
+use crate::timer::TimeoutInfo;
+use crossbeam::crossbeam_channel::{unbounded, Receiver};
+use min_max_heap::MinMaxHeap;
+use std::collections::VecDeque;
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::AtomicU64;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The progress of replaying the wal, reported after each record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayProgress {
+    /// the count of the replayed records
+    pub replayed: usize,
+    /// the count of the records to be replayed
+    pub total: usize,
+    /// the height of the last replayed record
+    pub height: Height,
+    /// the log type of the last replayed record
+    pub log_type: LogType,
+}
+
+/// A wal record failing to be replayed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReplayFailure {
+    /// the index of the record in the replayed records
+    pub index: usize,
+    /// the height of the record
+    pub height: Height,
+    /// the log type of the record
+    pub log_type: LogType,
+    /// the error the record encounters
+    pub error: BftError,
+}
+
+/// The result of replaying the wal.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// the count of the replayed records
+    pub total: usize,
+    /// the records failing to be replayed
+    pub failures: Vec<ReplayFailure>,
+}
+
+/// The support of a replay, which checks the blocks and the signatures by the user's support
+/// and stubs out the side effects.
+struct DryRunSupport<T> {
+    support: Arc<T>,
+}
+
+impl<T, B> BftSupport<B> for DryRunSupport<T>
+where
+    T: BftSupport<B>,
+    B: BlockPayload,
+{
+    type Error = String;
+
+    fn check_block(
+        &self,
+        block: &B,
+        block_hash: &Hash,
+        signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        is_lock: bool,
+        proposer: &Address,
+    ) -> Result<VerifyResp, Self::Error> {
+        self.support
+            .check_block(
+                block,
+                block_hash,
+                signed_proposal_hash,
+                height_round,
+                is_lock,
+                proposer,
+            )
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn transmit(&self, _outbound: Outbound) -> Result<(), Self::Error> {
+        Err("transmit is stubbed out in a replay".to_string())
+    }
+
+    fn commit(&self, _commit: Commit<B>) -> Result<Option<Status>, Self::Error> {
+        Err("commit is stubbed out in a replay".to_string())
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(B, Hash), Self::Error> {
+        Err("get_block is stubbed out in a replay".to_string())
+    }
+
+    fn sign(&self, hash: &Hash) -> Result<Signature, Self::Error> {
+        self.support.sign(hash).map_err(|e| format!("{:?}", e))
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, Self::Error> {
+        self.support
+            .check_sig(signature, hash)
+            .map_err(|e| format!("{:?}", e))
+    }
+
+    fn check_sigs(&self, batch: &[(Signature, Hash)]) -> Vec<Option<Address>> {
+        self.support.check_sigs(batch)
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        self.support.crypt_hash(msg)
+    }
+}
+
+/// A state machine of the stub support restored from a copy of a wal, with the channels it
+/// outputs to. The copy is removed on drop.
+struct Replica<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    engine: Bft<DryRunSupport<T>, B>,
+    timer_receiver: Receiver<TimeoutInfo>,
+    outbox: Receiver<Outbound>,
+    copy_path: PathBuf,
+}
+
+impl<T, B> Replica<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    fn open(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+    ) -> BftResult<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        let copy_path = env::temp_dir().join(format!("bft-replay-{}-{}", process::id(), nanos));
+        if let Err(e) = copy_dir(Path::new(wal_path), &copy_path) {
+            let _ = fs::remove_dir_all(&copy_path);
+            return Err(BftError::InitWalErr(
+                format!("{} encounters {:?}", wal_path, e).into(),
+            ));
+        }
+
+        let copy_str = copy_path.to_string_lossy().to_string();
+        // the proofs of the replica are not archived, the signatures and the blocks are
+        // checked in place without the verifier pools, and the stubbed out commits and
+        // get_blocks are not re-attempted
+        let config = BftConfig {
+            proof_store: None,
+            sig_verify_threads: 0,
+            speculative_verify_threads: 0,
+            commit_retry_times: 0,
+            get_block_retry_times: 0,
+            ..config
+        };
+        let wal_log = match Wal::new(&copy_str, &config) {
+            Ok(wal_log) => wal_log,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&copy_path);
+                return Err(BftError::InitWalErr(
+                    format!("{} encounters {:?}", copy_str, e).into(),
+                ));
+            }
+        };
+        let (msg_sender, msg_receiver) = unbounded();
+        let (timer_sender, timer_receiver) = unbounded();
+        let (outbox_sender, outbox) = unbounded();
+        let engine = Bft::new(
+            msg_sender,
+            msg_receiver,
+            timer_sender.into(),
+            timer_receiver.clone(),
+            Arc::new(DryRunSupport { support }),
+            address,
+            wal_log,
+            config,
+            Arc::new(AtomicU64::new(INIT_HEIGHT)),
+            Some(outbox_sender),
+            None,
+        );
+        Ok(Replica {
+            engine,
+            timer_receiver,
+            outbox,
+            copy_path,
+        })
+    }
+}
+
+impl<T, B> Drop for Replica<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.copy_path);
+    }
+}
+
+/// A function to replay a copy of the wal in the [`wal_path`] without starting consensus.
+pub(crate) fn dry_run_replay<T, B>(
+    support: Arc<T>,
+    address: Address,
+    wal_path: &str,
+    config: BftConfig,
+) -> BftResult<ReplayReport>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    let mut replica = Replica::<T, B>::open(support, address, wal_path, config)?;
+    Ok(replica.engine.load_wal_log())
+}
+
+/// A step of the replay debugger.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum ReplayStep<B: BlockPayload = Block> {
+    /// A wal record with its height, log type and codec.
+    Wal(Height, LogType, RlpCodec, Vec<u8>),
+    /// An inbound msg of the captured trace.
+    Msg(BftMsg<B>),
+}
+
+/// A debugger feeding a recorded wal and a captured trace of inbound msgs through a fresh
+/// state machine step by step, to reproduce a run exactly. The state machine runs on a copy
+/// of the wal with the checks of the user's support, its `commit` and `get_block` are stubbed
+/// out and its outbound msgs are returned by the steps instead of transmitted. The timers are
+/// not fired by themselves but by `fire_timer`, so the caller decides when they are due.
+pub struct ReplayDebugger<T, B: BlockPayload = Block>
+where
+    T: BftSupport<B> + 'static,
+{
+    replica: Replica<T, B>,
+    steps: VecDeque<ReplayStep<B>>,
+    timers: MinMaxHeap<TimeoutInfo>,
+    stepped: usize,
+}
+
+impl<T, B> ReplayDebugger<T, B>
+where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    /// A function to create a debugger of the wal in the [`wal_path`] followed by the inbound
+    /// msgs of the [`trace`], which are the msgs the state machine receives.
+    /// The [`support`] checks the blocks and the signatures.
+    pub fn new(
+        support: Arc<T>,
+        address: Address,
+        wal_path: &str,
+        config: BftConfig,
+        trace: Vec<BftMsg<B>>,
+    ) -> BftResult<Self> {
+        let mut replica = Replica::open(support, address, wal_path, config)?;
+        let mut steps: VecDeque<ReplayStep<B>> = replica
+            .engine
+            .wal_log
+            .load()
+            .into_iter()
+            .map(|(height, log_type, codec, encode)| {
+                ReplayStep::Wal(height, log_type, codec, encode)
+            })
+            .collect();
+        steps.extend(trace.into_iter().map(ReplayStep::Msg));
+        Ok(ReplayDebugger {
+            replica,
+            steps,
+            timers: MinMaxHeap::new(),
+            stepped: 0,
+        })
+    }
+
+    /// A function to feed the next step, return the outbound msgs it produces.
+    /// Return `None` if all the steps are fed.
+    pub fn step(&mut self) -> Option<BftResult<Vec<Outbound>>> {
+        let step = self.steps.pop_front()?;
+        let engine = &mut self.replica.engine;
+        let result = match step {
+            ReplayStep::Wal(_, log_type, codec, encode) => {
+                engine.process_wal_log(log_type, codec, encode)
+            }
+            ReplayStep::Msg(msg) => engine.process(msg, true),
+        };
+        self.stepped += 1;
+        Some(result.map(|_| self.outbound()))
+    }
+
+    /// A function to feed the steps until the state machine reaches the [`height`] or all the
+    /// steps are fed, return the errors of the steps.
+    pub fn run_to_height(&mut self, height: Height) -> Vec<BftError> {
+        let mut errors = Vec::new();
+        while self.height() < height {
+            match self.step() {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => errors.push(e),
+                None => break,
+            }
+        }
+        errors
+    }
+
+    /// A function to fire the earliest timer the state machine has set, return the outbound
+    /// msgs it produces. Return `None` if no timer is set.
+    pub fn fire_timer(&mut self) -> Option<BftResult<Vec<Outbound>>> {
+        for timeout_info in self.replica.timer_receiver.try_iter() {
+            self.timers.push(timeout_info);
+        }
+        let timeout_info = self.timers.pop_min()?;
+        let result = self.replica.engine.timeout_process(timeout_info, true);
+        Some(result.map(|_| self.outbound()))
+    }
+
+    /// A function to peek the next step.
+    pub fn peek(&self) -> Option<&ReplayStep<B>> {
+        self.steps.front()
+    }
+
+    /// A function to get the count of the fed steps.
+    pub fn stepped(&self) -> usize {
+        self.stepped
+    }
+
+    /// A function to get the count of the steps to be fed.
+    pub fn remaining(&self) -> usize {
+        self.steps.len()
+    }
+
+    /// A function to get the height of the state machine.
+    pub fn height(&self) -> Height {
+        self.replica.engine.height
+    }
+
+    /// A function to get the round of the state machine.
+    pub fn round(&self) -> Round {
+        self.replica.engine.round
+    }
+
+    /// A function to dump the internal state of the state machine in JSON, the same as
+    /// `BftActuator::debug_dump`.
+    pub fn dump(&mut self) -> String {
+        self.replica.engine.debug_dump()
+    }
+
+    fn outbound(&self) -> Vec<Outbound> {
+        self.replica.outbox.try_iter().collect()
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<(), io::Error> {
+    DirBuilder::new().recursive(true).create(to)?;
+    for entry in fs::read_dir(from)? {
+        let path = entry?.path();
+        if let Some(name) = path.file_name() {
+            if path.is_file() {
+                fs::copy(&path, to.join(name))?;
+            }
+        }
+    }
+    Ok(())
+}
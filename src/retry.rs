@@ -0,0 +1,98 @@
+use crate::{Height, Outbound};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// A msg waiting to be transmitted again.
+#[derive(Debug, Clone)]
+pub(crate) struct PendingMsg {
+    pub(crate) outbound: Outbound,
+    pub(crate) height: Option<Height>,
+    attempts: u32,
+    next_try: Instant,
+}
+
+/// A queue of failed transmissions, re-attempted with exponential backoff.
+#[derive(Debug)]
+pub(crate) struct RetryQueue {
+    /// The max count of re-attempts of a msg, 0 disables retrying.
+    max_attempts: u32,
+    /// The backoff before the first re-attempt, doubled after each failure.
+    base_backoff: Duration,
+    capacity: usize,
+    pending: VecDeque<PendingMsg>,
+}
+
+impl RetryQueue {
+    /// A function to create a new retry queue.
+    pub(crate) fn new(max_attempts: u32, base_backoff: Duration, capacity: usize) -> Self {
+        RetryQueue {
+            max_attempts,
+            base_backoff,
+            capacity,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// A function to queue a msg failed to transmit for the first time,
+    /// return `false` if it is dropped.
+    pub(crate) fn push(&mut self, outbound: Outbound, height: Option<Height>) -> bool {
+        self.push_pending(PendingMsg {
+            outbound,
+            height,
+            attempts: 0,
+            next_try: Instant::now(),
+        })
+    }
+
+    /// A function to queue a msg failed to transmit again, return `false` if it is dropped.
+    pub(crate) fn retry(&mut self, pending: PendingMsg) -> bool {
+        self.push_pending(pending)
+    }
+
+    fn push_pending(&mut self, mut pending: PendingMsg) -> bool {
+        if pending.attempts >= self.max_attempts {
+            return false;
+        }
+        if self.pending.len() >= self.capacity {
+            // the oldest msg is the most likely to be obsolete
+            self.pending.pop_front();
+        }
+        let backoff = self
+            .base_backoff
+            .checked_mul(2u32.saturating_pow(pending.attempts))
+            .unwrap_or(self.base_backoff);
+        pending.attempts += 1;
+        pending.next_try = Instant::now() + backoff;
+        self.pending.push_back(pending);
+        true
+    }
+
+    /// A function to take out the msgs due to transmit, msgs below the [`min_height`] are dropped.
+    pub(crate) fn pop_due(&mut self, min_height: Height) -> Vec<PendingMsg> {
+        let now = Instant::now();
+        let mut due = Vec::new();
+        let mut rest = VecDeque::with_capacity(self.pending.len());
+        for pending in self.pending.drain(..) {
+            if pending.height.is_some_and(|height| height < min_height) {
+                continue;
+            }
+            if pending.next_try <= now {
+                due.push(pending);
+            } else {
+                rest.push_back(pending);
+            }
+        }
+        self.pending = rest;
+        due
+    }
+
+    /// The duration until the next msg is due, None if the queue is empty.
+    pub(crate) fn next_due(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.pending
+            .iter()
+            .map(|pending| pending.next_try.saturating_duration_since(now))
+            .min()
+    }
+}
@@ -0,0 +1,139 @@
+use crate::params::DEFAULT_LOG_TARGET;
+use crate::timer::{run_timer, SharedTimeout, TimeoutInfo, TimerSetter};
+
+use crossbeam::crossbeam_channel::{
+    unbounded, Receiver, Select, Sender, TryRecvError, TrySendError,
+};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+/// The longest a worker waits without any msg, timer or task due.
+const IDLE_WAIT: Duration = Duration::from_secs(100);
+
+/// A state machine run by a worker of the runtime.
+pub(crate) trait Task {
+    /// A function to register the channels the task waits on.
+    fn register<'a>(&'a self, sel: &mut Select<'a>);
+    /// A function to get the duration until the task is due without any msg.
+    fn next_due(&self) -> Option<Duration>;
+    /// A function to process what is ready without blocking, false once the task ends.
+    fn poll(&mut self) -> bool;
+}
+
+/// The job of a worker to create a task on its thread.
+pub(crate) type Job = Box<dyn FnOnce() -> Box<dyn Task> + Send>;
+
+/// A runtime shared by the actuators of a process instead of the timer thread and the main
+/// thread each of them spawns. The timers of all the actuators are taken by one timer
+/// thread, and their main loops are run by a bounded pool of workers, each actuator by the
+/// same worker through its life. The actuators are set to the runtime by
+/// `BftConfig::runtime`. The threads of the signature and the block verifiers are not
+/// shared.
+pub struct BftRuntime {
+    timer: Sender<SharedTimeout>,
+    workers: Vec<Sender<Job>>,
+    next_worker: AtomicUsize,
+}
+
+impl BftRuntime {
+    /// A function to start a runtime of one timer thread and the [`worker_threads`] workers,
+    /// at least one.
+    pub fn new(worker_threads: usize) -> Self {
+        let (timer, timer_seter) = unbounded::<SharedTimeout>();
+        thread::Builder::new()
+            .name("bft_shared_timer".to_string())
+            .spawn(move || {
+                run_timer(&timer_seter, DEFAULT_LOG_TARGET, |timeout| {
+                    let SharedTimeout { info, notify } = timeout;
+                    match notify.try_send(info) {
+                        Err(TrySendError::Full(info)) => {
+                            Err(TrySendError::Full(SharedTimeout { info, notify }))
+                        }
+                        // the timers of a killed state machine are dropped
+                        _ => Ok(()),
+                    }
+                });
+            })
+            .unwrap_or_else(|_| panic!("starts the shared timer thread failed!"));
+        let workers = (0..worker_threads.max(1))
+            .map(|index| {
+                let (job_sender, job_receiver) = unbounded::<Job>();
+                thread::Builder::new()
+                    .name(format!("bft_worker_{}", index))
+                    .spawn(move || work(job_receiver))
+                    .unwrap_or_else(|_| panic!("starts the worker thread {} failed!", index));
+                job_sender
+            })
+            .collect();
+        BftRuntime {
+            timer,
+            workers,
+            next_worker: AtomicUsize::new(0),
+        }
+    }
+
+    /// A function to get the count of the workers.
+    pub fn worker_threads(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// A function to get the setter of the shared timer, whose timers are notified to the
+    /// [`notify`].
+    pub(crate) fn timer_setter(&self, notify: Sender<TimeoutInfo>) -> TimerSetter {
+        TimerSetter::Shared {
+            timer: self.timer.clone(),
+            notify,
+        }
+    }
+
+    /// A function to run the task created by the [`job`] on the next worker in turn.
+    pub(crate) fn spawn(&self, job: Job) {
+        let index = self.next_worker.fetch_add(1, Ordering::SeqCst) % self.workers.len();
+        self.workers[index]
+            .send(job)
+            .unwrap_or_else(|_| panic!("the worker thread {} is down!", index));
+    }
+}
+
+impl Debug for BftRuntime {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("BftRuntime")
+            .field("worker_threads", &self.workers.len())
+            .finish()
+    }
+}
+
+/// The loop of a worker, which waits on the channels of all its tasks and polls them. It
+/// ends when the runtime is dropped and all its tasks end.
+fn work(job_receiver: Receiver<Job>) {
+    let mut job_receiver = Some(job_receiver);
+    let mut tasks: Vec<Box<dyn Task>> = Vec::new();
+    loop {
+        let due = tasks
+            .iter()
+            .filter_map(|task| task.next_due())
+            .min()
+            .unwrap_or(IDLE_WAIT);
+        {
+            let mut sel = Select::new();
+            if let Some(job_receiver) = job_receiver.as_ref() {
+                sel.recv(job_receiver);
+            }
+            tasks.iter().for_each(|task| task.register(&mut sel));
+            let _ = sel.ready_timeout(due);
+        }
+        if let Some(receiver) = job_receiver.as_ref() {
+            match receiver.try_recv() {
+                Ok(job) => tasks.push(job()),
+                Err(TryRecvError::Disconnected) => job_receiver = None,
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+        tasks.retain_mut(|task| task.poll());
+        if job_receiver.is_none() && tasks.is_empty() {
+            break;
+        }
+    }
+}
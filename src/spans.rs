@@ -0,0 +1,54 @@
+use crate::objects::Step;
+use crate::{Address, Height, Round};
+
+use tracing::{info_span, Span};
+
+/// The spans of the consensus progress: a height span contains its round spans, which contain
+/// their step spans. A span is closed after the consensus leaves it.
+pub(crate) struct ConsensusSpans {
+    height: Option<(Height, Span)>,
+    round: Option<(Round, Span)>,
+    step: Option<(Step, Span)>,
+}
+
+impl ConsensusSpans {
+    pub(crate) fn new() -> Self {
+        ConsensusSpans {
+            height: None,
+            round: None,
+            step: None,
+        }
+    }
+
+    /// A function to open the spans of the height, the round and the step if they change.
+    pub(crate) fn update(&mut self, address: &Address, height: Height, round: Round, step: Step) {
+        if self.height.as_ref().map(|(h, _)| *h) != Some(height) {
+            let span = info_span!("height", node = ?address, height);
+            self.height = Some((height, span));
+            self.round = None;
+        }
+        if self.round.as_ref().map(|(r, _)| *r) != Some(round) {
+            let span = match &self.height {
+                Some((_, parent)) => info_span!(parent: parent, "round", round),
+                None => info_span!("round", round),
+            };
+            self.round = Some((round, span));
+            self.step = None;
+        }
+        if self.step.as_ref().map(|(s, _)| *s) != Some(step) {
+            let span = match &self.round {
+                Some((_, parent)) => info_span!(parent: parent, "step", step = ?step),
+                None => info_span!("step", step = ?step),
+            };
+            self.step = Some((step, span));
+        }
+    }
+
+    /// A function to get the span of the current step, which the msgs are processed in.
+    pub(crate) fn current(&self) -> Span {
+        match &self.step {
+            Some((_, span)) => span.clone(),
+            None => Span::none(),
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::objects::{Step, VoteType};
+use crate::{Address, CommitStats, Round};
+
+use std::time::{Duration, Instant};
+
+/// The recorder of the consensus metrics of the current height.
+#[derive(Clone, Debug)]
+pub(crate) struct StatsRecorder {
+    step_since: Instant,
+    propose: Duration,
+    prevote: Duration,
+    verify: Duration,
+    precommit: Duration,
+    prevotes: u64,
+    precommits: u64,
+}
+
+impl StatsRecorder {
+    pub(crate) fn new() -> Self {
+        StatsRecorder {
+            step_since: Instant::now(),
+            propose: Duration::default(),
+            prevote: Duration::default(),
+            verify: Duration::default(),
+            precommit: Duration::default(),
+            prevotes: 0,
+            precommits: 0,
+        }
+    }
+
+    /// A function to start recording a new height.
+    pub(crate) fn reset(&mut self) {
+        *self = StatsRecorder::new();
+    }
+
+    /// A function to add the time spent in the step being left.
+    pub(crate) fn leave_step(&mut self, step: Step) {
+        let now = Instant::now();
+        let spent = now - self.step_since;
+        self.step_since = now;
+        match step {
+            Step::Propose | Step::ProposeWait => self.propose += spent,
+            Step::Prevote | Step::PrevoteWait => self.prevote += spent,
+            #[cfg(feature = "verify_req")]
+            Step::VerifyWait => self.verify += spent,
+            Step::Precommit | Step::PrecommitWait => self.precommit += spent,
+            Step::Commit | Step::CommitWait => {}
+        }
+    }
+
+    /// A function to count a vote of the current height.
+    pub(crate) fn receive_vote(&mut self, vote_type: &VoteType) {
+        match vote_type {
+            VoteType::Prevote => self.prevotes += 1,
+            VoteType::Precommit => self.precommits += 1,
+        }
+    }
+
+    /// A function to build the metrics of the height committed at the round.
+    pub(crate) fn build(&self, round: Round, proposers: Vec<Address>) -> CommitStats {
+        CommitStats {
+            rounds: round.saturating_add(1),
+            propose_duration: self.propose,
+            prevote_duration: self.prevote,
+            verify_duration: self.verify,
+            precommit_duration: self.precommit,
+            prevotes: self.prevotes,
+            precommits: self.precommits,
+            proposers,
+        }
+    }
+}
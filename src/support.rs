@@ -0,0 +1,178 @@
+use crate::*;
+
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{All, Message, PublicKey, Secp256k1, SecretKey};
+use std::sync::Mutex;
+use tiny_keccak::{Hasher, Keccak};
+
+const SIGNATURE_LEN: usize = 65;
+
+/// The errors of `SimpleSupport`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimpleSupportError {
+    /// The secret key is not a valid secp256k1 secret key.
+    InvalidKey,
+    /// The signature is malformed or recovers no public key.
+    InvalidSignature(String),
+    /// The support is poisoned by a panic.
+    Poisoned,
+}
+
+/// A reference implementation of `BftSupport`, which signs by secp256k1 with recoverable
+/// signatures, hashes by keccak256 and derives the addresses from the public keys as Ethereum
+/// does. It accepts every block, feeds a block of the height bytes, and returns the status of
+/// its authority list on each commit. Replace the block parts with the ones of the chain when
+/// taking it as a template, and create the actuator by `BftActuator::new_with_outbox` to
+/// transmit the outbound msgs.
+pub struct SimpleSupport {
+    secp: Secp256k1<All>,
+    secret_key: SecretKey,
+    address: Address,
+    authority_list: Vec<Node>,
+    interval: Option<u64>,
+    commits: Mutex<Vec<(Height, Hash)>>,
+}
+
+impl SimpleSupport {
+    /// A function to create a support signing by the 32 bytes [`secret_key`], whose status of
+    /// each height has the [`authority_list`] and the [`interval`].
+    pub fn new(
+        secret_key: &[u8],
+        authority_list: Vec<Node>,
+        interval: Option<u64>,
+    ) -> Result<Self, SimpleSupportError> {
+        let secp = Secp256k1::new();
+        let secret_key =
+            SecretKey::from_slice(secret_key).map_err(|_| SimpleSupportError::InvalidKey)?;
+        let address = public_key_address(&PublicKey::from_secret_key(&secp, &secret_key));
+        Ok(SimpleSupport {
+            secp,
+            secret_key,
+            address,
+            authority_list,
+            interval,
+            commits: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// A function to get the address of the secret key.
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// A function to get the heights and the block hashes committed so far.
+    pub fn commits(&self) -> Result<Vec<(Height, Hash)>, SimpleSupportError> {
+        self.commits
+            .lock()
+            .map(|commits| commits.clone())
+            .map_err(|_| SimpleSupportError::Poisoned)
+    }
+}
+
+impl BftSupport for SimpleSupport {
+    type Error = SimpleSupportError;
+
+    fn check_block(
+        &self,
+        _block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, SimpleSupportError> {
+        // check the format, the headers and the txs of the block here
+        Ok(VerifyResp {
+            is_pass: true,
+            round: height_round.1,
+            #[cfg(feature = "compact_block")]
+            complete_block: _block.clone(),
+            failure: None,
+        })
+    }
+
+    fn transmit(&self, _outbound: Outbound) -> Result<(), SimpleSupportError> {
+        // the outbound msgs are taken from the outbox of the actuator
+        Ok(())
+    }
+
+    fn commit(&self, commit: Commit) -> Result<Option<Status>, SimpleSupportError> {
+        // execute the block and add it into the chain here
+        self.commits
+            .lock()
+            .map_err(|_| SimpleSupportError::Poisoned)?
+            .push((commit.height, commit.proof.block_hash));
+        Ok(Some(Status {
+            height: commit.height,
+            interval: self.interval,
+            authority_list: self.authority_list.clone(),
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
+        }))
+    }
+
+    fn get_block(&self, height: Height) -> Result<(Block, Hash), SimpleSupportError> {
+        // pack the txs of the pool here
+        let block: Block = height.to_be_bytes().to_vec().into();
+        let block_hash = keccak256(&block);
+        Ok((block, block_hash))
+    }
+
+    fn sign(&self, hash: &Hash) -> Result<Signature, SimpleSupportError> {
+        let msg = Message::from_digest(hash_bytes(hash));
+        let (recovery_id, compact) = self
+            .secp
+            .sign_ecdsa_recoverable(&msg, &self.secret_key)
+            .serialize_compact();
+        let mut signature = compact.to_vec();
+        signature.push(recovery_id.to_i32() as u8);
+        Ok(signature.into())
+    }
+
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, SimpleSupportError> {
+        if signature.len() != SIGNATURE_LEN {
+            return Err(SimpleSupportError::InvalidSignature(format!(
+                "length {}",
+                signature.len()
+            )));
+        }
+        let invalid = |e: secp256k1::Error| SimpleSupportError::InvalidSignature(e.to_string());
+        let recovery_id =
+            RecoveryId::from_i32(i32::from(signature[SIGNATURE_LEN - 1])).map_err(invalid)?;
+        let signature =
+            RecoverableSignature::from_compact(&signature[..SIGNATURE_LEN - 1], recovery_id)
+                .map_err(invalid)?;
+        let msg = Message::from_digest(hash_bytes(hash));
+        let public_key = self.secp.recover_ecdsa(&msg, &signature).map_err(invalid)?;
+        Ok(public_key_address(&public_key))
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        keccak256(msg)
+    }
+}
+
+/// A function to hash the [`msg`] by keccak256.
+pub fn keccak256(msg: &[u8]) -> Hash {
+    let mut keccak = Keccak::v256();
+    keccak.update(msg);
+    let mut output = [0u8; HASH_LEN];
+    keccak.finalize(&mut output);
+    Hash::from(output)
+}
+
+/// A function to get the address of the [`public_key`], the last 20 bytes of the keccak256 of
+/// its uncompressed encoding without the prefix.
+pub fn public_key_address(public_key: &PublicKey) -> Address {
+    let hash = keccak256(&public_key.serialize_uncompressed()[1..]);
+    let mut address = [0u8; ADDRESS_LEN];
+    address.copy_from_slice(&hash[HASH_LEN - ADDRESS_LEN..]);
+    Address::from(address)
+}
+
+fn hash_bytes(hash: &Hash) -> [u8; HASH_LEN] {
+    let mut bytes = [0u8; HASH_LEN];
+    bytes.copy_from_slice(hash);
+    bytes
+}
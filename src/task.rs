@@ -0,0 +1,95 @@
+use crate::*;
+use crate::{algorithm::Supervised, runtime::Task, timer::TimeoutInfo};
+
+use crossbeam::crossbeam_channel::{Sender, TrySendError};
+use min_max_heap::MinMaxHeap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Handle;
+use tokio::sync::Notify;
+
+/// The longest the task waits without any msg or timer due.
+const IDLE_WAIT: Duration = Duration::from_secs(100);
+
+/// A function to spawn the state machine of the [`task`] on the tokio runtime of the
+/// [`handle`]. Its timers are taken from the [`timers`] by `tokio::time` and notified to the
+/// [`timer_notify`]. The task waits for the [`notify`], which the actuator and the verifier
+/// threads wake after they send to it.
+pub(crate) fn spawn<T, B>(
+    handle: &Handle,
+    task: Supervised<T, B>,
+    timers: Arc<Mutex<MinMaxHeap<TimeoutInfo>>>,
+    timer_notify: Sender<TimeoutInfo>,
+    notify: Arc<Notify>,
+) where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    handle.spawn(run(task, timers, timer_notify, notify));
+}
+
+/// The main loop as a task, which processes what is ready and waits for a wake or the next
+/// timer. The processing blocks on the user-defined functions and the wal, so it runs on the
+/// blocking threads of the runtime instead of the workers. It ends on receiving `Kill`.
+async fn run<T, B>(
+    mut task: Supervised<T, B>,
+    timers: Arc<Mutex<MinMaxHeap<TimeoutInfo>>>,
+    timer_notify: Sender<TimeoutInfo>,
+    notify: Arc<Notify>,
+) where
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
+{
+    loop {
+        let polled = tokio::task::spawn_blocking(move || {
+            let alive = task.poll();
+            (task, alive)
+        })
+        .await;
+        let alive = match polled {
+            Ok((polled, alive)) => {
+                task = polled;
+                alive
+            }
+            // the runtime shuts down
+            Err(_) => return,
+        };
+        if !alive {
+            return;
+        }
+        // the timers set by the poll are counted in
+        let next_timer = notify_due_timers(&timers, &timer_notify);
+        if task.is_ready() {
+            continue;
+        }
+        let now = Instant::now();
+        let wait = next_timer
+            .map(|timestamp| timestamp.saturating_duration_since(now))
+            .into_iter()
+            .chain(task.next_due())
+            .min()
+            .unwrap_or(IDLE_WAIT);
+        let _ = tokio::time::timeout(wait, notify.notified()).await;
+    }
+}
+
+/// A function to notify the due timers, returns the time the next timer is due.
+fn notify_due_timers(
+    timers: &Mutex<MinMaxHeap<TimeoutInfo>>,
+    timer_notify: &Sender<TimeoutInfo>,
+) -> Option<Instant> {
+    let mut timers = timers.lock().unwrap();
+    let now = Instant::now();
+    while let Some(timestamp) = timers.peek_min().map(|timer| timer.timestamp) {
+        if timestamp > now {
+            break;
+        }
+        let timer = timers.pop_min().unwrap();
+        // the notify channel is full, notify the timer after the state machine consumes it
+        if let Err(TrySendError::Full(timer)) = timer_notify.try_send(timer) {
+            timers.push(timer);
+            break;
+        }
+    }
+    timers.peek_min().map(|timer| timer.timestamp)
+}
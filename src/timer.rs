@@ -1,21 +1,28 @@
 use crate::objects::Step;
+use crate::params::DEFAULT_LOG_TARGET;
 use crate::{Height, Round};
 
 use std::cmp::{Ord, Ordering, PartialOrd};
+use std::convert::TryFrom;
+#[cfg(feature = "tokio")]
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::{Duration, Instant};
 
-use crossbeam::crossbeam_channel::{Receiver, Sender};
+use crossbeam::crossbeam_channel::{Receiver, RecvTimeoutError, SendError, Sender, TrySendError};
 #[allow(unused_imports)]
 use log::{log, warn};
 use min_max_heap::MinMaxHeap;
 use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
 
+const NOTIFY_FULL_RETRY_MILLIS: u64 = 10;
+
 /// Timer infomation.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub(crate) struct TimeoutInfo {
     /// A timestamp of a timer.
     pub(crate) timestamp: Instant,
-    ///
+    /// The duration from the height start to the timestamp, in nanoseconds.
     pub(crate) duration: u64,
     /// The height of the timer.
     pub(crate) height: Height,
@@ -27,7 +34,7 @@ pub(crate) struct TimeoutInfo {
 
 impl PartialOrd for TimeoutInfo {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.timestamp.partial_cmp(&other.timestamp)
+        Some(self.cmp(other))
     }
 }
 
@@ -56,7 +63,8 @@ impl Decodable for TimeoutInfo {
                 let height: Height = r.val_at(1)?;
                 let round: Round = r.val_at(2)?;
                 let step: u8 = r.val_at(3)?;
-                let step: Step = Step::from(step);
+                let step =
+                    Step::try_from(step).map_err(|_| DecoderError::Custom("invalid step"))?;
                 Ok(TimeoutInfo {
                     timestamp: Instant::now() + Duration::from_nanos(duration),
                     duration,
@@ -80,6 +88,78 @@ pub trait GetInstant {
     fn get_instant(&self) -> Instant;
 }
 
+/// A timer set to the shared timer of a runtime, notified to the state machine setting it.
+#[derive(Debug, Clone)]
+pub(crate) struct SharedTimeout {
+    pub(crate) info: TimeoutInfo,
+    pub(crate) notify: Sender<TimeoutInfo>,
+}
+
+impl PartialEq for SharedTimeout {
+    fn eq(&self, other: &Self) -> bool {
+        self.info == other.info
+    }
+}
+
+impl Eq for SharedTimeout {}
+
+impl PartialOrd for SharedTimeout {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SharedTimeout {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.info.cmp(&other.info)
+    }
+}
+
+impl GetInstant for SharedTimeout {
+    fn get_instant(&self) -> Instant {
+        self.info.timestamp
+    }
+}
+
+/// The setter of the timers of a state machine, to its own timer thread or to the shared timer
+/// of a runtime.
+#[derive(Debug, Clone)]
+pub(crate) enum TimerSetter {
+    Own(Sender<TimeoutInfo>),
+    Shared {
+        timer: Sender<SharedTimeout>,
+        notify: Sender<TimeoutInfo>,
+    },
+    /// The timers taken by the tokio task running the state machine.
+    #[cfg(feature = "tokio")]
+    Queue(Arc<Mutex<MinMaxHeap<TimeoutInfo>>>),
+}
+
+impl TimerSetter {
+    pub(crate) fn send(&self, info: TimeoutInfo) -> Result<(), SendError<TimeoutInfo>> {
+        match self {
+            TimerSetter::Own(timer) => timer.send(info),
+            TimerSetter::Shared { timer, notify } => timer
+                .send(SharedTimeout {
+                    info,
+                    notify: notify.clone(),
+                })
+                .map_err(|e| SendError(e.0.info)),
+            #[cfg(feature = "tokio")]
+            TimerSetter::Queue(timers) => {
+                timers.lock().unwrap().push(info);
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<Sender<TimeoutInfo>> for TimerSetter {
+    fn from(timer: Sender<TimeoutInfo>) -> Self {
+        TimerSetter::Own(timer)
+    }
+}
+
 /// Sender and receiver of a timeout infomation channel.
 pub struct WaitTimer<T>
 where
@@ -87,6 +167,7 @@ where
 {
     timer_seter: Receiver<T>,
     timer_notify: Sender<T>,
+    log_target: String,
 }
 
 impl<T> WaitTimer<T>
@@ -98,48 +179,86 @@ where
         WaitTimer {
             timer_notify: ts,
             timer_seter: rs,
+            log_target: DEFAULT_LOG_TARGET.to_string(),
         }
     }
 
+    /// A function to set the target the timer logs under.
+    pub fn set_log_target(&mut self, log_target: &str) {
+        self.log_target = log_target.to_string();
+    }
+
     /// A function to start a timer.
     pub fn start(&self) {
-        let mut timer_heap = MinMaxHeap::<T>::new();
-
-        'outer: loop {
-            // take the peek of the min-heap-timer sub now as the sleep time otherwise set timeout as 100
-            let timeout = if !timer_heap.is_empty() {
-                let peek_min_time = timer_heap.peek_min().unwrap().get_instant();
-                let now = Instant::now();
-                if peek_min_time > now {
-                    peek_min_time - now
-                } else {
-                    Duration::new(0, 0)
-                }
-            } else {
-                Duration::from_secs(100)
-            };
+        let timer_notify = &self.timer_notify;
+        run_timer(&self.timer_seter, &self.log_target, |time_info| {
+            timer_notify.try_send(time_info)
+        });
+    }
+}
 
-            let set_time = self.timer_seter.recv_timeout(timeout);
+/// The loop of a timer, which notifies the timers set to the [`timer_seter`] when they are
+/// due by the [`notify`]. A timer failing with `Full` is notified again later, and the loop
+/// ends when the [`notify`] fails with `Disconnected`.
+pub(crate) fn run_timer<T, F>(timer_seter: &Receiver<T>, log_target: &str, mut notify: F)
+where
+    T: Eq + PartialEq + Ord + PartialOrd + Clone + GetInstant,
+    F: FnMut(T) -> Result<(), TrySendError<T>>,
+{
+    let mut timer_heap = MinMaxHeap::<T>::new();
+    let mut notify_full = false;
 
-            // put the timeval into a timerheap
-            // put the TimeoutInfo into a hashmap, K: timeval  V: TimeoutInfo
-            if set_time.is_ok() {
-                let time_out = set_time.unwrap();
-                timer_heap.push(time_out);
+    'outer: loop {
+        // take the peek of the min-heap-timer sub now as the sleep time otherwise set timeout as 100
+        let timeout = if notify_full {
+            // the notify channel is full, wait for the receiver to consume
+            Duration::from_millis(NOTIFY_FULL_RETRY_MILLIS)
+        } else if !timer_heap.is_empty() {
+            let peek_min_time = timer_heap.peek_min().unwrap().get_instant();
+            let now = Instant::now();
+            if peek_min_time > now {
+                peek_min_time - now
+            } else {
+                Duration::new(0, 0)
             }
+        } else {
+            Duration::from_secs(100)
+        };
+
+        // put the timeval into a timerheap
+        // put the TimeoutInfo into a hashmap, K: timeval  V: TimeoutInfo
+        match timer_seter.recv_timeout(timeout) {
+            Ok(time_out) => timer_heap.push(time_out),
+            // nobody sets timers any more
+            Err(RecvTimeoutError::Disconnected) if timer_heap.is_empty() => break,
+            Err(RecvTimeoutError::Disconnected) => thread::sleep(timeout),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
 
-            if !timer_heap.is_empty() {
-                let now = Instant::now();
+        if !timer_heap.is_empty() {
+            let now = Instant::now();
 
-                // if some timers are set as the same time, send timeout messages and pop them
-                while !timer_heap.is_empty()
-                    && now >= timer_heap.peek_min().cloned().unwrap().get_instant()
-                {
-                    let time_info = timer_heap.pop_min().unwrap();
-                    if let Err(e) = self.timer_notify.send(time_info) {
-                        warn!("send time notification failed with {:?}", e);
+            // if some timers are set as the same time, send timeout messages and pop them
+            notify_full = false;
+            while !timer_heap.is_empty()
+                && now >= timer_heap.peek_min().cloned().unwrap().get_instant()
+            {
+                let time_info = timer_heap.pop_min().unwrap();
+                match notify(time_info) {
+                    Ok(()) => {}
+                    Err(TrySendError::Full(time_info)) => {
+                        // never block here, or the timer_seter can not be consumed
+                        timer_heap.push(time_info);
+                        notify_full = true;
+                        break;
+                    }
+                    Err(e) => {
+                        warn!(
+                            target: log_target,
+                            "send time notification failed with {:?}", e
+                        );
                         break 'outer;
-                    };
+                    }
                 }
             }
         }
@@ -1,51 +1,146 @@
+pub use crate::verify::{get_total_weight, get_votes_weight};
 use crate::*;
 use crate::{
     algorithm::{Bft, INIT_HEIGHT, INIT_ROUND},
-    collectors::{ProposalCollector, RoundCollector, VoteCollector, VoteSet, CACHE_N},
-    error::{handle_err, BftError, BftResult},
+    clock::{Clock, SystemClock},
+    codec::encode_by,
+    collectors::{ProposalCollector, RoundCollector, VoteCollector, CACHE_N},
+    compress::compress_block,
+    error::{handle_err, report_err, BftError, BftResult, ErrorInfo, MsgKind},
+    frame::{decode_frame, encode_frame},
     objects::*,
+    params::{BftConfig, BftParams, ProposerSelection, MAX_TIMEOUT},
+    replay::{ReplayFailure, ReplayProgress, ReplayReport},
     timer::TimeoutInfo,
+    verifier::{BlockJob, CheckedSigs},
+    verify::{
+        self, decode_signed_proposal, decode_signed_vote, is_above_threshold, precommit_of_proof,
+        ProofError,
+    },
     wal::Wal,
+    wire::{WireKind, WireMsg},
 };
-#[cfg(feature = "verify_req")]
+
+use crossbeam::crossbeam_channel::TrySendError;
 #[allow(unused_imports)]
 use log::{log, warn};
 #[cfg(feature = "random_proposer")]
 use rand_core::{RngCore, SeedableRng};
 #[cfg(feature = "random_proposer")]
 use rand_pcg::Pcg64Mcg as Pcg;
+use std::collections::hash_map::Entry;
 #[cfg(feature = "verify_req")]
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
 use std::fs;
-#[cfg(feature = "verify_req")]
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
 const TIMEOUT_LOW_HEIGHT_MESSAGE_COEF: u32 = 20;
 const TIMEOUT_LOW_ROUND_MESSAGE_COEF: u32 = 20;
+const OPEN_WAL_RETRY_MILLIS: u64 = 1000;
 
-impl<T> Bft<T>
+impl<T, B> Bft<T, B>
 where
-    T: BftSupport + 'static,
+    T: BftSupport<B> + 'static,
+    B: BlockPayload,
 {
-    pub(crate) fn load_wal_log(&mut self) {
-        info!("Node {:?} starts loading wal log!", self.params.address);
-        let vec_buf = self.wal_log.load();
-        for (log_type, encode) in vec_buf {
-            handle_err(self.process_wal_log(log_type, encode), &self.params.address);
+    pub(crate) fn load_wal_log(&mut self) -> ReplayReport {
+        info!(
+            target: &self.params.log_target,
+            "Node {:?} starts loading wal log!", self.params.address
+        );
+        let records = self.wal_log.load();
+        let total = records.len();
+        // a record replayed may make the node sign before its own record of the same height and
+        // round is replayed, so the guard is restored from the whole wal first
+        self.restore_sign_guard(&records);
+        let mut report = ReplayReport {
+            total,
+            failures: Vec::new(),
+        };
+        for (index, (height, log_type, codec, encode)) in records.into_iter().enumerate() {
+            if let Err(e) = self.process_wal_log(log_type, codec, encode) {
+                report_err(&e, &self.params, &*self.function);
+                report.failures.push(ReplayFailure {
+                    index,
+                    height,
+                    log_type,
+                    error: e,
+                });
+            }
+            self.function.on_replay_progress(&ReplayProgress {
+                replayed: index + 1,
+                total,
+                height,
+                log_type,
+            });
         }
         info!(
-            "Node {:?} successfully processed the whole wal log!",
-            self.params.address
+            target: &self.params.log_target,
+            "Node {:?} successfully processed the whole wal log!", self.params.address
         );
+        report
+    }
+
+    /// A function to restore the signing guard from the own proposals and votes of the wal
+    /// [`records`] after a restart.
+    fn restore_sign_guard(&mut self, records: &[(Height, LogType, RlpCodec, Vec<u8>)]) {
+        for (_, log_type, _, encode) in records {
+            let (key, block_hash) = match log_type {
+                LogType::Proposal => match rlp::decode::<SignedProposal>(encode) {
+                    Ok(signed_proposal)
+                        if signed_proposal.proposal.proposer == self.params.address =>
+                    {
+                        let proposal = signed_proposal.proposal;
+                        ((proposal.height, proposal.round, None), proposal.block_hash)
+                    }
+                    _ => continue,
+                },
+                LogType::Vote => match rlp::decode::<SignedVote>(encode) {
+                    Ok(signed_vote) if signed_vote.vote.voter == self.params.address => {
+                        let vote = signed_vote.vote;
+                        (
+                            (vote.height, vote.round, Some(vote.vote_type)),
+                            vote.block_hash,
+                        )
+                    }
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            self.sign_guard.entry(key).or_insert(block_hash);
+        }
+    }
+
+    /// A function to replay a wal record, whose signed msgs are checked by the [`codec`] they
+    /// are saved by, so the records saved before a codec switch are still valid.
+    pub(crate) fn process_wal_log(
+        &mut self,
+        log_type: LogType,
+        codec: RlpCodec,
+        encode: Vec<u8>,
+    ) -> BftResult<()> {
+        self.msg_codec = codec;
+        let result = self.replay_wal_log(log_type, encode);
+        self.msg_codec = self.params.config.rlp_codec;
+        result
     }
 
-    fn process_wal_log(&mut self, log_type: LogType, encode: Vec<u8>) -> BftResult<()> {
+    fn replay_wal_log(&mut self, log_type: LogType, encode: Vec<u8>) -> BftResult<()> {
         match log_type {
             LogType::Proposal => {
-                info!("Node {:?} loads proposal", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads proposal", self.params.address
+                );
                 let signed_proposal: SignedProposal = rlp::decode(&encode).map_err(|e| {
-                    BftError::DecodeErr(format!("signed_proposal encounters {:?}", e))
+                    BftError::DecodeErr(format!("signed_proposal encounters {:?}", e).into())
                 })?;
                 let proposal = signed_proposal.proposal;
                 let block_hash = proposal.block_hash;
@@ -54,52 +149,118 @@ where
                     .get_block(proposal.height, &block_hash)
                     .ok_or_else(|| {
                         BftError::ShouldNotHappen(
-                            "can not fetch block from cache when load signed_proposal".to_string(),
+                            "can not fetch block from cache when load signed_proposal"
+                                .to_string()
+                                .into(),
                         )
                     })?;
-                let proposal_block_encode = combine_two(&encode, &block);
+                let proposal_block_encode = combine_two(&encode, &block.to_bytes());
                 self.process(BftMsg::Proposal(proposal_block_encode), false)?;
             }
             LogType::Vote => {
-                info!("Node {:?} loads vote", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads vote", self.params.address
+                );
                 self.process(BftMsg::Vote(encode), false)?;
             }
             LogType::Feed => {
-                info!("Node {:?} loads feed", self.params.address);
-                let feed: Feed = rlp::decode(&encode)
-                    .map_err(|e| BftError::DecodeErr(format!("feed encounters {:?}", e)))?;
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads feed", self.params.address
+                );
+                let feed: Feed<B> = rlp::decode(&encode)
+                    .map_err(|e| BftError::DecodeErr(format!("feed encounters {:?}", e).into()))?;
                 self.process(BftMsg::Feed(feed), false)?;
             }
+            LogType::HeightStart => {
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads height start", self.params.address
+                );
+                let (height, millis) = decode_height_start(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("height start encounters {:?}", e).into())
+                })?;
+                if height == self.height {
+                    // the time passed before the restart counts for the CommitWait
+                    let elapsed = Duration::from_millis(unix_millis().saturating_sub(millis));
+                    let now = Instant::now();
+                    self.htime = now.checked_sub(elapsed).unwrap_or(now);
+                }
+            }
+            LogType::RevokeFeed => {
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads revoking feed", self.params.address
+                );
+                let (height, block_hash) = decode_revoke_feed(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("revoking feed encounters {:?}", e).into())
+                })?;
+                self.process(BftMsg::RevokeFeed { height, block_hash }, false)?;
+            }
             LogType::Status => {
-                info!("Node {:?} loads status", self.params.address);
-                let status: Status = rlp::decode(&encode)
-                    .map_err(|e| BftError::DecodeErr(format!("status encounters {:?}", e)))?;
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads status", self.params.address
+                );
+                check_status_size(&encode, &self.params.config)?;
+                let status: Status = rlp::decode(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("status encounters {:?}", e).into())
+                })?;
                 self.process(BftMsg::Status(status), false)?;
             }
             LogType::Proof => {
-                info!("Node {:?} loads proof", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads proof", self.params.address
+                );
+                check_proof_size(&Rlp::new(&encode), &self.params.config)?;
                 let proof: Proof = rlp::decode(&encode)
-                    .map_err(|e| BftError::DecodeErr(format!("proof encounters {:?}", e)))?;
+                    .map_err(|e| BftError::DecodeErr(format!("proof encounters {:?}", e).into()))?;
                 self.set_proof(&proof);
             }
             LogType::VerifyResp => {
-                info!("Node {:?} loads verify_resp", self.params.address);
-                let verify_resp: VerifyResp = rlp::decode(&encode)
-                    .map_err(|e| BftError::DecodeErr(format!("verify_resp encounters {:?}", e)))?;
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads verify_resp", self.params.address
+                );
+                let verify_resp: VerifyResp = rlp::decode(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("verify_resp encounters {:?}", e).into())
+                })?;
                 self.process(BftMsg::VerifyResp(verify_resp), false)?;
             }
 
             LogType::TimeOutInfo => {
-                info!("Node {:?} loads timeout_info", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads timeout_info", self.params.address
+                );
                 let time_out_info: TimeoutInfo = rlp::decode(&encode).map_err(|e| {
-                    BftError::DecodeErr(format!("time_out_info encounters {:?}", e))
+                    BftError::DecodeErr(format!("time_out_info encounters {:?}", e).into())
                 })?;
                 self.timeout_process(time_out_info, false)?;
             }
 
+            LogType::Authorities => {
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads authorities", self.params.address
+                );
+                let authority_manage: AuthorityManage = rlp::decode(&encode).map_err(|e| {
+                    BftError::DecodeErr(format!("authority_manage encounters {:?}", e).into())
+                })?;
+                self.restore_authorities(authority_manage)?;
+            }
+
             LogType::Block => {
-                info!("Node {:?} loads block", self.params.address);
+                info!(
+                    target: &self.params.log_target,
+                    "Node {:?} loads block", self.params.address
+                );
                 let (height, block, block_hash) = decode_block(&encode)?;
+                let block = B::from_bytes(&block)
+                    .map(Arc::new)
+                    .map_err(|e| BftError::DecodeErr(format!("block encounters {:?}", e).into()))?;
                 self.blocks.add(height, &block_hash, &block);
             }
         }
@@ -111,28 +272,36 @@ where
         proposal: &Proposal,
     ) -> BftResult<Vec<u8>> {
         let block_hash = &proposal.block_hash;
-        let signed_proposal = self.build_signed_proposal(&proposal)?;
-        let signed_proposal_encode = rlp::encode(&signed_proposal);
+        let signed_proposal = self.build_signed_proposal(proposal)?;
         let block = self
             .blocks
             .get_block(proposal.height, block_hash)
             .ok_or_else(|| {
                 BftError::ShouldNotHappen(
-                    "can not fetch block from cache when send signed_proposal".to_string(),
+                    "can not fetch block from cache when send signed_proposal"
+                        .to_string()
+                        .into(),
                 )
             })?;
-        let encode = combine_two(&signed_proposal_encode, &block);
+        let mut stream = self.encode_buf.borrow_mut();
+        let encode = combine_two(
+            encode_into(&mut stream, &signed_proposal, self.params.config.rlp_codec),
+            &block.to_bytes(),
+        );
         Ok(encode)
     }
 
-    pub(crate) fn build_signed_proposal(&self, proposal: &Proposal) -> BftResult<SignedProposal> {
-        let encode = rlp::encode(proposal);
-        let hash = self.function.crypt_hash(&encode);
+    pub(crate) fn build_signed_proposal(
+        &mut self,
+        proposal: &Proposal,
+    ) -> BftResult<SignedProposal> {
+        self.guard_signing(proposal.height, proposal.round, None, proposal.block_hash)?;
+        let hash = self.hash_to_sign(SignKind::Proposal, proposal);
 
         let signature = self
             .function
             .sign(&hash)
-            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, proposal)))?;
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, proposal).into()))?;
 
         Ok(SignedProposal {
             proposal: proposal.clone(),
@@ -140,14 +309,19 @@ where
         })
     }
 
-    pub(crate) fn build_signed_vote(&self, vote: &Vote) -> BftResult<SignedVote> {
-        let encode = rlp::encode(vote);
-        let hash = self.function.crypt_hash(&encode);
+    pub(crate) fn build_signed_vote(&mut self, vote: &Vote) -> BftResult<SignedVote> {
+        self.guard_signing(
+            vote.height,
+            vote.round,
+            Some(vote.vote_type.clone()),
+            vote.block_hash,
+        )?;
+        let hash = self.hash_to_sign(SignKind::Vote, vote);
 
         let signature = self
             .function
             .sign(&hash)
-            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote)))?;
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote).into()))?;
 
         Ok(SignedVote {
             vote: vote.clone(),
@@ -155,6 +329,203 @@ where
         })
     }
 
+    /// A function to request the signature of an own vote by `sign_async`, return `None` if
+    /// the signing is pending.
+    pub(crate) fn request_vote_sig(&mut self, vote: &Vote) -> BftResult<Option<SignedVote>> {
+        self.expire_pending_signs();
+        self.guard_signing(
+            vote.height,
+            vote.round,
+            Some(vote.vote_type.clone()),
+            vote.block_hash,
+        )?;
+        let hash = self.hash_to_sign(SignKind::Vote, vote);
+        let ticket = self.next_sign_ticket;
+        self.next_sign_ticket = self.next_sign_ticket.wrapping_add(1);
+
+        let signature = self
+            .function
+            .sign_async(&hash, ticket)
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote).into()))?;
+        match signature {
+            Some(signature) => Ok(Some(SignedVote {
+                vote: vote.clone(),
+                signature,
+            })),
+            None => {
+                let deadline =
+                    Instant::now() + Duration::from_millis(self.params.config.sign_timeout_millis);
+                self.pending_signs.insert(
+                    ticket,
+                    PendingSign {
+                        vote: vote.clone(),
+                        deadline,
+                    },
+                );
+                Ok(None)
+            }
+        }
+    }
+
+    /// A function to drop the pending signings which time out.
+    fn expire_pending_signs(&mut self) {
+        let now = Instant::now();
+        let expired: Vec<SignTicket> = self
+            .pending_signs
+            .iter()
+            .filter(|(_, pending)| pending.deadline <= now)
+            .map(|(ticket, _)| *ticket)
+            .collect();
+        for ticket in expired {
+            if let Some(pending) = self.pending_signs.remove(&ticket) {
+                report_err(
+                    &BftError::SignFailed(
+                        format!("signing of {:?} times out", pending.vote).into(),
+                    ),
+                    &self.params,
+                    &*self.function,
+                );
+            }
+        }
+    }
+
+    /// A function to handle the signature delivered for a pending `sign_async` request, the
+    /// signed vote is sent if it is still of the current height and round. It is sent to the
+    /// node itself even for a retransmission, as the earlier signing may have timed out, and
+    /// a vote received again is dropped.
+    pub(crate) fn handle_signature(
+        &mut self,
+        ticket: SignTicket,
+        signature: Signature,
+    ) -> BftResult<()> {
+        let pending = self.pending_signs.remove(&ticket).ok_or_else(|| {
+            BftError::ObsoleteMsg(format!("signature of unknown ticket {}", ticket).into())
+        })?;
+        let vote = pending.vote;
+        if Instant::now() > pending.deadline {
+            return Err(BftError::SignFailed(
+                format!("signature of {:?} is delivered after timeout", vote).into(),
+            ));
+        }
+        if vote.height != self.height || vote.round != self.round {
+            return Err(BftError::ObsoleteMsg(
+                ErrorInfo::new(format!("signature of {:?}", vote)).at(self.height, self.round),
+            ));
+        }
+        let hash = self.hash_to_sign(SignKind::Vote, &vote);
+        let address = self
+            .recover_sig(&signature, &hash)
+            .map_err(|e| BftError::SignFailed(format!("{:?} of {:?}", e, vote).into()))?;
+        if address != self.params.address {
+            return Err(BftError::SignFailed(
+                format!("signature of {:?} recovers {:?}", vote, address).into(),
+            ));
+        }
+        self.send_own_vote(&SignedVote { vote, signature }, false);
+        Ok(())
+    }
+
+    /// A function to transmit an own vote, and send it to the node itself unless it is a
+    /// retransmission.
+    pub(crate) fn send_own_vote(&mut self, signed_vote: &SignedVote, resend: bool) {
+        let encode = self.encode(signed_vote);
+        self.transmit(OutboundMsg::Vote(encode.clone()), Target::Broadcast);
+        if !resend {
+            handle_err(
+                self.send_bft_msg(BftMsg::Vote(encode)),
+                &self.params,
+                &*self.function,
+            );
+        }
+    }
+
+    /// A function to get the block hash of the own vote of the [`vote_type`] signed at the
+    /// height and round, such as the one replayed from wal after a restart. The vote is
+    /// repeated instead of a conflicting one, which the guard refuses.
+    pub(crate) fn signed_vote_hash(
+        &self,
+        height: Height,
+        round: Round,
+        vote_type: &VoteType,
+    ) -> Option<Hash> {
+        self.sign_guard
+            .get(&(height, round, Some(vote_type.clone())))
+            .copied()
+    }
+
+    /// A function to refuse signing a payload of the [`block_hash`] different from the one
+    /// signed for the same height, round and step, the proposal is of the step `None`.
+    /// The guard is kept in memory for the current and the last heights.
+    fn guard_signing(
+        &mut self,
+        height: Height,
+        round: Round,
+        vote_type: Option<VoteType>,
+        block_hash: Hash,
+    ) -> BftResult<()> {
+        let last_height = self.height.saturating_sub(1);
+        self.sign_guard.retain(|(h, _, _), _| *h >= last_height);
+        match self.sign_guard.entry((height, round, vote_type)) {
+            Entry::Occupied(entry) if *entry.get() != block_hash => Err(BftError::SignFailed(
+                ErrorInfo::new(format!(
+                    "refuses signing {:?} of {:?} after {:?}",
+                    block_hash,
+                    entry.key().2,
+                    entry.get()
+                ))
+                .at(height, round),
+            )),
+            Entry::Occupied(_) => Ok(()),
+            Entry::Vacant(entry) => {
+                entry.insert(block_hash);
+                Ok(())
+            }
+        }
+    }
+
+    /// A function to get the hash the node signs for its own [`object`], which is encoded by
+    /// the configured codec.
+    pub(crate) fn hash_to_sign<E: CodecEncodable>(&self, kind: SignKind, object: &E) -> Hash {
+        self.hash_by(kind, object, self.params.config.rlp_codec)
+    }
+
+    /// A function to get the hash signed for the [`object`] of the msg being processed, which
+    /// is encoded by the codec of the msg.
+    pub(crate) fn hash_signed<E: CodecEncodable>(&self, kind: SignKind, object: &E) -> Hash {
+        self.hash_by(kind, object, self.msg_codec)
+    }
+
+    /// A function to get the hash signed for the [`object`] encoded by the [`codec`] under the
+    /// sign domain, which is encoded in the reused buffer.
+    fn hash_by<E: CodecEncodable>(&self, kind: SignKind, object: &E, codec: RlpCodec) -> Hash {
+        let mut stream = self.encode_buf.borrow_mut();
+        let encode = encode_into(&mut stream, object, codec);
+        let sign_domain = self.params.config.sign_scheme().domain;
+        get_signed_hash(kind, sign_domain, encode, |msg| {
+            self.function.crypt_hash(msg)
+        })
+    }
+
+    /// A function to get the rlp encoding of the [`object`] by the configured codec, which the
+    /// outbound msgs and the wal records are encoded by.
+    pub(crate) fn encode<E: CodecEncodable + ?Sized>(&self, object: &E) -> Vec<u8> {
+        encode_by(object, self.params.config.rlp_codec)
+    }
+
+    /// A function to save the rlp encoding of the [`object`] to wal, which is encoded by the
+    /// configured codec in the reused buffer.
+    pub(crate) fn save_encoded<E: CodecEncodable>(
+        &mut self,
+        height: Height,
+        log_type: LogType,
+        object: &E,
+    ) -> io::Result<()> {
+        let mut stream = self.encode_buf.borrow_mut();
+        let codec = self.params.config.rlp_codec;
+        self.wal_log
+            .save(height, log_type, encode_into(&mut stream, object, codec))
+    }
+
     #[inline]
     fn get_authorities(&self, height: Height) -> BftResult<&Vec<Node>> {
         let p = &self.authority_manage;
@@ -166,12 +537,33 @@ where
 
         if authorities.is_empty() {
             return Err(BftError::ShouldNotHappen(
-                "the authority_list is empty".to_string(),
+                "the authority_list is empty".to_string().into(),
             ));
         }
         Ok(authorities)
     }
 
+    /// A function to get the authorities whose votes of the round are not received,
+    /// only the current height and the last height are tracked.
+    pub(crate) fn missing_voters(
+        &mut self,
+        height: Height,
+        round: Round,
+        vote_type: &VoteType,
+    ) -> BftResult<Vec<Address>> {
+        if height != self.height && !self.is_last_height(height) {
+            return Err(BftError::NotReady(
+                ErrorInfo::new(format!("the votes of height {} are not tracked", height))
+                    .at(height, round)
+                    .kind(MsgKind::Vote),
+            ));
+        }
+        let authorities = self.get_authorities(height)?.clone();
+        Ok(self
+            .votes
+            .missing_voters(height, round, vote_type, &authorities))
+    }
+
     #[inline]
     fn get_vote_weight(&self, height: Height, address: &Address) -> u64 {
         if height != self.height {
@@ -190,19 +582,26 @@ where
 
     pub(crate) fn get_proposer(&self, height: Height, round: Round) -> BftResult<&Address> {
         let authorities = self.get_authorities(height)?;
-        let nonce = height + round;
-        let weight: Vec<u64> = authorities
-            .iter()
-            .map(|node| u64::from(node.proposal_weight))
-            .collect();
+        let selection = self.params.config.proposer_selection;
+        let index = match selection {
+            ProposerSelection::Priority => {
+                self.authority_manage
+                    .priority_index(height, round, authorities)
+            }
+            ProposerSelection::Weighted => None,
+        }
+        .unwrap_or_else(|| proposer_index(height, round, authorities, selection));
         let proposer: &Address = &authorities
-            .get(get_index(nonce, &weight))
-            .unwrap_or_else(|| {
-                panic!(
-                    "Node {:?} selects a proposer not in authorities, it should not happen!",
-                    self.params.address
+            .get(index)
+            .ok_or_else(|| {
+                BftError::ShouldNotHappen(
+                    format!(
+                        "selects a proposer not in the authorities at h:{}, r:{}",
+                        height, round
+                    )
+                    .into(),
                 )
-            })
+            })?
             .address;
         Ok(proposer)
     }
@@ -211,17 +610,39 @@ where
     pub(crate) fn set_proof(&mut self, proof: &Proof) {
         if self.proof.height < proof.height {
             self.proof = proof.clone();
+            if let Some(proof_store) = &self.proof_store {
+                handle_err(proof_store.put_proof(proof), &self.params, &*self.function);
+            }
         }
     }
 
-    pub(crate) fn set_status(&mut self, status: &Status) {
-        self.authority_manage
-            .receive_authorities_list(status.height, status.authority_list.clone());
+    pub(crate) fn set_status(&mut self, status: &Status, need_wal: bool) -> BftResult<()> {
+        let changed = self
+            .authority_manage
+            .receive_authorities_list(status.height, status.authority_list.clone())?;
+        self.votes.refresh_quorum(self.authority_manage.weight_sum);
+        self.refresh_priorities();
         trace!(
+            target: &self.params.log_target,
             "Node {:?} updates authority_manage {:?}",
             self.params.address,
             self.authority_manage
         );
+        if changed && need_wal {
+            self.save_authorities(next_height(status.height)?);
+        }
+
+        if let Some(rotation) = self.pending_key.take() {
+            info!(
+                target: &self.params.log_target,
+                "Node {:?} rotates to {:?} from height {}",
+                self.params.address,
+                rotation.new,
+                rotation.activation_height
+            );
+            self.params.address = rotation.new;
+            self.function.rotate_key(&rotation);
+        }
 
         if self.consensus_power
             && !status
@@ -230,8 +651,10 @@ where
                 .any(|node| node.address == self.params.address)
         {
             info!(
+                target: &self.params.log_target,
                 "Node {:?} loses consensus power in height {} and stops the bft-rs process!",
-                self.params.address, status.height
+                self.params.address,
+                status.height
             );
             self.consensus_power = false;
         } else if !self.consensus_power
@@ -241,8 +664,10 @@ where
                 .any(|node| node.address == self.params.address)
         {
             info!(
+                target: &self.params.log_target,
                 "Node {:?} accesses consensus power in height {} and starts the bft-rs process!",
-                self.params.address, status.height
+                self.params.address,
+                status.height
             );
             self.consensus_power = true;
         }
@@ -251,31 +676,240 @@ where
             // update the bft interval
             self.params.timer.set_total_duration(interval);
         }
+        if let Some(consensus_params) = &status.consensus_params {
+            self.params.apply(consensus_params);
+            info!(
+                target: &self.params.log_target,
+                "Node {:?} applies {:?} from height {}",
+                self.params.address,
+                consensus_params,
+                next_height(status.height)?
+            );
+        }
+        Ok(())
+    }
+
+    /// A function to save the authority_manage, so the old authority list and the schedule
+    /// survive a restart even if the statuses are pruned from wal.
+    pub(crate) fn save_authorities(&mut self, height: Height) {
+        handle_err(
+            self.wal_log
+                .save(
+                    height,
+                    LogType::Authorities,
+                    &self.encode(&self.authority_manage),
+                )
+                .map_err(|e| {
+                    BftError::SaveWalErr(format!("{:?} of {:?}", e, self.authority_manage).into())
+                }),
+            &self.params,
+            &*self.function,
+        );
+    }
+
+    fn restore_authorities(&mut self, authority_manage: AuthorityManage) -> BftResult<()> {
+        let max_authorities = self.params.config.max_authorities;
+        check_list_len(
+            "authorities",
+            authority_manage.authorities.len(),
+            max_authorities,
+        )?;
+        check_list_len(
+            "authorities_old",
+            authority_manage.authorities_old.len(),
+            max_authorities,
+        )?;
+        check_list_len("schedule", authority_manage.schedule.len(), max_authorities)?;
+        self.authority_manage = authority_manage;
+        self.votes.refresh_quorum(self.authority_manage.weight_sum);
+        self.refresh_priorities();
+        Ok(())
+    }
+
+    /// A function to cache the priorities of the authority lists if the proposers are selected
+    /// by them, so a selection does not accumulate them again.
+    pub(crate) fn refresh_priorities(&mut self) {
+        if self.params.config.proposer_selection == ProposerSelection::Priority {
+            self.authority_manage.refresh_priorities();
+        }
     }
 
-    pub(crate) fn set_polc(&mut self, hash: &Hash, voteset: &VoteSet) {
+    /// A function to lock the block hash with the votes of the vote type at the current round.
+    pub(crate) fn set_polc(&mut self, hash: &Hash, vote_type: &VoteType) {
+        let votes = self
+            .votes
+            .get_voteset(self.height, self.round, vote_type)
+            .map(|voteset| voteset.extract_polc(hash))
+            .unwrap_or_default();
         self.block_hash = Some(hash.to_owned());
         self.lock_status = Some(LockStatus {
             block_hash: hash.to_owned(),
             round: self.round,
-            votes: voteset.extract_polc(hash),
+            votes,
         });
 
         debug!(
+            target: &self.params.log_target,
             "Node {:?} sets a PoLC on block_hash {:?} at h:{:?} r:{:?} ",
             self.params.address,
             hash.to_owned(),
             self.height,
             self.round
         );
+        handle_err(self.pipeline_next_feed(), &self.params, &*self.function);
+    }
+
+    /// A function to request the block of the next height in advance in the pipelining mode,
+    /// if the node is the proposer of its first round by the current authorities.
+    pub(crate) fn pipeline_next_feed(&mut self) -> BftResult<()> {
+        let height = next_height(self.height)?;
+        if !self.params.config.pipeline_proposal || self.pipelined_height == Some(height) {
+            return Ok(());
+        }
+        if *self.get_proposer(height, INIT_ROUND)? != self.params.address {
+            return Ok(());
+        }
+        self.pipelined_height = Some(height);
+        self.request_feed(height);
+        Ok(())
+    }
+
+    /// A function to get the block to propose at the [`height`] off the main loop, it is fed
+    /// back when it is got.
+    pub(crate) fn request_feed(&self, height: Height) {
+        let function = self.function.clone();
+        let sender = self.msg_sender.clone();
+        let params = self.params.clone();
+        let shared_height = self.shared_height.clone();
+
+        thread::spawn(move || {
+            let feed = get_block_with_retry(&*function, height, &params, &shared_height);
+            if let Some((block, block_hash)) = feed {
+                handle_err(
+                    sender
+                        .send(BftMsg::Feed(Feed {
+                            height,
+                            block,
+                            block_hash,
+                        }))
+                        .map_err(|e| BftError::SendMsgErr(format!("{:?}", e).into())),
+                    &params,
+                    &*function,
+                );
+            }
+        });
+    }
+
+    /// A function to transmit a msg to the target, the msg is queued to retry if it fails.
+    pub(crate) fn transmit(&mut self, msg: OutboundMsg, target: Target) {
+        let outbound = Outbound { msg, target };
+        if let Some(tracer) = self.tracer.as_mut() {
+            let result = tracer.record_outbound(&outbound);
+            self.check_trace_err(result);
+        }
+        if let Err(e) = self.try_transmit(&outbound) {
+            report_err(&e, &self.params, &*self.function);
+            let height = get_outbound_height(&outbound.msg);
+            if !self.retry_queue.push(outbound, height) {
+                debug!(
+                    target: &self.params.log_target,
+                    "Node {:?} drops a failed transmission without retrying", self.params.address
+                );
+            }
+        }
+    }
+
+    /// A function to transmit again the failed msgs which are due.
+    pub(crate) fn retry_transmit(&mut self) {
+        // votes of the last height are still useful for the peers falling behind
+        let min_height = self.last_height().unwrap_or(INIT_HEIGHT);
+        for pending in self.retry_queue.pop_due(min_height) {
+            if let Err(e) = self.try_transmit(&pending.outbound) {
+                if !self.retry_queue.retry(pending) {
+                    warn!(
+                        target: &self.params.log_target,
+                        "Node {:?} gives up transmitting after retries, {:?}",
+                        self.params.address,
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    fn try_transmit(&self, outbound: &Outbound) -> BftResult<()> {
+        let outbound = self.wrap_wire_msg(outbound);
+        let outbound = &outbound;
+        match &self.outbox {
+            Some(outbox) => outbox.try_send(outbound.clone()).map_err(|e| match e {
+                TrySendError::Full(_) => {
+                    BftError::TransmitFailed(format!("outbox is full, {:?}", outbound).into())
+                }
+                TrySendError::Disconnected(_) => BftError::TransmitFailed(
+                    format!("outbox is disconnected, {:?}", outbound).into(),
+                ),
+            }),
+            None => self
+                .function
+                .transmit(outbound.clone())
+                .map_err(|e| BftError::TransmitFailed(format!("{:?} of {:?}", e, outbound).into())),
+        }
+    }
+
+    /// A function to wrap the outbound msg in a wire envelope of the local chain id and codec.
+    fn wrap_wire_msg(&self, outbound: &Outbound) -> Outbound {
+        let chain_id = self.params.config.chain_id;
+        let codec = self.params.config.rlp_codec;
+        let msg = match &outbound.msg {
+            OutboundMsg::Proposal(encode) => {
+                let compressed = extract_two(encode).ok().and_then(|(proposal, block)| {
+                    compress_block(block, self.params.config.block_compression)
+                        .map(|(codec, block)| encode_frame(&[proposal, &block, &[codec]]))
+                });
+                let wire_msg = match compressed {
+                    Some(compressed) => WireMsg::new_compressed(chain_id, codec, compressed),
+                    None => WireMsg::new(chain_id, codec, WireKind::Proposal, encode.clone()),
+                };
+                OutboundMsg::Proposal(rlp::encode(&wire_msg))
+            }
+            OutboundMsg::Vote(encode) => OutboundMsg::Vote(rlp::encode(&WireMsg::new(
+                chain_id,
+                codec,
+                WireKind::Vote,
+                encode.clone(),
+            ))),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::GetTxs(encode) => OutboundMsg::GetTxs(rlp::encode(&WireMsg::new(
+                chain_id,
+                codec,
+                WireKind::GetTxs,
+                encode.clone(),
+            ))),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::Txs(encode) => OutboundMsg::Txs(rlp::encode(&WireMsg::new(
+                chain_id,
+                codec,
+                WireKind::Txs,
+                encode.clone(),
+            ))),
+        };
+        Outbound {
+            msg,
+            target: outbound.target.clone(),
+        }
     }
 
     #[inline]
-    pub(crate) fn set_timer(&self, duration: Duration, step: Step) {
+    pub(crate) fn set_timer(&self, duration: Duration, step: Step) -> BftResult<()> {
         debug!(
-            "Node {:?} will process {:?} after {:?}",
-            self.params.address, step, duration
+            target: &self.params.log_target,
+            "Node {:?} will process {:?} after {:?}", self.params.address, step, duration
         );
+        let duration = match &self.params.config.clock {
+            Some(clock) => clock.elapse(duration),
+            None => duration,
+        }
+        .min(MAX_TIMEOUT);
         let timestamp = Instant::now() + duration;
         let since = timestamp - self.htime;
         self.timer_seter
@@ -286,7 +920,7 @@ where
                 round: self.round,
                 step,
             })
-            .unwrap();
+            .map_err(|e| BftError::InternalErr(format!("set timer encounters {:?}", e).into()))
     }
 
     pub(crate) fn generate_proof(&mut self, lock_status: LockStatus) -> Proof {
@@ -296,38 +930,54 @@ where
             .into_iter()
             .map(|signed_vote| (signed_vote.vote.voter, signed_vote.signature))
             .collect();
+        let function = &self.function;
+        let authorities_hash = get_authorities_hash(
+            &self.authority_manage.authorities,
+            self.params.config.rlp_codec,
+            |msg| function.crypt_hash(msg),
+        );
         Proof {
             height: self.height,
             round: lock_status.round,
             block_hash,
             precommit_votes,
+            authorities_hash: Some(authorities_hash),
         }
     }
 
     pub(crate) fn flush_cache(&mut self) -> BftResult<()> {
+        self.fetch_status(self.height)?;
         self.fetch_proposal(self.height, 0)?;
         self.fetch_votes(self.height)?;
         Ok(())
     }
 
+    pub(crate) fn fetch_status(&mut self, height: Height) -> BftResult<()> {
+        let pending = self.pending_statuses.split_off(&next_height(height)?);
+        // the ones of the lower heights are obsolete
+        let mut statuses = mem::replace(&mut self.pending_statuses, pending);
+        if let Some(status) = statuses.remove(&height) {
+            self.send_bft_msg(BftMsg::Status(status))?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn fetch_proposal(&mut self, height: Height, round: Round) -> BftResult<()> {
         let opt = self.proposals.get_proposal(height, round).clone();
         if let Some(signed_proposal) = opt {
             self.proposals.remove(height, round);
 
-            let block_hash = signed_proposal.proposal.block_hash.clone();
+            let block_hash = signed_proposal.proposal.block_hash;
             let block = self.blocks.get_block(height, &block_hash).ok_or_else(|| {
                 BftError::ShouldNotHappen(
-                    "can not fetch block from cache when load signed_proposal".to_string(),
+                    "can not fetch block from cache when load signed_proposal"
+                        .to_string()
+                        .into(),
                 )
             })?;
-            let proposal_encode = rlp::encode(&signed_proposal);
-            let encode = combine_two(&proposal_encode, &block);
-            let msg = BftMsg::Proposal(encode);
-            let info = format!("{:?}", &msg);
-            self.msg_sender
-                .send(msg)
-                .map_err(|_| BftError::SendMsgErr(info))?;
+            let proposal_encode = self.encode(&signed_proposal);
+            let encode = combine_two(&proposal_encode, &block.to_bytes());
+            self.send_bft_msg(BftMsg::Proposal(encode))?;
         }
         Ok(())
     }
@@ -337,20 +987,16 @@ where
         let round_votes = votes.get_mut(&height);
         let mut vote_collector = RoundCollector::new();
 
-        if round_votes.is_some() {
-            vote_collector = round_votes.unwrap().clone();
+        if let Some(round_votes) = round_votes {
+            vote_collector = round_votes.clone();
             self.votes.remove(height);
         }
 
         for (_, step_votes) in vote_collector.round_votes.iter() {
             for (_, vote_set) in step_votes.step_votes.iter() {
                 for (_, signed_vote) in vote_set.votes_by_sender.iter() {
-                    let encode = rlp::encode(signed_vote);
-                    let msg = BftMsg::Vote(encode);
-                    let info = format!("{:?}", &msg);
-                    self.msg_sender
-                        .send(msg)
-                        .map_err(|_| BftError::SendMsgErr(info))?;
+                    let encode = self.encode(signed_vote);
+                    self.send_bft_msg(BftMsg::Vote(encode))?;
                 }
             }
         }
@@ -359,19 +1005,18 @@ where
 
     pub(crate) fn save_verify_res(
         &mut self,
-        round: Round,
+        block_hash: &Hash,
         verify_resp: &VerifyResp,
     ) -> BftResult<()> {
-        if self.verify_results.contains_key(&round)
-            && verify_resp.is_pass != self.verify_results.get(&round).unwrap().is_pass
+        if self.verify_results.contains_key(block_hash)
+            && verify_resp.is_pass != self.verify_results.get(block_hash).unwrap().is_pass
         {
-            Err(BftError::ShouldNotHappen(format!(
-                "get conflict verify result of round: {}",
-                round
-            )))
+            Err(BftError::ShouldNotHappen(
+                format!("get conflict verify result of block_hash: {:?}", block_hash).into(),
+            ))
         } else {
             self.verify_results
-                .entry(round)
+                .entry(*block_hash)
                 .or_insert_with(|| verify_resp.clone());
             Ok(())
         }
@@ -380,7 +1025,7 @@ where
     pub(crate) fn check_and_save_proposal(
         &mut self,
         signed_proposal: &SignedProposal,
-        block: &Block,
+        block: &Arc<B>,
         signed_proposal_hash: &[u8],
         need_wal: bool,
     ) -> BftResult<()> {
@@ -388,32 +1033,67 @@ where
         let block_hash = &proposal.block_hash;
         let height = proposal.height;
         let round = proposal.round;
+        let info = |detail: String| {
+            ErrorInfo::new(detail)
+                .at(height, round)
+                .sender(proposal.proposer)
+                .kind(MsgKind::Proposal)
+        };
 
-        if height < self.height - 1 {
-            return Err(BftError::ObsoleteMsg(format!("{:?}", signed_proposal)));
+        if self.is_below_last_height(height) {
+            if need_wal {
+                self.peer_stats.record(&proposal.proposer).obsolete_msgs += 1;
+            }
+            return Err(BftError::ObsoleteMsg(info(format!(
+                "{:?}",
+                signed_proposal
+            ))));
         }
 
-        let address = self
-            .function
-            .check_sig(
-                &signed_proposal.signature,
-                &self.function.crypt_hash(&rlp::encode(proposal)),
-            )
-            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, signed_proposal)))?;
+        self.check_sender_limit(&proposal.proposer, need_wal)?;
+        let proposal_hash = self.hash_signed(SignKind::Proposal, proposal);
+        let address = match self.recover_sig(&signed_proposal.signature, &proposal_hash) {
+            Ok(address) => address,
+            Err(e) => {
+                self.record_sig_failure(&proposal.proposer);
+                return Err(BftError::CheckSigFailed(info(format!(
+                    "{:?} of {:?}",
+                    e, signed_proposal
+                ))));
+            }
+        };
+        self.peer_score.record_success(&address);
+        if need_wal {
+            self.peer_stats.record(&address).proposals += 1;
+        }
         if proposal.proposer != address {
-            return Err(BftError::InvalidSender(format!(
+            return Err(BftError::InvalidSender(info(format!(
                 "recovers {:?} of {:?}",
                 address, signed_proposal
-            )));
+            ))));
         }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            height = proposal.height,
+            round = proposal.round,
+            proposer = ?proposal.proposer,
+            block_hash = ?proposal.block_hash,
+            "proposal"
+        );
 
-        if height == self.height || height == self.height - 1 {
+        if height == self.height || self.is_last_height(height) {
             self.check_proposer(proposal)?;
             self.check_lock_votes(proposal, block_hash)?;
 
-            if height == self.height - 1 {
+            if self.is_last_height(height) {
                 return Ok(());
             }
+            // the replayed proposals are stamped long ago
+            if need_wal {
+                self.check_timestamp(proposal)?;
+            }
+            #[cfg(feature = "compact_block")]
+            self.fetch_missing_txs(proposal, block, signed_proposal_hash)?;
             self.check_block_txs(
                 proposal,
                 block,
@@ -423,41 +1103,45 @@ where
         }
 
         // prevent too many higher proposals flush out current proposal
-        if height >= self.height && height < self.height + CACHE_N && round < self.round + CACHE_N {
-            self.proposals.add(&signed_proposal)?;
+        if height >= self.height
+            && height < self.height.saturating_add(CACHE_N)
+            && round < self.round.saturating_add(CACHE_N)
+        {
+            self.proposals.add(signed_proposal)?;
             let save = self.blocks.add(height, block_hash, block);
+            self.enforce_cache_cap();
 
             if need_wal {
                 if save {
-                    let encode = encode_block(height, block, block_hash);
+                    let encode = encode_block(height, &**block, block_hash);
                     handle_err(
                         self.wal_log
                             .save(height, LogType::Block, &encode)
-                            .or_else(|e| {
-                                Err(BftError::SaveWalErr(format!(
+                            .map_err(|e| {
+                                BftError::SaveWalErr(info(format!(
                                     "{:?} of proposal block with height {}, round {}",
                                     e, height, round
                                 )))
                             }),
-                        &self.params.address,
+                        &self.params,
+                        &*self.function,
                     );
                 }
                 handle_err(
-                    self.wal_log
-                        .save(height, LogType::Proposal, &rlp::encode(signed_proposal))
-                        .or_else(|e| {
-                            Err(BftError::SaveWalErr(format!(
-                                "{:?} of {:?}",
-                                e, signed_proposal
-                            )))
+                    self.save_encoded(height, LogType::Proposal, signed_proposal)
+                        .map_err(|e| {
+                            BftError::SaveWalErr(info(format!("{:?} of {:?}", e, signed_proposal)))
                         }),
-                    &self.params.address,
+                    &self.params,
+                    &*self.function,
                 );
             }
         }
 
-        if height > self.height || (height == self.height && round >= self.round + CACHE_N) {
-            return Err(BftError::HigherMsg(format!("{:?}", signed_proposal)));
+        if height > self.height
+            || (height == self.height && round >= self.round.saturating_add(CACHE_N))
+        {
+            return Err(BftError::HigherMsg(info(format!("{:?}", signed_proposal))));
         }
 
         Ok(())
@@ -471,177 +1155,495 @@ where
         let vote = &signed_vote.vote;
         let height = vote.height;
         let round = vote.round;
-        if height < self.height - 1 {
-            return Err(BftError::ObsoleteMsg(format!("{:?}", signed_vote)));
+        let info = |detail: String| {
+            ErrorInfo::new(detail)
+                .at(height, round)
+                .sender(vote.voter)
+                .kind(MsgKind::Vote)
+        };
+        if self.is_below_last_height(height) {
+            if need_wal {
+                self.peer_stats.record(&vote.voter).obsolete_msgs += 1;
+            }
+            return Err(BftError::ObsoleteMsg(info(format!("{:?}", signed_vote))));
         }
 
-        let vote_hash = self.function.crypt_hash(&rlp::encode(vote));
-        let address = self
-            .function
-            .check_sig(&signed_vote.signature, &vote_hash)
-            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, signed_vote)))?;
+        self.check_sender_limit(&vote.voter, need_wal)?;
+        let vote_hash = self.hash_signed(SignKind::Vote, vote);
+        let address = match self.recover_sig(&signed_vote.signature, &vote_hash) {
+            Ok(address) => address,
+            Err(e) => {
+                self.record_sig_failure(&vote.voter);
+                return Err(BftError::CheckSigFailed(info(format!(
+                    "{:?} of {:?}",
+                    e, signed_vote
+                ))));
+            }
+        };
+        self.peer_score.record_success(&address);
+        if need_wal {
+            let stats = self.peer_stats.record(&address);
+            match vote.vote_type {
+                VoteType::Prevote => stats.prevotes += 1,
+                VoteType::Precommit => stats.precommits += 1,
+            }
+        }
         if vote.voter != address {
-            return Err(BftError::InvalidSender(format!(
+            return Err(BftError::InvalidSender(info(format!(
                 "recovers {:?} of {:?}",
                 address, signed_vote
-            )));
+            ))));
         }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            height = vote.height,
+            round = vote.round,
+            voter = ?vote.voter,
+            vote_type = ?vote.vote_type,
+            block_hash = ?vote.block_hash,
+            "vote"
+        );
 
         if height == self.height {
             self.check_voter(vote)?;
         }
 
         // prevent too many high proposals flush out current proposal
-        if height >= self.height && height < self.height + CACHE_N && round < self.round + CACHE_N {
+        if height >= self.height
+            && height < self.height.saturating_add(CACHE_N)
+            && round < self.round.saturating_add(CACHE_N)
+        {
             let vote_weight = self.get_vote_weight(vote.height, &vote.voter);
-            let result = self.votes.add(&signed_vote, vote_weight, self.height);
+            let result = self.votes.add(
+                signed_vote,
+                vote_weight,
+                self.height,
+                self.authority_manage.weight_sum,
+                self.authority_manage.authorities.len(),
+            );
             if need_wal && result.is_ok() {
                 handle_err(
-                    self.wal_log
-                        .save(height, LogType::Vote, &rlp::encode(signed_vote))
-                        .or_else(|e| {
-                            Err(BftError::SaveWalErr(format!(
-                                "{:?} of {:?}",
-                                e, signed_vote
-                            )))
+                    self.save_encoded(height, LogType::Vote, signed_vote)
+                        .map_err(|e| {
+                            BftError::SaveWalErr(info(format!("{:?} of {:?}", e, signed_vote)))
                         }),
-                    &self.params.address,
+                    &self.params,
+                    &*self.function,
                 );
             }
-            handle_err(result, &self.params.address);
+            handle_err(result, &self.params, &*self.function);
+            self.enforce_cache_cap();
         }
 
-        if height > self.height || round >= self.round + CACHE_N {
-            return Err(BftError::HigherMsg(format!("{:?}", signed_vote)));
+        if height > self.height || round >= self.round.saturating_add(CACHE_N) {
+            return Err(BftError::HigherMsg(info(format!("{:?}", signed_vote))));
         }
 
         Ok(())
     }
 
+    /// A function to check and save the status, the authority_diff is resolved into the full
+    /// authority_list of the returned status, so the wal never depends on older statuses.
     pub(crate) fn check_and_save_status(
         &mut self,
-        status: &Status,
+        mut status: Status,
         need_wal: bool,
-    ) -> BftResult<()> {
+    ) -> BftResult<Status> {
         let height = status.height;
-        if self.height > 0 && height < self.height - 1 {
-            return Err(BftError::ObsoleteMsg(format!("{:?}", status)));
+        if self.is_below_last_height(height) {
+            return Err(BftError::ObsoleteMsg(format!("{:?}", status).into()));
+        }
+        let timeouts = status
+            .consensus_params
+            .as_ref()
+            .and_then(|params| params.timeouts);
+        if timeouts.is_some_and(|timeouts| !timeouts.is_valid()) {
+            return Err(BftError::InvalidParams(format!("{:?}", status).into()));
+        }
+        if let Some(authority_diff) = status.authority_diff.take() {
+            check_list_len(
+                "authority_diff",
+                authority_diff.add.len() + authority_diff.remove.len(),
+                self.params.config.max_authorities,
+            )?;
+            // the diff is based on the authorities of the status height
+            if height != self.height {
+                return Err(BftError::InvalidAuthorities(
+                    format!("{:?} at height {}", status, self.height).into(),
+                ));
+            }
+            status.authority_list = self.authority_manage.apply_diff(&authority_diff)?;
+        }
+        check_authorities(&status.authority_list).map_err(|e| {
+            BftError::InvalidAuthorities(
+                ErrorInfo::new(format!("{:?} of the status", e.info())).height(height),
+            )
+        })?;
+        check_list_len(
+            "scheduled_authorities",
+            status.scheduled_authorities.len(),
+            self.params.config.max_authorities,
+        )?;
+        for scheduled in status.scheduled_authorities.iter() {
+            check_list_len(
+                "scheduled authority_list",
+                scheduled.authority_list.len(),
+                self.params.config.max_authorities,
+            )?;
+        }
+        self.authority_manage
+            .schedule_authorities(mem::take(&mut status.scheduled_authorities))?;
+        // the scheduled list takes the place of the one in a late status at the boundary
+        if let Some(authority_list) = self.authority_manage.take_activated(next_height(height)?) {
+            info!(
+                target: &self.params.log_target,
+                "Node {:?} switches to the scheduled authority_list at height {}",
+                self.params.address,
+                next_height(height)?
+            );
+            status.authority_list = authority_list;
+        }
+        // the whole schedule is saved along, as the announcing statuses may be pruned from wal
+        status.scheduled_authorities = self.authority_manage.pending_schedule();
+        for rotation in self.authority_manage.take_rotations(next_height(height)?) {
+            if let Some(node) = status
+                .authority_list
+                .iter_mut()
+                .find(|node| node.address == rotation.old)
+            {
+                node.address = rotation.new;
+            }
+            if rotation.old == self.params.address {
+                self.pending_key = Some(rotation);
+            }
         }
+        check_list_len(
+            "authority_list",
+            status.authority_list.len(),
+            self.params.config.max_authorities,
+        )?;
         if need_wal {
             handle_err(
                 self.wal_log
-                    .save(self.height + 1, LogType::Proof, &rlp::encode(&self.proof))
-                    .or_else(|e| {
-                        Err(BftError::SaveWalErr(format!(
-                            "{:?} of {:?}",
-                            e, &self.proof
-                        )))
+                    .save(
+                        next_height(self.height)?,
+                        LogType::Proof,
+                        &self.encode(&self.proof),
+                    )
+                    .map_err(|e| {
+                        BftError::SaveWalErr(format!("{:?} of {:?}", e, &self.proof).into())
                     }),
-                &self.params.address,
+                &self.params,
+                &*self.function,
             );
-            let status_height = status.height;
+            let status_height = next_height(status.height)?;
             handle_err(
-                self.wal_log
-                    .save(status_height + 1, LogType::Status, &rlp::encode(status))
-                    .or_else(|e| Err(BftError::SaveWalErr(format!("{:?} of {:?}", e, status)))),
-                &self.params.address,
+                self.save_encoded(status_height, LogType::Status, &status)
+                    .map_err(|e| BftError::SaveWalErr(format!("{:?} of {:?}", e, status).into())),
+                &self.params,
+                &*self.function,
             );
         }
 
+        Ok(status)
+    }
+
+    /// A function to check the signature of the key rotation by its old address and keep it
+    /// until the status of the height before its activation height.
+    pub(crate) fn check_and_save_key_rotation(
+        &mut self,
+        rotation: KeyRotation,
+        need_wal: bool,
+    ) -> BftResult<()> {
+        if rotation.activation_height <= self.height {
+            return Err(BftError::ObsoleteMsg(
+                format!("{:?} at height {}", rotation, self.height).into(),
+            ));
+        }
+        let hash = self
+            .function
+            .crypt_hash(&rotation.signed_msg(self.params.config.rlp_codec));
+        let address = self
+            .recover_sig(&rotation.signature, &hash)
+            .map_err(|e| BftError::CheckSigFailed(format!("{:?} of {:?}", e, rotation).into()))?;
+        if address != rotation.old {
+            return Err(BftError::CheckSigFailed(
+                format!("recovers {:?} of {:?}", address, rotation).into(),
+            ));
+        }
+        info!(
+            target: &self.params.log_target,
+            "Node {:?} accepts {:?}", self.params.address, rotation
+        );
+        self.authority_manage.add_rotation(rotation)?;
+        if need_wal {
+            self.save_authorities(self.height);
+        }
         Ok(())
     }
 
     pub(crate) fn check_and_save_verify_resp(
         &mut self,
         verify_resp: &VerifyResp,
+        block_hash: &Hash,
         need_wal: bool,
     ) -> BftResult<()> {
         if need_wal {
             handle_err(
-                self.wal_log
-                    .save(self.height, LogType::VerifyResp, &rlp::encode(verify_resp))
-                    .or_else(|_| Err(BftError::SaveWalErr(format!("{:?}", verify_resp)))),
-                &self.params.address,
+                self.save_encoded(self.height, LogType::VerifyResp, verify_resp)
+                    .map_err(|_| BftError::SaveWalErr(format!("{:?}", verify_resp).into())),
+                &self.params,
+                &*self.function,
             );
         }
-        self.save_verify_res(verify_resp.round, verify_resp)?;
+        self.save_verify_res(block_hash, verify_resp)?;
 
         Ok(())
     }
 
-    pub(crate) fn check_and_save_feed(&mut self, feed: &Feed, need_wal: bool) -> BftResult<()> {
+    pub(crate) fn check_and_save_feed(&mut self, feed: Feed<B>, need_wal: bool) -> BftResult<()> {
         let height = feed.height;
         if height < self.height {
-            return Err(BftError::ObsoleteMsg(format!(
-                "feed with height {}",
-                height
-            )));
+            return Err(BftError::ObsoleteMsg(
+                format!("feed with height {}", height).into(),
+            ));
         }
 
         if height > self.height {
-            return Err(BftError::HigherMsg(format!("feed with height {}", height)));
+            if self.pipelined_height == Some(height) {
+                // the block got in advance is not saved in wal, it is got again after a restart
+                let block_hash = feed.block_hash;
+                self.blocks.add(height, &block_hash, &Arc::new(feed.block));
+                self.next_feed = Some(block_hash);
+                return Ok(());
+            }
+            return Err(BftError::HigherMsg(
+                format!("feed with height {}", height).into(),
+            ));
         }
 
         if need_wal {
             handle_err(
-                self.wal_log
-                    .save(height, LogType::Feed, &rlp::encode(feed))
-                    .or_else(|e| {
-                        Err(BftError::SaveWalErr(format!(
-                            "{:?} of feed with height {}",
-                            e, height
-                        )))
+                self.save_encoded(height, LogType::Feed, &feed)
+                    .map_err(|e| {
+                        BftError::SaveWalErr(
+                            format!("{:?} of feed with height {}", e, height).into(),
+                        )
                     }),
-                &self.params.address,
+                &self.params,
+                &*self.function,
             );
         }
 
-        let block_hash = feed.block_hash.clone();
-        self.blocks.add(height, &block_hash, &feed.block);
+        let block_hash = feed.block_hash;
+        self.blocks.add(height, &block_hash, &Arc::new(feed.block));
         self.feed = Some(block_hash);
         Ok(())
     }
 
-    pub(crate) fn check_block_txs(
+    pub(crate) fn check_and_revoke_feed(
         &mut self,
-        proposal: &Proposal,
-        block: &Block,
-        signed_proposal_hash: &Hash,
+        height: Height,
+        block_hash: &Hash,
+        need_wal: bool,
+    ) -> BftResult<()> {
+        if height != self.height || self.feed.as_ref() != Some(block_hash) {
+            return Err(BftError::ObsoleteMsg(
+                format!("revoking feed {:?} with height {}", block_hash, height).into(),
+            ));
+        }
+
+        if need_wal {
+            handle_err(
+                self.wal_log
+                    .save(
+                        height,
+                        LogType::RevokeFeed,
+                        &encode_revoke_feed(height, block_hash, self.params.config.rlp_codec),
+                    )
+                    .map_err(|e| {
+                        BftError::SaveWalErr(
+                            format!("{:?} of revoking feed with height {}", e, height).into(),
+                        )
+                    }),
+                &self.params,
+                &*self.function,
+            );
+        }
+
+        // the block is kept in the cache, as a sent proposal may refer to it
+        self.feed = None;
+        Ok(())
+    }
+
+    /// A function to request the txs missing in the compact block from the proposer, the
+    /// proposal is parked until the txs arrive. It is fetched at most once.
+    #[cfg(feature = "compact_block")]
+    pub(crate) fn fetch_missing_txs(
+        &mut self,
+        proposal: &Proposal,
+        block: &Arc<B>,
+        signed_proposal_encode: &[u8],
+    ) -> BftResult<()> {
+        let block_hash = &proposal.block_hash;
+        if self.verify_results.contains_key(block_hash) {
+            return Ok(());
+        }
+        let tx_hashes = self.function.missing_txs(block);
+        if tx_hashes.is_empty() {
+            return Ok(());
+        }
+        if let Some(parked) = self.txs_requests.get(block_hash) {
+            return if parked.is_some() {
+                Err(BftError::NotReady(
+                    format!("waits for txs of {:?}", proposal).into(),
+                ))
+            } else {
+                Err(BftError::CheckBlockFailed(
+                    format!(
+                        "lacks {} txs after fetching of {:?}",
+                        tx_hashes.len(),
+                        proposal
+                    )
+                    .into(),
+                ))
+            };
+        }
+
+        let get_txs = GetTxs {
+            height: proposal.height,
+            block_hash: *block_hash,
+            tx_hashes,
+            requester: self.params.address,
+        };
+        debug!(
+            target: &self.params.log_target,
+            "Node {:?} fetches {} txs of {:?} from {:?}",
+            self.params.address,
+            get_txs.tx_hashes.len(),
+            block_hash,
+            proposal.proposer
+        );
+        self.txs_requests.insert(
+            *block_hash,
+            Some(combine_two(signed_proposal_encode, &block.to_bytes())),
+        );
+        self.transmit(
+            OutboundMsg::GetTxs(self.encode(&get_txs)),
+            Target::Unicast(proposal.proposer),
+        );
+        Err(BftError::NotReady(
+            format!("fetches txs of {:?}", proposal).into(),
+        ))
+    }
+
+    pub(crate) fn check_block_txs(
+        &mut self,
+        proposal: &Proposal,
+        block: &Arc<B>,
+        signed_proposal_hash: &Hash,
     ) -> BftResult<()> {
         let height = proposal.height;
         let round = proposal.round;
         let block_hash = &proposal.block_hash;
 
+        // the block re-proposed in a later round is not verified again
+        if let Some(verify_resp) = self.verify_results.get(block_hash) {
+            debug!(
+                target: &self.params.log_target,
+                "Node {:?} reuses {:?} of block_hash {:?}",
+                self.params.address,
+                verify_resp,
+                block_hash
+            );
+            return if cfg!(feature = "verify_req") || verify_resp.is_pass {
+                Ok(())
+            } else {
+                Err(BftError::CheckBlockFailed(
+                    format!("{:?} of {:?}", verify_resp.failure, proposal).into(),
+                ))
+            };
+        }
+
+        // the block being checked ahead is checked again in its round without verify_req, as
+        // the main loop can not wait for the result
+        if self.speculating.contains(block_hash)
+            && (round > self.round || cfg!(feature = "verify_req"))
+        {
+            return Ok(());
+        }
+        // the block of a future round is checked ahead by the block verifier pool, it is
+        // checked as usual if the pool is busy
+        if round > self.round {
+            if let Some(block_verifier) = self.block_verifier.as_ref() {
+                let job = BlockJob {
+                    height,
+                    round,
+                    block: block.clone(),
+                    block_hash: *block_hash,
+                    signed_proposal_hash: *signed_proposal_hash,
+                    is_lock: proposal.lock_round.is_some(),
+                    proposer: proposal.proposer,
+                };
+                if block_verifier.dispatch(job).is_ok() {
+                    #[cfg(feature = "verify_req")]
+                    {
+                        if let Some(millis) = self.function.verify_deadline(block) {
+                            self.verify_deadlines
+                                .insert(*block_hash, Duration::from_millis(millis));
+                        }
+                    }
+                    self.speculating.insert(*block_hash);
+                    return Ok(());
+                }
+            }
+        }
+
         #[cfg(not(feature = "verify_req"))]
         {
             let verify_resp = self
                 .function
-                .check_block(block, block_hash, signed_proposal_hash, (height, round), proposal.lock_round.is_some(), proposal.proposer)
-                .map_err(|e| BftError::CheckBlockFailed(format!("{:?} of {:?}", e, proposal)))?;
-            self.check_and_save_verify_resp(&verify_resp, false)?;
+                .check_block(
+                    block,
+                    block_hash,
+                    signed_proposal_hash,
+                    (height, round),
+                    proposal.lock_round.is_some(),
+                    &proposal.proposer,
+                )
+                .map_err(|e| {
+                    BftError::CheckBlockFailed(format!("{:?} of {:?}", e, proposal).into())
+                })?;
+            self.check_and_save_verify_resp(&verify_resp, block_hash, false)?;
             if verify_resp.is_pass {
-                return Ok(());
+                Ok(())
             } else {
-                return Err(BftError::CheckBlockFailed(format!("of {:?}", proposal)));
+                Err(BftError::CheckBlockFailed(
+                    format!("{:?} of {:?}", verify_resp.failure, proposal).into(),
+                ))
             }
         }
 
         #[cfg(feature = "verify_req")]
         {
+            if let Some(millis) = self.function.verify_deadline(block) {
+                self.verify_deadlines
+                    .insert(*block_hash, Duration::from_millis(millis));
+            }
             let function = self.function.clone();
             let sender = self.msg_sender.clone();
             let block = block.clone();
-            let block_hash = block_hash.clone();
+            let block_hash = *block_hash;
             let is_lock = proposal.lock_round.is_some();
-            let signed_proposal_hash = signed_proposal_hash.clone();
-            let address = self.params.address.clone();
-            let proposer = proposal.proposer.clone();
+            let signed_proposal_hash = *signed_proposal_hash;
+            let params = self.params.clone();
+            let proposer = proposal.proposer;
             thread::spawn(move || {
                 match function.check_block(
                     &block,
                     &block_hash,
                     &signed_proposal_hash,
-                    (height ,round),
+                    (height, round),
                     is_lock,
                     &proposer,
                 ) {
@@ -649,14 +1651,15 @@ where
                         handle_err(
                             sender
                                 .send(BftMsg::VerifyResp(verify_resp))
-                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e))),
-                            &address,
+                                .map_err(|e| BftError::SendMsgErr(format!("{:?}", e).into())),
+                            &params,
+                            &*function,
                         );
                     }
                     Err(e) => {
                         warn!(
-                            "Node {:?} encounters BftError::CheckTxsFailed({:?})",
-                            address, e
+                            target: &params.log_target,
+                            "Node {:?} encounters BftError::CheckTxsFailed({:?})", params.address, e
                         );
                     }
                 };
@@ -668,107 +1671,141 @@ where
 
     pub(crate) fn check_proof(&mut self, height: Height, proof: &Proof) -> BftResult<()> {
         if height != self.height {
-            return Err(BftError::ShouldNotHappen(format!(
-                "check_proof for {:?}",
-                proof
-            )));
+            return Err(BftError::ShouldNotHappen(
+                format!("check_proof for {:?}", proof).into(),
+            ));
         }
 
-        let authorities = self.get_authorities(height)?;
-        self.check_proof_only(proof, height, authorities)?;
+        let authorities = self.get_authorities(height)?.clone();
+        self.check_proof_only(proof, height, &authorities)?;
         self.set_proof(proof);
 
         Ok(())
     }
 
     pub(crate) fn check_proof_only(
-        &self,
+        &mut self,
         proof: &Proof,
         height: Height,
         authorities: &[Node],
     ) -> BftResult<()> {
-        if proof.height == 0 {
-            return Ok(());
-        }
-        if height != proof.height + 1 {
-            return Err(BftError::CheckProofFailed(format!(
-                "the height {} is mismatching with proof.height {}",
-                height, proof.height
-            )));
-        }
-
-        let vote_addresses: Vec<Address> = proof
-            .precommit_votes
-            .iter()
-            .map(|(voter, _)| voter.clone())
-            .collect();
+        let info = |detail: String| {
+            ErrorInfo::new(detail)
+                .at(proof.height, proof.round)
+                .kind(MsgKind::Proof)
+        };
 
-        if get_votes_weight(authorities, &vote_addresses) * 3 <= get_total_weight(authorities) * 2 {
-            return Err(BftError::CheckProofFailed(format!(
-                "the proof doesn't collect 2/3+ weight \n {:?} ",
-                proof
-            )));
-        }
+        // the height, the block hash, the voters, the weight and the authorities_hash are
+        // checked by the verify core, the signatures are recovered in batch below
+        let function = &self.function;
+        verify::check_proof_votes(proof, height, authorities, self.msg_codec, |msg| {
+            function.crypt_hash(msg)
+        })
+        .map_err(|e| match e {
+            ProofError::NilProof => BftError::NilProof(info(format!("{:?}", proof))),
+            e => BftError::CheckProofFailed(info(format!("{:?} in {:?}", e, proof))),
+        })?;
 
-        let authority_addresses: Vec<Address> = authorities
+        let (voters, batch): (Vec<Address>, Vec<(Signature, Hash)>) = proof
+            .precommit_votes
             .iter()
-            .map(|node| node.address.clone())
-            .collect();
-        for (voter, sig) in proof.precommit_votes.clone() {
-            if authority_addresses.contains(&voter) {
-                let vote = Vote {
-                    vote_type: VoteType::Precommit,
-                    height: proof.height,
-                    round: proof.round,
-                    block_hash: proof.block_hash.clone(),
-                    voter: voter.clone(),
-                };
-                let msg = rlp::encode(&vote);
-                let address = self
-                    .function
-                    .check_sig(&sig, &self.function.crypt_hash(&msg))
-                    .map_err(|e| {
-                        BftError::CheckProofFailed(format!("{:?}, sig {:?} in {:?}", e, sig, proof))
-                    })?;
-                if address != voter {
-                    return Err(BftError::CheckProofFailed(format!(
+            .map(|(voter, sig)| {
+                let precommit = precommit_of_proof(proof, *voter);
+                (
+                    *voter,
+                    (sig.clone(), self.hash_signed(SignKind::Vote, &precommit)),
+                )
+            })
+            .unzip();
+        let addresses = self.recover_sigs(&batch);
+        for ((voter, (sig, _)), address) in voters.iter().zip(&batch).zip(addresses) {
+            match address {
+                Some(address) if address == *voter => {}
+                Some(address) => {
+                    return Err(BftError::CheckProofFailed(info(format!(
                         "recover {:?} by voter {:?} in {:?}",
-                        &address, &voter, proof
-                    )));
+                        &address, voter, proof
+                    ))));
+                }
+                None => {
+                    return Err(BftError::CheckProofFailed(info(format!(
+                        "check sig failed, sig {:?} in {:?}",
+                        sig, proof
+                    ))));
                 }
-            } else {
-                return Err(BftError::CheckProofFailed(format!(
-                    "voter {:?} invalid in {:?}",
-                    &voter, proof
-                )));
             }
         }
 
         Ok(())
     }
 
+    /// A function to get the timestamp of the proposals, `None` if the stamping is disabled.
+    pub(crate) fn proposal_timestamp(&self) -> Option<u64> {
+        if self.params.config.proposal_timestamp {
+            Some(self.clock_millis())
+        } else {
+            None
+        }
+    }
+
+    /// A function to get the wall clock of the local clock, in milliseconds.
+    fn clock_millis(&self) -> u64 {
+        match &self.params.config.clock {
+            Some(clock) => clock.unix_millis(),
+            None => unix_millis(),
+        }
+    }
+
+    fn check_timestamp(&self, proposal: &Proposal) -> BftResult<()> {
+        if let Some(timestamp) = proposal.timestamp {
+            let now = self.clock_millis();
+            let drift = timestamp.abs_diff(now);
+            if drift > self.params.config.max_clock_drift_millis {
+                return Err(BftError::CheckTimestampFailed(
+                    ErrorInfo::new(format!("drift {}ms of {:?}", drift, proposal))
+                        .at(proposal.height, proposal.round)
+                        .sender(proposal.proposer)
+                        .kind(MsgKind::Proposal),
+                ));
+            }
+        }
+        Ok(())
+    }
+
     pub(crate) fn check_lock_votes(
         &mut self,
         proposal: &Proposal,
         block_hash: &Hash,
     ) -> BftResult<()> {
         let height = proposal.height;
-        if height < self.height - 1 || height > self.height {
-            return Err(BftError::ShouldNotHappen(format!(
-                "check_lock_votes for {:?}",
-                proposal
-            )));
+        if self.is_below_last_height(height) || height > self.height {
+            return Err(BftError::ShouldNotHappen(
+                format!("check_lock_votes for {:?}", proposal).into(),
+            ));
         }
 
         let mut map = HashMap::new();
         if let Some(lock_round) = proposal.lock_round {
-            for signed_vote in &proposal.lock_votes {
-                let voter = self.check_vote(height, lock_round, block_hash, signed_vote)?;
+            let batch: Vec<(Signature, Hash)> = proposal
+                .lock_votes
+                .iter()
+                .map(|signed_vote| {
+                    let vote_hash = self.hash_signed(SignKind::Vote, &signed_vote.vote);
+                    (signed_vote.signature.clone(), vote_hash)
+                })
+                .collect();
+            let addresses = self.recover_sigs(&batch);
+            for (signed_vote, address) in proposal.lock_votes.iter().zip(addresses) {
+                let voter =
+                    self.check_vote(height, lock_round, block_hash, signed_vote, address)?;
                 if map.insert(voter, 1).is_some() {
-                    return Err(BftError::CheckLockVotesFailed(format!(
-                        "vote repeat of {:?} in {:?} with lock_votes {:?}",
-                        signed_vote, proposal, &proposal.lock_votes
-                    )));
+                    return Err(BftError::CheckLockVotesFailed(
+                        format!(
+                            "vote repeat of {:?} in {:?} with lock_votes {:?}",
+                            signed_vote, proposal, &proposal.lock_votes
+                        )
+                        .into(),
+                    ));
                 }
             }
         } else {
@@ -779,76 +1816,85 @@ where
         let vote_addresses: Vec<Address> = proposal
             .lock_votes
             .iter()
-            .map(|signed_vote| signed_vote.vote.voter.clone())
+            .map(|signed_vote| signed_vote.vote.voter)
             .collect();
 
-        if get_votes_weight(authorities, &vote_addresses) * 3 > get_total_weight(authorities) * 2 {
+        if is_above_threshold(
+            get_votes_weight(authorities, &vote_addresses),
+            get_total_weight(authorities),
+        ) {
             return Ok(());
         }
-        Err(BftError::CheckLockVotesFailed(format!(
-            "less than 2/3+ weight of {:?} with lock_votes {:?}",
-            proposal, &proposal.lock_votes
-        )))
+        Err(BftError::CheckLockVotesFailed(
+            format!(
+                "less than 2/3+ weight of {:?} with lock_votes {:?}",
+                proposal, &proposal.lock_votes
+            )
+            .into(),
+        ))
     }
 
+    /// A function to check a lock vote with the [`address`] recovered from its signature.
     pub(crate) fn check_vote(
         &mut self,
         height: Height,
         round: Round,
         block_hash: &Hash,
         signed_vote: &SignedVote,
+        address: Option<Address>,
     ) -> BftResult<Address> {
-        if height < self.height - 1 {
-            return Err(BftError::ShouldNotHappen(format!(
-                "check_vote for {:?}",
-                signed_vote
-            )));
+        if self.is_below_last_height(height) {
+            return Err(BftError::ShouldNotHappen(
+                format!("check_vote for {:?}", signed_vote).into(),
+            ));
         }
 
         let vote = &signed_vote.vote;
         if vote.height != height || vote.round != round {
-            return Err(BftError::CheckLockVotesFailed(format!(
-                "vote {:?} mismatching height: {} or round: {}",
-                signed_vote, height, round
-            )));
+            return Err(BftError::CheckLockVotesFailed(
+                format!(
+                    "vote {:?} mismatching height: {} or round: {}",
+                    signed_vote, height, round
+                )
+                .into(),
+            ));
         }
 
         if &vote.block_hash != block_hash {
-            return Err(BftError::CheckLockVotesFailed(format!(
-                "vote {:?} not for rightful block_hash {:?}",
-                vote, block_hash
-            )));
+            return Err(BftError::CheckLockVotesFailed(
+                format!(
+                    "vote {:?} not for rightful block_hash {:?}",
+                    vote, block_hash
+                )
+                .into(),
+            ));
         }
 
         let authorities = self.get_authorities(height)?;
         let voter = &vote.voter;
         if !authorities.iter().any(|node| &node.address == voter) {
-            return Err(BftError::CheckLockVotesFailed(format!(
-                "the voter {:?} not in authorities",
-                voter
-            )));
+            return Err(BftError::CheckLockVotesFailed(
+                format!("the voter {:?} not in authorities", voter).into(),
+            ));
         }
 
-        let signature = &signed_vote.signature;
-        let vote_hash = self.function.crypt_hash(&rlp::encode(vote));
-        let address = self
-            .function
-            .check_sig(signature, &vote_hash)
-            .map_err(|e| {
-                BftError::CheckLockVotesFailed(format!(
-                    "check sig failed with {:?} of {:?}",
-                    e, signed_vote
-                ))
-            })?;
+        let address = address.ok_or_else(|| {
+            BftError::CheckLockVotesFailed(format!("check sig failed of {:?}", signed_vote).into())
+        })?;
         if &address != voter {
-            return Err(BftError::CheckLockVotesFailed(format!(
-                "recover {:?} of {:?}",
-                &address, signed_vote
-            )));
+            return Err(BftError::CheckLockVotesFailed(
+                format!("recover {:?} of {:?}", &address, signed_vote).into(),
+            ));
         }
 
-        let vote_weight = self.get_vote_weight(height, &voter);
-        let _ = self.votes.add(&signed_vote, vote_weight, self.height);
+        let vote_weight = self.get_vote_weight(height, voter);
+        let _ = self.votes.add(
+            signed_vote,
+            vote_weight,
+            self.height,
+            self.authority_manage.weight_sum,
+            self.authority_manage.authorities.len(),
+        );
         Ok(address)
     }
 
@@ -857,20 +1903,21 @@ where
         let round = proposal.round;
         let address = &proposal.proposer;
 
-        if height < self.height - 1 || height > self.height {
-            return Err(BftError::ShouldNotHappen(format!(
-                "check_proposer for {:?}",
-                proposal
-            )));
+        if self.is_below_last_height(height) || height > self.height {
+            return Err(BftError::ShouldNotHappen(
+                format!("check_proposer for {:?}", proposal).into(),
+            ));
         }
         let proposer = self.get_proposer(height, round)?;
         if proposer == address {
             Ok(())
         } else {
-            Err(BftError::InvalidSender(format!(
-                "the rightful proposer is {:?} by {:?}",
-                proposer, proposal
-            )))
+            Err(BftError::InvalidSender(
+                ErrorInfo::new(format!("the rightful proposer is {:?}", proposer))
+                    .at(height, round)
+                    .sender(*address)
+                    .kind(MsgKind::Proposal),
+            ))
         }
     }
 
@@ -878,25 +1925,303 @@ where
         let height = vote.height;
         let voter = &vote.voter;
 
-        if height < self.height - 1 || height > self.height {
-            return Err(BftError::ShouldNotHappen(format!(
-                "check_voter for {:?}",
-                vote
-            )));
+        if self.is_below_last_height(height) || height > self.height {
+            return Err(BftError::ShouldNotHappen(
+                format!("check_voter for {:?}", vote).into(),
+            ));
         }
 
         let authorities = self.get_authorities(height)?;
 
         if !authorities.iter().any(|node| node.address == *voter) {
-            return Err(BftError::InvalidSender(format!(
-                "the {:?} of {:?} not in authorities",
-                voter, vote
-            )));
+            return Err(BftError::InvalidSender(
+                ErrorInfo::new(format!("{:?} not in authorities", vote))
+                    .at(height, vote.round)
+                    .sender(*voter)
+                    .kind(MsgKind::Vote),
+            ));
         }
 
         Ok(())
     }
 
+    /// Check the claimed sender of a msg against the ban list and its rate limit before
+    /// verifying the signature. Msgs replayed from wal are never limited.
+    pub(crate) fn check_sender_limit(&mut self, sender: &Address, need_wal: bool) -> BftResult<()> {
+        // the limit is checked before the msg is dispatched to the verifier pool
+        if !need_wal || self.checked_sigs.is_some() {
+            return Ok(());
+        }
+        if self.peer_score.is_banned(sender) {
+            return Err(BftError::BannedSender(
+                ErrorInfo::new("banned for signature failures").sender(*sender),
+            ));
+        }
+        if !self.rate_limiter.check(sender) {
+            return Err(BftError::RateLimited(
+                ErrorInfo::new("exceeds the msg rate").sender(*sender),
+            ));
+        }
+        Ok(())
+    }
+
+    /// A function to record the [`proposer`] of the block committed at the current height, and
+    /// report the proposers whose shares newly deviate beyond the tolerance.
+    pub(crate) fn record_proposer(&mut self, proposer: Address) {
+        if !self.proposer_window.record(self.height, proposer) {
+            return;
+        }
+        let tolerance = match self.params.config.proposer_share_tolerance {
+            Some(tolerance) => tolerance,
+            None => return,
+        };
+        let deviations = self
+            .proposer_window
+            .check_deviations(&self.authority_manage.authorities, tolerance);
+        for (address, share) in deviations {
+            warn!(
+                target: &self.params.log_target,
+                "Node {:?} finds the proposer share of {:?} deviating: {:?}",
+                self.params.address,
+                address,
+                share
+            );
+            self.function.on_proposer_deviation(&address, &share);
+        }
+    }
+
+    /// A function to get the approximate bytes of the cached blocks, proposals and votes.
+    pub(crate) fn cache_usage(&self) -> CacheUsage {
+        CacheUsage {
+            blocks: self.blocks.bytes(),
+            proposals: self.proposals.bytes(),
+            votes: self.votes.bytes(),
+        }
+    }
+
+    /// A function to evict the caches of the lowest heights until the approximate bytes are
+    /// within the `cache_bytes_cap`, the current height is never evicted.
+    pub(crate) fn enforce_cache_cap(&mut self) {
+        let cap = self.params.config.cache_bytes_cap;
+        if cap == 0 {
+            return;
+        }
+        loop {
+            let usage = self.cache_usage();
+            let bytes = usage.blocks + usage.proposals + usage.votes;
+            if bytes <= cap {
+                return;
+            }
+            let lowest = self
+                .blocks
+                .blocks
+                .iter()
+                .map(|(height, _)| *height)
+                .chain(self.proposals.proposals.iter().map(|(height, _)| *height))
+                .chain(self.votes.votes.iter().map(|(height, _)| *height))
+                .filter(|height| *height != self.height)
+                .min();
+            let height = match lowest {
+                Some(height) => height,
+                None => return,
+            };
+            warn!(
+                target: &self.params.log_target,
+                "Node {:?} evicts the caches of height {} as they take {} bytes over the cap {}",
+                self.params.address,
+                height,
+                bytes,
+                cap
+            );
+            self.blocks.evict(height);
+            self.proposals.evict(height);
+            self.votes.evict(height);
+        }
+    }
+
+    /// Check the height and the round peeked from an inbound proposal or vote before decoding
+    /// it and verifying its signature, the ones too far from the current height and round are
+    /// dropped and counted. The undecodable msgs are rejected later.
+    pub(crate) fn filter_ingress(&mut self, msg: &BftMsg<B>) -> BftResult<()> {
+        let (height, round) = match msg {
+            BftMsg::Proposal(encode) => match extract_proposal(encode)
+                .ok()
+                .and_then(|(signed_proposal, _, _)| peek_height_round(signed_proposal, 0))
+            {
+                Some(height_round) => height_round,
+                None => return Ok(()),
+            },
+            BftMsg::Vote(encode) => match peek_height_round(encode, 1) {
+                Some(height_round) => height_round,
+                None => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        let stats = &mut self.ingress_stats;
+        let counter = if height >= self.height.saturating_add(CACHE_N) {
+            &mut stats.higher_heights
+        } else if height.saturating_add(CACHE_N) < self.height {
+            &mut stats.lower_heights
+        } else if height == self.height && round >= self.round.saturating_add(CACHE_N) {
+            &mut stats.higher_rounds
+        } else if height == self.height && round.saturating_add(CACHE_N) < self.round {
+            &mut stats.lower_rounds
+        } else {
+            return Ok(());
+        };
+        *counter += 1;
+        Err(BftError::OutOfWindow(
+            ErrorInfo::new("dropped before the signature check").at(height, round),
+        ))
+    }
+
+    /// A function to look up a signature in the verified-signature cache and the ones checked
+    /// by the verifier pool, return `Some(None)` if it is known to be invalid.
+    fn lookup_sig(&mut self, key: &(Signature, Hash)) -> Option<Option<Address>> {
+        if let Some(address) = self.sig_cache.get_mut(key) {
+            return Some(Some(*address));
+        }
+        self.checked_sigs
+            .as_ref()
+            .and_then(|sigs| sigs.get(key))
+            .copied()
+    }
+
+    /// A function to recover the address of a signature, the verified ones are not checked again.
+    pub(crate) fn recover_sig(
+        &mut self,
+        signature: &Signature,
+        hash: &Hash,
+    ) -> Result<Address, String> {
+        let key = (signature.clone(), *hash);
+        if let Some(address) = self.lookup_sig(&key) {
+            return address.ok_or_else(|| "invalid signature".to_string());
+        }
+        let address = self
+            .function
+            .check_sig(signature, hash)
+            .map_err(|e| format!("{:?}", e))?;
+        self.sig_cache.insert(key, address);
+        Ok(address)
+    }
+
+    /// A function to recover the addresses of a batch of signatures by `check_sigs`,
+    /// return `None` for the invalid ones. The verified ones are not checked again.
+    pub(crate) fn recover_sigs(&mut self, batch: &[(Signature, Hash)]) -> Vec<Option<Address>> {
+        let mut addresses: Vec<Option<Option<Address>>> =
+            batch.iter().map(|key| self.lookup_sig(key)).collect();
+        let unchecked: Vec<(Signature, Hash)> = batch
+            .iter()
+            .zip(&addresses)
+            .filter(|(_, address)| address.is_none())
+            .map(|(key, _)| key.clone())
+            .collect();
+        if !unchecked.is_empty() {
+            let mut results = self.function.check_sigs(&unchecked).into_iter();
+            for (key, address) in batch
+                .iter()
+                .zip(addresses.iter_mut())
+                .filter(|(_, address)| address.is_none())
+            {
+                // a missing result is taken as invalid
+                let result = results.next().unwrap_or(None);
+                if let Some(recovered) = result {
+                    self.sig_cache.insert(key.clone(), recovered);
+                }
+                *address = Some(result);
+            }
+        }
+        addresses
+            .into_iter()
+            .map(|address| address.unwrap_or(None))
+            .collect()
+    }
+
+    /// A function to save the signatures checked by the verifier pool, the valid ones
+    /// are cached.
+    pub(crate) fn save_checked_sigs(&mut self, sigs: CheckedSigs) {
+        for (key, address) in &sigs {
+            if let Some(address) = address {
+                self.sig_cache.insert(key.clone(), *address);
+            }
+        }
+        self.checked_sigs = Some(sigs);
+    }
+
+    /// A function to collect the signatures of a signed_vote to check by the verifier pool,
+    /// the verified ones are skipped.
+    pub(crate) fn collect_vote_sigs(&mut self, encode: &[u8]) -> BftResult<Vec<(Signature, Hash)>> {
+        let signed_vote: SignedVote = rlp::decode(encode)
+            .map_err(|e| BftError::DecodeErr(format!("signed_vote encounters {:?}", e).into()))?;
+        let vote = &signed_vote.vote;
+        if self.is_below_last_height(vote.height) {
+            return Err(BftError::ObsoleteMsg(format!("{:?}", signed_vote).into()));
+        }
+        self.check_sender_limit(&vote.voter, true)?;
+        let vote_hash = self.hash_signed(SignKind::Vote, vote);
+        let mut sigs = vec![(signed_vote.signature, vote_hash)];
+        sigs.retain(|key| !self.sig_cache.contains_key(key));
+        Ok(sigs)
+    }
+
+    /// A function to collect the signatures of a signed_proposal to check by the verifier pool,
+    /// including the lock_votes and the proof to check along with it. The verified ones are skipped.
+    pub(crate) fn collect_proposal_sigs(
+        &mut self,
+        encode: &[u8],
+    ) -> BftResult<Vec<(Signature, Hash)>> {
+        // the compressed block is checked after decompressed in process
+        let (signed_proposal_encode, block, _) = extract_proposal(encode)?;
+        check_block_size(block, &self.params.config)?;
+        check_signed_proposal_size(signed_proposal_encode, &self.params.config)?;
+        let signed_proposal: SignedProposal = rlp::decode(signed_proposal_encode).map_err(|e| {
+            BftError::DecodeErr(format!("signed_proposal encounters {:?}", e).into())
+        })?;
+        let proposal = &signed_proposal.proposal;
+        let height = proposal.height;
+        if self.is_below_last_height(height) {
+            return Err(BftError::ObsoleteMsg(
+                format!("{:?}", signed_proposal).into(),
+            ));
+        }
+        self.check_sender_limit(&proposal.proposer, true)?;
+
+        let mut sigs = vec![(
+            signed_proposal.signature.clone(),
+            self.hash_signed(SignKind::Proposal, proposal),
+        )];
+        if height == self.height || self.is_last_height(height) {
+            if proposal.lock_round.is_some() {
+                sigs.extend(proposal.lock_votes.iter().map(|signed_vote| {
+                    (
+                        signed_vote.signature.clone(),
+                        self.hash_signed(SignKind::Vote, &signed_vote.vote),
+                    )
+                }));
+            }
+            let proof = &proposal.proof;
+            if height == self.height && proof.height != 0 {
+                sigs.extend(proof.precommit_votes.iter().map(|(voter, sig)| {
+                    let precommit = precommit_of_proof(proof, *voter);
+                    (sig.clone(), self.hash_signed(SignKind::Vote, &precommit))
+                }));
+            }
+        }
+        sigs.retain(|key| !self.sig_cache.contains_key(key));
+        Ok(sigs)
+    }
+
+    pub(crate) fn record_sig_failure(&mut self, sender: &Address) {
+        self.peer_stats.record(sender).invalid_sigs += 1;
+        if self.peer_score.record_failure(sender) {
+            warn!(
+                target: &self.params.log_target,
+                "Node {:?} bans {:?} for repeated signature failures", self.params.address, sender
+            );
+        }
+    }
+
     pub(crate) fn filter_height(&self, voter: &Address) -> bool {
         let mut trans_flag = false;
 
@@ -929,37 +2254,88 @@ where
         trans_flag
     }
 
+    /// The main loop must never block on its own channel, so a full channel is reported as an error.
+    #[inline]
+    /// A function to feed a msg to the node itself. The main loop is the only one draining the
+    /// channel, so the msg is kept to be sent again instead of blocking or being lost if the
+    /// channel is full, and the ones after it wait behind it.
+    pub(crate) fn send_bft_msg(&mut self, msg: BftMsg<B>) -> BftResult<()> {
+        if !self.refeed.is_empty() {
+            self.refeed.push_back(msg);
+            return Ok(());
+        }
+        match self.msg_sender.try_send(msg) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(msg)) => {
+                self.refeed.push_back(msg);
+                Ok(())
+            }
+            Err(TrySendError::Disconnected(msg)) => Err(BftError::SendMsgErr(msg_info(&msg))),
+        }
+    }
+
+    /// A function to send the msgs kept by `send_bft_msg` in order, until the channel is full.
+    pub(crate) fn flush_refeed(&mut self) {
+        while let Some(msg) = self.refeed.pop_front() {
+            if let Err(e) = self.msg_sender.try_send(msg) {
+                match e {
+                    TrySendError::Full(msg) => self.refeed.push_front(msg),
+                    TrySendError::Disconnected(msg) => report_err(
+                        &BftError::SendMsgErr(msg_info(&msg)),
+                        &self.params,
+                        &*self.function,
+                    ),
+                }
+                break;
+            }
+        }
+    }
+
+    /// The last height, None at the genesis height.
+    #[inline]
+    pub(crate) fn last_height(&self) -> Option<Height> {
+        self.height.checked_sub(1)
+    }
+
     #[inline]
-    pub(crate) fn send_bft_msg(&self, msg: BftMsg) -> BftResult<()> {
-        let info = format!("{:?}", &msg);
-        self.msg_sender
-            .send(msg)
-            .map_err(|e| BftError::SendMsgErr(format!("{:?} of {:?}", e, info)))
+    pub(crate) fn is_last_height(&self, height: Height) -> bool {
+        self.last_height() == Some(height)
+    }
+
+    /// Nothing is below the last height at the genesis height.
+    #[inline]
+    pub(crate) fn is_below_last_height(&self, height: Height) -> bool {
+        self.last_height()
+            .is_some_and(|last_height| height < last_height)
     }
 
     #[inline]
     pub(crate) fn change_to_step(&mut self, step: Step) {
+        #[cfg(feature = "commit_stats")]
+        self.stats.leave_step(self.step);
         self.step = step;
+        #[cfg(feature = "tracing")]
+        self.spans
+            .update(&self.params.address, self.height, self.round, step);
+        self.function.on_step_change(self.height, self.round, step);
     }
 
     #[inline]
     pub(crate) fn cal_all_vote(&self, count: u64) -> bool {
-        let weight_sum = get_total_weight(&self.authority_manage.authorities);
-        count == weight_sum
+        count == self.authority_manage.weight_sum
     }
 
     #[inline]
     pub(crate) fn cal_above_threshold(&self, count: u64) -> bool {
-        let weight_sum = get_total_weight(&self.authority_manage.authorities);
-        count * 3 > weight_sum * 2
+        is_above_threshold(count, self.authority_manage.weight_sum)
     }
 
     pub(crate) fn clean_polc(&mut self) {
         self.block_hash = None;
         self.lock_status = None;
         debug!(
-            "Node {:?} cleans PoLC at h:{}, r:{}",
-            self.params.address, self.height, self.round
+            target: &self.params.log_target,
+            "Node {:?} cleans PoLC at h:{}, r:{}", self.params.address, self.height, self.round
         );
     }
 
@@ -968,10 +2344,14 @@ where
         // clear prevote count needed when goto new height
         self.block_hash = None;
         self.lock_status = None;
+        self.audit_lock = None;
         self.votes.clear_vote_count();
+        self.verify_results.clear();
 
         #[cfg(feature = "verify_req")]
-        self.verify_results.clear();
+        self.verify_deadlines.clear();
+        #[cfg(feature = "compact_block")]
+        self.txs_requests.clear();
     }
 
     #[inline]
@@ -985,12 +2365,34 @@ where
         self.round_filter.clear();
     }
 
-    pub(crate) fn clear(&mut self, proof: Proof) {
+    pub(crate) fn clear(&mut self, proof: Proof) -> BftResult<()> {
+        self.reset();
+        self.proof = proof;
+        //TODO: 将之前的 wal 文件备份
+        let wal_path = self.wal_log.dir.clone();
+        let _ = fs::remove_dir_all(&wal_path);
+        self.wal_log = Wal::new(&wal_path, &self.params.config)
+            .map_err(|e| BftError::InitWalErr(format!("{} encounters {:?}", wal_path, e).into()))?;
+        self.consensus_power = false;
+        Ok(())
+    }
+
+    /// A function to rebuild the in-memory states from wal.
+    pub(crate) fn restart_from_wal(&mut self) {
+        self.reset();
+        self.status = None;
+        self.blocks.clear();
+        self.load_wal_log();
+    }
+
+    fn reset(&mut self) {
         self.height = INIT_HEIGHT;
+        self.shared_height.store(INIT_HEIGHT, Ordering::Relaxed);
         self.round = INIT_ROUND;
         self.step = Step::default();
         self.block_hash = None;
         self.lock_status = None;
+        self.audit_lock = None;
         self.height_filter.clear();
         self.round_filter.clear();
         self.last_commit_round = None;
@@ -998,91 +2400,569 @@ where
         self.htime = Instant::now();
         self.feed = None;
         self.verify_results.clear();
-        self.proof = proof;
+        #[cfg(feature = "verify_req")]
+        {
+            self.verify_deadlines.clear();
+            self.verify_timed_out = None;
+        }
+        #[cfg(feature = "compact_block")]
+        self.txs_requests.clear();
+        self.proof = Proof::default();
         self.authority_manage = AuthorityManage::new();
         self.proposals = ProposalCollector::new();
         self.votes = VoteCollector::new();
-        //TODO: 将之前的 wal 文件备份
-        let wal_path = &self.wal_log.dir;
-        let _ = fs::remove_dir_all(wal_path);
-        self.wal_log = Wal::new(wal_path).unwrap();
-        self.consensus_power = false;
+        self.checked_sigs = None;
     }
 }
 
-#[inline]
-pub fn get_total_weight(authorities: &[Node]) -> u64 {
-    let weight: Vec<u64> = authorities
-        .iter()
-        .map(|node| u64::from(node.vote_weight))
-        .collect();
-    weight.iter().sum()
+/// A function to open the wal, retries until it succeeds.
+pub(crate) fn open_wal<T: BftSupport<B>, B: BlockPayload>(
+    wal_path: &str,
+    params: &BftParams,
+    function: &T,
+) -> Wal {
+    loop {
+        match Wal::new(wal_path, &params.config) {
+            Ok(wal_log) => return wal_log,
+            Err(e) => {
+                report_err(
+                    &BftError::InitWalErr(format!("{} encounters {:?}", wal_path, e).into()),
+                    params,
+                    function,
+                );
+                thread::sleep(Duration::from_millis(OPEN_WAL_RETRY_MILLIS));
+            }
+        }
+    }
 }
 
+/// A function to commit, re-attempts with backoff if it fails. Return `Ok(None)` if the status
+/// is delivered later, or the node reaches a higher height before the commit succeeds.
+pub(crate) fn commit_with_retry<T: BftSupport<B>, B: BlockPayload>(
+    function: &T,
+    commit: Commit<B>,
+    round: Round,
+    params: &BftParams,
+    shared_height: &AtomicU64,
+) -> BftResult<Option<Status>> {
+    let height = commit.height;
+    let max_backoff = Duration::from_millis(params.config.commit_retry_max_millis);
+    let mut backoff = Duration::from_millis(params.config.commit_retry_millis).min(max_backoff);
+    let mut attempts = 0;
+    loop {
+        let e = match function.commit(commit.clone()) {
+            Ok(status) => return Ok(status),
+            Err(e) => e,
+        };
+        let info = ErrorInfo::new(format!("{:?} after {} re-attempts", e, attempts))
+            .at(height, round)
+            .kind(MsgKind::Block);
+        if attempts >= params.config.commit_retry_times {
+            return Err(BftError::CommitStalled(info));
+        }
+        report_err(&BftError::CommitFailed(info), params, function);
+        thread::sleep(backoff);
+        // the status of the height has arrived in another way, such as syncing
+        if shared_height.load(Ordering::Relaxed) > height {
+            return Ok(None);
+        }
+        backoff = backoff
+            .checked_mul(2)
+            .map_or(max_backoff, |backoff| backoff.min(max_backoff));
+        attempts += 1;
+    }
+}
+
+/// A function to get the block to propose at the [`height`], re-attempts with backoff if it
+/// fails until the propose timeout, then falls back to the empty block of the support.
+/// Return `None` if there is no block to propose, or the node reaches a higher height before
+/// a re-attempt.
+pub(crate) fn get_block_with_retry<T: BftSupport<B>, B: BlockPayload>(
+    function: &T,
+    height: Height,
+    params: &BftParams,
+    shared_height: &AtomicU64,
+) -> Option<(B, Hash)> {
+    let deadline = Instant::now() + params.timer.get_propose();
+    let mut backoff = Duration::from_millis(params.config.get_block_retry_millis);
+    let mut attempts = 0;
+    loop {
+        let e = match function.get_block(height) {
+            Ok(feed) => return Some(feed),
+            Err(e) => e,
+        };
+        let info = ErrorInfo::new(format!("{:?} after {} re-attempts", e, attempts))
+            .height(height)
+            .kind(MsgKind::Feed);
+        report_err(&BftError::GetBlockFailed(info), params, function);
+        if attempts >= params.config.get_block_retry_times || Instant::now() + backoff > deadline {
+            break;
+        }
+        thread::sleep(backoff);
+        if shared_height.load(Ordering::Relaxed) > height {
+            return None;
+        }
+        backoff *= 2;
+        attempts += 1;
+    }
+    let info = ErrorInfo::new(format!("get_block fails {} times", attempts + 1))
+        .height(height)
+        .kind(MsgKind::Feed);
+    report_err(&BftError::FeedUnavailable(info), params, function);
+    function.empty_block(height)
+}
+
+/// The unix time in milliseconds.
+pub(crate) fn unix_millis() -> u64 {
+    SystemClock.unix_millis()
+}
+
+/// The next height, fails instead of overflowing.
 #[inline]
-pub fn get_votes_weight(authorities: &[Node], vote_addresses: &[Address]) -> u64 {
-    let votes_weight: Vec<u64> = authorities
-        .iter()
-        .filter(|node| vote_addresses.contains(&node.address))
-        .map(|node| u64::from(node.vote_weight))
-        .collect();
-    votes_weight.iter().sum()
+pub(crate) fn next_height(height: Height) -> BftResult<Height> {
+    height
+        .checked_add(1)
+        .ok_or_else(|| BftError::ShouldNotHappen(format!("height {} overflows", height).into()))
 }
 
+/// A function to frame two segments in the current frame format.
 pub fn combine_two(first: &[u8], second: &[u8]) -> Vec<u8> {
-    let first_len = first.len() as u64;
-    let len_mark = first_len.to_be_bytes();
-    let mut encode = Vec::with_capacity(8 + first.len() + second.len());
-    encode.extend_from_slice(&len_mark);
-    encode.extend_from_slice(first);
-    encode.extend_from_slice(second);
-    encode
+    encode_frame(&[first, second])
 }
 
+/// A function to extract two segments from a frame of the current or legacy format.
 pub fn extract_two(encode: &[u8]) -> BftResult<(&[u8], &[u8])> {
-    let encode_len = encode.len();
-    if encode_len < 8 {
-        return Err(BftError::DecodeErr(format!(
-            "extract_two failed, encode.len {} is less than 8",
-            encode_len
-        )));
-    }
-    let mut len: [u8; 8] = [0; 8];
-    len.copy_from_slice(&encode[0..8]);
-    let first_len = u64::from_be_bytes(len) as usize;
-    if encode_len < first_len + 8 {
-        return Err(BftError::DecodeErr(format!(
-            "extract_two failed, encode.len {} is less than first_len + 8",
-            encode_len
-        )));
-    }
-    let (combine, two) = encode.split_at(first_len + 8);
-    let (_, one) = combine.split_at(8);
-    Ok((one, two))
+    match decode_frame(encode)?.as_slice() {
+        [one, two] => Ok((one, two)),
+        segments => Err(BftError::DecodeErr(
+            format!(
+                "extract_two failed, the frame has {} segments",
+                segments.len()
+            )
+            .into(),
+        )),
+    }
+}
+
+/// A function to extract the signed_proposal and the block segment from a proposal frame,
+/// and the codec byte if the block segment is compressed.
+pub fn extract_proposal(encode: &[u8]) -> BftResult<(&[u8], &[u8], Option<u8>)> {
+    match decode_frame(encode)?.as_slice() {
+        [proposal, block] => Ok((proposal, block, None)),
+        [proposal, block, [codec]] => Ok((proposal, block, Some(*codec))),
+        segments => Err(BftError::DecodeErr(
+            format!(
+                "extract_proposal failed, the frame has {} segments",
+                segments.len()
+            )
+            .into(),
+        )),
+    }
+}
+
+/// Get the height of a proposal or vote msg, return None for other msgs or undecodable ones.
+pub(crate) fn get_msg_height<B: BlockPayload>(msg: &BftMsg<B>) -> Option<Height> {
+    match msg {
+        BftMsg::Proposal(encode) => get_proposal_height(encode),
+        BftMsg::Vote(encode) => get_vote_height(encode),
+        _ => None,
+    }
+}
+
+pub(crate) fn get_outbound_height(msg: &OutboundMsg) -> Option<Height> {
+    match msg {
+        OutboundMsg::Proposal(encode) => get_proposal_height(encode),
+        OutboundMsg::Vote(encode) => get_vote_height(encode),
+        #[cfg(feature = "compact_block")]
+        OutboundMsg::GetTxs(encode) => rlp::decode::<GetTxs>(encode).ok().map(|m| m.height),
+        #[cfg(feature = "compact_block")]
+        OutboundMsg::Txs(encode) => rlp::decode::<Txs>(encode).ok().map(|m| m.height),
+    }
+}
+
+/// A function to get the compact context of the [`msg`] failing to be sent, which names the msg
+/// and its height instead of formatting the whole payload.
+pub(crate) fn msg_info<B: BlockPayload>(msg: &BftMsg<B>) -> ErrorInfo {
+    let (detail, kind, height) = match msg {
+        BftMsg::Proposal(encode) => (
+            "proposal",
+            Some(MsgKind::Proposal),
+            get_proposal_height(encode),
+        ),
+        BftMsg::Vote(encode) => ("vote", Some(MsgKind::Vote), get_vote_height(encode)),
+        BftMsg::Status(status) => ("status", Some(MsgKind::Status), Some(status.height)),
+        BftMsg::VerifyResp(_) => ("verify_resp", Some(MsgKind::VerifyResp), None),
+        BftMsg::Feed(feed) => ("feed", Some(MsgKind::Feed), Some(feed.height)),
+        BftMsg::Tick => ("tick", None, None),
+        BftMsg::RevokeFeed { height, .. } => ("revoke_feed", Some(MsgKind::Feed), Some(*height)),
+        BftMsg::Signature(..) => ("signature", None, None),
+        BftMsg::KeyRotation(rotation) => (
+            "key_rotation",
+            Some(MsgKind::Status),
+            Some(rotation.activation_height),
+        ),
+        #[cfg(feature = "compact_block")]
+        BftMsg::GetTxs(_) => ("get_txs", Some(MsgKind::Txs), None),
+        #[cfg(feature = "compact_block")]
+        BftMsg::Txs(_) => ("txs", Some(MsgKind::Txs), None),
+        BftMsg::Pause => ("pause", None, None),
+        BftMsg::Start => ("start", None, None),
+        BftMsg::Clear(proof) => ("clear", Some(MsgKind::Proof), Some(proof.height)),
+        BftMsg::ExportSnapshot(_) => ("export_snapshot", None, None),
+        BftMsg::ImportSnapshot(snapshot) => ("import_snapshot", None, Some(snapshot.height)),
+        BftMsg::Dump(_) => ("dump", None, None),
+        BftMsg::MissingVoters(height, ..) => ("missing_voters", None, Some(*height)),
+        BftMsg::PeerStats(_) => ("peer_stats", None, None),
+        BftMsg::IngressStats(_) => ("ingress_stats", None, None),
+        BftMsg::CacheUsage(_) => ("cache_usage", None, None),
+        BftMsg::ProposerShares(_) => ("proposer_shares", None, None),
+        BftMsg::PruneWal(height, _) => ("prune_wal", Some(MsgKind::Wal), Some(*height)),
+        BftMsg::Trace(..) => ("trace", None, None),
+        BftMsg::Kill => ("kill", None, None),
+        BftMsg::Corrupt | BftMsg::CorruptWith(_) => ("corrupt", None, None),
+    };
+    let mut info = ErrorInfo::new(detail);
+    info.kind = kind;
+    info.height = height;
+    info
+}
+
+fn get_proposal_height(encode: &[u8]) -> Option<Height> {
+    decode_signed_proposal(encode).map(|signed_proposal| signed_proposal.proposal.height)
 }
 
-pub fn encode_block(height: Height, block: &Block, block_hash: &Hash) -> Vec<u8> {
+fn get_vote_height(encode: &[u8]) -> Option<Height> {
+    decode_signed_vote(encode).map(|signed_vote| signed_vote.vote.height)
+}
+
+pub(crate) fn check_list_len(name: &str, len: usize, max_len: usize) -> BftResult<()> {
+    if len > max_len {
+        return Err(BftError::MsgTooLarge(
+            format!("{} length {} exceeds the limit {}", name, len, max_len).into(),
+        ));
+    }
+    Ok(())
+}
+
+fn rlp_item_count(rlp: &Rlp, name: &str) -> BftResult<usize> {
+    rlp.item_count()
+        .map_err(|e| BftError::DecodeErr(format!("{} encounters {:?}", name, e).into()))
+}
+
+fn rlp_at<'a>(rlp: &Rlp<'a>, index: usize, name: &str) -> BftResult<Rlp<'a>> {
+    rlp.at(index)
+        .map_err(|e| BftError::DecodeErr(format!("{} encounters {:?}", name, e).into()))
+}
+
+/// A function to rlp encode the [`object`] by the [`codec`] into the reused [`stream`] instead
+/// of allocating a vector, the bytes are valid until the stream is used again.
+pub(crate) fn encode_into<'a, E: CodecEncodable>(
+    stream: &'a mut RlpStream,
+    object: &E,
+    codec: RlpCodec,
+) -> &'a [u8] {
+    stream.clear();
+    stream.append_by(object, codec);
+    stream.as_raw()
+}
+
+/// Check the block payload size of a proposal msg.
+pub(crate) fn check_block_size(block: &[u8], config: &BftConfig) -> BftResult<()> {
+    check_list_len("block", block.len(), config.max_block_size)
+}
+
+/// Check the list lengths of an encoded signed_proposal before decoding it.
+pub(crate) fn check_signed_proposal_size(encode: &[u8], config: &BftConfig) -> BftResult<()> {
+    let proposal = rlp_at(&Rlp::new(encode), 0, "signed_proposal")?;
+    let lock_votes = rlp_at(&proposal, 5, "lock_votes")?;
+    check_list_len(
+        "lock_votes",
+        rlp_item_count(&lock_votes, "lock_votes")?,
+        config.max_lock_votes,
+    )?;
+    check_proof_size(&rlp_at(&proposal, 3, "proof")?, config)
+}
+
+/// Peek the height and the round of an encoded signed_proposal or signed_vote before
+/// decoding it, they are at the [`offset`] of the proposal or the vote.
+pub(crate) fn peek_height_round(encode: &[u8], offset: usize) -> Option<(Height, Round)> {
+    let msg = Rlp::new(encode).at(0).ok()?;
+    Some((msg.val_at(offset).ok()?, msg.val_at(offset + 1).ok()?))
+}
+
+/// Check the count of precommit_votes of an encoded proof before decoding it.
+pub(crate) fn check_proof_size(proof: &Rlp, config: &BftConfig) -> BftResult<()> {
+    let voters = rlp_at(proof, 3, "precommit_votes")?;
+    check_list_len(
+        "precommit_votes",
+        rlp_item_count(&voters, "precommit_votes")?,
+        config.max_precommit_votes,
+    )
+}
+
+/// Check the length of authority_list of an encoded status before decoding it.
+pub(crate) fn check_status_size(encode: &[u8], config: &BftConfig) -> BftResult<()> {
+    let authority_list = rlp_at(&Rlp::new(encode), 2, "authority_list")?;
+    check_list_len(
+        "authority_list",
+        rlp_item_count(&authority_list, "authority_list")?,
+        config.max_authorities,
+    )
+}
+
+pub fn encode_block<B: BlockPayload>(height: Height, block: &B, block_hash: &Hash) -> Vec<u8> {
+    let block = block.to_bytes();
     let height_mark = height.to_be_bytes();
-    let mut encode = Vec::with_capacity(8 + block.0.len());
+    let mut encode = Vec::with_capacity(8 + block.len());
     encode.extend_from_slice(&height_mark);
-    let combine = combine_two(&block_hash.0, &block);
+    let combine = combine_two(block_hash, &block);
     encode.extend_from_slice(&combine);
     encode
 }
 
 pub fn decode_block(encode: &[u8]) -> BftResult<(Height, Block, Hash)> {
+    if encode.len() < 8 {
+        return Err(BftError::DecodeErr(
+            format!(
+                "decode_block failed, encode.len {} is less than 8",
+                encode.len()
+            )
+            .into(),
+        ));
+    }
     let (h, combine) = encode.split_at(8);
     let (block_hash, block) = extract_two(combine)?;
     let mut height_mark: [u8; 8] = [0; 8];
     height_mark.copy_from_slice(h);
     let height = Height::from_be_bytes(height_mark);
-    Ok((height, block.into(), block_hash.into()))
+    Ok((height, block.into(), Hash::try_from(block_hash)?))
+}
+
+pub(crate) fn encode_revoke_feed(height: Height, block_hash: &Hash, codec: RlpCodec) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(2);
+    stream.append(&height).append_by(block_hash, codec);
+    stream.out()
+}
+
+pub(crate) fn decode_revoke_feed(encode: &[u8]) -> Result<(Height, Hash), DecoderError> {
+    let rlp = Rlp::new(encode);
+    Ok((rlp.val_at(0)?, rlp.val_at(1)?))
+}
+
+/// The start of a height is saved with the unix time in milliseconds.
+pub(crate) fn encode_height_start(height: Height, millis: u64) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(2);
+    stream.append(&height).append(&millis);
+    stream.out()
+}
+
+pub(crate) fn decode_height_start(encode: &[u8]) -> Result<(Height, u64), DecoderError> {
+    let rlp = Rlp::new(encode);
+    Ok((rlp.val_at(0)?, rlp.val_at(1)?))
+}
+
+/// A function to get the proposer of the [`round`] of the [`height`] by the [`authorities`] of
+/// the height, which is the one the state machines select. Networking layers and explorers
+/// can predict the proposers by it without a running node, with the [`selection`] of the
+/// nodes. `None` if the authorities are empty.
+pub fn proposer_at(
+    height: Height,
+    round: Round,
+    authorities: &[Node],
+    selection: ProposerSelection,
+) -> Option<Address> {
+    authorities
+        .get(proposer_index(height, round, authorities, selection))
+        .map(|node| node.address)
+}
+
+/// A function to get the index of the proposer in the [`authorities`], selected by the
+/// proposal weights.
+fn proposer_index(
+    height: Height,
+    round: Round,
+    authorities: &[Node],
+    selection: ProposerSelection,
+) -> usize {
+    let nonce = selection_step(height, round);
+    let weight = proposal_weights(authorities);
+    match selection {
+        ProposerSelection::Weighted => get_index(nonce, &weight),
+        ProposerSelection::Priority => get_priority_index(nonce, &weight),
+    }
+}
+
+/// The step the proposer of the [`round`] of the [`height`] is selected at.
+fn selection_step(height: Height, round: Round) -> u64 {
+    height.wrapping_add(round)
+}
+
+fn proposal_weights(authorities: &[Node]) -> Vec<u64> {
+    authorities
+        .iter()
+        .map(|node| u64::from(node.proposal_weight))
+        .collect()
+}
+
+/// A function to get the index selected at the [`step`] of the accumulated priorities of the
+/// [`weight`], see `PrioritySteps`.
+fn get_priority_index(step: u64, weight: &[u64]) -> usize {
+    match PrioritySteps::new(weight) {
+        Some(mut steps) => {
+            let step = (i128::from(step) % steps.sum) as usize;
+            steps.nth(step).unwrap_or_default()
+        }
+        None => 0,
+    }
+}
+
+/// The accumulated priorities of the proposal weights, which select the first one of the
+/// highest priorities at each step. The priorities start at zero and return to zero every
+/// weight sum steps, so the weights are divided by their gcd and the steps are taken modulo
+/// their sum. The weights summing above `MAX_PRIORITY_WEIGHT_SUM` are scaled down to it, a
+/// non-zero weight is kept at least 1.
+struct PrioritySteps {
+    weight: Vec<i128>,
+    sum: i128,
+    priority: Vec<i128>,
+}
+
+impl PrioritySteps {
+    /// `None` if the weights are all zero.
+    fn new(weight: &[u64]) -> Option<Self> {
+        let divisor = weight.iter().fold(0, |divisor, w| gcd(divisor, *w));
+        if divisor == 0 {
+            return None;
+        }
+        let divisor = divisor.max(weight_sum(weight) / MAX_PRIORITY_WEIGHT_SUM + 1);
+        let weight: Vec<i128> = weight
+            .iter()
+            .map(|w| {
+                let scaled = w / divisor;
+                if scaled == 0 && *w > 0 {
+                    1
+                } else {
+                    i128::from(scaled)
+                }
+            })
+            .collect();
+        let sum = weight.iter().sum();
+        let priority = vec![0i128; weight.len()];
+        Some(PrioritySteps {
+            weight,
+            sum,
+            priority,
+        })
+    }
+}
+
+impl Iterator for PrioritySteps {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        for (p, w) in self.priority.iter_mut().zip(self.weight.iter()) {
+            *p += w;
+        }
+        let priority = &self.priority;
+        let index = (0..priority.len()).fold(
+            0,
+            |max, i| {
+                if priority[i] > priority[max] {
+                    i
+                } else {
+                    max
+                }
+            },
+        );
+        self.priority[index] -= self.sum;
+        Some(index)
+    }
+}
+
+/// The indices selected by the accumulated priorities of an authority list over a whole
+/// cycle of the steps, so a selection is looked up instead of accumulated again.
+#[derive(Clone, Default)]
+pub(crate) struct PriorityCycle {
+    /// the proposal weights the priorities are accumulated by
+    weight: Vec<u64>,
+    indices: Vec<u32>,
+}
+
+impl PriorityCycle {
+    pub(crate) fn new(authorities: &[Node]) -> Self {
+        let weight = proposal_weights(authorities);
+        let indices = match PrioritySteps::new(&weight) {
+            Some(steps) => {
+                let sum = steps.sum as usize;
+                steps.take(sum).map(|index| index as u32).collect()
+            }
+            None => vec![0],
+        };
+        PriorityCycle { weight, indices }
+    }
+
+    /// A function to check whether the cycle is accumulated by the weights of the
+    /// [`authorities`].
+    pub(crate) fn is_of(&self, authorities: &[Node]) -> bool {
+        !self.indices.is_empty()
+            && self.weight.len() == authorities.len()
+            && self
+                .weight
+                .iter()
+                .zip(authorities.iter())
+                .all(|(w, node)| *w == u64::from(node.proposal_weight))
+    }
+
+    /// A function to get the index of the proposer of the [`round`] of the [`height`] in the
+    /// [`authorities`], `None` if the cycle is not of them.
+    pub(crate) fn proposer_index(
+        &self,
+        height: Height,
+        round: Round,
+        authorities: &[Node],
+    ) -> Option<usize> {
+        if !self.is_of(authorities) {
+            return None;
+        }
+        let step = selection_step(height, round) % self.indices.len() as u64;
+        Some(self.indices[step as usize] as usize)
+    }
+}
+
+impl fmt::Debug for PriorityCycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PriorityCycle")
+            .field("weight", &self.weight)
+            .field("steps", &self.indices.len())
+            .finish()
+    }
+}
+
+/// The max sum of the proposal weights the priorities are accumulated by, which bounds the
+/// steps of a selection.
+const MAX_PRIORITY_WEIGHT_SUM: u64 = 1 << 16;
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// A function to sum the proposal weights in u128, saturated at `u64::MAX`.
+fn weight_sum(weight: &[u64]) -> u64 {
+    let sum: u128 = weight.iter().map(|w| u128::from(*w)).sum();
+    u64::try_from(sum).unwrap_or(u64::MAX)
 }
 
 #[cfg(feature = "random_proposer")]
 pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
-    let sum: u64 = weight.iter().sum();
-    let x = u64::max_value() / sum;
+    let sum = weight_sum(weight);
+    if sum == 0 {
+        return 0;
+    }
+    let x = u64::MAX / sum;
 
     let mut rng = Pcg::seed_from_u64(seed);
     let mut res = rng.next_u64();
@@ -1091,7 +2971,7 @@ pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
     }
     let mut acc = 0;
     for (index, w) in weight.iter().enumerate() {
-        acc += *w;
+        acc = w.saturating_add(acc);
         if res < acc * x {
             return index;
         }
@@ -1101,12 +2981,15 @@ pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
 
 #[cfg(not(feature = "random_proposer"))]
 pub(crate) fn get_index(seed: u64, weight: &[u64]) -> usize {
-    let sum: u64 = weight.iter().sum();
+    let sum = weight_sum(weight);
+    if sum == 0 {
+        return 0;
+    }
     let x = seed % sum;
 
     let mut acc = 0;
     for (index, w) in weight.iter().enumerate() {
-        acc += *w;
+        acc = w.saturating_add(acc);
         if x < acc {
             return index;
         }
@@ -0,0 +1,187 @@
+use crate::algorithm::Waker;
+use crate::{
+    Address, BftMsg, BftSupport, BlockPayload, Hash, Height, Round, Signature, VerifyResp,
+};
+
+use crossbeam::crossbeam_channel::{bounded, Sender, TrySendError};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::thread;
+
+/// The recovered addresses of the signatures, `None` for the invalid ones.
+pub(crate) type CheckedSigs = HashMap<(Signature, Hash), Option<Address>>;
+
+/// A msg whose signatures are to be checked by the verifier pool.
+pub(crate) struct SigJob<B: BlockPayload> {
+    /// The msg to process again after the signatures are checked.
+    pub(crate) msg: BftMsg<B>,
+    /// The signatures with the hashes they sign.
+    pub(crate) sigs: Vec<(Signature, Hash)>,
+}
+
+/// A msg whose signatures are checked by the verifier pool.
+pub(crate) struct SigChecked<B: BlockPayload> {
+    pub(crate) msg: BftMsg<B>,
+    pub(crate) sigs: CheckedSigs,
+}
+
+/// A pool of threads checking the signatures of proposals and votes off the main loop.
+pub(crate) struct SigVerifier<B: BlockPayload> {
+    job_sender: Sender<SigJob<B>>,
+    // keep the result channel connected even if all the workers panic
+    _result_sender: Sender<SigChecked<B>>,
+}
+
+impl<B: BlockPayload> SigVerifier<B> {
+    /// A function to start a verifier pool of the [`threads`] count, the checked msgs are
+    /// fed back through the [`result_sender`], then the [`waker`] is called if there is one.
+    /// Return `None` if the [`threads`] is 0.
+    pub(crate) fn start<T: BftSupport<B> + 'static>(
+        threads: usize,
+        capacity: usize,
+        function: Arc<T>,
+        result_sender: Sender<SigChecked<B>>,
+        waker: Option<Waker>,
+        address: Address,
+    ) -> Option<Self> {
+        if threads == 0 {
+            return None;
+        }
+        let (job_sender, job_receiver) = bounded::<SigJob<B>>(capacity);
+        for index in 0..threads {
+            let function = function.clone();
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let waker = waker.clone();
+            thread::Builder::new()
+                .name(format!("bft_verifier_{}", index))
+                .spawn(move || {
+                    // the workers exit as the main loop drops the job sender
+                    for job in job_receiver.iter() {
+                        let sigs = check_sigs(&*function, job.sigs);
+                        if result_sender
+                            .send(SigChecked { msg: job.msg, sigs })
+                            .is_err()
+                        {
+                            return;
+                        }
+                        if let Some(waker) = waker.as_ref() {
+                            waker();
+                        }
+                    }
+                })
+                .unwrap_or_else(|_| panic!("Node {:?} starts verifier-thread failed!", address));
+        }
+        Some(SigVerifier {
+            job_sender,
+            _result_sender: result_sender,
+        })
+    }
+
+    /// A function to dispatch a job to the pool, the job is returned back if the pool is busy.
+    #[allow(clippy::result_large_err)]
+    pub(crate) fn dispatch(&self, job: SigJob<B>) -> Result<(), SigJob<B>> {
+        self.job_sender.try_send(job).map_err(|e| match e {
+            TrySendError::Full(job) | TrySendError::Disconnected(job) => job,
+        })
+    }
+}
+
+/// A block of a future-round proposal to be checked speculatively.
+pub(crate) struct BlockJob<B: BlockPayload> {
+    pub(crate) height: Height,
+    pub(crate) round: Round,
+    pub(crate) block: Arc<B>,
+    pub(crate) block_hash: Hash,
+    pub(crate) signed_proposal_hash: Hash,
+    pub(crate) is_lock: bool,
+    pub(crate) proposer: Address,
+}
+
+/// A block checked speculatively, with the error of `check_block` if it fails.
+pub(crate) struct BlockChecked {
+    pub(crate) height: Height,
+    pub(crate) block_hash: Hash,
+    pub(crate) result: Result<VerifyResp, String>,
+}
+
+/// A pool of threads checking the blocks of future-round proposals ahead of their rounds.
+pub(crate) struct BlockVerifier<B: BlockPayload> {
+    job_sender: Sender<BlockJob<B>>,
+    // keep the result channel connected even if all the workers panic
+    _result_sender: Sender<BlockChecked>,
+}
+
+impl<B: BlockPayload> BlockVerifier<B> {
+    /// A function to start a pool of the [`threads`] count, at most the [`threads`] count of
+    /// jobs are queued besides the running ones. The [`waker`] is called after a block is
+    /// checked if there is one. Return `None` if the [`threads`] is 0.
+    pub(crate) fn start<T: BftSupport<B> + 'static>(
+        threads: usize,
+        function: Arc<T>,
+        result_sender: Sender<BlockChecked>,
+        waker: Option<Waker>,
+        address: Address,
+    ) -> Option<Self> {
+        if threads == 0 {
+            return None;
+        }
+        let (job_sender, job_receiver) = bounded::<BlockJob<B>>(threads);
+        for index in 0..threads {
+            let function = function.clone();
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let waker = waker.clone();
+            thread::Builder::new()
+                .name(format!("bft_block_verifier_{}", index))
+                .spawn(move || {
+                    for job in job_receiver.iter() {
+                        let result = function
+                            .check_block(
+                                &job.block,
+                                &job.block_hash,
+                                &job.signed_proposal_hash,
+                                (job.height, job.round),
+                                job.is_lock,
+                                &job.proposer,
+                            )
+                            .map_err(|e| format!("{:?}", e));
+                        let checked = BlockChecked {
+                            height: job.height,
+                            block_hash: job.block_hash,
+                            result,
+                        };
+                        if result_sender.send(checked).is_err() {
+                            return;
+                        }
+                        if let Some(waker) = waker.as_ref() {
+                            waker();
+                        }
+                    }
+                })
+                .unwrap_or_else(|_| {
+                    panic!("Node {:?} starts block-verifier-thread failed!", address)
+                });
+        }
+        Some(BlockVerifier {
+            job_sender,
+            _result_sender: result_sender,
+        })
+    }
+
+    /// A function to dispatch a job to the pool, the job is returned back if the pool is busy.
+    pub(crate) fn dispatch(&self, job: BlockJob<B>) -> Result<(), BlockJob<B>> {
+        self.job_sender.try_send(job).map_err(|e| match e {
+            TrySendError::Full(job) | TrySendError::Disconnected(job) => job,
+        })
+    }
+}
+
+fn check_sigs<T: BftSupport<B>, B: BlockPayload>(
+    function: &T,
+    batch: Vec<(Signature, Hash)>,
+) -> CheckedSigs {
+    let addresses = function.check_sigs(&batch);
+    // the signatures lacking results are checked again in the main loop
+    batch.into_iter().zip(addresses).collect()
+}
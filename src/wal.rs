@@ -1,54 +1,87 @@
-use crate::objects::LogType;
-use crate::Height;
+use crate::codec::RlpCodec;
+use crate::objects::{AuthorityManage, LogType, SignedProposal, SignedVote};
+use crate::params::{BftConfig, WalRetention, WalSyncPolicy};
+use crate::timer::TimeoutInfo;
+use crate::utils::{decode_block, decode_height_start, decode_revoke_feed};
+use crate::{Feed, Hash, Height, KeyRotation, Node, Proof, Round, Status, VerifyResp};
 #[allow(unused_imports)]
 use log::{log, trace, warn};
+use std::collections::btree_map::Entry;
 use std::collections::BTreeMap;
-use std::fs::{read_dir, DirBuilder, File, OpenOptions};
+use std::convert::TryFrom;
+use std::fs::{self, read_dir, DirBuilder, File, OpenOptions};
 use std::io::{self, Read, Seek, Write};
-use std::mem::transmute;
-use std::str;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-const DELETE_FILE_INTERVAL: u64 = 3;
+/// The count of the past heights whose records are still saved.
+const SAVE_PAST_HEIGHTS: u64 = 3;
+
+const SEGMENT_EXT: &str = "seg";
+const SEGMENT_INDEX_EXT: &str = "idx";
+/// The per-height wal files of the former versions, which are migrated into the segments.
+const LEGACY_EXT: &str = "log";
+
+/// A segment record is `[u64 le height][u32 le len][u8 tag][msg]`, the tag is the log type in
+/// the low 4 bits and the codec of the msg in the high 4 bits, see `record_tag`.
+const RECORD_HEADER_LEN: usize = 8 + 4 + 1;
+/// A segment index entry is `[u64 le height][u64 le offset of its first record]`.
+const INDEX_ENTRY_LEN: usize = 8 + 8;
+
+/// An append-only file of the records of the successive heights.
+#[derive(Default)]
+struct Segment {
+    /// the offset of the first record of each height in the segment
+    heights: BTreeMap<Height, u64>,
+    size: u64,
+}
 
 pub(crate) struct Wal {
-    height_fs: BTreeMap<Height, File>,
     pub(crate) dir: String,
     current_height: Height,
     ifile: File, // store off-line height
+    // the segments by sequence number, the records are appended to the last one
+    segments: BTreeMap<u64, Segment>,
+    active_seq: u64,
+    active: File,
+    active_index: File,
+    segment_size: u64,
+    retention: WalRetention,
+    sync_policy: WalSyncPolicy,
+    // the codec the msgs are encoded by, which is saved with each record
+    codec: RlpCodec,
+    // the count of records saved since the last sync
+    unsynced: u32,
+    last_sync: Instant,
+    log_target: String,
 }
 
 impl Wal {
-    pub(crate) fn new(dir: &str) -> Result<Wal, io::Error> {
-        let fss = read_dir(&dir);
+    pub(crate) fn new(dir: &str, config: &BftConfig) -> Result<Wal, io::Error> {
+        let fss = read_dir(dir);
         if fss.is_err() {
-            DirBuilder::new()
-                .recursive(true)
-                .create(dir)
-                .expect("Create wal directory failed!");
+            DirBuilder::new().recursive(true).create(dir)?;
         }
 
         let file_path = dir.to_string() + "/" + "index";
-        let expect_str = format!("Seek wal file {:?} failed!", &file_path);
         let mut ifs = OpenOptions::new()
             .read(true)
             .create(true)
             .write(true)
+            .truncate(false)
             .open(file_path)?;
 
-        ifs.seek(io::SeekFrom::Start(0)).expect(&expect_str);
+        ifs.seek(io::SeekFrom::Start(0))?;
 
         let mut string_buf: String = String::new();
         let res_fsize = ifs.read_to_string(&mut string_buf)?;
         let cur_height: Height;
-        let last_file_path: String;
         if res_fsize == 0 {
-            last_file_path = dir.to_string() + "/0.log";
             cur_height = 0;
         } else {
             let hi_res = string_buf.parse::<Height>();
             if let Ok(hi) = hi_res {
                 cur_height = hi;
-                last_file_path = dir.to_string() + "/" + cur_height.to_string().as_str() + ".log"
             } else {
                 return Err(io::Error::new(
                     io::ErrorKind::InvalidData,
@@ -57,28 +90,106 @@ impl Wal {
             }
         }
 
-        let fs = OpenOptions::new()
+        let mut seqs = Vec::new();
+        let mut legacy_files = BTreeMap::new();
+        for entry in read_dir(dir)? {
+            let path = entry?.path();
+            if let Some(seq) = file_number(&path, SEGMENT_EXT) {
+                seqs.push(seq);
+            } else if let Some(height) = file_number(&path, LEGACY_EXT) {
+                legacy_files.insert(height, path);
+            }
+        }
+        seqs.sort();
+
+        let active_seq = seqs.last().cloned().unwrap_or(1);
+        let mut segments = BTreeMap::new();
+        for seq in seqs {
+            if seq == active_seq {
+                continue;
+            }
+            let path = segment_path(dir, seq);
+            // a sealed segment is never written again, so its index is complete if it is whole
+            let segment = match read_segment_index(&segment_index_path(dir, seq)) {
+                Some(heights) => Segment {
+                    heights,
+                    size: fs::metadata(&path)?.len(),
+                },
+                None => scan_segment(&path)?,
+            };
+            segments.insert(seq, segment);
+        }
+
+        // the active segment may end with a torn record, which is cut before appending
+        let active_path = segment_path(dir, active_seq);
+        let active = OpenOptions::new()
             .read(true)
             .create(true)
-            .write(true)
-            .open(last_file_path)?;
+            .append(true)
+            .open(&active_path)?;
+        let segment = scan_segment(&active_path)?;
+        active.set_len(segment.size)?;
+        let index_path = segment_index_path(dir, active_seq);
+        let entries: Vec<u8> = segment
+            .heights
+            .iter()
+            .flat_map(|(height, offset)| index_entry(*height, *offset))
+            .collect();
+        fs::write(&index_path, entries)?;
+        let active_index = OpenOptions::new().append(true).open(&index_path)?;
+        segments.insert(active_seq, segment);
 
-        let mut tmp = BTreeMap::new();
-        tmp.insert(cur_height, fs);
-
-        Ok(Wal {
-            height_fs: tmp,
+        let mut wal = Wal {
             dir: dir.to_string(),
             current_height: cur_height,
             ifile: ifs,
-        })
+            segments,
+            active_seq,
+            active,
+            active_index,
+            segment_size: config.wal_segment_size,
+            retention: config.wal_retention.clone(),
+            sync_policy: config.wal_sync,
+            unsynced: 0,
+            last_sync: Instant::now(),
+            codec: config.rlp_codec,
+            log_target: config.log_target(),
+        };
+        wal.migrate(legacy_files)?;
+        Ok(wal)
+    }
+
+    /// A function to move the records of the per-height files of the former versions into
+    /// the segments. The files are removed after the migrated records are synced.
+    fn migrate(&mut self, legacy_files: BTreeMap<Height, PathBuf>) -> Result<(), io::Error> {
+        if legacy_files.is_empty() {
+            return Ok(());
+        }
+        for (height, path) in &legacy_files {
+            let buf = fs::read(path)?;
+            let mut index = 0;
+            while index + 5 <= buf.len() {
+                let hd: [u8; 4] = [buf[index], buf[index + 1], buf[index + 2], buf[index + 3]];
+                let bodylen = u32::from_le_bytes(hd) as usize;
+                let mtype = buf[index + 4];
+                index += 5;
+                if index + bodylen > buf.len() {
+                    break;
+                }
+                self.append(*height, mtype, &buf[index..index + bodylen])?;
+                index += bodylen;
+            }
+        }
+        self.sync()?;
+        for path in legacy_files.values() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
     }
 
-    fn get_file_path(dir: &str, height: Height) -> String {
-        let mut name = height.to_string();
-        name += ".log";
-        let pathname = dir.to_string() + "/";
-        pathname.clone() + &*name
+    /// The height the wal has gone to.
+    pub(crate) fn height(&self) -> Height {
+        self.current_height
     }
 
     pub(crate) fn set_height(&mut self, height: Height) -> Result<(), io::Error> {
@@ -87,119 +198,514 @@ impl Wal {
         let hstr = height.to_string();
         let content = hstr.as_bytes();
         let _ = self.ifile.set_len(content.len() as u64);
-        self.ifile.write_all(&content)?;
+        self.ifile.write_all(content)?;
         self.ifile.sync_data()?;
 
-        let filename = Wal::get_file_path(&self.dir, height);
-        let fs = OpenOptions::new()
-            .create(true)
+        // the records of the finished height are synced with its end
+        if self.unsynced > 0 && self.sync_policy != WalSyncPolicy::Never {
+            self.sync()?;
+        }
+        let retention = self.retention.heights;
+        if retention > 0 && height > retention {
+            self.prune(height - retention)?;
+        }
+
+        Ok(())
+    }
+
+    fn need_sync(&self) -> bool {
+        match self.sync_policy {
+            WalSyncPolicy::Always => true,
+            WalSyncPolicy::EveryNRecords(n) => self.unsynced >= n,
+            WalSyncPolicy::IntervalMs(millis) => {
+                self.last_sync.elapsed() >= Duration::from_millis(millis)
+            }
+            WalSyncPolicy::Never => false,
+        }
+    }
+
+    /// A function to sync the records of the active segment to the disk.
+    fn sync(&mut self) -> Result<(), io::Error> {
+        self.active.sync_data()?;
+        self.active_index.sync_data()?;
+        self.unsynced = 0;
+        self.last_sync = Instant::now();
+        Ok(())
+    }
+
+    /// A function to seal the active segment and start a new one.
+    fn rotate(&mut self) -> Result<(), io::Error> {
+        // the sealed segment is not synced with the later heights
+        if self.sync_policy != WalSyncPolicy::Never {
+            self.sync()?;
+        }
+        let seq = self.active_seq + 1;
+        self.active = OpenOptions::new()
             .read(true)
-            .write(true)
-            .open(filename)?;
-        self.height_fs.insert(height, fs);
+            .create(true)
+            .append(true)
+            .open(segment_path(&self.dir, seq))?;
+        self.active_index = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(segment_index_path(&self.dir, seq))?;
+        self.active_seq = seq;
+        self.segments.insert(seq, Segment::default());
+        Ok(())
+    }
 
-        if height > DELETE_FILE_INTERVAL {
-            let saved_height_fs = self.height_fs.split_off(&(height - DELETE_FILE_INTERVAL));
-            {
-                for (height, _) in self.height_fs.iter() {
-                    let delfilename = Wal::get_file_path(&self.dir, *height);
-                    let _ = ::std::fs::remove_file(delfilename);
+    /// A function to prune the wal segments whose heights are all below the [`below_height`],
+    /// which are moved to the archive directory if it is set, or deleted.
+    /// The active segment and the current height are never pruned.
+    pub(crate) fn prune(&mut self, below_height: Height) -> Result<(), io::Error> {
+        let below_height = below_height.min(self.current_height);
+        let pruned: Vec<u64> = self
+            .segments
+            .iter()
+            .filter(|(seq, segment)| {
+                **seq != self.active_seq && segment.heights.keys().all(|h| *h < below_height)
+            })
+            .map(|(seq, _)| *seq)
+            .collect();
+
+        for seq in pruned {
+            for path in &[
+                segment_path(&self.dir, seq),
+                segment_index_path(&self.dir, seq),
+            ] {
+                if !path.exists() {
+                    continue;
+                }
+                match &self.retention.archive_dir {
+                    Some(archive_dir) => archive_file(path, Path::new(archive_dir))?,
+                    None => fs::remove_file(path)?,
                 }
             }
-            self.height_fs = saved_height_fs;
+            self.segments.remove(&seq);
         }
-
         Ok(())
     }
 
     pub(crate) fn save(&mut self, height: Height, mtype: LogType, msg: &[u8]) -> io::Result<()> {
-        trace!("Wal save mtype: {:?}, height: {}", mtype, height);
-        if !self.height_fs.contains_key(&height) {
-            // 2 more higher than current height, do not process it
-            if height > self.current_height + 1 {
-                return Ok(());
-            } else if height == self.current_height + 1 {
-                let filename = Wal::get_file_path(&self.dir, height);
-                let fs = OpenOptions::new()
-                    .read(true)
-                    .create(true)
-                    .write(true)
-                    .open(filename)?;
-                self.height_fs.insert(height, fs);
-            }
+        trace!(
+            target: &self.log_target,
+            "Wal save mtype: {:?}, height: {}", mtype, height
+        );
+        // 2 more higher than current height, do not process it
+        if height > self.current_height.saturating_add(1) {
+            return Ok(());
         }
-        let mlen = msg.len() as u32;
-        if mlen == 0 {
+        if height < self.current_height.saturating_sub(SAVE_PAST_HEIGHTS) {
+            warn!(
+                target: &self.log_target,
+                "Can't find wal log in height {} ", height
+            );
+            return Ok(());
+        }
+        if msg.is_empty() {
             return Ok(());
         }
 
-        if let Some(fs) = self.height_fs.get_mut(&height) {
-            let len_bytes: [u8; 4] = unsafe { transmute(mlen.to_le()) };
-            let mtype: u8 = mtype.into();
-            let type_bytes: [u8; 1] = unsafe { transmute(mtype.to_le()) };
-            fs.seek(io::SeekFrom::End(0))?;
-            fs.write_all(&len_bytes[..])?;
-            fs.write_all(&type_bytes[..])?;
-            fs.write_all(&msg)?;
-            fs.flush()?;
-        } else {
-            warn!("Can't find wal log in height {} ", height);
+        self.append(height, record_tag(mtype, self.codec), msg)?;
+        self.unsynced += 1;
+        if self.need_sync() {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// A function to append a record to the active segment, which is rotated if it is full.
+    fn append(&mut self, height: Height, mtype: u8, msg: &[u8]) -> io::Result<()> {
+        if self.segments[&self.active_seq].size >= self.segment_size.max(1) {
+            self.rotate()?;
         }
 
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + msg.len());
+        record.extend_from_slice(&height.to_le_bytes());
+        record.extend_from_slice(&(msg.len() as u32).to_le_bytes());
+        record.push(mtype);
+        record.extend_from_slice(msg);
+        self.active.write_all(&record)?;
+        self.active.flush()?;
+
+        let segment = self
+            .segments
+            .get_mut(&self.active_seq)
+            .expect("the active segment is always indexed");
+        let offset = segment.size;
+        segment.size += record.len() as u64;
+        if let Entry::Vacant(entry) = segment.heights.entry(height) {
+            entry.insert(offset);
+            self.active_index.write_all(&index_entry(height, offset))?;
+            self.active_index.flush()?;
+        }
         Ok(())
     }
 
-    pub(crate) fn load(&mut self) -> Vec<(LogType, Vec<u8>)> {
-        let mut vec_buf: Vec<u8> = Vec::new();
-        let mut vec_out: Vec<(LogType, Vec<u8>)> = Vec::new();
+    /// A function to load the records from the current height with the codecs they are saved by.
+    pub(crate) fn load(&mut self) -> Vec<(Height, LogType, RlpCodec, Vec<u8>)> {
+        let mut records: Vec<(Height, LogType, RlpCodec, Vec<u8>)> = Vec::new();
         let cur_height = self.current_height;
-        if self.height_fs.is_empty() || cur_height == 0 {
-            return vec_out;
+        if cur_height == 0 {
+            return Vec::new();
         }
 
-        for (height, mut fs) in &self.height_fs {
-            if *height < self.current_height {
+        for (seq, segment) in &self.segments {
+            // the index locates the first record to be loaded in the segment
+            let start = match segment
+                .heights
+                .range(cur_height..)
+                .map(|(_, offset)| *offset)
+                .min()
+            {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let path = segment_path(&self.dir, *seq);
+            let mut buf = Vec::new();
+            let res = File::open(&path).and_then(|mut file| {
+                file.seek(io::SeekFrom::Start(start))?;
+                file.take(segment.size - start).read_to_end(&mut buf)
+            });
+            if let Err(e) = res {
+                warn!(
+                    target: &self.log_target,
+                    "Read wal segment {:?} failed: {:?}", path, e
+                );
                 continue;
             }
-            let expect_str = format!("Seek wal file {:?} of height {} failed!", fs, *height);
-            fs.seek(io::SeekFrom::Start(0)).expect(&expect_str);
-            let res_fsize = fs.read_to_end(&mut vec_buf);
-            if res_fsize.is_err() {
-                return vec_out;
-            }
-            let expect_str = format!(
-                "Get size of buf of wal file {:?} of height {} failed!",
-                fs, *height
-            );
-            let fsize = res_fsize.expect(&expect_str);
-            if fsize <= 5 {
-                return vec_out;
+            let log_target = &self.log_target;
+            parse_records(&buf, |_, height, tag, msg| {
+                if height < cur_height {
+                    return;
+                }
+                match parse_tag(tag) {
+                    Some((log_type, codec)) => {
+                        records.push((height, log_type, codec, msg.to_vec()));
+                    }
+                    None => warn!(
+                        target: log_target,
+                        "Skip wal record of invalid tag {} at height {}", tag, height
+                    ),
+                }
+            });
+        }
+        // the records are replayed height by height
+        records.sort_by_key(|(height, _, _, _)| *height);
+        records
+    }
+}
+
+/// A wal record decoded by the RLP of its log type.
+#[derive(Debug)]
+pub enum DecodedRecord {
+    Proposal(SignedProposal),
+    Vote(SignedVote),
+    Status(Status),
+    Proof(Proof),
+    Feed(Feed),
+    VerifyResp(VerifyResp),
+    TimeOutInfo {
+        height: Height,
+        round: Round,
+        step: String,
+    },
+    /// A block cached for a proposal or a lock, in the shape of a feed.
+    Block(Feed),
+    Authorities {
+        authorities: Vec<Node>,
+        authorities_old: Vec<Node>,
+        authority_h_old: Height,
+        schedule: BTreeMap<Height, Vec<Node>>,
+        rotations: Vec<KeyRotation>,
+    },
+    RevokeFeed {
+        height: Height,
+        block_hash: Hash,
+    },
+    /// The start of a height, with the unix time in milliseconds.
+    HeightStart {
+        height: Height,
+        timestamp: u64,
+    },
+    /// A record failing to be decoded, with the error.
+    Invalid(String),
+}
+
+fn decode_record(log_type: LogType, msg: &[u8]) -> DecodedRecord {
+    let decoded = match log_type {
+        LogType::Proposal => rlp::decode(msg).map(DecodedRecord::Proposal),
+        LogType::Vote => rlp::decode(msg).map(DecodedRecord::Vote),
+        LogType::Status => rlp::decode(msg).map(DecodedRecord::Status),
+        LogType::Proof => rlp::decode(msg).map(DecodedRecord::Proof),
+        LogType::Feed => rlp::decode(msg).map(DecodedRecord::Feed),
+        LogType::VerifyResp => rlp::decode(msg).map(DecodedRecord::VerifyResp),
+        LogType::TimeOutInfo => {
+            rlp::decode::<TimeoutInfo>(msg).map(|info| DecodedRecord::TimeOutInfo {
+                height: info.height,
+                round: info.round,
+                step: format!("{:?}", info.step),
+            })
+        }
+        LogType::Authorities => {
+            rlp::decode::<AuthorityManage>(msg).map(|manage| DecodedRecord::Authorities {
+                authorities: manage.authorities,
+                authorities_old: manage.authorities_old,
+                authority_h_old: manage.authority_h_old,
+                schedule: manage.schedule,
+                rotations: manage.rotations,
+            })
+        }
+        LogType::HeightStart => decode_height_start(msg)
+            .map(|(height, timestamp)| DecodedRecord::HeightStart { height, timestamp }),
+        LogType::RevokeFeed => decode_revoke_feed(msg)
+            .map(|(height, block_hash)| DecodedRecord::RevokeFeed { height, block_hash }),
+        LogType::Block => {
+            return match decode_block(msg) {
+                Ok((height, block, block_hash)) => DecodedRecord::Block(Feed {
+                    height,
+                    block,
+                    block_hash,
+                }),
+                Err(e) => DecodedRecord::Invalid(format!("{:?}", e)),
+            };
+        }
+    };
+    decoded.unwrap_or_else(|e| DecodedRecord::Invalid(format!("{:?}", e)))
+}
+
+/// A public function to decode the records of the wal in the [`dir`] in the order they are saved.
+/// The records of invalid log types are skipped, see `verify`.
+pub fn inspect(dir: &str) -> Result<Vec<(Height, LogType, DecodedRecord)>, io::Error> {
+    let mut records = Vec::new();
+    for seq in segment_seqs(dir)? {
+        let buf = fs::read(segment_path(dir, seq))?;
+        parse_records(&buf, |_, height, tag, msg| {
+            if let Some((log_type, _)) = parse_tag(tag) {
+                records.push((height, log_type, decode_record(log_type, msg)));
             }
-            let mut index = 0;
-            loop {
-                if index + 5 > fsize {
-                    break;
+        });
+    }
+    Ok(records)
+}
+
+/// A public function to check the wal in the [`dir`], return the problems found: the records
+/// failing to be decoded or of invalid log types, the torn records and the stale indexes.
+pub fn verify(dir: &str) -> Result<Vec<String>, io::Error> {
+    let mut problems = Vec::new();
+    for seq in segment_seqs(dir)? {
+        let path = segment_path(dir, seq);
+        let buf = fs::read(&path)?;
+        let mut heights = BTreeMap::new();
+        let end = parse_records(&buf, |offset, height, tag, msg| {
+            heights.entry(height).or_insert(offset as u64);
+            match parse_tag(tag) {
+                Some((log_type, _)) => {
+                    if let DecodedRecord::Invalid(e) = decode_record(log_type, msg) {
+                        problems.push(format!(
+                            "{:?} offset {}: {:?} of height {} fails to be decoded, {}",
+                            path, offset, log_type, height, e
+                        ));
+                    }
                 }
-                let hd: [u8; 4] = [
-                    vec_buf[index],
-                    vec_buf[index + 1],
-                    vec_buf[index + 2],
-                    vec_buf[index + 3],
-                ];
-                let tmp: u32 = unsafe { transmute::<[u8; 4], u32>(hd) };
-                let bodylen = tmp as usize;
-                let mtype = vec_buf[index + 4];
-                index += 5;
-                if index + bodylen > fsize {
-                    break;
+                None => problems.push(format!(
+                    "{:?} offset {}: invalid tag {} of height {}",
+                    path, offset, tag, height
+                )),
+            }
+        });
+        if end < buf.len() {
+            problems.push(format!(
+                "{:?} offset {}: torn record of {} bytes",
+                path,
+                end,
+                buf.len() - end
+            ));
+        }
+        if read_segment_index(&segment_index_path(dir, seq)) != Some(heights) {
+            problems.push(format!("{:?}: the index does not match the records", path));
+        }
+    }
+    Ok(problems)
+}
+
+/// A public function to rewrite the wal in the [`dir`] with the records [`keep`] returns true
+/// for, given the index of the record in `inspect`, its height and log type. The records of
+/// invalid log types and the torn records are always dropped. Return the count of the dropped
+/// records. The wal must not be opened by a running node.
+pub fn drop_records<F>(dir: &str, mut keep: F) -> Result<usize, io::Error>
+where
+    F: FnMut(usize, Height, LogType) -> bool,
+{
+    let mut index = 0;
+    let mut dropped = 0;
+    for seq in segment_seqs(dir)? {
+        let path = segment_path(dir, seq);
+        let buf = fs::read(&path)?;
+        let mut kept = Vec::with_capacity(buf.len());
+        let mut heights = BTreeMap::new();
+        let end = parse_records(&buf, |offset, height, tag, msg| {
+            let retained = match parse_tag(tag) {
+                Some((log_type, _)) => {
+                    index += 1;
+                    keep(index - 1, height, log_type)
                 }
-                vec_out.push((
-                    LogType::from(mtype),
-                    vec_buf[index..index + bodylen].to_vec(),
-                ));
-                index += bodylen;
+                None => false,
+            };
+            if retained {
+                heights.entry(height).or_insert(kept.len() as u64);
+                kept.extend_from_slice(&buf[offset..offset + RECORD_HEADER_LEN + msg.len()]);
+            } else {
+                dropped += 1;
             }
+        });
+        if end < buf.len() {
+            dropped += 1;
         }
-        vec_out
+        if kept.len() == buf.len() {
+            continue;
+        }
+
+        // a missing index is rebuilt from the segment, so a crash never leaves a stale one
+        let index_path = segment_index_path(dir, seq);
+        if index_path.exists() {
+            fs::remove_file(&index_path)?;
+        }
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, &kept)?;
+        fs::rename(&tmp_path, &path)?;
+        let entries: Vec<u8> = heights
+            .iter()
+            .flat_map(|(height, offset)| index_entry(*height, *offset))
+            .collect();
+        fs::write(&tmp_path, entries)?;
+        fs::rename(&tmp_path, &index_path)?;
+    }
+    Ok(dropped)
+}
+
+/// The tag of a record of the [`log_type`] encoded by the [`codec`]. The codec bits of
+/// `RlpCodec::V1` are 0 as the records of the former versions, so they are read alike.
+fn record_tag(log_type: LogType, codec: RlpCodec) -> u8 {
+    let codec = match codec {
+        RlpCodec::V1 => 0,
+        codec => u8::from(codec),
+    };
+    (codec << 4) | u8::from(log_type)
+}
+
+/// Split the tag of a record into its log type and codec, `None` if either is invalid.
+fn parse_tag(tag: u8) -> Option<(LogType, RlpCodec)> {
+    let codec = match tag >> 4 {
+        0 => RlpCodec::V1,
+        codec => RlpCodec::try_from(codec).ok()?,
+    };
+    Some((LogType::from_u8(tag & 0x0f)?, codec))
+}
+
+/// Get the sequence numbers of the segments in the directory in order.
+fn segment_seqs(dir: &str) -> Result<Vec<u64>, io::Error> {
+    let mut seqs = Vec::new();
+    for entry in read_dir(dir)? {
+        if let Some(seq) = file_number(&entry?.path(), SEGMENT_EXT) {
+            seqs.push(seq);
+        }
+    }
+    seqs.sort();
+    Ok(seqs)
+}
+
+fn segment_path(dir: &str, seq: u64) -> PathBuf {
+    Path::new(dir).join(format!("{:020}.{}", seq, SEGMENT_EXT))
+}
+
+fn segment_index_path(dir: &str, seq: u64) -> PathBuf {
+    Path::new(dir).join(format!("{:020}.{}", seq, SEGMENT_INDEX_EXT))
+}
+
+/// Get the number the file is named by, if it has the extension.
+fn file_number(path: &Path, ext: &str) -> Option<u64> {
+    if path.extension()?.to_str()? != ext {
+        return None;
+    }
+    path.file_stem()?.to_str()?.parse::<u64>().ok()
+}
+
+fn index_entry(height: Height, offset: u64) -> Vec<u8> {
+    let mut entry = Vec::with_capacity(INDEX_ENTRY_LEN);
+    entry.extend_from_slice(&height.to_le_bytes());
+    entry.extend_from_slice(&offset.to_le_bytes());
+    entry
+}
+
+/// Read the index of a segment, `None` if it is missing or torn.
+fn read_segment_index(path: &Path) -> Option<BTreeMap<Height, u64>> {
+    let buf = fs::read(path).ok()?;
+    if buf.len() % INDEX_ENTRY_LEN != 0 {
+        return None;
+    }
+    let mut heights = BTreeMap::new();
+    for entry in buf.chunks(INDEX_ENTRY_LEN) {
+        let mut height = [0u8; 8];
+        let mut offset = [0u8; 8];
+        height.copy_from_slice(&entry[..8]);
+        offset.copy_from_slice(&entry[8..]);
+        heights.insert(Height::from_le_bytes(height), u64::from_le_bytes(offset));
+    }
+    Some(heights)
+}
+
+/// Rebuild the index of a segment from its records, the size excludes a torn record at the end.
+fn scan_segment(path: &Path) -> Result<Segment, io::Error> {
+    let buf = fs::read(path)?;
+    let mut heights = BTreeMap::new();
+    let size = parse_records(&buf, |offset, height, _, _| {
+        heights.entry(height).or_insert(offset as u64);
+    });
+    Ok(Segment {
+        heights,
+        size: size as u64,
+    })
+}
+
+/// Visit the complete records of the buffer with their offsets, return the end of the last one.
+fn parse_records<F>(buf: &[u8], mut visit: F) -> usize
+where
+    F: FnMut(usize, Height, u8, &[u8]),
+{
+    let mut index = 0;
+    while index + RECORD_HEADER_LEN <= buf.len() {
+        let mut height = [0u8; 8];
+        let mut len = [0u8; 4];
+        height.copy_from_slice(&buf[index..index + 8]);
+        len.copy_from_slice(&buf[index + 8..index + 12]);
+        let bodylen = u32::from_le_bytes(len) as usize;
+        let mtype = buf[index + 12];
+        let body = index + RECORD_HEADER_LEN;
+        if body + bodylen > buf.len() {
+            break;
+        }
+        visit(
+            index,
+            Height::from_le_bytes(height),
+            mtype,
+            &buf[body..body + bodylen],
+        );
+        index = body + bodylen;
+    }
+    index
+}
+
+/// Move the file into the archive directory, copy it if they are on different file systems.
+fn archive_file(path: &Path, archive_dir: &Path) -> Result<(), io::Error> {
+    DirBuilder::new().recursive(true).create(archive_dir)?;
+    let target = match path.file_name() {
+        Some(name) => archive_dir.join(name),
+        None => return Ok(()),
+    };
+    if fs::rename(path, &target).is_err() {
+        fs::copy(path, &target)?;
+        fs::remove_file(path)?;
     }
+    Ok(())
 }
@@ -0,0 +1,120 @@
+//! The tests of the conversions of the proofs of cita-bft.
+//!
+//! ```text
+//! cargo test --features cita --test cita_test
+//! ```
+
+pub mod common;
+
+use crate::common::utils::{check_sig, sign};
+use bft_rs::cita::{check_cita_proof, CitaBftProof, CitaProof, CitaProofType, CitaStep};
+use bft_rs::objects::VoteType;
+use bft_rs::*;
+use std::convert::TryFrom;
+
+fn address(i: u8) -> Address {
+    Address::from([i; ADDRESS_LEN])
+}
+
+// stands for the hash of the precommit serialized by cita-bft
+fn precommit_hash(proof: &CitaBftProof, voter: &Address) -> Hash {
+    let mut hash = [0u8; HASH_LEN];
+    hash[..ADDRESS_LEN].copy_from_slice(voter);
+    hash[ADDRESS_LEN] = proof.height as u8;
+    hash[ADDRESS_LEN + 1] = proof.round as u8;
+    Hash::from(hash)
+}
+
+fn cita_proof(voters: &[Address]) -> CitaBftProof {
+    let mut proof = CitaBftProof {
+        proposal: Hash::from([7; HASH_LEN]),
+        height: 10,
+        round: 2,
+        commits: Default::default(),
+    };
+    for voter in voters {
+        let signature = sign(voter, &precommit_hash(&proof, voter));
+        proof.commits.insert(*voter, signature);
+    }
+    proof
+}
+
+#[test]
+fn test_proof_conversions() {
+    let cita_proof = cita_proof(&[address(1), address(2)]);
+    let proof = Proof::from(&cita_proof);
+    assert_eq!(proof.height, 10);
+    assert_eq!(proof.round, 2);
+    assert_eq!(proof.block_hash, cita_proof.proposal);
+    assert_eq!(proof.precommit_votes, cita_proof.commits);
+    assert_eq!(proof.authorities_hash, None);
+    assert_eq!(CitaBftProof::try_from(&proof).unwrap(), cita_proof);
+}
+
+#[test]
+fn test_step_mapping() {
+    for step in 0..9u8 {
+        assert_eq!(u8::from(CitaStep::try_from(step).unwrap()), step);
+    }
+    assert!(CitaStep::try_from(9).is_err());
+    assert_eq!(CitaStep::from(VoteType::Prevote), CitaStep::Prevote);
+    assert_eq!(CitaStep::from(VoteType::Precommit), CitaStep::Precommit);
+    assert_eq!(
+        VoteType::try_from(CitaStep::Precommit).unwrap(),
+        VoteType::Precommit
+    );
+    assert!(VoteType::try_from(CitaStep::PrecommitAuth).is_err());
+
+    let proof = CitaProof {
+        content: vec![1, 2, 3],
+        proof_type: CitaProofType::Bft,
+    };
+    assert_eq!(proof.bft_content().unwrap(), &[1, 2, 3][..]);
+    let proof = CitaProof {
+        content: vec![1, 2, 3],
+        proof_type: CitaProofType::Raft,
+    };
+    assert!(proof.bft_content().is_err());
+}
+
+#[test]
+fn test_check_cita_proof() {
+    let authorities: Vec<Node> = (1..5).map(|i| Node::new(address(i), 1, 1)).collect();
+    let proof = cita_proof(&[address(1), address(2), address(3)]);
+    assert!(check_cita_proof(
+        &proof,
+        11,
+        &authorities,
+        precommit_hash,
+        check_sig
+    ));
+    // the proof is of the previous height
+    assert!(!check_cita_proof(
+        &proof,
+        10,
+        &authorities,
+        precommit_hash,
+        check_sig
+    ));
+
+    // no more than 2/3 weight
+    let proof = cita_proof(&[address(1), address(2)]);
+    assert!(!check_cita_proof(
+        &proof,
+        11,
+        &authorities,
+        precommit_hash,
+        check_sig
+    ));
+
+    // a precommit signs another round
+    let mut proof = cita_proof(&[address(1), address(2), address(3)]);
+    proof.round = 3;
+    assert!(!check_cita_proof(
+        &proof,
+        11,
+        &authorities,
+        precommit_hash,
+        check_sig
+    ));
+}
@@ -0,0 +1,93 @@
+//! The tests of the rlp codecs of the byte wrappers and of the codec carried by the wire
+//! envelopes.
+
+use bft_rs::codec::{encode_by, RlpCodec};
+use bft_rs::objects::{Vote, VoteType};
+use bft_rs::wire::{WireKind, WireMsg};
+use bft_rs::*;
+
+const CHAIN_ID: u64 = 1;
+
+fn vote() -> Vote {
+    Vote {
+        vote_type: VoteType::Precommit,
+        height: 7,
+        round: 1,
+        block_hash: Hash::from([3; HASH_LEN]),
+        voter: Address::from([5; ADDRESS_LEN]),
+    }
+}
+
+#[test]
+fn test_rlp_codecs() {
+    let hash = Hash::from([3; HASH_LEN]);
+    let signature = Signature::from(vec![1, 2, 3]);
+    let v1_hash = encode_by(&hash, RlpCodec::V1);
+    let v1_signature = encode_by(&signature, RlpCodec::V1);
+    let v1_vote = encode_by(&vote(), RlpCodec::V1);
+    // a one-element list of a 32 bytes string, the same as the plain `Encodable`
+    assert_eq!(v1_hash[..2], [0xe1, 0xa0]);
+    assert_eq!(v1_signature, vec![0xc4, 0x83, 1, 2, 3]);
+    assert_eq!(v1_vote, rlp::encode(&vote()).to_vec());
+
+    let v2_hash = encode_by(&hash, RlpCodec::V2);
+    let v2_signature = encode_by(&signature, RlpCodec::V2);
+    let v2_vote = encode_by(&vote(), RlpCodec::V2);
+    // a plain 32 bytes string
+    assert_eq!(v2_hash[0], 0xa0);
+    assert_eq!(v2_hash[1..], hash[..]);
+    assert_eq!(v2_signature, vec![0x83, 1, 2, 3]);
+    assert_ne!(v1_vote, v2_vote);
+
+    // both codecs are decoded
+    assert_eq!(rlp::decode::<Hash>(&v1_hash).unwrap(), hash);
+    assert_eq!(rlp::decode::<Hash>(&v2_hash).unwrap(), hash);
+    assert_eq!(rlp::decode::<Signature>(&v1_signature).unwrap(), signature);
+    assert_eq!(rlp::decode::<Signature>(&v2_signature).unwrap(), signature);
+    assert_eq!(rlp::decode::<Vote>(&v1_vote).unwrap(), vote());
+    assert_eq!(rlp::decode::<Vote>(&v2_vote).unwrap(), vote());
+
+    // a fixed bytes wrapper of another length is rejected, the empty bytes too
+    assert!(rlp::decode::<Address>(&v2_hash).is_err());
+    for codec in [RlpCodec::V1, RlpCodec::V2].iter() {
+        let empty = encode_by(&Signature::from(vec![]), *codec);
+        assert!(rlp::decode::<Hash>(&empty).is_err());
+        assert!(rlp::decode::<Address>(&empty).is_err());
+    }
+}
+
+#[test]
+fn test_wire_msg_codec() {
+    for codec in [RlpCodec::V1, RlpCodec::V2].iter() {
+        let payload = encode_by(&vote(), *codec);
+        let encode = rlp::encode(&WireMsg::new(
+            CHAIN_ID,
+            *codec,
+            WireKind::Vote,
+            payload.clone(),
+        ));
+        assert_eq!(
+            WireMsg::open(&encode, CHAIN_ID, *codec, WireKind::Vote).unwrap(),
+            payload
+        );
+    }
+
+    // the envelope of the former codec keeps its 4-item layout
+    let v1_encode = rlp::encode(&WireMsg::new(
+        CHAIN_ID,
+        RlpCodec::V1,
+        WireKind::Vote,
+        vec![],
+    ));
+    assert_eq!(rlp::Rlp::new(&v1_encode).item_count().unwrap(), 4);
+
+    // the msgs of another codec are rejected
+    let v2_encode = rlp::encode(&WireMsg::new(
+        CHAIN_ID,
+        RlpCodec::V2,
+        WireKind::Vote,
+        vec![],
+    ));
+    assert!(WireMsg::open(&v2_encode, CHAIN_ID, RlpCodec::V1, WireKind::Vote).is_err());
+    assert!(WireMsg::open(&v1_encode, CHAIN_ID, RlpCodec::V2, WireKind::Vote).is_err());
+}
@@ -0,0 +1,52 @@
+use super::config::Config;
+use super::utils::get_random_float;
+use bft_rs::clock::Clock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A clock of a simulated node, which is ahead of the system clock by the offset and runs
+/// faster than it by the drift, so the timeouts of the nodes fire at different wall-clock times.
+#[derive(Debug)]
+pub struct SkewedClock {
+    offset_millis: i64,
+    drift_ppm: f64,
+    start: Instant,
+    start_millis: u64,
+}
+
+impl SkewedClock {
+    pub fn new(offset_millis: i64, drift_ppm: f64) -> Self {
+        let start_millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_millis() as u64)
+            .unwrap_or(0);
+        SkewedClock {
+            offset_millis,
+            drift_ppm,
+            start: Instant::now(),
+            start_millis,
+        }
+    }
+
+    // the offset and the drift of a node are sampled once, and kept after restarting it
+    pub fn random(config: &Config) -> Self {
+        let offset_millis = get_random_float(config.clock_offset) as i64;
+        let drift_ppm = get_random_float(config.clock_drift);
+        SkewedClock::new(offset_millis, drift_ppm)
+    }
+
+    fn rate(&self) -> f64 {
+        // never stop or reverse the clock
+        (1.0 + self.drift_ppm / 1_000_000.0).max(0.01)
+    }
+}
+
+impl Clock for SkewedClock {
+    fn unix_millis(&self) -> u64 {
+        let elapsed = self.start.elapsed().as_millis() as f64 * self.rate();
+        (self.start_millis as i64 + elapsed as i64 + self.offset_millis).max(0) as u64
+    }
+
+    fn elapse(&self, duration: Duration) -> Duration {
+        duration.mul_f64(1.0 / self.rate())
+    }
+}
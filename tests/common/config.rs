@@ -1,12 +1,18 @@
 use super::utils::RandomMode;
 use std::time::Duration;
-use std::u64::MAX as MAX_U64;
-use std::u8::MAX as MAX_U8;
 
 pub const LIVENESS_TICK: Duration = Duration::from_secs(60);
 pub const ADDRESS_SIZE: usize = 20; // 160
-pub const RANDOM_U8: RandomMode = RandomMode::Uniform(0u64, MAX_U8 as u64);
-pub const RANDOM_U64: RandomMode = RandomMode::Uniform(0u64, MAX_U64);
+pub const RANDOM_U8: RandomMode = RandomMode::Uniform(0u64, u8::MAX as u64);
+pub const RANDOM_U64: RandomMode = RandomMode::Uniform(0u64, u64::MAX);
+
+/// The rates of the faults injected into the msgs of a link from one node to another.
+#[derive(Clone, Copy, Debug)]
+pub struct LinkFaults {
+    pub lost_rate: f64,
+    pub duplicate_rate: f64,
+    pub reorder_rate: f64,
+}
 
 #[derive(Clone, Copy)]
 pub struct Config {
@@ -15,6 +21,9 @@ pub struct Config {
     pub min_block_size: usize,
     pub check_txs_failed_rate: f64,
     pub message_lost_rate: f64,
+    pub message_duplicate_rate: f64,
+    // the rate of the msgs held back behind the later ones of the link
+    pub message_reorder_rate: f64,
     pub max_delay: u64, // ms
     pub min_delay: u64, // ms
     pub check_txs_delay: RandomMode,
@@ -22,6 +31,19 @@ pub struct Config {
     pub sync_trigger_duration: u64, //ms
     pub sync_delay: RandomMode,
     pub message_delay: RandomMode,
+    pub clock_offset: RandomMode, // ms
+    pub clock_drift: RandomMode,  // ppm
+}
+
+impl Config {
+    /// The faults of the links without their own ones.
+    pub fn link_faults(&self) -> LinkFaults {
+        LinkFaults {
+            lost_rate: self.message_lost_rate,
+            duplicate_rate: self.message_duplicate_rate,
+            reorder_rate: self.message_reorder_rate,
+        }
+    }
 }
 
 pub const PERFECT_CONFIG: Config = Config {
@@ -30,6 +52,8 @@ pub const PERFECT_CONFIG: Config = Config {
     min_block_size: 100,
     check_txs_failed_rate: 0.0,
     message_lost_rate: 0.0,
+    message_duplicate_rate: 0.0,
+    message_reorder_rate: 0.0,
     max_delay: 60_000,
     min_delay: 1,
     check_txs_delay: RandomMode::Normal(3.0, 1.0),
@@ -37,6 +61,8 @@ pub const PERFECT_CONFIG: Config = Config {
     sync_trigger_duration: 6_000,
     sync_delay: RandomMode::Normal(3.0, 1.0),
     message_delay: RandomMode::Normal(10.0, 5.0),
+    clock_offset: RandomMode::Normal(0.0, 0.0),
+    clock_drift: RandomMode::Normal(0.0, 0.0),
 };
 
 pub const NORMAL_CONFIG: Config = Config {
@@ -45,6 +71,8 @@ pub const NORMAL_CONFIG: Config = Config {
     min_block_size: 100,
     check_txs_failed_rate: 0.02,
     message_lost_rate: 0.01,
+    message_duplicate_rate: 0.01,
+    message_reorder_rate: 0.02,
     max_delay: 60_000,
     min_delay: 5,
     check_txs_delay: RandomMode::Normal(100.0, 30.0),
@@ -52,6 +80,8 @@ pub const NORMAL_CONFIG: Config = Config {
     sync_trigger_duration: 6_000,
     sync_delay: RandomMode::Normal(10.0, 2.0),
     message_delay: RandomMode::Normal(30.0, 20.0),
+    clock_offset: RandomMode::Normal(0.0, 200.0),
+    clock_drift: RandomMode::Normal(0.0, 100.0),
 };
 
 pub const BAD_CONFIG: Config = Config {
@@ -60,6 +90,8 @@ pub const BAD_CONFIG: Config = Config {
     min_block_size: 100,
     check_txs_failed_rate: 0.2,
     message_lost_rate: 0.2,
+    message_duplicate_rate: 0.1,
+    message_reorder_rate: 0.2,
     max_delay: 60_000,
     min_delay: 1,
     check_txs_delay: RandomMode::Normal(500.0, 100.0),
@@ -67,6 +99,8 @@ pub const BAD_CONFIG: Config = Config {
     sync_trigger_duration: 6_000,
     sync_delay: RandomMode::Normal(100.0, 50.0),
     message_delay: RandomMode::Normal(800.0, 500.0),
+    clock_offset: RandomMode::Normal(0.0, 1_000.0),
+    clock_drift: RandomMode::Normal(0.0, 1_000.0),
 };
 
 pub const HELL_CONFIG: Config = Config {
@@ -75,6 +109,8 @@ pub const HELL_CONFIG: Config = Config {
     min_block_size: 100,
     check_txs_failed_rate: 0.2,
     message_lost_rate: 0.2,
+    message_duplicate_rate: 0.2,
+    message_reorder_rate: 0.3,
     max_delay: 60_000,
     min_delay: 1,
     check_txs_delay: RandomMode::Normal(1000.0, 500.0),
@@ -82,4 +118,6 @@ pub const HELL_CONFIG: Config = Config {
     sync_trigger_duration: 6_000,
     sync_delay: RandomMode::Normal(1000.0, 1000.0),
     message_delay: RandomMode::Normal(5000.0, 3000.0),
+    clock_offset: RandomMode::Normal(0.0, 3_000.0),
+    clock_drift: RandomMode::Normal(0.0, 10_000.0),
 };
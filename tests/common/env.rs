@@ -1,8 +1,14 @@
 extern crate bft_rs;
 
+use self::bft_rs::byzantine::ByzantineStrategy;
+use self::bft_rs::params::BftConfig;
+use self::bft_rs::runtime::BftRuntime;
 use self::bft_rs::timer::{GetInstant, WaitTimer};
 use self::bft_rs::{Address, Hash, Height};
-use super::config::{Config, LIVENESS_TICK};
+use super::clock::SkewedClock;
+use super::config::{Config, LinkFaults, LIVENESS_TICK};
+use super::nemesis::{Fault, Nemesis};
+use super::signer::Signer;
 use super::support::Support;
 use super::utils::*;
 use bft_rs::{BftActuator, BftMsg, Commit, Node, Status};
@@ -10,17 +16,47 @@ use crossbeam::crossbeam_channel::{select, unbounded, Receiver, RecvError, Sende
 #[allow(unused_imports)]
 use log::{info, log};
 use lru_cache::LruCache;
+use serde_derive::{Deserialize, Serialize};
 use std::cmp::{Ord, Ordering, PartialOrd};
 use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
 use std::sync::Arc;
 use std::thread;
 use std::time::{Duration, Instant};
 
+// a reordered msg is held back by the delays of several msgs
+const REORDER_COEF: u32 = 4;
+
+// the seed of a run is taken from the variable if set, otherwise drawn
+const SEED_VAR: &str = "BFT_TEST_SEED";
+
+/// The first line of a record, the seed of the randomness of the run.
+#[derive(Debug, Serialize, Deserialize)]
+struct RecordHeader {
+    seed: u64,
+}
+
+/// A line of a record, the copies of a msg delivered on a link.
+#[derive(Debug, Serialize, Deserialize)]
+struct Delivery {
+    // the ms since the run begins when the msg is sent
+    at: u64,
+    from: usize,
+    // the sequence of the msg among the ones sent by the node
+    seq: u64,
+    to: usize,
+    // the ms each copy is delayed by, empty if the msg is lost
+    delays: Vec<u64>,
+}
+
 pub struct Env {
     pub config: Config,
     pub wal_dir: &'static str,
     pub live_nodes: HashMap<Address, Box<BftActuator>>,
-    pub byzantine_nodes: Vec<Address>,
+    pub byzantine_nodes: HashMap<Address, ByzantineStrategy>,
+    // the forks and the stalls observed while the byzantine weight is not below 1/3
+    pub observations: Vec<String>,
     pub msg_recv: Receiver<(BftMsg, Address)>,
     pub msg_send: Sender<(BftMsg, Address)>,
     pub commit_recv: Receiver<(Commit, Address)>,
@@ -35,20 +71,89 @@ pub struct Env {
     pub last_reach_consensus_time: Instant,
     pub commits: LruCache<Height, Hash>,
     pub nodes_height: HashMap<Address, Height>,
+    pub clocks: HashMap<Address, Arc<SkewedClock>>,
+    pub signers: HashMap<Address, Signer>,
+    pub partition: Vec<Address>,
+    // the faults of the links from one node to another, others take the ones of the config
+    pub link_faults: HashMap<(Address, Address), LinkFaults>,
+    // the delay distributions of the links, others take the one of the config
+    pub link_delays: HashMap<(Address, Address), RandomMode>,
+    pub seed: u64,
+    recorder: Option<File>,
+    // the deliveries of a record replayed, instead of the ones drawn
+    replay: Option<HashMap<(usize, u64, usize), Vec<u64>>>,
+    sent: HashMap<Address, u64>,
+    begin: Instant,
+    // the base of the configs of the nodes, such as the runtime they are run by
+    base_config: BftConfig,
 }
 
 impl Env {
     pub fn new(config: Config, nodes_num: usize, wal_dir: &'static str) -> Env {
+        Env::new_with_seed(config, nodes_num, wal_dir, draw_seed())
+    }
+
+    /// Create the env of the nodes whose randomness is seeded by the [`seed`].
+    pub fn new_with_seed(
+        config: Config,
+        nodes_num: usize,
+        wal_dir: &'static str,
+        seed: u64,
+    ) -> Env {
+        Env::build(config, nodes_num, wal_dir, seed, BftConfig::default())
+    }
+
+    /// Create the env of the nodes run by the shared [`runtime`].
+    pub fn new_with_runtime(
+        config: Config,
+        nodes_num: usize,
+        wal_dir: &'static str,
+        runtime: Arc<BftRuntime>,
+    ) -> Env {
+        let base_config = BftConfig {
+            runtime: Some(runtime),
+            ..BftConfig::default()
+        };
+        Env::build(config, nodes_num, wal_dir, draw_seed(), base_config)
+    }
+
+    /// Create the env of the nodes run as the tasks of the tokio runtime of the [`handle`].
+    #[cfg(feature = "tokio")]
+    pub fn new_with_tokio(
+        config: Config,
+        nodes_num: usize,
+        wal_dir: &'static str,
+        handle: tokio::runtime::Handle,
+    ) -> Env {
+        let base_config = BftConfig {
+            tokio_handle: Some(handle),
+            ..BftConfig::default()
+        };
+        Env::build(config, nodes_num, wal_dir, draw_seed(), base_config)
+    }
+
+    fn build(
+        config: Config,
+        nodes_num: usize,
+        wal_dir: &'static str,
+        seed: u64,
+        base_config: BftConfig,
+    ) -> Env {
+        set_seed(seed);
+        info!("the run is seeded by {}", seed);
         let mut live_nodes = HashMap::new();
         let mut nodes_height = HashMap::new();
+        let mut clocks = HashMap::new();
+        let mut signers = HashMap::new();
         let mut authority_list = vec![];
         let (msg_send, msg_recv) = unbounded();
         let (commit_send, commit_recv) = unbounded();
         for i in 0..nodes_num {
-            let address = generate_address();
+            let signer = Signer::generate();
+            let address = signer.address;
 
             let node = Node {
-                address: address.clone(),
+                address,
                 proposal_weight: 1u32,
                 vote_weight: 1u32,
             };
@@ -56,14 +161,24 @@ impl Env {
 
             let node_support = Support {
                 config,
-                address: address.clone(),
+                address,
+                signer: signer.clone(),
                 msg_send: msg_send.clone(),
                 commit_send: commit_send.clone(),
             };
+            let clock = Arc::new(SkewedClock::random(&config));
+            info!("node {:?} runs on {:?}", address, clock);
             let wal_path = format!("{}{}", wal_dir, i);
-            let actuator = BftActuator::new(Arc::new(node_support), address.clone(), &wal_path);
-            live_nodes.insert(address.clone(), Box::new(actuator));
+            let actuator = BftActuator::new_with_config(
+                Arc::new(node_support),
+                address,
+                &wal_path,
+                node_config(&clock, &base_config),
+            );
+            live_nodes.insert(address, Box::new(actuator));
             nodes_height.insert(address, 0);
+            clocks.insert(address, clock);
+            signers.insert(address, signer);
         }
 
         let interval = Some(3000);
@@ -72,6 +187,9 @@ impl Env {
             height: 0u64,
             interval,
             authority_list: authority_list.clone(),
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
         };
 
         let mut status_list = LruCache::new(16);
@@ -91,7 +209,8 @@ impl Env {
             config,
             wal_dir,
             live_nodes,
-            byzantine_nodes: vec![],
+            byzantine_nodes: HashMap::new(),
+            observations: vec![],
             msg_recv,
             msg_send,
             commit_recv,
@@ -105,16 +224,58 @@ impl Env {
             last_reach_consensus_time: Instant::now(),
             commits: LruCache::new(16),
             nodes_height,
+            clocks,
+            signers,
+            partition: vec![],
+            link_faults: HashMap::new(),
+            link_delays: HashMap::new(),
+            seed,
+            recorder: None,
+            replay: None,
+            sent: HashMap::new(),
+            begin: Instant::now(),
+            base_config,
         }
     }
 
-    pub fn run(&mut self, stop_height: u64) {
+    /// Create the env of a run recorded in the file of the [`path`], which is replayed by the
+    /// seed and the deliveries of the record. The nodes still run by the real time, so the
+    /// replay takes the same config, nodes and schedule as the record.
+    pub fn replay(config: Config, nodes_num: usize, wal_dir: &'static str, path: &str) -> Env {
+        let mut lines = BufReader::new(File::open(path).unwrap()).lines();
+        let header: RecordHeader = serde_json::from_str(&lines.next().unwrap().unwrap()).unwrap();
+        let mut env = Env::new_with_seed(config, nodes_num, wal_dir, header.seed);
+        let replay = lines
+            .map(|line| serde_json::from_str::<Delivery>(&line.unwrap()).unwrap())
+            .map(|delivery| ((delivery.from, delivery.seq, delivery.to), delivery.delays))
+            .collect();
+        env.replay = Some(replay);
+        env
+    }
+
+    /// Record the seed and the deliveries of the run to the file of the [`path`].
+    pub fn record(&mut self, path: &str) {
+        let mut file = File::create(path).unwrap();
+        let header = RecordHeader { seed: self.seed };
+        writeln!(file, "{}", serde_json::to_string(&header).unwrap()).unwrap();
+        self.recorder = Some(file);
+    }
+
+    pub fn run(&mut self, schedule: &[Nemesis], stop_height: u64) {
+        self.begin = Instant::now();
+        schedule.iter().for_each(|nemesis| self.schedule(nemesis));
         let event = Event {
             process_time: Instant::now(),
             to: Address::default(),
             content: Content::Sync,
         };
         self.test2timer.send(event).unwrap();
+        let event = Event {
+            process_time: Instant::now() + LIVENESS_TICK,
+            to: Address::default(),
+            content: Content::LivenessTimeout(self.status.height, 1),
+        };
+        self.test2timer.send(event).unwrap();
 
         loop {
             let mut get_msg = Err(RecvError);
@@ -128,17 +289,7 @@ impl Env {
             }
 
             if let Ok((msg, from)) = get_msg {
-                self.live_nodes.iter().for_each(|(address, _)| {
-                    if address != &from {
-                        let delay = message_delay(&self.config);
-                        let event = Event {
-                            process_time: Instant::now() + delay,
-                            to: address.clone(),
-                            content: Content::Msg(msg.clone()),
-                        };
-                        self.test2timer.send(event).unwrap();
-                    }
-                });
+                self.deliver(msg, from);
             }
             if let Ok((commit, sender)) = get_commit {
                 let ch = commit.height;
@@ -210,12 +361,22 @@ impl Env {
                         }
                     }
                     Content::Status(status) => {
-                        self.nodes_height.insert(to.clone(), status.height);
+                        self.nodes_height.insert(to, status.height);
                         if let Some(actuator) = self.live_nodes.get(&to) {
-                            actuator.send(BftMsg::Status(status)).unwrap();
+                            actuator.send_status(status).unwrap();
                         }
                     }
                     Content::LivenessTimeout(height, n) => {
+                        if height == self.status.height && !self.is_byzantine_tolerated() {
+                            self.observe(format!(
+                                "no node reach consensus in last {} minutes at height {}",
+                                n, height
+                            ));
+                            self.live_nodes
+                                .iter()
+                                .for_each(|(_, actuator)| actuator.send(BftMsg::Kill).unwrap());
+                            break;
+                        }
                         if height == self.status.height {
                             info!(
                                 "WARNING! no node reach consensus in last {} minutes at height {}",
@@ -233,43 +394,209 @@ impl Env {
                         self.corrupt();
                         self.try_sync();
                     }
-                    Content::Corrupt => {
-                        self.byzantine_nodes.push(to);
+                    Content::Corrupt(strategy) => {
+                        self.byzantine_nodes.insert(to, strategy);
                     }
                     Content::Start(i) => {
-                        let actuator = self.generate_node(to.clone(), i);
+                        let actuator = self.generate_node(to, i);
                         info!("Node {:?} is started", to);
                         self.live_nodes.insert(to, Box::new(actuator));
+                        // the statuses sent while the node is stopped are missed, so it is
+                        // synced again from its wal
+                        self.nodes_height.insert(to, 0);
                     }
                     Content::Stop => {
                         let actuator = self.live_nodes.remove(&to).unwrap();
                         actuator.send(BftMsg::Kill).unwrap();
                         info!("Node {:?} is stopped", to);
                     }
+                    Content::Partition(nodes) => {
+                        info!("Nodes {:?} are partitioned", nodes);
+                        self.partition = nodes;
+                    }
+                    Content::Clear => {
+                        info!("The partition is cleared");
+                        self.partition.clear();
+                    }
+                }
+            }
+        }
+
+        if self.observations.is_empty() {
+            info!("Successfully pass the test!");
+        } else {
+            info!("Pass the test, observed {:?}", self.observations);
+        }
+    }
+
+    // the receivers are taken in the order of the authority list, so the draws of the links
+    // are reproduced by the seed
+    fn deliver(&mut self, msg: BftMsg, from: Address) {
+        let seq = self.sent.entry(from).or_insert(0);
+        *seq += 1;
+        let seq = *seq;
+        let at = self.begin.elapsed().as_millis() as u64;
+        let from_index = self.get_node_index(&from);
+        let partition = &self.partition;
+        let receivers: Vec<(usize, Address)> = self
+            .authority_list
+            .iter()
+            .map(|node| node.address)
+            .enumerate()
+            .filter(|(_, address)| {
+                address != &from
+                    && self.live_nodes.contains_key(address)
+                    && partition.contains(address) == partition.contains(&from)
+            })
+            .collect();
+        for (to_index, to) in receivers {
+            let delays = match self.replay.as_mut() {
+                Some(replay) => replay
+                    .remove(&(from_index, seq, to_index))
+                    .unwrap_or_default(),
+                None => self.draw_delays(&from, &to),
+            };
+            for delay in delays.iter() {
+                let event = Event {
+                    process_time: Instant::now() + Duration::from_millis(*delay),
+                    to,
+                    content: Content::Msg(msg.clone()),
+                };
+                self.test2timer.send(event).unwrap();
+            }
+            if let Some(recorder) = self.recorder.as_mut() {
+                let delivery = Delivery {
+                    at,
+                    from: from_index,
+                    seq,
+                    to: to_index,
+                    delays,
+                };
+                writeln!(recorder, "{}", serde_json::to_string(&delivery).unwrap()).unwrap();
+            }
+        }
+    }
+
+    // the ms the copies of a msg on the link are delayed by, empty if it is lost
+    fn draw_delays(&self, from: &Address, to: &Address) -> Vec<u64> {
+        let faults = self
+            .link_faults
+            .get(&(*from, *to))
+            .copied()
+            .unwrap_or_else(|| self.config.link_faults());
+        if is_message_lost(&faults) {
+            return Vec::new();
+        }
+        let mode = self
+            .link_delays
+            .get(&(*from, *to))
+            .copied()
+            .unwrap_or(self.config.message_delay);
+        let copies = if is_message_duplicated(&faults) { 2 } else { 1 };
+        (0..copies)
+            .map(|_| {
+                let mut delay = link_delay(mode, &self.config);
+                if is_message_reordered(&faults) {
+                    delay += link_delay(mode, &self.config) * REORDER_COEF;
+                }
+                delay.as_millis() as u64
+            })
+            .collect()
+    }
+
+    /// Inject the [`faults`] into the msgs from the node [`from`] to the node [`to`] instead of
+    /// the ones of the config.
+    pub fn set_link_faults(&mut self, from: usize, to: usize, faults: LinkFaults) {
+        if let (Some(from), Some(to)) = (self.get_node_address(from), self.get_node_address(to)) {
+            self.link_faults.insert((from, to), faults);
+        }
+    }
+
+    /// Delay the msgs from the node [`from`] to the node [`to`] by the distribution [`mode`]
+    /// instead of the one of the config.
+    pub fn set_link_delay(&mut self, from: usize, to: usize, mode: RandomMode) {
+        if let (Some(from), Some(to)) = (self.get_node_address(from), self.get_node_address(to)) {
+            self.link_delays.insert((from, to), mode);
+        }
+    }
+
+    /// Place the nodes in the [`zones`], such as datacenters, the msgs within a zone are
+    /// delayed by the distribution [`intra`] and the ones between two zones by [`inter`].
+    pub fn set_zones(&mut self, zones: &[&[usize]], intra: RandomMode, inter: RandomMode) {
+        for (i, zone) in zones.iter().enumerate() {
+            for (j, other) in zones.iter().enumerate() {
+                let mode = if i == j { intra } else { inter };
+                for from in zone.iter() {
+                    for to in other.iter().filter(|to| *to != from) {
+                        self.set_link_delay(*from, *to, mode);
+                    }
                 }
             }
         }
+    }
+
+    /// Corrupt the [`nodes`] by their strategies from the beginning of the run.
+    pub fn set_byzantine(&mut self, nodes: &[(usize, ByzantineStrategy)]) {
+        nodes.iter().for_each(|(i, strategy)| {
+            if let Some(address) = self.get_node_address(*i) {
+                self.byzantine_nodes.insert(address, *strategy);
+            }
+        });
+    }
+
+    /// Corrupt the nearest [`ratio`] of the nodes from the beginning of the run, the first
+    /// nodes of the authority list take the [`strategies`] in turn.
+    pub fn set_byzantine_ratio(&mut self, ratio: f64, strategies: &[ByzantineStrategy]) {
+        let count = (ratio * self.authority_list.len() as f64).round() as usize;
+        let nodes: Vec<(usize, ByzantineStrategy)> = (0..count.min(self.authority_list.len()))
+            .zip(strategies.iter().copied().cycle())
+            .collect();
+        self.set_byzantine(&nodes);
+    }
 
-        info!("Successfully pass the test!");
+    /// Whether the byzantine nodes weigh below 1/3 of the votes, then the safety is asserted,
+    /// otherwise the forks and the stalls are observed.
+    pub fn is_byzantine_tolerated(&self) -> bool {
+        let weight = |byzantine: bool| -> u64 {
+            self.authority_list
+                .iter()
+                .filter(|node| self.byzantine_nodes.contains_key(&node.address) == byzantine)
+                .map(|node| u64::from(node.vote_weight))
+                .sum()
+        };
+        weight(true) * 2 < weight(false)
+    }
+
+    fn observe(&mut self, observation: String) {
+        info!("WARNING! {}", observation);
+        self.observations.push(observation);
     }
 
     pub fn generate_node(&self, address: Address, i: usize) -> BftActuator {
         let node_support = Support {
             config: self.config,
-            address: address.clone(),
+            address,
+            signer: self.signers[&address].clone(),
             msg_send: self.msg_send.clone(),
             commit_send: self.commit_send.clone(),
         };
         let wal_path = format!("{}{}", self.wal_dir, i);
-        BftActuator::new(Arc::new(node_support), address, &wal_path)
+        let config = node_config(&self.clocks[&address], &self.base_config);
+        BftActuator::new_with_config(Arc::new(node_support), address, &wal_path, config)
     }
 
     pub fn check_consistency(&mut self, commit: &Commit) {
         if self.commits.contains_key(&commit.height) {
-            let hash = &hash(&commit.block);
-            let compare = self.commits.get_mut(&commit.height).unwrap();
+            let hash = hash(&commit.block);
+            let compare = *self.commits.get_mut(&commit.height).unwrap();
             if hash != compare {
-                panic!("consistency is broken of commit {:?}", commit);
+                if self.is_byzantine_tolerated() {
+                    panic!("consistency is broken of commit {:?}", commit);
+                }
+                self.observe(format!(
+                    "a fork of {:?} and {:?} at height {}",
+                    hash, compare, commit.height
+                ));
             }
         } else {
             info!("too old commit, failed to check consistency!");
@@ -281,6 +608,9 @@ impl Env {
             height,
             authority_list: self.authority_list.clone(),
             interval: self.interval,
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
         };
         self.status = status.clone();
         self.status_list.insert(height, status.clone());
@@ -292,7 +622,8 @@ impl Env {
             .nodes_height
             .iter()
             .filter(|(address, _)| {
-                self.live_nodes.contains_key(*address) && !self.byzantine_nodes.contains(*address)
+                self.live_nodes.contains_key(*address)
+                    && !self.byzantine_nodes.contains_key(*address)
             })
             .collect();
         if let Some(max_height) = live_honest_heights.values().max() {
@@ -303,7 +634,7 @@ impl Env {
                         let delay = sync_delay(*max_height - height, &self.config);
                         let event = Event {
                             process_time: Instant::now() + delay,
-                            to: address.clone(),
+                            to: *address,
                             content: Content::Status(status.clone()),
                         };
                         self.test2timer.send(event).unwrap();
@@ -322,28 +653,84 @@ impl Env {
 
     pub fn corrupt(&self) {
         self.live_nodes.iter().for_each(|(address, actuator)| {
-            if self.byzantine_nodes.contains(address) {
-                actuator.send(BftMsg::Corrupt).unwrap();
+            if let Some(strategy) = self.byzantine_nodes.get(address) {
+                actuator.send(BftMsg::CorruptWith(*strategy)).unwrap();
             }
         });
     }
 
     pub fn get_node_address(&self, i: usize) -> Option<Address> {
+        self.authority_list.get(i).map(|node| node.address)
+    }
+
+    pub fn get_node_index(&self, address: &Address) -> usize {
         self.authority_list
-            .get(i)
-            .and_then(|node| Some(node.address.clone()))
+            .iter()
+            .position(|node| node.address == *address)
+            .unwrap_or(usize::MAX)
+    }
+
+    pub fn send_msg(&mut self, i: usize, msg: BftMsg, duration: Duration) {
+        self.set_node(i, Content::Msg(msg), duration);
+    }
+
+    fn schedule(&mut self, nemesis: &Nemesis) {
+        let duration = Duration::from_millis(nemesis.at);
+        match nemesis.fault {
+            Fault::Stop(i) => self.set_node(i, Content::Stop, duration),
+            Fault::Start(i) => self.set_node(i, Content::Start(i), duration),
+            Fault::Corrupt(i) => {
+                self.set_node(i, Content::Corrupt(ByzantineStrategy::Random), duration)
+            }
+            Fault::CorruptWith(i, strategy) => {
+                self.set_node(i, Content::Corrupt(strategy), duration)
+            }
+            Fault::Partition(nodes) => {
+                let nodes = nodes
+                    .iter()
+                    .filter_map(|i| self.get_node_address(*i))
+                    .collect();
+                self.set_env(Content::Partition(nodes), duration);
+            }
+            Fault::Clear => self.set_env(Content::Clear, duration),
+        }
     }
 
-    pub fn set_node(&mut self, i: usize, content: Content, duration: Duration) {
+    fn set_node(&mut self, i: usize, content: Content, duration: Duration) {
         if let Some(address) = self.get_node_address(i) {
             let event = Event {
                 process_time: Instant::now() + duration,
-                to: address.clone(),
+                to: address,
                 content,
             };
             self.test2timer.send(event).unwrap();
         }
     }
+
+    fn set_env(&mut self, content: Content, duration: Duration) {
+        let event = Event {
+            process_time: Instant::now() + duration,
+            to: Address::default(),
+            content,
+        };
+        self.test2timer.send(event).unwrap();
+    }
+}
+
+fn draw_seed() -> u64 {
+    std::env::var(SEED_VAR)
+        .ok()
+        .and_then(|seed| seed.parse().ok())
+        .unwrap_or_else(rand::random)
+}
+
+// the proposals are stamped by the skewed clocks, to check the drifts between the nodes
+fn node_config(clock: &Arc<SkewedClock>, base_config: &BftConfig) -> BftConfig {
+    BftConfig {
+        proposal_timestamp: true,
+        clock: Some(clock.clone()),
+        ..base_config.clone()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -362,7 +749,7 @@ impl Eq for Event {}
 
 impl PartialOrd for Event {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        self.process_time.partial_cmp(&other.process_time)
+        Some(self.cmp(other))
     }
 }
 
@@ -386,5 +773,7 @@ pub enum Content {
     Sync,
     Stop,
     Start(usize),
-    Corrupt,
+    Corrupt(ByzantineStrategy),
+    Partition(Vec<Address>),
+    Clear,
 }
@@ -1,4 +1,9 @@
+pub mod clock;
 pub mod config;
 pub mod env;
+pub mod nemesis;
+pub mod signer;
+#[cfg(feature = "bench")]
+pub mod sim;
 pub mod support;
 pub mod utils;
@@ -0,0 +1,93 @@
+use bft_rs::byzantine::ByzantineStrategy;
+
+/// A fault injected by `Env::run`, the nodes are the indexes in the authority list.
+#[derive(Clone, Copy, Debug)]
+pub enum Fault {
+    Stop(usize),
+    Start(usize),
+    // corrupt by the `Random` strategy
+    Corrupt(usize),
+    CorruptWith(usize, ByzantineStrategy),
+    // the msgs between the nodes and the others are dropped, while the status still syncs
+    Partition(&'static [usize]),
+    // heal the partition
+    Clear,
+}
+
+/// A fault at the milliseconds since `Env::run` begins.
+#[derive(Clone, Copy, Debug)]
+pub struct Nemesis {
+    pub at: u64, // ms
+    pub fault: Fault,
+}
+
+pub const NO_FAULT: &[Nemesis] = &[];
+
+// stop one node, then two, then all of them, and start them again
+pub const RESTART_SCHEDULE: &[Nemesis] = &[
+    Nemesis {
+        at: 2_000,
+        fault: Fault::Stop(0),
+    },
+    Nemesis {
+        at: 8_000,
+        fault: Fault::Stop(1),
+    },
+    Nemesis {
+        at: 15_000,
+        fault: Fault::Start(1),
+    },
+    Nemesis {
+        at: 30_000,
+        fault: Fault::Stop(1),
+    },
+    Nemesis {
+        at: 30_000,
+        fault: Fault::Stop(2),
+    },
+    Nemesis {
+        at: 30_000,
+        fault: Fault::Stop(3),
+    },
+    Nemesis {
+        at: 32_000,
+        fault: Fault::Start(0),
+    },
+    Nemesis {
+        at: 36_000,
+        fault: Fault::Start(1),
+    },
+    Nemesis {
+        at: 36_000,
+        fault: Fault::Start(2),
+    },
+    Nemesis {
+        at: 40_000,
+        fault: Fault::Start(3),
+    },
+];
+
+// cut off a minority, which the others go on without, then a half, which stalls all of them
+pub const PARTITION_SCHEDULE: &[Nemesis] = &[
+    Nemesis {
+        at: 5_000,
+        fault: Fault::Partition(&[0]),
+    },
+    Nemesis {
+        at: 20_000,
+        fault: Fault::Clear,
+    },
+    Nemesis {
+        at: 30_000,
+        fault: Fault::Partition(&[0, 1]),
+    },
+    Nemesis {
+        at: 45_000,
+        fault: Fault::Clear,
+    },
+];
+
+pub const BYZANTINE_SCHEDULE: &[Nemesis] = &[Nemesis {
+    at: 1_000,
+    fault: Fault::Corrupt(0),
+}];
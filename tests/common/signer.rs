@@ -0,0 +1,68 @@
+use super::support::TestError;
+use super::utils::generate_address;
+#[cfg(not(feature = "simple_support"))]
+use super::utils::{check_sig, sign};
+#[cfg(feature = "simple_support")]
+use bft_rs::{support::SimpleSupport, BftSupport};
+use bft_rs::{Address, Hash, Signature};
+#[cfg(feature = "simple_support")]
+use std::sync::Arc;
+
+/// The signer of a test node, which signs by secp256k1 of `SimpleSupport` with the
+/// simple_support feature, or signs by the address followed by the hash without it.
+#[derive(Clone)]
+pub struct Signer {
+    pub address: Address,
+    #[cfg(feature = "simple_support")]
+    crypto: Arc<SimpleSupport>,
+}
+
+impl Signer {
+    #[cfg(not(feature = "simple_support"))]
+    pub fn generate() -> Self {
+        Signer {
+            address: generate_address(),
+        }
+    }
+
+    #[cfg(feature = "simple_support")]
+    pub fn generate() -> Self {
+        loop {
+            // the random bytes out of the secp256k1 order are generated again
+            let secret_key = generate_address()
+                .iter()
+                .chain(generate_address().iter())
+                .take(32)
+                .cloned()
+                .collect::<Vec<u8>>();
+            if let Ok(crypto) = SimpleSupport::new(&secret_key, vec![], None) {
+                return Signer {
+                    address: crypto.address(),
+                    crypto: Arc::new(crypto),
+                };
+            }
+        }
+    }
+
+    #[cfg(not(feature = "simple_support"))]
+    pub fn sign(&self, hash: &Hash) -> Result<Signature, TestError> {
+        Ok(sign(&self.address, hash))
+    }
+
+    #[cfg(feature = "simple_support")]
+    pub fn sign(&self, hash: &Hash) -> Result<Signature, TestError> {
+        self.crypto.sign(hash).map_err(|_| TestError::SignFailed)
+    }
+
+    #[cfg(not(feature = "simple_support"))]
+    pub fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, TestError> {
+        check_sig(signature, hash).ok_or(TestError::CheckSigFailed)
+    }
+
+    #[cfg(feature = "simple_support")]
+    pub fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, TestError> {
+        self.crypto
+            .check_sig(signature, hash)
+            .map_err(|_| TestError::CheckSigFailed)
+    }
+}
@@ -0,0 +1,497 @@
+use super::utils::hash;
+use bft_rs::bench::BenchEngine;
+use bft_rs::error::BftError;
+use bft_rs::objects::{SignedProposal, SignedVote, VoteType};
+use bft_rs::params::BftConfig;
+use bft_rs::utils::extract_proposal;
+use bft_rs::wire::{WireKind, WireMsg};
+use bft_rs::*;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// A commit of a node, with the height and the hash of the committed block.
+#[derive(Clone, Debug)]
+pub struct Committed {
+    pub node: usize,
+    pub height: Height,
+    pub block_hash: Hash,
+}
+
+/// The errors reported by the nodes, with the heights reported at.
+pub type Reports = Vec<(usize, Option<Height>)>;
+
+pub struct SimSupport {
+    node: usize,
+    address: Address,
+    commits: Arc<Mutex<Vec<Committed>>>,
+    violations: Arc<Mutex<Vec<String>>>,
+    exhausted: Arc<Mutex<Reports>>,
+}
+
+impl BftSupport for SimSupport {
+    type Error = String;
+
+    fn check_block(
+        &self,
+        _block: &Block,
+        _block_hash: &Hash,
+        _signed_proposal_hash: &Hash,
+        height_round: (Height, Round),
+        _is_lock: bool,
+        _proposer: &Address,
+    ) -> Result<VerifyResp, String> {
+        Ok(VerifyResp {
+            is_pass: true,
+            round: height_round.1,
+            #[cfg(feature = "compact_block")]
+            complete_block: _block.clone(),
+            failure: None,
+        })
+    }
+
+    fn transmit(&self, _outbound: Outbound) -> Result<(), String> {
+        // the outbound msgs are taken from the outbox by the simulation
+        Ok(())
+    }
+
+    fn commit(&self, commit: Commit) -> Result<Option<Status>, String> {
+        self.commits.lock().unwrap().push(Committed {
+            node: self.node,
+            height: commit.height,
+            block_hash: commit.proof.block_hash,
+        });
+        Ok(None)
+    }
+
+    fn get_block(&self, _height: Height) -> Result<(Block, Hash), String> {
+        Err("the blocks are fed by the simulation".to_string())
+    }
+
+    // simplified as the integration tests, the signature is the address
+    fn sign(&self, _hash: &Hash) -> Result<Signature, String> {
+        Ok(self.address.to_vec().into())
+    }
+
+    fn check_sig(&self, signature: &Signature, _hash: &Hash) -> Result<Address, String> {
+        let sig: &[u8] = signature;
+        Address::try_from(sig).map_err(|e| format!("{:?}", e))
+    }
+
+    fn crypt_hash(&self, msg: &[u8]) -> Hash {
+        hash(msg)
+    }
+
+    fn on_error(&self, error: &BftError) {
+        match error {
+            BftError::LockViolation(info) => self
+                .violations
+                .lock()
+                .unwrap()
+                .push(format!("node {} encounters {:?}", self.node, info)),
+            BftError::RoundExhausted(info) => self
+                .exhausted
+                .lock()
+                .unwrap()
+                .push((self.node, info.height)),
+            _ => {}
+        }
+    }
+}
+
+pub fn config() -> BftConfig {
+    // the signatures and the blocks are checked in place, nothing is re-attempted, the fed
+    // block is proposed again in the later rounds, the msgs delivered without time passing are
+    // not rate limited, and the own votes are audited against the locking rules
+    BftConfig {
+        sig_verify_threads: 0,
+        speculative_verify_threads: 0,
+        commit_retry_times: 0,
+        get_block_retry_times: 0,
+        retain_feed: true,
+        msg_rate: 0,
+        lock_audit: true,
+        ..BftConfig::default()
+    }
+}
+
+/// The point at which a node crashes in processing a msg or its timers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashPoint {
+    /// Before the msg or the timers are processed and saved in wal.
+    BeforeProcess,
+    /// After its own proposal and votes are sent, before they are saved in wal.
+    BeforeSaveOwn,
+    /// After all are saved in wal, before the outbound msgs are sent.
+    BeforeSend,
+    /// After the outbound msgs are sent.
+    AfterSend,
+}
+
+/// A step of the schedule, the selectors are taken modulo the count of the msgs in flight or
+/// the nodes.
+#[derive(Clone, Debug)]
+pub enum Action {
+    /// Deliver a msg in flight.
+    Deliver(usize),
+    /// Deliver a msg in flight and keep it in flight.
+    Duplicate(usize),
+    /// Drop a msg in flight.
+    Drop(usize),
+    /// Fire the timers of a node.
+    Fire(usize),
+    /// Deliver a msg in flight, or fire the timers of a node if there is none, and crash the
+    /// node at the point, then restart it from its wal.
+    Crash(usize, CrashPoint),
+}
+
+/// The in-process nodes with the msgs in flight between them. Each node is fed a block of its
+/// own at every height it reaches, and the status of every height it commits.
+pub struct Sim {
+    pub nodes: Vec<BenchEngine<SimSupport>>,
+    pub authorities: Vec<Node>,
+    pub in_flight: Vec<(usize, BftMsg)>,
+    fed_heights: Vec<Height>,
+    status_heights: Vec<Height>,
+    fed_blocks: HashMap<Height, HashSet<Hash>>,
+    commits: Arc<Mutex<Vec<Committed>>>,
+    violations: Arc<Mutex<Vec<String>>>,
+    exhausted: Arc<Mutex<Reports>>,
+    // the block hash each node signs for, by the signer, the height, the round and the vote
+    // type, `None` for the proposals
+    signed: HashMap<(Address, Height, Round, Option<VoteType>), Hash>,
+    equivocations: Vec<String>,
+}
+
+impl Sim {
+    pub fn new(count: usize) -> Self {
+        Self::with_config(count, config())
+    }
+
+    /// Create the simulation of the [`count`] nodes of the [`config`].
+    pub fn with_config(count: usize, config: BftConfig) -> Self {
+        let authorities: Vec<Node> = (0..count)
+            .map(|i| {
+                let mut address = [0u8; ADDRESS_LEN];
+                address[..8].copy_from_slice(&(i as u64 + 1).to_be_bytes());
+                Node {
+                    address: Address::from(address),
+                    proposal_weight: 1,
+                    vote_weight: 1,
+                }
+            })
+            .collect();
+        let commits = Arc::new(Mutex::new(Vec::new()));
+        let violations = Arc::new(Mutex::new(Vec::new()));
+        let exhausted = Arc::new(Mutex::new(Vec::new()));
+        let nodes = authorities
+            .iter()
+            .enumerate()
+            .map(|(node, authority)| {
+                let support = SimSupport {
+                    node,
+                    address: authority.address,
+                    commits: commits.clone(),
+                    violations: violations.clone(),
+                    exhausted: exhausted.clone(),
+                };
+                BenchEngine::new(Arc::new(support), authority.address, config.clone()).unwrap()
+            })
+            .collect();
+        let mut sim = Sim {
+            nodes,
+            authorities,
+            in_flight: Vec::new(),
+            fed_heights: vec![0; count],
+            status_heights: vec![0; count],
+            fed_blocks: HashMap::new(),
+            commits,
+            violations,
+            exhausted,
+            signed: HashMap::new(),
+            equivocations: Vec::new(),
+        };
+        for node in 0..count {
+            let status = sim.status(0);
+            sim.nodes[node].process(BftMsg::Status(status));
+            // the timer of CommitWait begins the first height
+            sim.nodes[node].fire_timers();
+            sim.settle(node);
+        }
+        sim
+    }
+
+    pub fn status(&self, height: Height) -> Status {
+        Status {
+            height,
+            interval: None,
+            authority_list: self.authorities.clone(),
+            authority_diff: None,
+            scheduled_authorities: Vec::new(),
+            consensus_params: None,
+        }
+    }
+
+    pub fn run(&mut self, action: &Action) {
+        match *action {
+            Action::Deliver(selector) | Action::Duplicate(selector) | Action::Drop(selector) => {
+                if self.in_flight.is_empty() {
+                    return;
+                }
+                let index = selector % self.in_flight.len();
+                let (node, msg) = match action {
+                    Action::Duplicate(_) => self.in_flight[index].clone(),
+                    _ => self.in_flight.remove(index),
+                };
+                if let Action::Drop(_) = action {
+                    return;
+                }
+                self.nodes[node].process(msg);
+                self.settle(node);
+            }
+            Action::Fire(selector) => {
+                let node = selector % self.nodes.len();
+                self.nodes[node].fire_timers();
+                self.settle(node);
+            }
+            Action::Crash(selector, point) => {
+                let (node, msg) = if self.in_flight.is_empty() {
+                    (selector % self.nodes.len(), None)
+                } else {
+                    let (node, msg) = self.in_flight.remove(selector % self.in_flight.len());
+                    (node, Some(msg))
+                };
+                self.crash(node, msg, point);
+            }
+        }
+    }
+
+    fn crash(&mut self, node: usize, msg: Option<BftMsg>, point: CrashPoint) {
+        if point != CrashPoint::BeforeProcess {
+            self.nodes[node].hold_own_msgs(point == CrashPoint::BeforeSaveOwn);
+            match msg {
+                Some(msg) => self.nodes[node].process(msg),
+                None => self.nodes[node].fire_timers(),
+            };
+            self.nodes[node].hold_own_msgs(false);
+            let outbound = self.nodes[node].outbound();
+            if point != CrashPoint::BeforeSend {
+                for outbound in outbound {
+                    self.send(node, outbound);
+                }
+            }
+        }
+        // the engines are kept in order by swapping the restarted one back
+        let engine = self.nodes.swap_remove(node);
+        // the replayed records fail as they did before the crash, such as the obsolete msgs
+        let (engine, _) = engine.restart().unwrap();
+        self.nodes.push(engine);
+        let last = self.nodes.len() - 1;
+        self.nodes.swap(node, last);
+        self.settle(node);
+    }
+
+    /// Put the outbound msgs of the [`node`] in flight, and feed it the block of a new height
+    /// and the status of a committed height, until nothing changes.
+    fn settle(&mut self, node: usize) {
+        loop {
+            let outbound = self.nodes[node].outbound();
+            let height = self.nodes[node].height();
+            let committed = self
+                .commits
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|c| c.node == node && c.height > self.status_heights[node])
+                .map(|c| c.height)
+                .max();
+            if outbound.is_empty() && height <= self.fed_heights[node] && committed.is_none() {
+                return;
+            }
+            for outbound in outbound {
+                self.send(node, outbound);
+            }
+            if height > self.fed_heights[node] {
+                self.fed_heights[node] = height;
+                self.feed(node, height);
+            }
+            if let Some(height) = committed {
+                self.status_heights[node] = height;
+                let status = self.status(height);
+                self.nodes[node].process(BftMsg::Status(status));
+            }
+        }
+    }
+
+    fn feed(&mut self, node: usize, height: Height) {
+        let mut block = height.to_be_bytes().to_vec();
+        block.push(node as u8);
+        let block_hash = hash(&block);
+        self.fed_blocks
+            .entry(height)
+            .or_default()
+            .insert(block_hash);
+        self.nodes[node].process(BftMsg::Feed(Feed {
+            height,
+            block: block.into(),
+            block_hash,
+        }));
+    }
+
+    fn send(&mut self, from: usize, outbound: Outbound) {
+        let BftConfig {
+            chain_id,
+            rlp_codec,
+            ..
+        } = config();
+        let msg = match outbound.msg {
+            OutboundMsg::Proposal(encode) => {
+                let encode =
+                    WireMsg::open(&encode, chain_id, rlp_codec, WireKind::Proposal).unwrap();
+                let (signed_proposal, _, _) = extract_proposal(&encode).unwrap();
+                let proposal = rlp::decode::<SignedProposal>(signed_proposal)
+                    .unwrap()
+                    .proposal;
+                let key = (proposal.proposer, proposal.height, proposal.round, None);
+                self.check_signed(key, proposal.block_hash);
+                BftMsg::Proposal(encode)
+            }
+            OutboundMsg::Vote(encode) => {
+                let encode = WireMsg::open(&encode, chain_id, rlp_codec, WireKind::Vote).unwrap();
+                let vote = rlp::decode::<SignedVote>(&encode).unwrap().vote;
+                let key = (vote.voter, vote.height, vote.round, Some(vote.vote_type));
+                self.check_signed(key, vote.block_hash);
+                BftMsg::Vote(encode)
+            }
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::GetTxs(encode) => BftMsg::GetTxs(
+                WireMsg::open(&encode, chain_id, rlp_codec, WireKind::GetTxs).unwrap(),
+            ),
+            #[cfg(feature = "compact_block")]
+            OutboundMsg::Txs(encode) => {
+                BftMsg::Txs(WireMsg::open(&encode, chain_id, rlp_codec, WireKind::Txs).unwrap())
+            }
+        };
+        match outbound.target {
+            Target::Broadcast => {
+                for node in (0..self.nodes.len()).filter(|node| *node != from) {
+                    self.in_flight.push((node, msg.clone()));
+                }
+            }
+            Target::Unicast(address) => {
+                if let Some(node) = self
+                    .authorities
+                    .iter()
+                    .position(|authority| authority.address == address)
+                {
+                    self.in_flight.push((node, msg));
+                }
+            }
+        }
+    }
+
+    fn check_signed(&mut self, key: (Address, Height, Round, Option<VoteType>), block_hash: Hash) {
+        let signed = self.signed.entry(key.clone()).or_insert(block_hash);
+        if *signed != block_hash {
+            self.equivocations.push(format!(
+                "{:?} signs {:?} after {:?}",
+                key, block_hash, signed
+            ));
+        }
+    }
+
+    /// The nodes which report `RoundExhausted`, with the heights reported at.
+    pub fn round_exhausted(&self) -> Reports {
+        self.exhausted.lock().unwrap().clone()
+    }
+
+    /// Check the agreement that no two blocks are committed at one height, the validity
+    /// that a committed block is one fed to a node, and that no node signs two proposals or
+    /// two votes of one type for different blocks at one height and round, nor violates the
+    /// locking rules.
+    pub fn check_safety(&self) -> Result<(), String> {
+        if let Some(equivocation) = self.equivocations.first() {
+            return Err(equivocation.clone());
+        }
+        if let Some(violation) = self.violations.lock().unwrap().first() {
+            return Err(violation.clone());
+        }
+        let mut committed: HashMap<Height, Hash> = HashMap::new();
+        for commit in self.commits.lock().unwrap().iter() {
+            let fed = self
+                .fed_blocks
+                .get(&commit.height)
+                .is_some_and(|hashes| hashes.contains(&commit.block_hash));
+            if !fed {
+                return Err(format!("{:?} commits a block not fed", commit));
+            }
+            let block_hash = committed.entry(commit.height).or_insert(commit.block_hash);
+            if *block_hash != commit.block_hash {
+                return Err(format!(
+                    "{:?} disagrees with {:?} at the same height",
+                    commit, block_hash
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn committed_heights(&self) -> Vec<Height> {
+        let commits = self.commits.lock().unwrap();
+        (0..self.nodes.len())
+            .map(|node| {
+                commits
+                    .iter()
+                    .filter(|c| c.node == node)
+                    .map(|c| c.height)
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    pub fn min_committed_height(&self) -> Height {
+        self.committed_heights().into_iter().min().unwrap_or(0)
+    }
+
+    pub fn max_committed_height(&self) -> Height {
+        self.committed_heights().into_iter().max().unwrap_or(0)
+    }
+
+    /// Feed the [`node`] the status of the highest height committed by any node if it falls
+    /// behind, as its host syncs the committed blocks from the peers.
+    fn sync(&mut self, node: usize) {
+        let height = self.max_committed_height();
+        if height >= self.nodes[node].height() && height > self.status_heights[node] {
+            self.status_heights[node] = height;
+            let status = self.status(height);
+            self.nodes[node].process(BftMsg::Status(status));
+            self.settle(node);
+        }
+    }
+
+    /// Deliver all the msgs in flight, sync the lagging nodes and fire the timers of all the
+    /// nodes, for the [`rounds`] or until the [`done`] holds.
+    pub fn run_without_faults<F>(&mut self, rounds: usize, done: F)
+    where
+        F: Fn(&Sim) -> bool,
+    {
+        for _ in 0..rounds {
+            if done(self) {
+                return;
+            }
+            while !self.in_flight.is_empty() {
+                self.run(&Action::Deliver(0));
+            }
+            for node in 0..self.nodes.len() {
+                self.sync(node);
+                self.run(&Action::Fire(node));
+            }
+            // the commits and the verify requests run in threads of their own, which are given
+            // the time to deliver their results
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
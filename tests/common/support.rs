@@ -2,6 +2,7 @@ extern crate bft_rs;
 
 use self::bft_rs::*;
 use super::config::Config;
+use super::signer::Signer;
 use super::utils::*;
 use crossbeam::crossbeam_channel::Sender;
 use std::thread;
@@ -9,6 +10,7 @@ use std::thread;
 pub struct Support {
     pub config: Config,
     pub address: Address,
+    pub signer: Signer,
     pub msg_send: Sender<(BftMsg, Address)>,
     pub commit_send: Sender<(Commit, Address)>,
 }
@@ -33,20 +35,24 @@ impl BftSupport for Support {
                 round: height_round.1,
                 #[cfg(feature = "compact_block")]
                 complete_block: get_complete_block(block),
+                failure: None,
             })
         } else {
             Err(TestError::CheckTxsFailed)
         }
     }
 
-    fn transmit(&self, msg: BftMsg) {
-        self.msg_send.send((msg, self.address.clone())).unwrap();
+    fn transmit(&self, outbound: Outbound) -> Result<(), TestError> {
+        self.msg_send
+            .send((outbound.msg.into(), self.address))
+            .unwrap();
+        Ok(())
     }
 
-    fn commit(&self, commit: Commit) -> Result<Status, TestError> {
-        let address = self.address.clone();
+    fn commit(&self, commit: Commit) -> Result<Option<Status>, TestError> {
+        let address = self.address;
         self.commit_send.send((commit, address)).unwrap();
-        Err(TestError::CommitProposed)
+        Ok(None)
     }
 
     fn get_block(&self, _height: Height) -> Result<(Block, Hash), TestError> {
@@ -56,12 +62,11 @@ impl BftSupport for Support {
     }
 
     fn sign(&self, hash: &Hash) -> Result<Signature, TestError> {
-        Ok(sign(hash, &self.address))
+        self.signer.sign(hash)
     }
 
-    fn check_sig(&self, signature: &Signature, _hash: &Hash) -> Result<Address, TestError> {
-        let sig: &[u8] = signature;
-        Ok(sig.into())
+    fn check_sig(&self, signature: &Signature, hash: &Hash) -> Result<Address, TestError> {
+        self.signer.check_sig(signature, hash)
     }
 
     fn crypt_hash(&self, msg: &[u8]) -> Hash {
@@ -74,5 +79,5 @@ pub enum TestError {
     CheckBlockFailed,
     CheckTxsFailed,
     CheckSigFailed,
-    CommitProposed,
+    SignFailed,
 }
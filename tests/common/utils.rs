@@ -1,7 +1,14 @@
 use rand::distributions::{Distribution, Normal, Uniform};
+use rand_core::SeedableRng;
+use rand_pcg::Pcg64Mcg as Pcg;
+use std::cell::RefCell;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
 use std::fs::{self, read_dir};
+use std::hash::Hasher;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
 use std::time::Duration;
-use std::u64::MAX as MAX_U64;
 
 use super::config::*;
 use bft_rs::*;
@@ -26,10 +33,7 @@ pub fn generate_block(byzantine: bool, config: &Config) -> Block {
     } else {
         config.max_block_size
     };
-    let mut vec = Vec::with_capacity(size);
-    unsafe {
-        vec.set_len(size);
-    }
+    let mut vec = vec![0u8; size];
     let mark = if byzantine { 1u8 } else { 0u8 };
     vec.insert(0, mark);
     for i in 1..config.min_block_size {
@@ -40,10 +44,7 @@ pub fn generate_block(byzantine: bool, config: &Config) -> Block {
 
 pub fn get_complete_block(block: &Block) -> Block {
     let complete_block_len = block.as_slice().len() * 2;
-    let mut vec = Vec::with_capacity(complete_block_len);
-    unsafe {
-        vec.set_len(complete_block_len);
-    }
+    let mut vec = vec![0u8; complete_block_len];
     vec.extend_from_slice(block);
     vec.extend_from_slice(block);
     vec.into()
@@ -83,12 +84,26 @@ pub fn commit_delay(config: &Config) -> Duration {
     Duration::from_millis(delay)
 }
 
-pub fn is_message_lost(config: &Config) -> bool {
-    get_dice_result(config.message_lost_rate)
+// the dice passes by the rate of 1 - likelihood, so a fault happens when it fails
+pub fn is_message_lost(faults: &LinkFaults) -> bool {
+    !get_dice_result(faults.lost_rate)
+}
+
+pub fn is_message_duplicated(faults: &LinkFaults) -> bool {
+    !get_dice_result(faults.duplicate_rate)
+}
+
+pub fn is_message_reordered(faults: &LinkFaults) -> bool {
+    !get_dice_result(faults.reorder_rate)
 }
 
 pub fn message_delay(config: &Config) -> Duration {
-    let rand_num = get_random_integer(config.message_delay);
+    link_delay(config.message_delay, config)
+}
+
+// the delay of a link by its own distribution, bounded as the others
+pub fn link_delay(mode: RandomMode, config: &Config) -> Duration {
+    let rand_num = get_random_integer(mode);
     let cost_time = if rand_num < config.max_delay {
         if rand_num < config.min_delay {
             config.min_delay
@@ -106,19 +121,30 @@ pub fn generate_address() -> Address {
     for _i in 0..ADDRESS_SIZE {
         vec.push(get_random_integer(RANDOM_U8) as u8);
     }
-    vec.into()
+    Address::try_from(vec).unwrap()
 }
 
 pub fn hash(msg: &[u8]) -> Hash {
     let mut hasher = BigEndian::<Sha256>::new();
     hash_slice(msg, &mut hasher);
     let output = hasher.result().as_ref().to_vec();
-    output.into()
+    Hash::try_from(output).unwrap()
 }
 
-// simplified for test
-pub fn sign(_hash_value: &Hash, address: &Address) -> Signature {
-    address.to_vec().into()
+// simplified for test, a signature is the signer address followed by the signed hash
+pub fn sign(voter: &Address, hash: &Hash) -> Signature {
+    let mut signature = voter.to_vec();
+    signature.extend_from_slice(hash);
+    signature.into()
+}
+
+pub fn check_sig(signature: &Signature, hash: &Hash) -> Option<Address> {
+    if signature.len() != ADDRESS_LEN + HASH_LEN || signature[ADDRESS_LEN..] != hash[..] {
+        return None;
+    }
+    let mut address = [0u8; ADDRESS_LEN];
+    address.copy_from_slice(&signature[..ADDRESS_LEN]);
+    Some(Address::from(address))
 }
 
 pub fn clean_wal(wal_dir: &str) {
@@ -152,9 +178,30 @@ pub fn set_log_file(path: &str, level: LevelFilter) {
     let _ = log4rs::init_config(config);
 }
 
+static SEED: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    static RNG: RefCell<Pcg> = RefCell::new(seeded_rng(SEED.load(Ordering::SeqCst)));
+}
+
+// the randomness of a thread is seeded by the seed of the run and the name of the thread, so
+// the draws of a thread are reproduced by the seed whichever order the threads run in
+fn seeded_rng(seed: u64) -> Pcg {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(thread::current().name().unwrap_or_default().as_bytes());
+    Pcg::seed_from_u64(seed ^ hasher.finish())
+}
+
+/// Seed the randomness of the threads started later, and the current thread again. The seed
+/// is process-wide, so the runs are reproduced one test thread at a time.
+pub fn set_seed(seed: u64) {
+    SEED.store(seed, Ordering::SeqCst);
+    RNG.with(|rng| *rng.borrow_mut() = seeded_rng(seed));
+}
+
 pub fn get_dice_result(likelihood: f64) -> bool {
     let rand_num = get_random_integer(RANDOM_U64) as f64;
-    let rate = rand_num / ((MAX_U64 - 1) as f64);
+    let rate = rand_num / ((u64::MAX - 1) as f64);
     rate > likelihood
 }
 
@@ -165,31 +212,23 @@ pub enum RandomMode {
 }
 
 pub fn get_random_integer(mode: RandomMode) -> u64 {
-    let v;
     match mode {
-        RandomMode::Normal(_, _) => {
-            v = get_random_float(mode) as u64;
-        }
+        RandomMode::Normal(_, _) => get_random_float(mode) as u64,
         RandomMode::Uniform(lower_bound, upper_bound) => {
             let between = Uniform::from(lower_bound..upper_bound);
-            v = between.sample(&mut rand::thread_rng());
+            RNG.with(|rng| between.sample(&mut *rng.borrow_mut()))
         }
     }
-    v
 }
 
 pub fn get_random_float(mode: RandomMode) -> f64 {
-    let v;
     match mode {
         RandomMode::Normal(mean, standard_deviation) => {
             let normal = Normal::new(mean, standard_deviation);
-            v = normal.sample(&mut rand::thread_rng());
-        }
-        RandomMode::Uniform(_, _) => {
-            v = get_random_integer(mode) as f64;
+            RNG.with(|rng| normal.sample(&mut *rng.borrow_mut()))
         }
+        RandomMode::Uniform(_, _) => get_random_integer(mode) as f64,
     }
-    v
 }
 
 fn hash_slice<T, H>(slice: &[T], digest: &mut H)
@@ -1,13 +1,20 @@
 pub mod common;
 
-use crate::common::config::{BAD_CONFIG, NORMAL_CONFIG, PERFECT_CONFIG};
-use crate::common::env::{Content, Env};
+use crate::common::config::{Config, LinkFaults, BAD_CONFIG, NORMAL_CONFIG, PERFECT_CONFIG};
+use crate::common::env::Env;
+use crate::common::nemesis::{
+    Fault, Nemesis, BYZANTINE_SCHEDULE, NO_FAULT, PARTITION_SCHEDULE, RESTART_SCHEDULE,
+};
 use crate::common::utils::{
     clean_log_file, clean_wal, get_random_integer, set_log_file, RandomMode,
 };
+use bft_rs::byzantine::ByzantineStrategy;
+use bft_rs::runtime::BftRuntime;
+use bft_rs::BftMsg;
 #[allow(unused_imports)]
 use log::{info, log, LevelFilter};
 use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[test]
@@ -18,7 +25,130 @@ fn test_basic() {
     clean_log_file(path);
     set_log_file(path, LevelFilter::Debug);
     let mut env = Env::new(PERFECT_CONFIG, 4, wal_dir);
-    env.run(10);
+    env.run(NO_FAULT, 10);
+}
+
+#[test]
+fn test_genesis() {
+    let path = "log/test_genesis.log";
+    let wal_dir = "wal/test_genesis/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    let mut env = Env::new(PERFECT_CONFIG, 4, wal_dir);
+
+    // nodes at the genesis height receive msgs before any status
+    for i in 0..4 {
+        env.send_msg(i, BftMsg::Start, Duration::from_millis(0));
+    }
+
+    env.run(NO_FAULT, 10);
+}
+
+#[test]
+fn test_clock_skew() {
+    let path = "log/test_clock_skew.log";
+    let wal_dir = "wal/test_clock_skew/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // the clocks are about a second apart and a percent fast or slow,
+    // within the max drift of the proposal timestamps
+    let config = Config {
+        clock_offset: RandomMode::Normal(0.0, 1_000.0),
+        clock_drift: RandomMode::Normal(0.0, 10_000.0),
+        ..NORMAL_CONFIG
+    };
+    let mut env = Env::new(config, 4, wal_dir);
+    env.run(NO_FAULT, 30);
+}
+
+#[test]
+fn test_lossy_links() {
+    let path = "log/test_lossy_links.log";
+    let wal_dir = "wal/test_lossy_links/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // the lost msgs are recovered by the retransmissions, the duplicated and the reordered
+    // ones are tolerated
+    let config = Config {
+        message_lost_rate: 0.2,
+        message_duplicate_rate: 0.2,
+        message_reorder_rate: 0.2,
+        ..NORMAL_CONFIG
+    };
+    let mut env = Env::new(config, 4, wal_dir);
+    // node 0 hears nothing from node 1, and node 2 most msgs of node 3 late or twice
+    env.set_link_faults(
+        1,
+        0,
+        LinkFaults {
+            lost_rate: 1.0,
+            duplicate_rate: 0.0,
+            reorder_rate: 0.0,
+        },
+    );
+    env.set_link_faults(
+        3,
+        2,
+        LinkFaults {
+            lost_rate: 0.0,
+            duplicate_rate: 0.5,
+            reorder_rate: 0.8,
+        },
+    );
+    env.run(NO_FAULT, 30);
+}
+
+#[test]
+fn test_datacenters() {
+    let path = "log/test_datacenters.log";
+    let wal_dir = "wal/test_datacenters/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // two datacenters of 5ms inside and 150ms between them
+    let mut env = Env::new(NORMAL_CONFIG, 7, wal_dir);
+    env.set_zones(
+        &[&[0, 1, 2, 3], &[4, 5, 6]],
+        RandomMode::Normal(5.0, 1.0),
+        RandomMode::Normal(150.0, 20.0),
+    );
+    env.run(NO_FAULT, 30);
+}
+
+#[test]
+fn test_record_replay() {
+    let path = "log/test_record_replay.log";
+    let record = "log/test_record_replay.rec";
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    let wal_dir = "wal/test_record/wal";
+    clean_wal(wal_dir);
+    let mut env = Env::new(NORMAL_CONFIG, 4, wal_dir);
+    env.record(record);
+    env.run(NO_FAULT, 10);
+
+    let wal_dir = "wal/test_replay/wal";
+    clean_wal(wal_dir);
+    let mut replayed = Env::replay(NORMAL_CONFIG, 4, wal_dir, record);
+    assert_eq!(replayed.seed, env.seed);
+    assert_eq!(replayed.authority_list, env.authority_list);
+    replayed.run(NO_FAULT, 10);
+}
+
+#[test]
+fn test_shared_runtime() {
+    let path = "log/test_shared_runtime.log";
+    let wal_dir = "wal/test_shared_runtime/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // 7 nodes run by one timer thread and 2 workers, restarted on the same runtime
+    let runtime = Arc::new(BftRuntime::new(2));
+    let mut env = Env::new_with_runtime(NORMAL_CONFIG, 7, wal_dir, runtime);
+    env.run(RESTART_SCHEDULE, 50);
 }
 
 #[test]
@@ -29,20 +159,18 @@ fn test_restart() {
     clean_log_file(path);
     set_log_file(path, LevelFilter::Debug);
     let mut env = Env::new(NORMAL_CONFIG, 4, wal_dir);
+    env.run(RESTART_SCHEDULE, 100);
+}
 
-    env.set_node(0, Content::Stop, Duration::from_millis(2_000));
-    env.set_node(1, Content::Stop, Duration::from_millis(8_000));
-    env.set_node(1, Content::Start(1), Duration::from_millis(15_000));
-    // stop all nodes, start all nodes
-    env.set_node(1, Content::Stop, Duration::from_millis(30_000));
-    env.set_node(2, Content::Stop, Duration::from_millis(30_000));
-    env.set_node(3, Content::Stop, Duration::from_millis(30_000));
-    env.set_node(0, Content::Start(0), Duration::from_millis(32_000));
-    env.set_node(1, Content::Start(1), Duration::from_millis(36_000));
-    env.set_node(2, Content::Start(2), Duration::from_millis(36_000));
-    env.set_node(3, Content::Start(3), Duration::from_millis(40_000));
-
-    env.run(100);
+#[test]
+fn test_partition() {
+    let path = "log/test_partition.log";
+    let wal_dir = "wal/test_partition/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    let mut env = Env::new(NORMAL_CONFIG, 4, wal_dir);
+    env.run(PARTITION_SCHEDULE, 50);
 }
 
 #[test]
@@ -54,9 +182,9 @@ fn test_wild() {
     set_log_file(path, LevelFilter::Debug);
     let mut env = Env::new(BAD_CONFIG, 4, wal_dir);
 
-    let mut stat = HashMap::new();
+    let mut running = HashMap::new();
     for i in 0..4 {
-        stat.insert(i, Content::Start(i));
+        running.insert(i, true);
     }
 
     let mut rands = vec![];
@@ -66,34 +194,31 @@ fn test_wild() {
         rands.push(rand);
     }
     rands.sort();
+    let mut schedule = vec![];
     rands.into_iter().for_each(|n| {
         let ele = (n % 4) as usize;
-        let duration = Duration::from_millis(n);
-        let old_stat = stat.get(&ele).unwrap();
-        match old_stat {
-            Content::Start(_) => {
-                env.set_node(ele, Content::Stop, duration);
-                stat.insert(ele, Content::Stop);
-                info!("stop node {} after {:?}", ele, duration);
-            }
-            Content::Stop => {
-                env.set_node(ele, Content::Start(ele), duration);
-                stat.insert(ele, Content::Start(ele));
-                info!("start node {} after {:?}", ele, duration);
-            }
-            _ => {}
-        }
+        let is_running = running.get_mut(&ele).unwrap();
+        let fault = if *is_running {
+            Fault::Stop(ele)
+        } else {
+            Fault::Start(ele)
+        };
+        *is_running = !*is_running;
+        info!("{:?} after {}ms", fault, n);
+        schedule.push(Nemesis { at: n, fault });
     });
 
-    stat.iter().for_each(|(i, stat)| {
-        if let Content::Stop = *stat {
-            let duration = Duration::from_millis(max_duration);
-            env.set_node(*i, Content::Start(*i), duration);
-            info!("finally start node {} after {:?}", i, duration);
+    running.iter().for_each(|(i, is_running)| {
+        if !*is_running {
+            info!("finally start node {} after {}ms", i, max_duration);
+            schedule.push(Nemesis {
+                at: max_duration,
+                fault: Fault::Start(*i),
+            });
         }
     });
 
-    env.run(50);
+    env.run(&schedule, 50);
 }
 
 #[test]
@@ -104,6 +229,44 @@ fn test_byzantine() {
     clean_log_file(path);
     set_log_file(path, LevelFilter::Debug);
     let mut env = Env::new(NORMAL_CONFIG, 4, wal_dir);
-    env.set_node(0, Content::Corrupt, Duration::from_millis(1000));
-    env.run(100);
+    env.run(BYZANTINE_SCHEDULE, 100);
+}
+
+#[test]
+fn test_byzantine_ratio() {
+    let path = "log/test_byzantine_ratio.log";
+    let wal_dir = "wal/test_byzantine_ratio/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // 2 of 7 nodes, below 1/3 of the weight, so the consistency is asserted
+    let mut env = Env::new(NORMAL_CONFIG, 7, wal_dir);
+    env.set_byzantine_ratio(
+        2.0 / 7.0,
+        &[
+            ByzantineStrategy::ConflictingProposals,
+            ByzantineStrategy::FlipFlop,
+        ],
+    );
+    assert!(env.is_byzantine_tolerated());
+    env.run(NO_FAULT, 50);
+    assert!(env.observations.is_empty());
+}
+
+#[test]
+fn test_byzantine_beyond_tolerance() {
+    let path = "log/test_byzantine_beyond_tolerance.log";
+    let wal_dir = "wal/test_byzantine_beyond_tolerance/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // 2 of 4 nodes, the forks and the stalls are observed rather than asserted
+    let mut env = Env::new(NORMAL_CONFIG, 4, wal_dir);
+    env.set_byzantine(&[
+        (0, ByzantineStrategy::LockAmnesia),
+        (1, ByzantineStrategy::FlipFlop),
+    ]);
+    assert!(!env.is_byzantine_tolerated());
+    env.run(NO_FAULT, 20);
+    info!("observed {:?}", env.observations);
 }
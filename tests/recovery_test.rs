@@ -0,0 +1,68 @@
+//! The conformance tests of the crash recovery of in-process nodes, which crash at random
+//! points of processing a msg or the timers and restart from the same wal.
+//!
+//! ```text
+//! cargo test --features bench --test recovery_test
+//! ```
+
+pub mod common;
+
+use crate::common::sim::{Action, CrashPoint, Sim};
+use proptest::prelude::*;
+
+const MAX_ROUNDS: usize = 1000;
+const CRASH_POINTS: [CrashPoint; 4] = [
+    CrashPoint::BeforeProcess,
+    CrashPoint::BeforeSaveOwn,
+    CrashPoint::BeforeSend,
+    CrashPoint::AfterSend,
+];
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        6 => any::<usize>().prop_map(Action::Deliver),
+        2 => any::<usize>().prop_map(Action::Fire),
+        1 => (any::<usize>(), prop::sample::select(CRASH_POINTS.to_vec()))
+            .prop_map(|(selector, point)| Action::Crash(selector, point)),
+    ]
+}
+
+#[test]
+fn test_every_crash_point() {
+    for point in CRASH_POINTS.iter() {
+        let mut sim = Sim::new(4);
+        // crash the receivers of the first msgs in flight, around the first height
+        for selector in 0..sim.nodes.len() {
+            sim.run(&Action::Crash(selector, *point));
+            sim.check_safety().unwrap();
+        }
+        sim.run_without_faults(MAX_ROUNDS, |sim| sim.min_committed_height() >= 2);
+        assert!(
+            sim.min_committed_height() >= 2,
+            "the nodes do not commit after crashing {:?}",
+            point
+        );
+        sim.check_safety().unwrap();
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_recovery_of_random_crashes(
+        count in 4usize..=7,
+        actions in prop::collection::vec(action(), 1..400),
+    ) {
+        let mut sim = Sim::new(count);
+        for action in actions.iter() {
+            sim.run(action);
+            prop_assert_eq!(sim.check_safety(), Ok(()));
+        }
+        // the crashes stop, and the nodes make progress again
+        let height = sim.max_committed_height();
+        sim.run_without_faults(MAX_ROUNDS, |sim| sim.max_committed_height() > height);
+        prop_assert!(sim.max_committed_height() > height, "no progress after the crashes");
+        prop_assert_eq!(sim.check_safety(), Ok(()));
+    }
+}
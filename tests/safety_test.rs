@@ -0,0 +1,316 @@
+//! The property tests of the safety of 4 to 7 in-process nodes, exchanging msgs by random
+//! schedules of deliveries, duplications, drops and timers.
+//!
+//! ```text
+//! cargo test --features bench --test safety_test
+//! ```
+
+pub mod common;
+
+use crate::common::sim::{self, Action, Sim};
+use crate::common::utils::hash;
+use bft_rs::byzantine::ByzantineStrategy;
+use bft_rs::error::BftError;
+use bft_rs::evidence::{verify_evidence, DuplicateProposalEvidence, Evidence};
+use bft_rs::objects::SignedProposal;
+use bft_rs::params::{BftConfig, ProposerSelection};
+use bft_rs::*;
+use proptest::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MAX_ROUNDS: usize = 1000;
+
+// the receivers of the proposals of a height and a round, by block hash
+type Receivers = HashMap<Hash, (SignedProposal, HashSet<usize>)>;
+
+fn action() -> impl Strategy<Value = Action> {
+    prop_oneof![
+        6 => any::<usize>().prop_map(Action::Deliver),
+        1 => any::<usize>().prop_map(Action::Duplicate),
+        1 => any::<usize>().prop_map(Action::Drop),
+        2 => any::<usize>().prop_map(Action::Fire),
+    ]
+}
+
+#[test]
+fn test_sim_commits_without_faults() {
+    let mut sim = Sim::new(4);
+    sim.run_without_faults(MAX_ROUNDS, |sim| sim.min_committed_height() >= 2);
+    assert!(sim.min_committed_height() >= 2, "the nodes do not commit");
+    sim.check_safety().unwrap();
+}
+
+#[test]
+fn test_sim_rejects_invalid_authorities() {
+    let mut sim = Sim::new(4);
+    let height = sim.nodes[0].height();
+    let valid = sim.status(height);
+
+    let mut repeated = valid.clone();
+    repeated
+        .authority_list
+        .push(valid.authority_list[0].clone());
+    // the same address of another weight
+    let mut conflicting = valid.clone();
+    let mut node = valid.authority_list[0].clone();
+    node.vote_weight = 2;
+    conflicting.authority_list.push(node.clone());
+    let added_twice = Status {
+        authority_diff: Some(AuthorityDiff {
+            add: vec![node.clone(), node],
+            remove: Vec::new(),
+        }),
+        ..valid.clone()
+    };
+    let mut no_vote_weight = valid.clone();
+    let mut no_proposal_weight = valid.clone();
+    for node in no_vote_weight.authority_list.iter_mut() {
+        node.vote_weight = 0;
+    }
+    for node in no_proposal_weight.authority_list.iter_mut() {
+        node.proposal_weight = 0;
+    }
+    let empty = Status {
+        authority_list: Vec::new(),
+        ..valid
+    };
+    for status in [
+        repeated,
+        conflicting,
+        added_twice,
+        no_vote_weight,
+        no_proposal_weight,
+        empty,
+    ] {
+        let errors = sim.nodes[0].process(BftMsg::Status(status.clone()));
+        assert!(
+            errors
+                .iter()
+                .any(|e| matches!(e, BftError::InvalidAuthorities(_))),
+            "{:?} is not rejected",
+            status
+        );
+        assert_eq!(sim.nodes[0].height(), height);
+    }
+}
+
+#[test]
+fn test_sim_proposer_shares() {
+    let mut sim = Sim::new(4);
+    sim.run_without_faults(MAX_ROUNDS, |sim| sim.min_committed_height() >= 4);
+    let shares = sim.nodes[0].proposer_shares();
+    assert_eq!(shares.len(), 4);
+    let proposals: u64 = shares.values().map(|share| share.proposals).sum();
+    assert!(
+        proposals >= 4,
+        "the proposers of the commits are not recorded"
+    );
+    for authority in sim.authorities.iter() {
+        let share = shares[&authority.address];
+        assert_eq!(share.expected_share, 0.25);
+        assert_eq!(
+            share.actual_share,
+            share.proposals as f64 / proposals as f64
+        );
+    }
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn test_safety_of_random_schedules(
+        count in 4usize..=7,
+        actions in prop::collection::vec(action(), 1..400),
+    ) {
+        let mut sim = Sim::new(count);
+        for action in actions.iter() {
+            sim.run(action);
+            prop_assert_eq!(sim.check_safety(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_safety_with_a_vote_byzantine_node(
+        strategy in prop_oneof![
+            Just(ByzantineStrategy::LockAmnesia),
+            Just(ByzantineStrategy::FlipFlop),
+        ],
+        corrupt in 0usize..4,
+        actions in prop::collection::vec(action(), 1..400),
+    ) {
+        let mut sim = Sim::new(4);
+        sim.nodes[corrupt].process(BftMsg::CorruptWith(strategy));
+        for action in actions.iter() {
+            sim.run(action);
+            prop_assert_eq!(sim.check_safety(), Ok(()));
+        }
+    }
+}
+
+#[test]
+fn test_sim_priority_selection_cost() {
+    let mut sim = Sim::with_config(
+        4,
+        BftConfig {
+            proposer_selection: ProposerSelection::Priority,
+            ..sim::config()
+        },
+    );
+    // a large set of skewed weights, whose priorities take the most steps to cycle, at a
+    // height late in the cycle
+    let height: Height = 60_000;
+    let mut status = sim.status(height);
+    for i in status.authority_list.len()..300 {
+        let mut address = [0u8; ADDRESS_LEN];
+        address[..8].copy_from_slice(&(i as u64 + 1).to_be_bytes());
+        status.authority_list.push(Node {
+            address: Address::from(address),
+            proposal_weight: (i as u32 + 1).pow(3),
+            vote_weight: 1,
+        });
+    }
+    status.authority_list[0].proposal_weight = u32::MAX;
+    sim.nodes[0].process(BftMsg::Status(status.clone()));
+    // the timer of CommitWait begins the next height
+    sim.run(&Action::Fire(0));
+    assert_eq!(sim.nodes[0].height(), height + 1);
+
+    let mut authorities = status.authority_list;
+    authorities.sort();
+    let round = sim.nodes[0].round();
+    let proposer = utils::proposer_at(height + 1, round, &authorities, ProposerSelection::Priority);
+    // the priorities are accumulated once for the authorities instead of at each selection
+    let start = Instant::now();
+    for _ in 0..100 {
+        assert_eq!(sim.nodes[0].proposer().ok(), proposer);
+    }
+    assert!(
+        start.elapsed() < Duration::from_secs(1),
+        "the selections take {:?}",
+        start.elapsed()
+    );
+}
+
+#[test]
+fn test_sim_rounds_past_max_round() {
+    let max_round = 2;
+    let mut sim = Sim::with_config(
+        4,
+        BftConfig {
+            max_round: Some(max_round),
+            ..sim::config()
+        },
+    );
+    let height = sim.nodes[0].height();
+    // the proposals are all lost, so each round ends in the nil precommits
+    for _ in 0..MAX_ROUNDS {
+        if sim.nodes.iter().all(|node| node.round() > max_round + 1) {
+            break;
+        }
+        sim.in_flight
+            .retain(|(_, msg)| !matches!(msg, BftMsg::Proposal(_)));
+        if sim.in_flight.is_empty() {
+            for node in 0..sim.nodes.len() {
+                sim.run(&Action::Fire(node));
+            }
+        } else {
+            sim.run(&Action::Deliver(0));
+        }
+    }
+    for (node, engine) in sim.nodes.iter().enumerate() {
+        assert_eq!(engine.height(), height);
+        assert!(
+            engine.round() > max_round + 1,
+            "node {} stays at round {}",
+            node,
+            engine.round()
+        );
+    }
+    let mut exhausted = sim.round_exhausted();
+    exhausted.sort();
+    let reported: Vec<_> = (0..sim.nodes.len())
+        .map(|node| (node, Some(height)))
+        .collect();
+    assert_eq!(exhausted, reported);
+
+    // the later rounds still commit the height once the proposals get through
+    sim.run_without_faults(MAX_ROUNDS, |sim| sim.min_committed_height() >= height);
+    assert!(sim.min_committed_height() >= height);
+    sim.check_safety().unwrap();
+}
+
+#[test]
+fn test_sim_conflicting_proposals() {
+    let mut sim = Sim::new(4);
+    let height = sim.nodes[0].height() + 1;
+    let proposer =
+        utils::proposer_at(height, 0, &sim.authorities, ProposerSelection::Weighted).unwrap();
+    let corrupt = sim
+        .authorities
+        .iter()
+        .position(|node| node.address == proposer)
+        .unwrap();
+    sim.nodes[corrupt].process(BftMsg::CorruptWith(ByzantineStrategy::ConflictingProposals));
+
+    // the proposals of the corrupt node by the height and the round, it may propose at a later
+    // round of the current height before the next height
+    let mut slots: HashMap<(Height, Round), Receivers> = HashMap::new();
+    let mut proposals = Receivers::new();
+    for _ in 0..MAX_ROUNDS {
+        for (node, msg) in sim.in_flight.iter() {
+            if let BftMsg::Proposal(encode) = msg {
+                let (signed_proposal, _, _) = utils::extract_proposal(encode).unwrap();
+                let signed_proposal: SignedProposal = rlp::decode(signed_proposal).unwrap();
+                let proposal = &signed_proposal.proposal;
+                if proposal.proposer == proposer {
+                    slots
+                        .entry((proposal.height, proposal.round))
+                        .or_default()
+                        .entry(proposal.block_hash)
+                        .or_insert_with(|| (signed_proposal.clone(), HashSet::new()))
+                        .1
+                        .insert(*node);
+                }
+            }
+        }
+        if let Some(conflicting) = slots.values().find(|slot| slot.len() >= 2) {
+            proposals = conflicting.clone();
+            break;
+        }
+        if sim.in_flight.is_empty() {
+            for node in 0..sim.nodes.len() {
+                sim.run(&Action::Fire(node));
+            }
+            // the commit of the first height runs in a thread of its own
+            thread::sleep(Duration::from_millis(1));
+        } else {
+            sim.run(&Action::Deliver(0));
+        }
+    }
+    assert_eq!(proposals.len(), 2, "the conflicting proposals are not sent");
+    assert!(sim.check_safety().is_err());
+
+    // the two halves of the other nodes receive one proposal each
+    let mut receivers: Vec<HashSet<usize>> = proposals.values().map(|(_, r)| r.clone()).collect();
+    receivers.sort_by_key(|r| r.len());
+    assert!(receivers[0].is_disjoint(&receivers[1]));
+    let all: HashSet<usize> = receivers[0].union(&receivers[1]).copied().collect();
+    let others: HashSet<usize> = (0..sim.nodes.len()).filter(|n| *n != corrupt).collect();
+    assert_eq!(all, others);
+
+    let mut signed: Vec<SignedProposal> = proposals.into_iter().map(|(_, (p, _))| p).collect();
+    let evidence =
+        DuplicateProposalEvidence::new(signed.pop().unwrap(), signed.pop().unwrap()).unwrap();
+    let check_sig = |signature: &Signature, _hash: &Hash| Address::try_from(&signature[..]).ok();
+    assert!(verify_evidence(
+        &Evidence::DuplicateProposal(evidence),
+        &sim.authorities,
+        SignScheme::default(),
+        check_sig,
+        hash
+    ));
+}
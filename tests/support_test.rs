@@ -0,0 +1,57 @@
+//! The tests of the signatures and the hashes of `SimpleSupport`.
+//!
+//! ```text
+//! cargo test --features simple_support --test support_test
+//! ```
+
+use bft_rs::support::{keccak256, SimpleSupport, SimpleSupportError};
+use bft_rs::*;
+
+fn support(key: u8) -> SimpleSupport {
+    SimpleSupport::new(&[key; 32], vec![], None).unwrap()
+}
+
+#[test]
+fn test_keccak256() {
+    // the keccak256 of the empty input
+    let hash = keccak256(&[]);
+    assert_eq!(
+        hash.to_vec(),
+        vec![
+            0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+            0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+            0x5d, 0x85, 0xa4, 0x70,
+        ]
+    );
+}
+
+#[test]
+fn test_sign_and_check_sig() {
+    let signer = support(1);
+    let checker = support(2);
+    let hash = signer.crypt_hash(b"proposal");
+    let signature = signer.sign(&hash).unwrap();
+    assert_eq!(checker.check_sig(&signature, &hash), Ok(signer.address()));
+
+    // a signature of another hash recovers another address
+    let other = signer.crypt_hash(b"vote");
+    assert_ne!(checker.check_sig(&signature, &other), Ok(signer.address()));
+}
+
+#[test]
+fn test_check_malformed_sig() {
+    let signer = support(1);
+    let hash = signer.crypt_hash(b"proposal");
+    let mut signature = signer.sign(&hash).unwrap().to_vec();
+    signature.pop();
+    let result = signer.check_sig(&signature.into(), &hash);
+    assert!(matches!(
+        result,
+        Err(SimpleSupportError::InvalidSignature(_))
+    ));
+
+    assert_eq!(
+        SimpleSupport::new(&[0u8; 32], vec![], None).err(),
+        Some(SimpleSupportError::InvalidKey)
+    );
+}
@@ -0,0 +1,30 @@
+//! The integration test of the nodes run as the tasks of a tokio runtime.
+//!
+//! ```text
+//! cargo test --features tokio --test tokio_test
+//! ```
+
+pub mod common;
+
+use crate::common::config::PERFECT_CONFIG;
+use crate::common::env::Env;
+use crate::common::nemesis::RESTART_SCHEDULE;
+use crate::common::utils::{clean_log_file, clean_wal, set_log_file};
+use log::LevelFilter;
+
+#[test]
+fn test_tokio_runtime() {
+    let path = "log/test_tokio_runtime.log";
+    let wal_dir = "wal/test_tokio_runtime/wal";
+    clean_wal(wal_dir);
+    clean_log_file(path);
+    set_log_file(path, LevelFilter::Debug);
+    // 4 nodes on 2 threads of the runtime, restarted on it
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut env = Env::new_with_tokio(PERFECT_CONFIG, 4, wal_dir, runtime.handle().clone());
+    env.run(RESTART_SCHEDULE, 50);
+}
@@ -0,0 +1,445 @@
+//! The tests of the proof validation of the `verify` module.
+
+pub mod common;
+
+use crate::common::utils::{check_sig, hash, sign};
+use bft_rs::codec::RlpCodec;
+use bft_rs::objects::{SignedVote, Vote, VoteType};
+use bft_rs::params::{BftConfig, ProposerSelection};
+use bft_rs::*;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+const HEIGHT: Height = 9;
+
+fn address(i: u8) -> Address {
+    Address::from([i; ADDRESS_LEN])
+}
+
+fn authorities(addresses: &[u8]) -> Vec<Node> {
+    addresses
+        .iter()
+        .map(|i| Node::new(address(*i), 1, 1))
+        .collect()
+}
+
+fn proof(voters: &[u8], authorities_hash: Option<Hash>) -> Proof {
+    proof_of(hash(b"block"), voters, authorities_hash)
+}
+
+fn proof_of(block_hash: Hash, voters: &[u8], authorities_hash: Option<Hash>) -> Proof {
+    let precommit_votes: HashMap<Address, Signature> = voters
+        .iter()
+        .map(|i| {
+            let vote = Vote {
+                vote_type: VoteType::Precommit,
+                height: HEIGHT,
+                round: 0,
+                block_hash,
+                voter: address(*i),
+            };
+            (address(*i), sign(&address(*i), &hash(&rlp::encode(&vote))))
+        })
+        .collect();
+    Proof {
+        height: HEIGHT,
+        round: 0,
+        block_hash,
+        precommit_votes,
+        authorities_hash,
+    }
+}
+
+#[test]
+fn test_check_proof() {
+    let authorities = authorities(&[1, 2, 3, 4]);
+    let proof = proof(&[1, 2, 3], None);
+    assert!(check_proof(
+        &proof,
+        HEIGHT + 1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof(
+        &proof,
+        HEIGHT,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+
+    // a signature of another voter
+    let mut forged = proof.clone();
+    let sig = forged.precommit_votes[&address(1)].clone();
+    forged.precommit_votes.insert(address(2), sig);
+    assert_eq!(
+        try_check_proof(
+            &forged,
+            HEIGHT + 1,
+            &authorities,
+            SignScheme::default(),
+            hash,
+            check_sig
+        ),
+        Err(ProofError::InvalidSignature(address(2)))
+    );
+
+    // no more than 2/3 weight
+    let proof = self::proof(&[1, 2], None);
+    assert!(!check_proof(
+        &proof,
+        HEIGHT + 1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+}
+
+#[test]
+fn test_check_proof_with_authorities_hash() {
+    let authorities = authorities(&[1, 2, 3, 4]);
+    let authorities_hash = get_authorities_hash(&authorities, RlpCodec::V1, hash);
+    let proof = proof(&[1, 2, 3], Some(authorities_hash));
+    assert!(check_proof_with_authorities_hash(
+        &proof,
+        HEIGHT + 1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+
+    // the proof without the authorities_hash cannot be checked against the expected one
+    let legacy = self::proof(&[1, 2, 3], None);
+    assert!(check_proof(
+        &legacy,
+        HEIGHT + 1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof_with_authorities_hash(
+        &legacy,
+        HEIGHT + 1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+
+    // a substituted authority list signing the proof is rejected
+    let substituted = self::authorities(&[1, 2, 3]);
+    let substituted_hash = get_authorities_hash(&substituted, RlpCodec::V1, hash);
+    let proof = self::proof(&[1, 2, 3], Some(substituted_hash));
+    assert!(check_proof(
+        &proof,
+        HEIGHT + 1,
+        &substituted,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof_with_authorities_hash(
+        &proof,
+        HEIGHT + 1,
+        &substituted,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof_with_authorities_hash(
+        &proof,
+        HEIGHT + 1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+}
+
+#[test]
+fn test_check_genesis_proof() {
+    let authorities = authorities(&[1, 2, 3, 4]);
+    let authorities_hash = get_authorities_hash(&authorities, RlpCodec::V1, hash);
+    let genesis = Proof::default();
+    assert!(check_proof(
+        &genesis,
+        1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    // a proof of height 0 proves nothing beyond the genesis block
+    assert!(!check_proof(
+        &genesis,
+        HEIGHT + 1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof_with_authorities_hash(
+        &genesis,
+        1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+
+    let genesis = Proof {
+        authorities_hash: Some(authorities_hash),
+        ..Proof::default()
+    };
+    assert!(check_proof_with_authorities_hash(
+        &genesis,
+        1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+    assert!(!check_proof_with_authorities_hash(
+        &genesis,
+        HEIGHT + 1,
+        &authorities,
+        &authorities_hash,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+}
+
+#[test]
+fn test_check_nil_proof() {
+    let authorities = authorities(&[1, 2, 3, 4]);
+    // the precommits of the nil hash are signed validly, but commit no block
+    let proof = proof_of(Hash::default(), &[1, 2, 3, 4], None);
+    assert_eq!(
+        try_check_proof(
+            &proof,
+            HEIGHT + 1,
+            &authorities,
+            SignScheme::default(),
+            hash,
+            check_sig
+        ),
+        Err(ProofError::NilProof)
+    );
+}
+
+#[test]
+fn test_check_proof_with_repeated_authorities() {
+    // the repeated authority is counted once, so 2 of 4 distinct weights are not a quorum
+    let mut authorities = authorities(&[1, 2, 3, 4]);
+    for _ in 0..3 {
+        authorities.push(Node::new(address(1), 1, 1));
+    }
+    let proof = proof(&[1, 2], None);
+    assert_eq!(verify::get_total_weight(&authorities), 4);
+    assert_eq!(
+        verify::get_votes_weight(&authorities, &[address(1), address(2), address(1)]),
+        2
+    );
+    assert_eq!(
+        try_check_proof(
+            &proof,
+            HEIGHT + 1,
+            &authorities,
+            SignScheme::default(),
+            hash,
+            check_sig
+        ),
+        Err(ProofError::InsufficientWeight {
+            weight: 2,
+            total_weight: 4
+        })
+    );
+}
+
+#[test]
+fn test_threshold_of_large_weights() {
+    assert!(verify::is_above_threshold(u64::MAX, u64::MAX));
+    assert!(!verify::is_above_threshold(u64::MAX / 3 * 2, u64::MAX));
+    let authorities: Vec<Node> = (1..=4)
+        .map(|i| Node::new(address(i), 1, u32::MAX))
+        .collect();
+    assert_eq!(
+        verify::get_total_weight(&authorities),
+        4 * u64::from(u32::MAX)
+    );
+    let proof = proof(&[1, 2, 3], None);
+    assert!(check_proof(
+        &proof,
+        HEIGHT + 1,
+        &authorities,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+}
+
+#[test]
+fn test_sign_domain() {
+    let vote = Vote {
+        vote_type: VoteType::Precommit,
+        height: HEIGHT,
+        round: 1,
+        block_hash: hash(b"block"),
+        voter: address(1),
+    };
+    let encode = rlp::encode(&vote);
+    let plain_hash = get_signed_hash(SignKind::Vote, None, &encode, hash);
+    assert_eq!(plain_hash, hash(&encode));
+    let plain_vote = SignedVote::new(vote.clone(), sign(&vote.voter, &plain_hash));
+    assert!(verify::check_signed_vote(
+        &plain_vote,
+        SignScheme::default(),
+        hash,
+        check_sig
+    ));
+
+    let vote_hash = get_signed_hash(SignKind::Vote, Some(1), &encode, hash);
+    assert_ne!(vote_hash, plain_hash);
+    // the same bytes are signed apart as another kind or on another chain
+    assert_ne!(
+        get_signed_hash(SignKind::Proposal, Some(1), &encode, hash),
+        vote_hash
+    );
+    assert_ne!(
+        get_signed_hash(SignKind::Vote, Some(2), &encode, hash),
+        vote_hash
+    );
+
+    let scheme = |domain| SignScheme {
+        codec: RlpCodec::V1,
+        domain,
+    };
+    assert!(!verify::check_signed_vote(
+        &plain_vote,
+        scheme(Some(1)),
+        hash,
+        check_sig
+    ));
+    let signed_vote = SignedVote::new(vote.clone(), sign(&vote.voter, &vote_hash));
+    assert!(verify::check_signed_vote(
+        &signed_vote,
+        scheme(Some(1)),
+        hash,
+        check_sig
+    ));
+    assert!(!verify::check_signed_vote(
+        &signed_vote,
+        scheme(Some(2)),
+        hash,
+        check_sig
+    ));
+
+    // the config derives the domain from the chain id once the separation is set
+    let mut config = BftConfig {
+        chain_id: 1,
+        ..BftConfig::default()
+    };
+    assert_eq!(config.sign_scheme(), scheme(None));
+    config.sign_domain_separation = true;
+    assert_eq!(config.sign_scheme(), scheme(Some(1)));
+}
+
+#[test]
+fn test_proposer_at() {
+    assert_eq!(
+        utils::proposer_at(HEIGHT, 0, &[], ProposerSelection::Weighted),
+        None
+    );
+
+    let authorities = authorities(&[1, 2, 3, 4]);
+    for round in 0..16 {
+        let proposer =
+            utils::proposer_at(HEIGHT, round, &authorities, ProposerSelection::Weighted).unwrap();
+        assert!(authorities.iter().any(|node| node.address == proposer));
+        // the selection is deterministic
+        assert_eq!(
+            utils::proposer_at(HEIGHT, round, &authorities, ProposerSelection::Weighted),
+            Some(proposer)
+        );
+    }
+
+    // an authority of no proposal weight is never selected
+    let mut weighted = authorities.clone();
+    weighted[0].proposal_weight = 0;
+    for round in 0..16 {
+        assert_ne!(
+            utils::proposer_at(HEIGHT, round, &weighted, ProposerSelection::Weighted),
+            Some(address(1))
+        );
+    }
+}
+
+#[test]
+fn test_proposer_priority() {
+    let mut authorities = authorities(&[1, 2, 3, 4]);
+    authorities[3].proposal_weight = 3;
+    let proposers: Vec<Address> = (0..12)
+        .map(|height| {
+            utils::proposer_at(height, 0, &authorities, ProposerSelection::Priority).unwrap()
+        })
+        .collect();
+    // the proposers rotate in proportion to the weights, and the heavy one is spread out
+    let expected: Vec<Address> = [4, 1, 2, 4, 3, 4].iter().map(|i| address(*i)).collect();
+    assert_eq!(proposers[..6], expected[..]);
+    assert_eq!(proposers[6..], expected[..]);
+
+    // the next round takes the next step
+    assert_eq!(
+        utils::proposer_at(0, 1, &authorities, ProposerSelection::Priority),
+        Some(address(1))
+    );
+
+    // the weights of a common divisor rotate as the divided ones
+    for node in authorities.iter_mut() {
+        node.proposal_weight *= 5;
+    }
+    for (height, proposer) in proposers.iter().enumerate() {
+        assert_eq!(
+            utils::proposer_at(
+                height as Height,
+                0,
+                &authorities,
+                ProposerSelection::Priority
+            ),
+            Some(*proposer)
+        );
+    }
+    assert_eq!(
+        utils::proposer_at(0, 0, &[], ProposerSelection::Priority),
+        None
+    );
+}
+
+#[test]
+fn test_invalid_vote_type() {
+    assert_eq!(VoteType::try_from(1), Ok(VoteType::Precommit));
+    assert!(VoteType::try_from(2).is_err());
+
+    // a vote of an unknown type is rejected instead of panicking
+    let mut stream = rlp::RlpStream::new_list(5);
+    stream
+        .append(&2u8)
+        .append(&HEIGHT)
+        .append(&0u64)
+        .append(&hash(b"block"))
+        .append(&address(1));
+    assert!(rlp::decode::<Vote>(&stream.out()).is_err());
+}
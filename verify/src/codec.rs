@@ -0,0 +1,126 @@
+use crate::Error;
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use rlp::{DecoderError, Encodable, Prototype, Rlp, RlpStream};
+
+/// The rlp codec of the byte wrappers `Address`, `Hash`, `Signature` and `Block`.
+/// The signed hashes of the proposals and the votes are taken over their encodings, so all the
+/// nodes of a chain use the same codec.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RlpCodec {
+    /// The former codec, which encodes a wrapper as a one-element list of its bytes.
+    #[default]
+    V1,
+    /// The canonical codec, which encodes a wrapper as a plain rlp string of its bytes as
+    /// Ethereum-style tooling does.
+    V2,
+}
+
+impl From<RlpCodec> for u8 {
+    fn from(codec: RlpCodec) -> Self {
+        match codec {
+            RlpCodec::V1 => 1,
+            RlpCodec::V2 => 2,
+        }
+    }
+}
+
+impl TryFrom<u8> for RlpCodec {
+    type Error = Error;
+    fn try_from(codec: u8) -> Result<Self, Self::Error> {
+        match codec {
+            1 => Ok(RlpCodec::V1),
+            2 => Ok(RlpCodec::V2),
+            _ => Err(Error::Decode(format!("invalid rlp codec {}", codec))),
+        }
+    }
+}
+
+/// An object whose rlp encoding depends on the codec of the byte wrappers in it.
+/// Its `Encodable` is the encoding by `RlpCodec::V1`, and both codecs are decoded by its
+/// `Decodable`.
+pub trait CodecEncodable {
+    /// Append the object to the stream by the [`codec`], it is appended by `AppendBy`.
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec);
+}
+
+impl<T: CodecEncodable> CodecEncodable for Option<T> {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        match self {
+            None => {
+                s.begin_list(0);
+            }
+            Some(value) => {
+                s.begin_list(1).append_by(value, codec);
+            }
+        }
+    }
+}
+
+impl<T: CodecEncodable> CodecEncodable for [T] {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.append_list_by(self, codec);
+    }
+}
+
+/// The appends of the objects encoded by a codec to a stream.
+pub trait AppendBy {
+    /// Append the [`value`] by the [`codec`], chainable.
+    fn append_by<E: CodecEncodable + ?Sized>(&mut self, value: &E, codec: RlpCodec) -> &mut Self;
+    /// Append the list of [`values`] by the [`codec`], chainable.
+    fn append_list_by<E: CodecEncodable>(&mut self, values: &[E], codec: RlpCodec) -> &mut Self;
+}
+
+impl AppendBy for RlpStream {
+    fn append_by<E: CodecEncodable + ?Sized>(&mut self, value: &E, codec: RlpCodec) -> &mut Self {
+        self.append(&By(value, codec))
+    }
+
+    fn append_list_by<E: CodecEncodable>(&mut self, values: &[E], codec: RlpCodec) -> &mut Self {
+        self.begin_list(values.len());
+        for value in values {
+            self.append_by(value, codec);
+        }
+        self
+    }
+}
+
+/// An object with the codec it is encoded by, which counts as one item of the stream.
+struct By<'a, E: ?Sized>(&'a E, RlpCodec);
+
+impl<E: CodecEncodable + ?Sized> Encodable for By<'_, E> {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.0.rlp_append_by(s, self.1);
+    }
+}
+
+/// A function to get the rlp encoding of the [`object`] by the [`codec`].
+pub fn encode_by<E: CodecEncodable + ?Sized>(object: &E, codec: RlpCodec) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+    stream.append_by(object, codec);
+    stream.out()
+}
+
+/// A function to append the bytes of a wrapper by the codec.
+pub(crate) fn append_bytes(s: &mut RlpStream, bytes: &[u8], codec: RlpCodec) {
+    match codec {
+        RlpCodec::V1 => {
+            s.begin_list(1).append(&bytes);
+        }
+        RlpCodec::V2 => {
+            // the plain string is the item itself, which the enclosing append counts
+            s.encoder().encode_value(bytes);
+        }
+    }
+}
+
+/// A function to decode the bytes of a wrapper of either codec.
+pub(crate) fn decode_bytes(r: &Rlp) -> Result<Vec<u8>, DecoderError> {
+    match r.prototype()? {
+        Prototype::List(1) => r.val_at(0),
+        Prototype::Data(_) => r.as_val(),
+        _ => Err(DecoderError::RlpInconsistentLengthAndData),
+    }
+}
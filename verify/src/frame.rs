@@ -0,0 +1,100 @@
+use crate::Error;
+
+use alloc::format;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// The format byte of the legacy framing, which is the highest byte of the 8-byte
+/// length prefix of the first segment.
+pub const FRAME_FORMAT_LEGACY: u8 = 0;
+/// The format byte of the current framing.
+pub const FRAME_FORMAT_V1: u8 = 1;
+
+const LEN_PREFIX_SIZE: usize = 8;
+const COUNT_SIZE: usize = 4;
+
+/// A function to encode the segments into a frame of the current format.
+/// The frame is a format byte, the 4-byte big-endian segments count, and the segments
+/// each prefixed with its 8-byte big-endian length.
+pub fn encode_frame(segments: &[&[u8]]) -> Vec<u8> {
+    let size: usize = segments
+        .iter()
+        .map(|segment| LEN_PREFIX_SIZE + segment.len())
+        .sum();
+    let mut encode = Vec::with_capacity(1 + COUNT_SIZE + size);
+    encode.push(FRAME_FORMAT_V1);
+    encode.extend_from_slice(&(segments.len() as u32).to_be_bytes());
+    for segment in segments {
+        encode.extend_from_slice(&(segment.len() as u64).to_be_bytes());
+        encode.extend_from_slice(segment);
+    }
+    encode
+}
+
+/// A function to decode the segments of a frame, the legacy two-segment framing is accepted.
+/// A frame with truncated or trailing bytes is rejected.
+pub fn decode_frame(encode: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    match encode.first() {
+        Some(&FRAME_FORMAT_LEGACY) => decode_legacy_frame(encode),
+        Some(&FRAME_FORMAT_V1) => decode_v1_frame(&encode[1..]),
+        Some(format) => Err(Error::Decode(format!(
+            "frame encounters unknown format {}",
+            format
+        ))),
+        None => Err(Error::Decode("frame is empty".into())),
+    }
+}
+
+fn decode_v1_frame(encode: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    if encode.len() < COUNT_SIZE {
+        return Err(Error::Decode("frame lacks the segments count".into()));
+    }
+    let (count_mark, mut rest) = encode.split_at(COUNT_SIZE);
+    let mut count: [u8; COUNT_SIZE] = [0; COUNT_SIZE];
+    count.copy_from_slice(count_mark);
+    let count = u32::from_be_bytes(count);
+    // every segment takes at least its length prefix, do not trust the count for allocation
+    let mut segments = Vec::with_capacity((count as usize).min(rest.len() / LEN_PREFIX_SIZE));
+    for index in 0..count {
+        let (segment, next) = split_segment(rest, index)?;
+        segments.push(segment);
+        rest = next;
+    }
+    if !rest.is_empty() {
+        return Err(Error::Decode(format!(
+            "frame has {} trailing bytes",
+            rest.len()
+        )));
+    }
+    Ok(segments)
+}
+
+/// The legacy framing is the first segment prefixed with its 8-byte big-endian length,
+/// followed by the second segment.
+fn decode_legacy_frame(encode: &[u8]) -> Result<Vec<&[u8]>, Error> {
+    let (first, second) = split_segment(encode, 0)?;
+    Ok(vec![first, second])
+}
+
+fn split_segment(encode: &[u8], index: u32) -> Result<(&[u8], &[u8]), Error> {
+    if encode.len() < LEN_PREFIX_SIZE {
+        return Err(Error::Decode(format!(
+            "frame segment {} lacks the length prefix, {} bytes left",
+            index,
+            encode.len()
+        )));
+    }
+    let (len_mark, rest) = encode.split_at(LEN_PREFIX_SIZE);
+    let mut len: [u8; LEN_PREFIX_SIZE] = [0; LEN_PREFIX_SIZE];
+    len.copy_from_slice(len_mark);
+    let len = u64::from_be_bytes(len);
+    if len > rest.len() as u64 {
+        return Err(Error::Decode(format!(
+            "frame segment {} length {} exceeds the {} bytes left",
+            index,
+            len,
+            rest.len()
+        )));
+    }
+    Ok(rest.split_at(len as usize))
+}
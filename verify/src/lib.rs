@@ -0,0 +1,397 @@
+//! The verification core of bft-rs, which checks proofs, signed proposals and signed votes
+//! without a running node. It builds with `no_std + alloc` by disabling the default `std`
+//! feature, so the WASM light clients and the on-chain bridge contracts reuse the same code
+//! the nodes run. bft-rs re-exports it, and the types are the same.
+//!
+//! Without `std`, the precommits of a `Proof` are kept in a `BTreeMap`.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+use crate::codec::{append_bytes, decode_bytes, AppendBy, CodecEncodable, RlpCodec};
+#[cfg(not(feature = "std"))]
+use alloc::collections::BTreeMap as HashMap;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use core::hash::{Hash as Hashable, Hasher};
+use core::ops::Deref;
+use hex_fmt::HexFmt;
+#[allow(unused_imports)]
+use log::{error, log};
+use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+use serde_derive::{Deserialize, Serialize};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+pub use crate::verify::{
+    check_proof, check_proof_with_authorities_hash, get_authorities_hash, get_proposal_hash,
+    get_signed_hash, try_check_proof, try_check_proof_with_authorities_hash, ProofError, SignKind,
+    SignScheme,
+};
+
+/// Define the rlp codecs of the byte wrappers.
+pub mod codec;
+/// Define the versioned multi-segment framing.
+pub mod frame;
+/// Define structures including Proposal and Vote.
+pub mod objects;
+/// Define the checks of the proofs, the signed proposals and the signed votes.
+pub mod verify;
+/// Define the versioned wire envelope of signed_proposals and signed_votes.
+pub mod wire;
+
+/// The error of decoding the byte wrappers, the frames and the wire envelopes.
+/// bft-rs converts it to the `BftError` of the same meaning.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The bytes are malformed.
+    Decode(String),
+    /// The wire envelope has a mismatching version, chain id or kind.
+    MismatchingWireMsg(String),
+}
+
+/// The byte length of the node address.
+pub const ADDRESS_LEN: usize = 20;
+/// The byte length of the hash.
+pub const HASH_LEN: usize = 32;
+
+/// Define the structure of the node address.
+#[derive(Clone, Copy, Default, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Address([u8; ADDRESS_LEN]);
+/// Define the structure of the hash.
+/// The default all-zero hash is the nil hash.
+#[derive(Clone, Copy, Default, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Hash([u8; HASH_LEN]);
+/// Define the structure of the signature.
+#[derive(Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Signature(Vec<u8>);
+/// Define the structure of the block.
+/// It is the consensus content, which should be serialized and wrapped.
+#[derive(Clone, Eq, PartialEq)]
+pub struct Block(Vec<u8>);
+
+macro_rules! impl_traits_for_vecu8_wraper {
+    ($name: ident) => {
+        impl $name {
+            pub fn to_vec(&self) -> Vec<u8> {
+                self.0.clone()
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name(vec![])
+            }
+        }
+
+        impl Deref for $name {
+            type Target = Vec<u8>;
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl Encodable for $name {
+            fn rlp_append(&self, s: &mut RlpStream) {
+                self.rlp_append_by(s, RlpCodec::V1);
+            }
+        }
+
+        impl CodecEncodable for $name {
+            fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+                append_bytes(s, &self.0, codec);
+            }
+        }
+
+        impl Decodable for $name {
+            fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+                Ok($name(decode_bytes(r)?))
+            }
+        }
+
+        impl From<Vec<u8>> for $name {
+            fn from(v: Vec<u8>) -> Self {
+                $name(v)
+            }
+        }
+
+        impl From<&[u8]> for $name {
+            fn from(v: &[u8]) -> Self {
+                $name(v.to_vec())
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "{:?}", &format!("{:<10}", HexFmt(&self.0)),)
+            }
+        }
+    };
+}
+
+macro_rules! impl_traits_for_fixed_bytes_wraper {
+    ($name: ident, $len: expr) => {
+        impl $name {
+            pub fn to_vec(&self) -> Vec<u8> {
+                self.0.to_vec()
+            }
+        }
+
+        impl Deref for $name {
+            type Target = [u8];
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl Encodable for $name {
+            fn rlp_append(&self, s: &mut RlpStream) {
+                self.rlp_append_by(s, RlpCodec::V1);
+            }
+        }
+
+        impl CodecEncodable for $name {
+            fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+                append_bytes(s, &self.0, codec);
+            }
+        }
+
+        impl Decodable for $name {
+            fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+                let v = decode_bytes(r)?;
+                $name::try_from(v.as_slice())
+                    .map_err(|_| DecoderError::Custom("invalid fixed bytes length"))
+            }
+        }
+
+        impl From<[u8; $len]> for $name {
+            fn from(v: [u8; $len]) -> Self {
+                $name(v)
+            }
+        }
+
+        impl TryFrom<&[u8]> for $name {
+            type Error = Error;
+            fn try_from(v: &[u8]) -> Result<Self, Self::Error> {
+                if v.len() != $len {
+                    return Err(Error::Decode(format!(
+                        "{} length {}, expected {}",
+                        stringify!($name),
+                        v.len(),
+                        $len
+                    )));
+                }
+                let mut bytes = [0u8; $len];
+                bytes.copy_from_slice(v);
+                Ok($name(bytes))
+            }
+        }
+
+        impl TryFrom<Vec<u8>> for $name {
+            type Error = Error;
+            fn try_from(v: Vec<u8>) -> Result<Self, Self::Error> {
+                $name::try_from(v.as_slice())
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+                write!(f, "{:?}", &format!("{:<10}", HexFmt(&self.0)),)
+            }
+        }
+    };
+}
+
+impl_traits_for_fixed_bytes_wraper!(Address, ADDRESS_LEN);
+impl_traits_for_fixed_bytes_wraper!(Hash, HASH_LEN);
+
+impl Hash {
+    /// A function to check whether it is the nil hash.
+    pub fn is_nil(&self) -> bool {
+        self.0 == [0u8; HASH_LEN]
+    }
+}
+impl_traits_for_vecu8_wraper!(Signature);
+impl_traits_for_vecu8_wraper!(Block);
+
+pub type Height = u64;
+
+pub type Round = u64;
+
+/// The bft node
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Node {
+    /// the address of the node
+    pub address: Address,
+    /// the weight of being a proposer
+    pub proposal_weight: u32,
+    /// the weight of calculating vote
+    pub vote_weight: u32,
+}
+
+impl Debug for Node {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Node {{ addr: {:?}, w: {}/{}}}",
+            self.address, self.proposal_weight, self.vote_weight,
+        )
+    }
+}
+
+impl Encodable for Node {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for Node {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(3)
+            .append_by(&self.address, codec)
+            .append(&self.proposal_weight)
+            .append(&self.vote_weight);
+    }
+}
+
+impl Decodable for Node {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(3) => {
+                let address: Address = r.val_at(0)?;
+                let proposal_weight: u32 = r.val_at(1)?;
+                let vote_weight: u32 = r.val_at(2)?;
+                Ok(Node {
+                    address,
+                    proposal_weight,
+                    vote_weight,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+impl Node {
+    pub fn new(address: Address, proposal_weight: u32, vote_weight: u32) -> Self {
+        Node {
+            address,
+            proposal_weight,
+            vote_weight,
+        }
+    }
+
+    pub fn set_address(address: Address) -> Self {
+        Self::new(address, 1, 1)
+    }
+}
+
+/// Proof
+#[derive(Clone, Eq, PartialEq, Default, Serialize, Deserialize)]
+pub struct Proof {
+    /// proof height
+    pub height: Height,
+    /// the reaching-consensus round
+    pub round: Round,
+    /// the reaching-consensus block hash
+    pub block_hash: Hash,
+    /// the voters and corresponding signatures
+    pub precommit_votes: HashMap<Address, Signature>,
+    /// the hash of the authority list of the proof height, which is the `crypt_hash` of the
+    /// rlp encoded list sorted by address. It is not signed by the precommit votes.
+    pub authorities_hash: Option<Hash>,
+}
+
+impl Debug for Proof {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Proof {{ h: {}, r: {}, hash: {:?}}}",
+            self.height, self.round, self.block_hash,
+        )
+    }
+}
+
+impl Hashable for Proof {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.height.hash(state);
+        self.round.hash(state);
+        self.block_hash.hash(state);
+        //TODO: Ignore precommit_votes maybe leaves flaws
+    }
+}
+
+impl Encodable for Proof {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for Proof {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        // the proof without authorities_hash keeps the legacy encoding
+        let len = if self.authorities_hash.is_some() {
+            6
+        } else {
+            5
+        };
+        s.begin_list(len)
+            .append(&self.height)
+            .append(&self.round)
+            .append_by(&self.block_hash, codec);
+        let mut key_values: Vec<(Address, Signature)> =
+            self.precommit_votes.clone().into_iter().collect();
+        key_values.sort();
+        let mut key_list: Vec<Address> = vec![];
+        let mut value_list: Vec<Signature> = vec![];
+        key_values.iter().for_each(|(address, sig)| {
+            key_list.push(*address);
+            value_list.push(sig.clone());
+        });
+        s.append_list_by(&key_list, codec)
+            .append_list_by(&value_list, codec);
+        if let Some(authorities_hash) = &self.authorities_hash {
+            s.append_by(authorities_hash, codec);
+        }
+    }
+}
+
+impl Decodable for Proof {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(len @ 5) | Prototype::List(len @ 6) => {
+                let height: Height = r.val_at(0)?;
+                let round: Round = r.val_at(1)?;
+                let block_hash: Hash = r.val_at(2)?;
+                let key_list: Vec<Address> = r.list_at(3)?;
+                let value_list: Vec<Signature> = r.list_at(4)?;
+                if key_list.len() != value_list.len() {
+                    error!(
+                        "Decode proof error, key_list_len {}, value_list_len{}",
+                        key_list.len(),
+                        value_list.len()
+                    );
+                    return Err(DecoderError::RlpIncorrectListLen);
+                }
+                let precommit_votes: HashMap<_, _> = key_list.into_iter().zip(value_list).collect();
+                let authorities_hash: Option<Hash> =
+                    if len == 6 { Some(r.val_at(5)?) } else { None };
+                Ok(Proof {
+                    height,
+                    round,
+                    block_hash,
+                    precommit_votes,
+                    authorities_hash,
+                })
+            }
+            _ => {
+                error!("Decode proof error, the prototype is {:?}", r.prototype());
+                Err(DecoderError::RlpInconsistentLengthAndData)
+            }
+        }
+    }
+}
@@ -0,0 +1,291 @@
+use crate::codec::{AppendBy, CodecEncodable, RlpCodec};
+use crate::{Address, Error, Hash, Height, Proof, Round, Signature};
+use alloc::format;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::{Debug, Formatter, Result as FmtResult};
+use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+use serde_derive::{Deserialize, Serialize};
+
+/// A proposal of a block.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Proposal {
+    /// the height of proposal
+    pub height: Height,
+    /// the round of proposal
+    pub round: Round,
+    /// block hash
+    pub block_hash: Hash,
+    /// the proof of previous height
+    pub proof: Proof,
+    /// the lock round of the proposal
+    pub lock_round: Option<Round>,
+    /// the lock votes of the proposal
+    pub lock_votes: Vec<SignedVote>,
+    /// proposer address
+    pub proposer: Address,
+    /// the unix time of the proposer when proposing, in milliseconds
+    pub timestamp: Option<u64>,
+}
+
+impl Debug for Proposal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "Proposal {{ h: {}, r: {}, hash:{:?}, addr: {:?}}}",
+            self.height, self.round, self.block_hash, self.proposer,
+        )
+    }
+}
+
+impl Encodable for Proposal {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for Proposal {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        // the proposal without timestamp keeps the legacy encoding
+        let len = if self.timestamp.is_some() { 8 } else { 7 };
+        s.begin_list(len)
+            .append(&self.height)
+            .append(&self.round)
+            .append_by(&self.block_hash, codec)
+            .append_by(&self.proof, codec)
+            .append(&self.lock_round)
+            .append_list_by(&self.lock_votes, codec)
+            .append_by(&self.proposer, codec);
+        if let Some(timestamp) = self.timestamp {
+            s.append(&timestamp);
+        }
+    }
+}
+
+impl Decodable for Proposal {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(len @ 7) | Prototype::List(len @ 8) => {
+                let height: Height = r.val_at(0)?;
+                let round: Round = r.val_at(1)?;
+                let block_hash: Hash = r.val_at(2)?;
+                let proof: Proof = r.val_at(3)?;
+                let lock_round: Option<Round> = r.val_at(4)?;
+                let lock_votes: Vec<SignedVote> = r.list_at(5)?;
+                let proposer: Address = r.val_at(6)?;
+                let timestamp: Option<u64> = if len == 8 { Some(r.val_at(7)?) } else { None };
+                Ok(Proposal {
+                    height,
+                    round,
+                    block_hash,
+                    proof,
+                    lock_round,
+                    lock_votes,
+                    proposer,
+                    timestamp,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A proposal with the signature of its proposer.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SignedProposal {
+    pub proposal: Proposal,
+    pub signature: Signature,
+}
+
+impl SignedProposal {
+    pub fn new(proposal: Proposal, signature: Signature) -> Self {
+        SignedProposal {
+            proposal,
+            signature,
+        }
+    }
+}
+
+impl Debug for SignedProposal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "SignedProposal {{ proposal: {:?}, sig: {:?}}}",
+            self.proposal, self.signature,
+        )
+    }
+}
+
+impl Encodable for SignedProposal {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for SignedProposal {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(2)
+            .append_by(&self.proposal, codec)
+            .append_by(&self.signature, codec);
+    }
+}
+
+impl Decodable for SignedProposal {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let proposal: Proposal = r.val_at(0)?;
+                let signature: Signature = r.val_at(1)?;
+                Ok(SignedProposal {
+                    proposal,
+                    signature,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A vote to a proposal.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct Vote {
+    /// Prevote or precommit
+    pub vote_type: VoteType,
+    /// the height of vote
+    pub height: Height,
+    /// the round of vote
+    pub round: Round,
+    /// the content vote for
+    pub block_hash: Hash,
+    /// voter address
+    pub voter: Address,
+}
+
+impl Debug for Vote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "{:?} {{ h: {}, r: {}, hash: {:?}, addr: {:?}}}",
+            self.vote_type, self.height, self.round, self.block_hash, self.voter,
+        )
+    }
+}
+
+impl Encodable for Vote {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for Vote {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        let vote_type: u8 = self.vote_type.clone().into();
+        s.begin_list(5)
+            .append(&vote_type)
+            .append(&self.height)
+            .append(&self.round)
+            .append_by(&self.block_hash, codec)
+            .append_by(&self.voter, codec);
+    }
+}
+
+impl Decodable for Vote {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(5) => {
+                let vote_type: u8 = r.val_at(0)?;
+                let vote_type = VoteType::try_from(vote_type)
+                    .map_err(|_| DecoderError::Custom("invalid vote type"))?;
+                let height: Height = r.val_at(1)?;
+                let round: Round = r.val_at(2)?;
+                let block_hash: Hash = r.val_at(3)?;
+                let voter: Address = r.val_at(4)?;
+                Ok(Vote {
+                    vote_type,
+                    height,
+                    round,
+                    block_hash,
+                    voter,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// A vote with the signature of its voter.
+#[derive(Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub struct SignedVote {
+    pub vote: Vote,
+    pub signature: Signature,
+}
+
+impl SignedVote {
+    pub fn new(vote: Vote, signature: Signature) -> Self {
+        SignedVote { vote, signature }
+    }
+}
+
+impl Debug for SignedVote {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "SignedVote {{ vote: {:?}, sig: {:?}}}",
+            self.vote, self.signature,
+        )
+    }
+}
+
+impl Encodable for SignedVote {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        self.rlp_append_by(s, RlpCodec::V1);
+    }
+}
+
+impl CodecEncodable for SignedVote {
+    fn rlp_append_by(&self, s: &mut RlpStream, codec: RlpCodec) {
+        s.begin_list(2)
+            .append_by(&self.vote, codec)
+            .append_by(&self.signature, codec);
+    }
+}
+
+impl Decodable for SignedVote {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(2) => {
+                let vote: Vote = r.val_at(0)?;
+                let signature: Signature = r.val_at(1)?;
+                Ok(SignedVote { vote, signature })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}
+
+/// The type of a vote.
+#[derive(Clone, Debug, Eq, PartialEq, Hash, Serialize, Deserialize)]
+pub enum VoteType {
+    Prevote,
+    Precommit,
+}
+
+impl TryFrom<u8> for VoteType {
+    type Error = Error;
+    fn try_from(s: u8) -> Result<Self, Self::Error> {
+        match s {
+            0 => Ok(VoteType::Prevote),
+            1 => Ok(VoteType::Precommit),
+            _ => Err(Error::Decode(format!("invalid vote type {}", s))),
+        }
+    }
+}
+
+impl From<VoteType> for u8 {
+    fn from(val: VoteType) -> Self {
+        match val {
+            VoteType::Prevote => 0,
+            VoteType::Precommit => 1,
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! The checks of the proofs, the signed proposals and the signed votes, shared by the state
+//! machine, light clients and bridge contracts.
+
+use crate::codec::{encode_by, CodecEncodable, RlpCodec};
+use crate::frame::decode_frame;
+use crate::objects::{SignedProposal, SignedVote, Vote, VoteType};
+use crate::wire::{WireKind, WireMsg};
+use crate::{Address, Hash, Height, Node, Proof, Signature};
+
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// The kind of a signed msg, which is mixed into its signed hash under a sign domain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignKind {
+    Proposal,
+    Vote,
+}
+
+impl SignKind {
+    fn context(self) -> &'static [u8] {
+        match self {
+            SignKind::Proposal => b"bft-rs proposal",
+            SignKind::Vote => b"bft-rs vote",
+        }
+    }
+}
+
+/// How the proposals and the votes of a chain are signed, which all the nodes and the light
+/// clients of the chain share, see `BftConfig::sign_scheme`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SignScheme {
+    /// The codec the signed msgs are rlp encoded by.
+    pub codec: RlpCodec,
+    /// The chain id the signed hashes are separated by, see `get_signed_hash`.
+    pub domain: Option<u64>,
+}
+
+impl SignScheme {
+    /// A function to get the hash signed for the [`object`] of the [`kind`], which is encoded
+    /// by the codec and hashed under the domain of the scheme.
+    pub fn signed_hash<E: CodecEncodable + ?Sized>(
+        &self,
+        kind: SignKind,
+        object: &E,
+        crypt_hash: impl Fn(&[u8]) -> Hash,
+    ) -> Hash {
+        get_signed_hash(
+            kind,
+            self.domain,
+            &encode_by(object, self.codec),
+            crypt_hash,
+        )
+    }
+}
+
+/// A public function to get the hash signed for the rlp [`encode`] of a msg of the [`kind`]
+/// under the [`sign_domain`], the chain id the signed hashes are separated by.
+/// With the domain, the hash signed is the one of the context of the msg kind, the chain id and
+/// the rlp encoding of the msg, so a signature is never valid for another kind of msg or on
+/// another chain. Without it, the rlp encoding is hashed as it is.
+pub fn get_signed_hash(
+    kind: SignKind,
+    sign_domain: Option<u64>,
+    encode: &[u8],
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+) -> Hash {
+    match sign_domain {
+        Some(chain_id) => {
+            let context = kind.context();
+            let mut msg = Vec::with_capacity(context.len() + 8 + encode.len());
+            msg.extend_from_slice(context);
+            msg.extend_from_slice(&chain_id.to_be_bytes());
+            msg.extend_from_slice(encode);
+            crypt_hash(&msg)
+        }
+        None => crypt_hash(encode),
+    }
+}
+
+/// Whether the `count` is above 2/3 of the `weight_sum`, compared in u128 so the large weights
+/// do not overflow.
+#[inline]
+pub fn is_above_threshold(count: u64, weight_sum: u64) -> bool {
+    u128::from(count) * 3 > u128::from(weight_sum) * 2
+}
+
+/// A function to sum the vote weights of the [`authorities`], a repeated address is counted
+/// once.
+#[inline]
+pub fn get_total_weight(authorities: &[Node]) -> u64 {
+    let addresses: Vec<Address> = authorities.iter().map(|node| node.address).collect();
+    get_votes_weight(authorities, &addresses)
+}
+
+/// A function to sum the vote weights of the [`authorities`] in the [`vote_addresses`].
+/// An address is counted once even if it repeats in either list.
+#[inline]
+pub fn get_votes_weight(authorities: &[Node], vote_addresses: &[Address]) -> u64 {
+    let mut counted: Vec<&Address> = Vec::with_capacity(vote_addresses.len());
+    let sum = authorities
+        .iter()
+        .filter(|node| {
+            if counted.contains(&&node.address) || !vote_addresses.contains(&node.address) {
+                return false;
+            }
+            counted.push(&node.address);
+            true
+        })
+        .map(|node| u128::from(node.vote_weight))
+        .sum::<u128>();
+    // the weights are accumulated in u128 and saturated at `u64::MAX`
+    u64::try_from(sum).unwrap_or(u64::MAX)
+}
+
+/// A function to restore the precommit of the [`voter`] in the [`proof`].
+#[inline]
+pub fn precommit_of_proof(proof: &Proof, voter: Address) -> Vote {
+    Vote {
+        vote_type: VoteType::Precommit,
+        height: proof.height,
+        round: proof.round,
+        block_hash: proof.block_hash,
+        voter,
+    }
+}
+
+/// The reason a proof fails the check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProofError {
+    /// The proof is not of the height before the one checked.
+    MismatchingHeight {
+        height: Height,
+        proof_height: Height,
+    },
+    /// The proof commits the nil block hash.
+    NilProof,
+    /// The deduplicated voters of the proof do not collect 2/3+ of the vote weight.
+    InsufficientWeight { weight: u64, total_weight: u64 },
+    /// The `authorities_hash` of the proof is missing or mismatches the authority list or the
+    /// expected one.
+    MismatchingAuthoritiesHash,
+    /// The voter is not in the authority list.
+    InvalidVoter(Address),
+    /// The signature of the voter is invalid.
+    InvalidSignature(Address),
+}
+
+/// A public function for proof validation.
+/// The input [`height`] is the height of block containing the proof.
+/// The input [`authorities`] is the authority_list for the proof check.
+/// The input [`scheme`] is the one the precommits are signed by.
+/// The fn [`crypt_hash`], [`check_sig`] are user-defined.
+pub fn check_proof(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    try_check_proof(proof, height, authorities, scheme, crypt_hash, check_sig).is_ok()
+}
+
+/// A public function for proof validation like `check_proof`, which returns the reason the
+/// proof fails.
+pub fn try_check_proof(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> Result<(), ProofError> {
+    check_proof_votes(proof, height, authorities, scheme.codec, &crypt_hash)?;
+    check_precommit_sigs(
+        proof,
+        |voter| {
+            let precommit = precommit_of_proof(proof, *voter);
+            scheme.signed_hash(SignKind::Vote, &precommit, &crypt_hash)
+        },
+        check_sig,
+    )
+}
+
+/// A function to check everything of the [`proof`] but the signatures of its precommits: the
+/// `authorities_hash` by the [`codec`] if any, the height, the block hash, the voters and their
+/// weight. The state machine checks the signatures in batch after it.
+pub fn check_proof_votes(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    codec: RlpCodec,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+) -> Result<(), ProofError> {
+    if let Some(authorities_hash) = proof.authorities_hash {
+        if authorities_hash != get_authorities_hash(authorities, codec, &crypt_hash) {
+            return Err(ProofError::MismatchingAuthoritiesHash);
+        }
+    }
+    check_precommit_votes(proof, height, authorities)
+}
+
+/// A public function for proof validation against the [`expected_authorities_hash`] the caller
+/// trusts, such as the one in the header of a light client, so a proof of a substituted
+/// authority list is rejected. The proof must carry the `authorities_hash`, which equals both
+/// the expected one and the hash of the [`authorities`], even the genesis one.
+pub fn check_proof_with_authorities_hash(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    expected_authorities_hash: &Hash,
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    try_check_proof_with_authorities_hash(
+        proof,
+        height,
+        authorities,
+        expected_authorities_hash,
+        scheme,
+        crypt_hash,
+        check_sig,
+    )
+    .is_ok()
+}
+
+/// A public function for proof validation like `check_proof_with_authorities_hash`, which
+/// returns the reason the proof fails.
+pub fn try_check_proof_with_authorities_hash(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    expected_authorities_hash: &Hash,
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> Result<(), ProofError> {
+    if proof.authorities_hash.as_ref() != Some(expected_authorities_hash) {
+        return Err(ProofError::MismatchingAuthoritiesHash);
+    }
+    try_check_proof(proof, height, authorities, scheme, crypt_hash, check_sig)
+}
+
+/// A function to check the height, the weight and the signatures of the precommits in the
+/// [`proof`], the signed hash of the precommit of each voter is got by [`precommit_hash`].
+/// The `authorities_hash` is left to the caller.
+pub fn check_precommits(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+    precommit_hash: impl Fn(&Address) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> Result<(), ProofError> {
+    check_precommit_votes(proof, height, authorities)?;
+    check_precommit_sigs(proof, precommit_hash, check_sig)
+}
+
+/// A function to check the height, the block hash, the voters and the weight of the precommits
+/// in the [`proof`]. The proof of height 0 without precommits is only taken for the genesis
+/// block of height 1.
+fn check_precommit_votes(
+    proof: &Proof,
+    height: Height,
+    authorities: &[Node],
+) -> Result<(), ProofError> {
+    let mismatching_height = ProofError::MismatchingHeight {
+        height,
+        proof_height: proof.height,
+    };
+    if proof.height == 0 {
+        return if height == 1 && proof.precommit_votes.is_empty() {
+            Ok(())
+        } else {
+            Err(mismatching_height)
+        };
+    }
+    if Some(height) != proof.height.checked_add(1) {
+        return Err(mismatching_height);
+    }
+    // a quorum of nil precommits commits no block
+    if proof.block_hash == Hash::default() {
+        return Err(ProofError::NilProof);
+    }
+
+    if let Some(voter) = proof
+        .precommit_votes
+        .keys()
+        .find(|voter| !authorities.iter().any(|node| node.address == **voter))
+    {
+        return Err(ProofError::InvalidVoter(*voter));
+    }
+    let vote_addresses: Vec<Address> = proof.precommit_votes.keys().cloned().collect();
+    let weight = get_votes_weight(authorities, &vote_addresses);
+    let total_weight = get_total_weight(authorities);
+    if !is_above_threshold(weight, total_weight) {
+        return Err(ProofError::InsufficientWeight {
+            weight,
+            total_weight,
+        });
+    }
+    Ok(())
+}
+
+/// A function to check the signature of each precommit in the [`proof`] is signed by its voter.
+fn check_precommit_sigs(
+    proof: &Proof,
+    precommit_hash: impl Fn(&Address) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> Result<(), ProofError> {
+    match proof
+        .precommit_votes
+        .iter()
+        .find(|(voter, sig)| check_sig(sig, &precommit_hash(voter)) != Some(**voter))
+    {
+        Some((voter, _)) => Err(ProofError::InvalidSignature(*voter)),
+        None => Ok(()),
+    }
+}
+
+/// A public function to hash the authority list as the `authorities_hash` of a proof.
+/// The list is sorted by address before hashing, so the order of the input does not matter,
+/// and encoded by the [`codec`] of the chain.
+pub fn get_authorities_hash(
+    authorities: &[Node],
+    codec: RlpCodec,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+) -> Hash {
+    let mut authorities = authorities.to_vec();
+    authorities.sort();
+    crypt_hash(&encode_by(authorities.as_slice(), codec))
+}
+
+/// A public function for get_proposal_hash from the wire envelope of BftMsg::Proposal
+pub fn get_proposal_hash(encode: &[u8], crypt_hash: impl Fn(&[u8]) -> Hash) -> Option<Hash> {
+    let wire_msg: WireMsg = rlp::decode(encode).ok()?;
+    if wire_msg.kind != WireKind::Proposal {
+        return None;
+    }
+    let (signed_proposal_encode, _) = split_proposal(&wire_msg.payload)?;
+    Some(crypt_hash(signed_proposal_encode))
+}
+
+/// A function to decode the signed_proposal from the payload of a proposal wire envelope,
+/// the block segment is left out.
+pub fn decode_signed_proposal(payload: &[u8]) -> Option<SignedProposal> {
+    let (signed_proposal_encode, _) = split_proposal(payload)?;
+    rlp::decode(signed_proposal_encode).ok()
+}
+
+/// A function to decode the signed_vote from the payload of a vote wire envelope.
+pub fn decode_signed_vote(payload: &[u8]) -> Option<SignedVote> {
+    rlp::decode(payload).ok()
+}
+
+/// A function to check the signature of the [`signed_proposal`] is signed by its proposer.
+/// The lock votes and the proof of the proposal are checked by `check_signed_vote` and
+/// `check_proof` against the authority lists of their heights.
+pub fn check_signed_proposal(
+    signed_proposal: &SignedProposal,
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    let proposal = &signed_proposal.proposal;
+    let hash = scheme.signed_hash(SignKind::Proposal, proposal, crypt_hash);
+    check_sig(&signed_proposal.signature, &hash) == Some(proposal.proposer)
+}
+
+/// A function to check the signature of the [`signed_vote`] is signed by its voter.
+pub fn check_signed_vote(
+    signed_vote: &SignedVote,
+    scheme: SignScheme,
+    crypt_hash: impl Fn(&[u8]) -> Hash,
+    check_sig: impl Fn(&Signature, &Hash) -> Option<Address>,
+) -> bool {
+    let vote = &signed_vote.vote;
+    let hash = scheme.signed_hash(SignKind::Vote, vote, crypt_hash);
+    check_sig(&signed_vote.signature, &hash) == Some(vote.voter)
+}
+
+/// The signed_proposal segment and the block segment of a proposal payload.
+fn split_proposal(payload: &[u8]) -> Option<(&[u8], &[u8])> {
+    match decode_frame(payload).ok()?.as_slice() {
+        [proposal, block] | [proposal, block, [_]] => Some((proposal, block)),
+        _ => None,
+    }
+}
@@ -0,0 +1,160 @@
+use crate::codec::RlpCodec;
+use crate::Error;
+
+use alloc::format;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+use rlp::{Decodable, DecoderError, Encodable, Prototype, Rlp, RlpStream};
+
+/// The current version of the wire envelope.
+pub const WIRE_VERSION: u8 = 1;
+/// The version of the wire envelope carrying a proposal with the compressed block segment.
+pub const WIRE_VERSION_COMPRESSED: u8 = 2;
+
+/// The kind of the payload in a wire envelope.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WireKind {
+    Proposal,
+    Vote,
+    GetTxs,
+    Txs,
+}
+
+impl From<WireKind> for u8 {
+    fn from(kind: WireKind) -> Self {
+        match kind {
+            WireKind::Proposal => 0,
+            WireKind::Vote => 1,
+            WireKind::GetTxs => 2,
+            WireKind::Txs => 3,
+        }
+    }
+}
+
+/// The versioned envelope of the signed_proposals and signed_votes on the wire.
+/// The chain id prevents msgs of a chain from being replayed on another chain, and the codec
+/// tells how the payload is encoded, so its signed hash is taken by the same one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WireMsg {
+    pub version: u8,
+    pub chain_id: u64,
+    pub codec: RlpCodec,
+    pub kind: WireKind,
+    pub payload: Vec<u8>,
+}
+
+impl WireMsg {
+    /// A function to create a new wire envelope of the current version.
+    pub fn new(chain_id: u64, codec: RlpCodec, kind: WireKind, payload: Vec<u8>) -> Self {
+        WireMsg {
+            version: WIRE_VERSION,
+            chain_id,
+            codec,
+            kind,
+            payload,
+        }
+    }
+
+    /// A function to create a new wire envelope of the proposal with the compressed block segment.
+    pub fn new_compressed(chain_id: u64, codec: RlpCodec, payload: Vec<u8>) -> Self {
+        WireMsg {
+            version: WIRE_VERSION_COMPRESSED,
+            chain_id,
+            codec,
+            kind: WireKind::Proposal,
+            payload,
+        }
+    }
+
+    /// A function to decode a wire envelope and check its version, chain id, codec and kind,
+    /// return the payload. The signed msgs of another codec are rejected, since the proofs
+    /// and the lock votes in them can only be checked by the codec of the chain.
+    pub fn open(
+        encode: &[u8],
+        chain_id: u64,
+        codec: RlpCodec,
+        kind: WireKind,
+    ) -> Result<Vec<u8>, Error> {
+        let wire_msg: WireMsg = rlp::decode(encode)
+            .map_err(|e| Error::Decode(format!("wire_msg encounters {:?}", e)))?;
+        let is_compressed_proposal =
+            wire_msg.version == WIRE_VERSION_COMPRESSED && wire_msg.kind == WireKind::Proposal;
+        if wire_msg.version != WIRE_VERSION && !is_compressed_proposal {
+            return Err(Error::MismatchingWireMsg(format!(
+                "version {} of {:?}",
+                wire_msg.version, wire_msg.kind
+            )));
+        }
+        if wire_msg.chain_id != chain_id {
+            return Err(Error::MismatchingWireMsg(format!(
+                "chain_id {} of {:?}",
+                wire_msg.chain_id, wire_msg.kind
+            )));
+        }
+        if wire_msg.codec != codec {
+            return Err(Error::MismatchingWireMsg(format!(
+                "codec {:?} of {:?}",
+                wire_msg.codec, wire_msg.kind
+            )));
+        }
+        if wire_msg.kind != kind {
+            return Err(Error::MismatchingWireMsg(format!(
+                "kind {:?}, expected {:?}",
+                wire_msg.kind, kind
+            )));
+        }
+        Ok(wire_msg.payload)
+    }
+}
+
+impl Encodable for WireMsg {
+    fn rlp_append(&self, s: &mut RlpStream) {
+        let kind: u8 = self.kind.into();
+        // the envelope of the former codec keeps the legacy encoding
+        let legacy = self.codec == RlpCodec::V1;
+        s.begin_list(if legacy { 4 } else { 5 })
+            .append(&self.version)
+            .append(&self.chain_id)
+            .append(&kind)
+            .append(&self.payload);
+        if !legacy {
+            s.append(&u8::from(self.codec));
+        }
+    }
+}
+
+impl Decodable for WireMsg {
+    fn decode(r: &Rlp) -> Result<Self, DecoderError> {
+        match r.prototype()? {
+            Prototype::List(len @ 4) | Prototype::List(len @ 5) => {
+                let version: u8 = r.val_at(0)?;
+                let chain_id: u64 = r.val_at(1)?;
+                let kind: u8 = r.val_at(2)?;
+                let kind = match kind {
+                    0 => WireKind::Proposal,
+                    1 => WireKind::Vote,
+                    2 => WireKind::GetTxs,
+                    3 => WireKind::Txs,
+                    _ => return Err(DecoderError::Custom("invalid wire kind")),
+                };
+                let payload: Vec<u8> = r.val_at(3)?;
+                let codec = if len == 5 {
+                    let codec: u8 = r.val_at(4)?;
+                    RlpCodec::try_from(codec)
+                        .map_err(|_| DecoderError::Custom("invalid rlp codec"))?
+                } else {
+                    RlpCodec::V1
+                };
+                Ok(WireMsg {
+                    version,
+                    chain_id,
+                    codec,
+                    kind,
+                    payload,
+                })
+            }
+            _ => Err(DecoderError::RlpInconsistentLengthAndData),
+        }
+    }
+}